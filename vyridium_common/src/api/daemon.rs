@@ -106,7 +106,22 @@ pub struct GetBlockByHashParams<'a> {
 
 #[derive(Serialize, Deserialize)]
 pub struct GetBlockTemplateParams<'a> {
-    pub address: Cow<'a, Address>
+    pub address: Cow<'a, Address>,
+    // Maximum size in bytes of the block
+    #[serde(default)]
+    pub max_size: Option<usize>,
+    // Transactions that must not be included
+    #[serde(default)]
+    pub exclude_txs: Vec<Hash>,
+    // Transactions to include first
+    #[serde(default)]
+    pub prioritize_txs: Vec<Hash>,
+    // Minimum fee per byte for a transaction to be included
+    #[serde(default)]
+    pub min_fee_per_byte: Option<u64>,
+    // Tips to build the block on, best ones are used by default
+    #[serde(default)]
+    pub tips: Option<IndexSet<Hash>>
 }
 
 #[derive(Serialize, Deserialize)]
@@ -130,6 +145,18 @@ pub struct GetBlockTemplateResult {
     pub topoheight: u64,
     // Difficulty target for the POW challenge
     pub difficulty: Difficulty,
+    // Total fees of the transactions included
+    #[serde(default)]
+    pub total_fees: u64,
+    // Expected block reward, it is reduced if the block become a side block
+    #[serde(default)]
+    pub reward: u64,
+    // Miner part of the block reward, fees are not included
+    #[serde(default)]
+    pub miner_reward: u64,
+    // Dev fee part of the block reward
+    #[serde(default)]
+    pub dev_reward: u64
 }
 
 #[derive(Serialize, Deserialize, PartialEq)]
@@ -252,6 +279,9 @@ pub struct GetInfoResult {
     pub block_reward: u64,
     // count how many transactions are present in mempool
     pub mempool_size: usize,
+    // minimum fee per byte required to enter the mempool, zero until it is full
+    #[serde(default)]
+    pub mempool_min_fee_per_byte: u64,
     // software version on which the daemon is running
     pub version: String,
     // Network state (mainnet, testnet, devnet)
@@ -263,6 +293,13 @@ pub struct SubmitTransactionParams {
     pub data: String // should be in hex format
 }
 
+// JSON-RPC error codes of submit_transaction used by the wallets
+// They are the ids of the daemon errors offset by 200
+pub const TX_ALREADY_IN_MEMPOOL_ERROR_CODE: i16 = 218;
+pub const TX_FEE_TOO_LOW_FOR_MEMPOOL_ERROR_CODE: i16 = 219;
+pub const MEMPOOL_FULL_ERROR_CODE: i16 = 220;
+pub const TX_ALREADY_IN_BLOCKCHAIN_ERROR_CODE: i16 = 295;
+
 #[derive(Serialize, Deserialize)]
 pub struct GetTransactionParams<'a> {
     pub hash: Cow<'a, Hash>
@@ -334,7 +371,51 @@ pub struct PeerEntry<'a> {
     pub pruned_topoheight: Option<u64>,
     pub peers: Cow<'a, HashMap<SocketAddr, Direction>>,
     pub cumulative_difficulty: Cow<'a, CumulativeDifficulty>,
-    pub connected_on: TimestampSeconds
+    pub connected_on: TimestampSeconds,
+    // Total bytes received from the peer
+    pub bytes_in: usize,
+    // Total bytes sent to the peer
+    pub bytes_out: usize,
+    // Packets received per type
+    pub packets_in: HashMap<String, u64>,
+    // Packets sent per type
+    pub packets_out: HashMap<String, u64>
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MiningProtocol {
+    Getwork,
+    Stratum
+}
+
+// Estimated hashrate of a miner based on its shares
+#[derive(Serialize, Deserialize)]
+pub struct MinerHashrate {
+    pub last_minute: f64,
+    pub last_15_minutes: f64,
+    pub last_hour: f64
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MinerEntry<'a> {
+    pub address: Address,
+    pub worker: Cow<'a, String>,
+    pub protocol: MiningProtocol,
+    pub first_seen: TimestampMillis,
+    pub blocks_accepted: usize,
+    pub blocks_rejected: usize,
+    pub shares_accepted: usize,
+    pub shares_rejected: usize,
+    // None if the miner is working on the network difficulty
+    pub share_difficulty: Option<Difficulty>,
+    pub last_share: Option<TimestampMillis>,
+    pub hashrate: MinerHashrate
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetMinersResult<'a> {
+    pub miners: Vec<MinerEntry<'a>>
 }
 
 #[derive(Serialize, Deserialize)]
@@ -345,7 +426,26 @@ pub struct P2pStatusResult<'a> {
     pub our_topoheight: u64,
     pub best_topoheight: u64,
     pub median_topoheight: u64,
-    pub peer_id: u64
+    pub peer_id: u64,
+    pub block_propagation: BlockPropagationStats
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BlockPropagationStats {
+    // Full blocks propagated to us
+    pub full_blocks: u64,
+    // Average time in milliseconds to add a full block
+    pub full_blocks_avg_time: u64,
+    // Compact blocks propagated to us
+    pub compact_blocks: u64,
+    // Average time in milliseconds to add a compact block
+    pub compact_blocks_avg_time: u64,
+    // Compact blocks rebuilt from our mempool only
+    pub compact_blocks_rebuilt: u64,
+    // Total TXs requested to rebuild compact blocks
+    pub compact_blocks_missing_txs: u64,
+    // Compact blocks requested fully after a failure
+    pub compact_blocks_fallbacks: u64
 }
 
 #[derive(Serialize, Deserialize)]
@@ -500,6 +600,26 @@ pub struct GetMempoolCacheResult {
     balances: HashMap<Hash, CiphertextCache>
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct EstimateFeeRateParams {
+    // blocks count in which the TX should be included
+    #[serde(default)]
+    pub target_blocks: Option<u64>
+}
+
+// All fee rates are in atomic units per KB
+#[derive(Serialize, Deserialize)]
+pub struct EstimateFeeRateResult {
+    // fee per KB to be included in the next block
+    pub next_block: u64,
+    // blocks count used for the target fee rate
+    pub target_blocks: u64,
+    // fee per KB to be included within the target blocks count
+    pub target: u64,
+    // lowest fee per KB accepted, without guarantee on the inclusion time
+    pub economy: u64
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct GetDifficultyResult {
     pub difficulty: Difficulty,
@@ -555,6 +675,7 @@ pub enum NotifyEvent {
     // it contains StableTopoHeightChangedEvent struct as value
     StableTopoHeightChanged,
     // When a transaction that was executed in a block is not reintroduced in mempool
+    // or when a transaction is deleted or evicted from mempool
     // It contains TransactionOrphanedEvent as value
     TransactionOrphaned,
     // When a new transaction is added in mempool
@@ -1,6 +1,7 @@
 pub mod daemon;
 mod data;
 pub mod query;
+pub mod stratum;
 pub mod wallet;
 
 use crate::{
@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::{
+    block::{Algorithm, EXTRA_NONCE_SIZE},
+    difficulty::Difficulty
+};
+
+// Stratum methods supported
+pub const METHOD_SUBSCRIBE: &str = "mining.subscribe";
+pub const METHOD_AUTHORIZE: &str = "mining.authorize";
+pub const METHOD_SUBMIT: &str = "mining.submit";
+pub const METHOD_NOTIFY: &str = "mining.notify";
+pub const METHOD_SET_DIFFICULTY: &str = "mining.set_difficulty";
+
+// Bytes of the extra nonce set by the server for each connection
+// This split the work between all the connections of the server
+pub const EXTRA_NONCE_PREFIX_SIZE: usize = 8;
+// Bytes of the extra nonce left to the miner
+pub const EXTRA_NONCE_MINER_SIZE: usize = EXTRA_NONCE_SIZE - EXTRA_NONCE_PREFIX_SIZE;
+
+// Error codes used by Stratum v1
+pub const ERROR_OTHER: i16 = 20;
+pub const ERROR_JOB_NOT_FOUND: i16 = 21;
+pub const ERROR_DUPLICATE_SHARE: i16 = 22;
+pub const ERROR_LOW_DIFFICULTY_SHARE: i16 = 23;
+pub const ERROR_UNAUTHORIZED_WORKER: i16 = 24;
+pub const ERROR_NOT_SUBSCRIBED: i16 = 25;
+// Not part of Stratum v1: the share was a block but the network rejected it
+pub const ERROR_BLOCK_REJECTED: i16 = 26;
+
+// Request sent by the miner or notification sent by the server
+// A notification has no id
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StratumRequest {
+    #[serde(default)]
+    pub id: Value,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value
+}
+
+// Error is serialized as [code, message, traceback]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StratumError(pub i16, pub String, pub Option<Value>);
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StratumResponse {
+    pub id: Value,
+    pub result: Value,
+    pub error: Option<StratumError>
+}
+
+// Any message received on a Stratum connection
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum StratumMessage {
+    Request(StratumRequest),
+    Response(StratumResponse)
+}
+
+impl StratumRequest {
+    pub fn new(id: Value, method: &str, params: Value) -> Self {
+        Self {
+            id,
+            method: method.to_owned(),
+            params
+        }
+    }
+
+    pub fn notification(method: &str, params: Value) -> Self {
+        Self::new(Value::Null, method, params)
+    }
+}
+
+impl StratumResponse {
+    pub fn result(id: Value, result: Value) -> Self {
+        Self {
+            id,
+            result,
+            error: None
+        }
+    }
+
+    pub fn error(id: Value, code: i16, message: String) -> Self {
+        Self {
+            id,
+            result: Value::Null,
+            error: Some(StratumError(code, message, None))
+        }
+    }
+}
+
+// Result of mining.subscribe
+// [subscriptions, extra nonce prefix in hex, extra nonce size left to the miner]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SubscribeResult(pub Vec<(String, String)>, pub String, pub usize);
+
+// Params of mining.notify
+// [job id, algorithm, miner work in hex, height, topoheight, clean jobs]
+// Miner work already contains the miner public key and the extra nonce prefix
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NotifyParams(pub String, pub Algorithm, pub String, pub u64, pub u64, pub bool);
+
+// Params of mining.set_difficulty
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetDifficultyParams(pub Difficulty);
+
+// Params of mining.submit
+// [worker name, job id, miner work in hex]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SubmitParams(pub String, pub String, pub String);
+
+// Result of mining.submit
+// true for an accepted share, the block hash in hex if the share was also a block accepted by the network
+pub fn is_block_submit_result(result: &Value) -> bool {
+    result.is_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_messages() {
+        let request: StratumMessage = serde_json::from_value(json!({"id": 1, "method": METHOD_SUBSCRIBE, "params": []})).unwrap();
+        assert!(matches!(request, StratumMessage::Request(ref r) if r.method == METHOD_SUBSCRIBE));
+
+        let response: StratumMessage = serde_json::from_value(json!({"id": 1, "result": true, "error": null})).unwrap();
+        assert!(matches!(response, StratumMessage::Response(ref r) if r.error.is_none()));
+
+        let error = serde_json::to_value(StratumResponse::error(json!(2), ERROR_JOB_NOT_FOUND, "Job not found".into())).unwrap();
+        assert_eq!(error["error"], json!([ERROR_JOB_NOT_FOUND, "Job not found", null]));
+    }
+}
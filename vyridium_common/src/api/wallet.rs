@@ -2,8 +2,9 @@ use std::borrow::Cow;
 use serde::{Deserialize, Serialize};
 use crate::{
     crypto::{Address, Hash},
+    time::TimestampSeconds,
     transaction::{
-        builder::{FeeBuilder, TransactionTypeBuilder},
+        builder::{FeeBuilder, TransactionTypeBuilder, TransferBuilder},
         Transaction
     }
 };
@@ -18,13 +19,17 @@ pub struct BuildTransactionParams {
     pub broadcast: bool,
     // Returns the TX in HEX format also
     #[serde(default = "default_false_value")]
-    pub tx_as_hex: bool
+    pub tx_as_hex: bool,
+    // Account expected by the request, it is rejected if it's not the active one
+    // All params having an account field behave the same, see `switch_account` in API.md
+    pub account: Option<u32>
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct EstimateFeesParams {
     #[serde(flatten)]
     pub tx_type: TransactionTypeBuilder,
+    pub account: Option<u32>
 }
 
 // :(
@@ -51,7 +56,8 @@ pub struct ListTransactionsParams {
     #[serde(default = "default_true_value")]
     pub accept_burn: bool,
     // Filter by extra data
-    pub query: Option<Query>
+    pub query: Option<Query>,
+    pub account: Option<u32>
 }
 
 #[derive(Serialize, Deserialize)]
@@ -71,7 +77,8 @@ pub struct GetAssetPrecisionParams<'a> {
 pub struct GetAddressParams {
     // Data to use for creating an integrated address
     // Returned address will contains all the data provided here
-    pub integrated_data: Option<DataElement>
+    pub integrated_data: Option<DataElement>,
+    pub account: Option<u32>
 }
 
 #[derive(Serialize, Deserialize)]
@@ -90,12 +97,33 @@ pub struct SetOnlineModeParams {
 
 #[derive(Serialize, Deserialize)]
 pub struct GetBalanceParams {
-    pub asset: Option<Hash>
+    pub asset: Option<Hash>,
+    pub account: Option<u32>
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct GetTransactionParams {
-    pub hash: Hash
+    pub hash: Hash,
+    pub account: Option<u32>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CreateAccountParams {
+    // Optional name to identify the account
+    pub name: Option<String>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SwitchAccountParams {
+    pub account: u32
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AccountEntry {
+    // Index used to derive the account keys from the seed
+    pub index: u32,
+    pub name: Option<String>,
+    pub address: Address
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -174,6 +202,15 @@ pub enum NotifyEvent {
     Online,
     // Same here
     Offline,
+    // When an invoice received a payment or expired
+    // Contains an InvoiceEntry as value
+    InvoiceUpdated,
+    // When a submitted transaction changed of status
+    // Contains a PendingTransactionEntry as value
+    PendingTransactionUpdated,
+    // When a scheduled payment was executed or skipped
+    // Contains a ScheduledPaymentEntry as value
+    ScheduledPaymentRun,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -241,4 +278,227 @@ pub struct EstimateExtraDataSizeParams {
 pub struct EstimateExtraDataSizeResult {
     // Integrated data size
     pub size: usize,
-}
\ No newline at end of file
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ContactEntry {
+    pub label: String,
+    // Address of the contact in normal format
+    pub address: Address,
+    // Data integrated in the address when sending to this contact
+    pub integrated_data: Option<DataElement>,
+    pub notes: Option<String>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AddContactParams {
+    pub label: String,
+    // Integrated data is saved with the contact
+    pub address: Address,
+    pub notes: Option<String>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ContactLabelParams {
+    pub label: String
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Json
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ExportHistoryParams {
+    #[serde(default)]
+    pub format: ExportFormat,
+    // Same filters as for listing the transactions
+    #[serde(flatten)]
+    pub filters: ListTransactionsParams
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ExportHistoryResult {
+    // Number of records exported
+    pub count: usize,
+    // Exported history in the requested format
+    pub content: String
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum InvoiceStatus {
+    // No payment received yet
+    Pending,
+    // Some payments were received but not enough to pay the whole amount
+    PartiallyPaid,
+    Paid,
+    // Expiration time was reached before being paid
+    Expired
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct InvoiceEntry {
+    pub id: String,
+    pub asset: Hash,
+    // Amount requested in atomic units
+    pub amount: u64,
+    // Amount received in atomic units
+    pub received: u64,
+    pub memo: Option<String>,
+    pub created_at: TimestampSeconds,
+    pub expire_at: Option<TimestampSeconds>,
+    pub status: InvoiceStatus,
+    // Transactions that paid this invoice
+    pub transactions: Vec<Hash>,
+    // Highest topoheight of the transactions that paid it
+    // Payments can still be reverted until it is stable
+    pub last_payment_topoheight: Option<u64>,
+    // Integrated address with the invoice ID to use for the payment
+    pub address: Address,
+    // Payment request URI to share with the payer
+    pub uri: String
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CreateInvoiceParams {
+    // Generated if not provided
+    pub id: Option<String>,
+    // By default, XELIS asset is used
+    pub asset: Option<Hash>,
+    pub amount: u64,
+    pub memo: Option<String>,
+    // Invoice expires after this delay in seconds
+    pub expire_in: Option<u64>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetInvoiceParams {
+    pub id: String
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ListInvoicesParams {
+    // Filter the invoices by their status
+    pub status: Option<InvoiceStatus>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ParsePaymentRequestParams<'a> {
+    pub uri: Cow<'a, str>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PaymentRequestResult {
+    pub address: Address,
+    pub asset: Option<Hash>,
+    pub amount: Option<u64>,
+    pub invoice_id: Option<String>,
+    pub memo: Option<String>,
+    // Extra data to set in the transfer to pay the invoice
+    pub extra_data: Option<DataElement>
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PendingTransactionStatus {
+    // Submitted to the daemon and waiting to be included in a block
+    Pending,
+    // Executed in a block that is not stable yet
+    Executed,
+    // Executed in a stable block, it is not tracked anymore
+    Confirmed,
+    // Rejected by the daemon when rebroadcasting it, it is not tracked anymore
+    Invalid
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PendingTransactionEntry {
+    pub hash: Hash,
+    pub nonce: u64,
+    pub status: PendingTransactionStatus,
+    pub submitted_at: TimestampSeconds,
+    // Topoheight of the block that executed it
+    pub topoheight: Option<u64>,
+    // How many times it was broadcasted again
+    pub rebroadcasts: u32
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentSchedule {
+    // Run every N topoheights
+    Topoheight {
+        interval: u64
+    },
+    // Run using a cron expression evaluated in UTC
+    Cron {
+        expression: String
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SchedulePaymentParams {
+    pub destination: Address,
+    // By default, XELIS asset is used
+    pub asset: Option<Hash>,
+    pub amount: u64,
+    pub extra_data: Option<DataElement>,
+    pub schedule: PaymentSchedule,
+    // Scheduled payments only run for the active account
+    pub account: Option<u32>
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ScheduledPaymentEntry {
+    pub id: u64,
+    pub destination: Address,
+    pub asset: Hash,
+    pub amount: u64,
+    pub extra_data: Option<DataElement>,
+    pub schedule: PaymentSchedule,
+    pub paused: bool,
+    // Topoheight or timestamp in seconds of the next run, depending on the schedule
+    pub next_run: u64,
+    // Count of transactions submitted
+    pub runs: u64,
+    // Count of runs skipped because the transaction couldn't be built or submitted
+    pub skipped: u64,
+    // Transaction submitted by the last run
+    pub last_tx: Option<Hash>,
+    // Reason of the last run being skipped
+    pub last_error: Option<String>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ScheduledPaymentParams {
+    pub id: u64
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BatchPayoutParams {
+    // Transfers to send, they can use different assets
+    pub transfers: Vec<TransferBuilder>,
+    // Fee builder used for each transaction
+    pub fee: Option<FeeBuilder>,
+    pub account: Option<u32>
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BatchPayoutRecipient {
+    // Transaction that includes this transfer
+    pub tx_hash: Option<Hash>,
+    // Reason of the transfer not being sent
+    pub error: Option<String>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BatchPayoutResult {
+    // Hashes of all transactions submitted
+    pub transactions: Vec<Hash>,
+    // Result for each transfer in the same order as requested
+    pub recipients: Vec<BatchPayoutRecipient>
+}
@@ -119,6 +119,11 @@ impl<'a> Worker<'a> {
         self.work.take().map(|(work, _)| work)
     }
 
+    // Get the current work
+    pub fn get_work(&self) -> Option<&MinerWork<'a>> {
+        self.work.as_ref().map(|(work, _)| work)
+    }
+
     // Switch the current context to a new work
     pub fn set_work(&mut self, work: MinerWork<'a>, kind: Algorithm) -> Result<(), WorkerError> {
         // Check if the algorithm changed or if it must be initialized
@@ -11,12 +11,19 @@ use crate::{
 };
 use super::{
     bech32::{Bech32Error, encode, convert_bits, decode},
+    Hash,
     PublicKey
 };
 use core::fmt;
 use log::debug;
 use serde::de::Error as SerdeError;
 use anyhow::Error;
+use thiserror::Error as ThisError;
+
+// Scheme used by the payment request URIs
+pub const PAYMENT_URI_SCHEME: &str = "vyri";
+// Key used in the extra data to identify an invoice
+pub const INVOICE_ID_KEY: &str = "invoice_id";
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum AddressType {
@@ -169,6 +176,180 @@ impl Address {
     }
 }
 
+#[derive(ThisError, Debug, PartialEq, Eq)]
+pub enum PaymentRequestError {
+    #[error("Invalid scheme, expected {}", PAYMENT_URI_SCHEME)]
+    InvalidScheme,
+    #[error("Invalid address in payment request")]
+    InvalidAddress,
+    #[error("Invalid value for parameter {}", _0)]
+    InvalidParameter(String),
+    #[error("Parameter {} is set several times", _0)]
+    DuplicatedParameter(String),
+    #[error("Invalid percent encoding")]
+    InvalidEncoding
+}
+
+// Payment request shared as an URI
+// Format: vyri:<address>?asset=<hash>&amount=<atomic units>&invoice=<id>&memo=<text>
+// All parameters are optional, unknown ones are ignored
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PaymentRequest {
+    pub address: Address,
+    pub asset: Option<Hash>,
+    // Amount in atomic units
+    pub amount: Option<u64>,
+    pub invoice_id: Option<String>,
+    pub memo: Option<String>
+}
+
+// Percent encode all characters except the unreserved ones
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte))
+        }
+    }
+    encoded
+}
+
+fn percent_decode(value: &str) -> Result<String, PaymentRequestError> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes.get(i + 1..i + 3).ok_or(PaymentRequestError::InvalidEncoding)?;
+            let hex = std::str::from_utf8(hex).map_err(|_| PaymentRequestError::InvalidEncoding)?;
+            decoded.push(u8::from_str_radix(hex, 16).map_err(|_| PaymentRequestError::InvalidEncoding)?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded).map_err(|_| PaymentRequestError::InvalidEncoding)
+}
+
+impl PaymentRequest {
+    pub fn new(address: Address) -> Self {
+        Self {
+            address,
+            asset: None,
+            amount: None,
+            invoice_id: None,
+            memo: None
+        }
+    }
+
+    // Extra data to attach to the transfer so the receiver can match the invoice
+    pub fn get_extra_data(&self) -> Option<DataElement> {
+        self.invoice_id.as_ref().map(|id| {
+            let mut fields = std::collections::HashMap::new();
+            fields.insert(DataValue::String(INVOICE_ID_KEY.to_owned()), DataElement::Value(DataValue::String(id.clone())));
+            DataElement::Fields(fields)
+        })
+    }
+
+    // Generate the URI of this payment request
+    pub fn to_uri(&self) -> String {
+        let mut params = Vec::new();
+        if let Some(asset) = &self.asset {
+            params.push(format!("asset={}", asset.to_hex()));
+        }
+        if let Some(amount) = self.amount {
+            params.push(format!("amount={}", amount));
+        }
+        if let Some(id) = &self.invoice_id {
+            params.push(format!("invoice={}", percent_encode(id)));
+        }
+        if let Some(memo) = &self.memo {
+            params.push(format!("memo={}", percent_encode(memo)));
+        }
+
+        let mut uri = format!("{}:{}", PAYMENT_URI_SCHEME, self.address);
+        if !params.is_empty() {
+            uri.push('?');
+            uri.push_str(&params.join("&"));
+        }
+        uri
+    }
+
+    // Parse a payment request from its URI
+    pub fn from_uri(uri: &str) -> Result<Self, PaymentRequestError> {
+        let (path, query) = match uri.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (uri, None)
+        };
+
+        let rest = path.strip_prefix(PAYMENT_URI_SCHEME)
+            .and_then(|rest| rest.strip_prefix(':'))
+            .ok_or(PaymentRequestError::InvalidScheme)?;
+
+        // Mainnet addresses already start with the scheme, accept them without repeating it
+        let address = Address::from_string(&rest.to_owned())
+            .or_else(|_| Address::from_string(&path.to_owned()))
+            .map_err(|_| PaymentRequestError::InvalidAddress)?;
+
+        let mut request = Self::new(address);
+        for param in query.into_iter().flat_map(|query| query.split('&')).filter(|param| !param.is_empty()) {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            let invalid = || PaymentRequestError::InvalidParameter(key.to_owned());
+            let duplicated = || PaymentRequestError::DuplicatedParameter(key.to_owned());
+            match key {
+                "asset" => {
+                    if request.asset.is_some() {
+                        return Err(duplicated())
+                    }
+                    let bytes = hex::decode(value).map_err(|_| invalid())?;
+                    request.asset = Some(Hash::from_bytes(&bytes).map_err(|_| invalid())?);
+                },
+                "amount" => {
+                    if request.amount.is_some() {
+                        return Err(duplicated())
+                    }
+                    request.amount = Some(value.parse().map_err(|_| invalid())?);
+                },
+                "invoice" => {
+                    if request.invoice_id.is_some() {
+                        return Err(duplicated())
+                    }
+                    let id = percent_decode(value)?;
+                    if id.is_empty() {
+                        return Err(invalid())
+                    }
+                    request.invoice_id = Some(id);
+                },
+                "memo" => {
+                    if request.memo.is_some() {
+                        return Err(duplicated())
+                    }
+                    request.memo = Some(percent_decode(value)?);
+                },
+                _ => debug!("Ignoring unknown parameter {} in payment request", key)
+            }
+        }
+
+        Ok(request)
+    }
+}
+
+impl FromStr for PaymentRequest {
+    type Err = PaymentRequestError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_uri(s)
+    }
+}
+
+impl Display for PaymentRequest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_uri())
+    }
+}
+
 impl FromStr for Address {
     type Err = Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -252,9 +433,9 @@ impl Display for Address {
 
 #[cfg(test)]
 mod tests {
-    use crate::crypto::KeyPair;
+    use crate::crypto::{Hash, KeyPair};
 
-    use super::{Address, AddressType};
+    use super::{Address, AddressType, PaymentRequest, PaymentRequestError};
 
     #[test]
     fn test_serde() {
@@ -264,4 +445,36 @@ mod tests {
         let addr2: Address = Address::from_string(&v).unwrap();
         assert_eq!(addr, addr2);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_payment_request_uri() {
+        let (pub_key, _) = KeyPair::new().split();
+        let address = Address::new(false, AddressType::Normal, pub_key.compress());
+        let request = PaymentRequest {
+            address,
+            asset: Some(Hash::zero()),
+            amount: Some(100_000),
+            invoice_id: Some("order-42".to_owned()),
+            memo: Some("Coffee & cake".to_owned())
+        };
+
+        let uri = request.to_uri();
+        assert!(uri.starts_with("vyri:vyrt:"));
+        assert!(uri.contains("memo=Coffee%20%26%20cake"));
+        assert_eq!(PaymentRequest::from_uri(&uri).unwrap(), request);
+    }
+
+    #[test]
+    fn test_payment_request_invalid() {
+        let (pub_key, _) = KeyPair::new().split();
+        let address = Address::new(true, AddressType::Normal, pub_key.compress());
+
+        // mainnet address can be used directly as URI
+        let request = PaymentRequest::from_uri(&format!("{}?amount=5", address)).unwrap();
+        assert_eq!(request.amount, Some(5));
+
+        assert_eq!(PaymentRequest::from_uri(&format!("bitcoin:{}", address)), Err(PaymentRequestError::InvalidScheme));
+        assert_eq!(PaymentRequest::from_uri(&format!("vyri:{}?amount=abc", address)), Err(PaymentRequestError::InvalidParameter("amount".to_owned())));
+        assert_eq!(PaymentRequest::from_uri(&format!("vyri:{}?amount=1&amount=2", address)), Err(PaymentRequestError::DuplicatedParameter("amount".to_owned())));
+    }
+}
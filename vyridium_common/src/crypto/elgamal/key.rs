@@ -8,7 +8,7 @@ use zeroize::Zeroize;
 use crate::{
     api::DataElement,
     config::MAXIMUM_SUPPLY,
-    crypto::{Address, AddressType},
+    crypto::{hash, Address, AddressType},
     serializer::{
         Reader,
         ReaderError,
@@ -25,6 +25,9 @@ use super::{
     H
 };
 
+// Domain separator used to derive the child keys
+const CHILD_KEY_DOMAIN: &[u8] = b"vyridium-child-key";
+
 #[derive(Clone)]
 pub struct PublicKey(RistrettoPoint);
 
@@ -109,6 +112,24 @@ impl PrivateKey {
         &self.0
     }
 
+    // Derive deterministically a child private key using its index
+    // The same parent key and index always give the same child key
+    pub fn derive_child(&self, index: u32) -> Self {
+        let mut bytes = Vec::with_capacity(CHILD_KEY_DOMAIN.len() + 32 + 4);
+        bytes.extend_from_slice(CHILD_KEY_DOMAIN);
+        bytes.extend_from_slice(self.0.as_bytes());
+        bytes.extend_from_slice(&index.to_be_bytes());
+
+        let mut scalar = Scalar::from_bytes_mod_order(hash(&bytes).to_bytes());
+        // A zero scalar is not a valid key, hash again until we get a valid one
+        while scalar == Scalar::ZERO {
+            scalar = Scalar::from_bytes_mod_order(hash(scalar.as_bytes()).to_bytes());
+        }
+        bytes.zeroize();
+
+        Self(scalar)
+    }
+
     // Decrypt a Ciphertext to a point
     pub fn decrypt_to_point(&self, ciphertext: &Ciphertext) -> RistrettoPoint {
         let commitment = ciphertext.commitment().as_point();
@@ -218,6 +239,17 @@ mod tests {
         assert!(signature.verify(message, public_key));
     }
 
+    #[test]
+    fn test_derive_child() {
+        let keypair = KeyPair::new();
+        let private_key = keypair.get_private_key();
+
+        let child = private_key.derive_child(1);
+        assert_eq!(child.as_scalar(), private_key.derive_child(1).as_scalar());
+        assert_ne!(child.as_scalar(), private_key.derive_child(2).as_scalar());
+        assert_ne!(child.as_scalar(), private_key.as_scalar());
+    }
+
     #[test]
     fn test_encrypt_decrypt() {
         let keypair = KeyPair::new();
@@ -46,11 +46,13 @@ use anyhow::Error;
 use fern::colors::{ColoredLevelConfig, Color};
 use regex::Regex;
 use log::{info, error, Level, debug, LevelFilter, warn};
+use serde::{Deserialize, Deserializer};
 use thiserror::Error;
 
 // used for launch param
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
 #[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
 pub enum LogLevel {
     Off,
     Error,
@@ -80,6 +82,14 @@ impl FromStr for ModuleConfig {
     }
 }
 
+// Same format as the launch param: module=level
+impl<'de> Deserialize<'de> for ModuleConfig {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 impl From<LogLevel> for LevelFilter {
     fn from(value: LogLevel) -> Self {
         match value {
@@ -10,7 +10,7 @@ use super::{
 use crate::{
     account::CiphertextCache,
     api::DataElement,
-    config::VYRIDIUM_ASSET,
+    config::{FEE_PER_KB, VYRIDIUM_ASSET},
     crypto::{
         elgamal::{
             Ciphertext, CompressedPublicKey, DecryptHandle, KeyPair, PedersenCommitment,
@@ -23,7 +23,7 @@ use crate::{
         Address, Hash, ProtocolTranscript, HASH_SIZE, SIGNATURE_SIZE,
     },
     serializer::{Reader, ReaderError, Serializer, Writer},
-    utils::calculate_tx_fee,
+    utils::calculate_tx_fee_with_fee_per_kb,
 };
 use bulletproofs::RangeProof;
 use curve25519_dalek::Scalar;
@@ -59,6 +59,8 @@ pub enum FeeBuilder {
     // calculate tx fees based on its size and multiply by this value
     Multiplier(f64),
     Value(u64), // set a direct value of how much fees you want to pay
+    // use the fee rate estimated to be included within this blocks count
+    Target(u64),
 }
 
 impl Default for FeeBuilder {
@@ -75,6 +77,12 @@ pub trait FeeHelper {
         1f64
     }
 
+    /// Get the fee per KB estimated to be included within the requested blocks count
+    /// The minimum fee per KB is used if no estimation is available
+    fn get_fee_per_kb_for_target(&self, _blocks: u64) -> Option<u64> {
+        None
+    }
+
     /// Verify if the account exists or if we should pay more fees for account creation
     fn account_exists(&self, account: &CompressedPublicKey) -> Result<bool, Self::Error>;
 }
@@ -324,35 +332,50 @@ impl TransactionBuilder {
     ) -> Result<u64, GenerationError<B::Error>> {
         let calculated_fee = match self.fee_builder {
             FeeBuilder::Multiplier(multiplier) => {
-                // Compute the size and transfers count
-                let size = self.estimate_size();
-                let (transfers, new_addresses) =
-                    if let TransactionTypeBuilder::Transfers(transfers) = &self.data {
-                        let mut new_addresses = 0;
-                        for transfer in transfers {
-                            if !state
-                                .account_exists(&transfer.destination.get_public_key())
-                                .map_err(GenerationError::State)?
-                            {
-                                new_addresses += 1;
-                            }
-                        }
-
-                        (transfers.len(), new_addresses)
-                    } else {
-                        (0, 0)
-                    };
-
-                let expected_fee = calculate_tx_fee(size, transfers, new_addresses);
+                let expected_fee = self.calculate_fee(state, FEE_PER_KB)?;
                 (expected_fee as f64 * multiplier) as u64
             }
             // If the value is set, use it
             FeeBuilder::Value(value) => value,
+            FeeBuilder::Target(blocks) => {
+                // Never go below the minimum fee per KB
+                let fee_per_kb = state
+                    .get_fee_per_kb_for_target(blocks)
+                    .map_or(FEE_PER_KB, |fee_per_kb| fee_per_kb.max(FEE_PER_KB));
+                self.calculate_fee(state, fee_per_kb)?
+            }
         };
 
         Ok(calculated_fee)
     }
 
+    // Compute the fees based on the estimated size and the transfers count
+    fn calculate_fee<B: FeeHelper>(
+        &self,
+        state: &mut B,
+        fee_per_kb: u64,
+    ) -> Result<u64, GenerationError<B::Error>> {
+        let size = self.estimate_size();
+        let (transfers, new_addresses) =
+            if let TransactionTypeBuilder::Transfers(transfers) = &self.data {
+                let mut new_addresses = 0;
+                for transfer in transfers {
+                    if !state
+                        .account_exists(&transfer.destination.get_public_key())
+                        .map_err(GenerationError::State)?
+                    {
+                        new_addresses += 1;
+                    }
+                }
+
+                (transfers.len(), new_addresses)
+            } else {
+                (0, 0)
+            };
+
+        Ok(calculate_tx_fee_with_fee_per_kb(size, transfers, new_addresses, fee_per_kb))
+    }
+
     fn get_new_source_ct(
         &self,
         mut ct: Ciphertext,
@@ -51,6 +51,12 @@ pub fn from_coin(value: impl Into<String>, coin_decimals: u8) -> Option<u64> {
 // Sending to a newly created address will increase the fee
 // Each transfers output will also increase the fee
 pub fn calculate_tx_fee(tx_size: usize, output_count: usize, new_addresses: usize) -> u64 {
+    calculate_tx_fee_with_fee_per_kb(tx_size, output_count, new_addresses, FEE_PER_KB)
+}
+
+// Same as calculate_tx_fee but with a custom fee per KB
+// This is used to pay more than the minimum to be included faster
+pub fn calculate_tx_fee_with_fee_per_kb(tx_size: usize, output_count: usize, new_addresses: usize, fee_per_kb: u64) -> u64 {
     let mut size_in_kb = tx_size as u64 / 1024;
 
     if tx_size % 1024 != 0 {
@@ -58,7 +64,7 @@ pub fn calculate_tx_fee(tx_size: usize, output_count: usize, new_addresses: usiz
         size_in_kb += 1;
     }
 
-    size_in_kb * FEE_PER_KB
+    size_in_kb * fee_per_kb
         + output_count as u64 * FEE_PER_TRANSFER
         + new_addresses as u64 * FEE_PER_ACCOUNT_CREATION
 }
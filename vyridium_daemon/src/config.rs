@@ -37,6 +37,10 @@ pub const OTHER_MINIMUM_DIFFICULTY: Difficulty = Difficulty::from_u64(BLOCK_TIME
 pub const GENESIS_BLOCK_DIFFICULTY: Difficulty = Difficulty::from_u64(1);
 // 1024 * 1024 + (256 * 1024) bytes = 1.25 MB maximum size per block with txs
 pub const MAX_BLOCK_SIZE: usize = (1024 * 1024) + (256 * 1024);
+// Count of recent blocks used to estimate the fee rates
+pub const FEE_ESTIMATION_BLOCKS: u64 = 20;
+// Default blocks count targeted by the fee rate estimation
+pub const FEE_ESTIMATION_DEFAULT_TARGET_BLOCKS: u64 = 3;
 // 2 seconds maximum in future (prevent any attack on reducing difficulty but keep margin for unsynced devices)
 pub const TIMESTAMP_IN_FUTURE_LIMIT: TimestampSeconds = 2 * 1000;
 
@@ -130,6 +134,10 @@ pub const P2P_PING_PEER_LIST_LIMIT: usize = 16;
 pub const P2P_DEFAULT_MAX_PEERS: usize = 32;
 // time in seconds between each time we try to connect to a new peer
 pub const P2P_EXTEND_PEERLIST_DELAY: u64 = 60;
+// maximum outgoing connections to peers in the same network group (IPv4 /16 or IPv6 /32)
+pub const P2P_MAX_OUTGOING_PEERS_PER_GROUP: usize = 2;
+// maximum outgoing peers saved on shutdown to be reconnected first
+pub const P2P_MAX_ANCHOR_PEERS: usize = 4;
 // Peer wait on error accept new p2p connections in seconds
 pub const P2P_PEER_WAIT_ON_ERROR: u64 = 15;
 // Delay in second to connect to priority nodes
@@ -168,6 +176,11 @@ pub const PEER_TIMEOUT_INIT_OUTGOING_CONNECTION: u64 = 30_000;
 pub const PEER_TIMEOUT_DISCONNECT: u64 = 1_500;
 // 16 additional bytes are for AEAD from ChaCha20Poly1305
 pub const PEER_MAX_PACKET_SIZE: u32 = MAX_BLOCK_SIZE as u32 + 16;
+// Max size of a packet once decrypted
+// Batched packets are split to never exceed it
+pub const PEER_MAX_PACKET_CONTENT_SIZE: usize = PEER_MAX_PACKET_SIZE as usize - 16;
+// Maximum objects that can be requested in a single packet
+pub const PEER_MAX_OBJECTS_PER_REQUEST: usize = 64;
 // Peer TX cache size
 // This is how many elements are stored in the LRU cache at maximum
 pub const PEER_TX_CACHE_SIZE: usize = 10240;
@@ -59,12 +59,13 @@ use crate::{
         DEV_PUBLIC_KEY, EMISSION_SPEED_FACTOR, PREDEFLATION_STOP, PREDEFLATION_MULTIPLIER, GENESIS_BLOCK_DIFFICULTY, MAX_BLOCK_SIZE,
         MILLIS_PER_SECOND, P2P_DEFAULT_MAX_PEERS, SIDE_BLOCK_REWARD_MAX_BLOCKS, PRUNE_SAFETY_LIMIT,
         SIDE_BLOCK_REWARD_PERCENT, SIDE_BLOCK_REWARD_MIN_PERCENT, STABLE_LIMIT, TIMESTAMP_IN_FUTURE_LIMIT,
-        P2P_DEFAULT_CONCURRENCY_TASK_COUNT_LIMIT
+        P2P_DEFAULT_CONCURRENCY_TASK_COUNT_LIMIT, FEE_ESTIMATION_BLOCKS
     },
     core::{
         blockdag,
         difficulty,
         error::BlockchainError,
+        fee_estimator::{estimate_fee_rates, get_fee_per_kb, FeeRateEstimation},
         mempool::Mempool,
         nonce_checker::NonceChecker,
         simulator::Simulator,
@@ -73,7 +74,7 @@ use crate::{
         state::{ChainState, ApplicableChainState},
         hard_fork::*
     },
-    p2p::P2pServer,
+    p2p::{P2pServer, traffic::RateLimits},
     rpc::{
         rpc::{
             get_block_type_for_block,
@@ -91,15 +92,20 @@ use std::{
         HashSet,
         VecDeque
     },
+    fs,
+    io,
     net::SocketAddr,
     num::NonZeroUsize,
     sync::{
         atomic::{AtomicU64, Ordering},
         Arc
     },
-    time::Instant
+    time::{Duration, Instant}
+};
+use tokio::{
+    sync::{Mutex, RwLock},
+    task::JoinHandle
 };
-use tokio::{sync::{Mutex, RwLock}, net::lookup_host};
 use log::{info, error, debug, warn, trace};
 use rand::Rng;
 
@@ -143,6 +149,15 @@ pub struct Config {
     /// Disable GetWork Server (WebSocket for miners).
     #[clap(long)]
     pub disable_getwork_server: bool,
+    /// Share difficulty of the jobs sent to miners (GetWork and Stratum).
+    /// Shares below the network difficulty are validated and counted for the miner stats.
+    /// Miners can request their own share difficulty, it can't be lower than the minimum difficulty of the network.
+    #[clap(long)]
+    pub getwork_share_difficulty: Option<u64>,
+    /// Enable the Stratum server (TCP for pool-compatible miners) on this bind address.
+    /// It shares the jobs of the GetWork Server, so it can't be used with --disable-getwork-server.
+    #[clap(long)]
+    pub stratum_bind_address: Option<String>,
     /// Disable RPC Server
     /// This will also disable the GetWork Server as it is loaded on RPC server.
     #[clap(long)]
@@ -197,9 +212,72 @@ pub struct Config {
     /// This is useful for seed nodes under heavy load or for nodes that don't want to connect to others.
     #[clap(long)]
     pub disable_p2p_outgoing_connections: bool,
+    /// Disable the compact blocks propagation.
+    /// 
+    /// By default, blocks are propagated using short TX ids to peers supporting it
+    /// and are rebuilt from the mempool on reception.
+    #[clap(long)]
+    pub disable_compact_blocks: bool,
+    /// Route all outgoing P2P connections through a SOCKS5 proxy.
+    /// 
+    /// Expected format is socks5://[user:password@]host:port.
+    /// Use socks5h:// to also resolve the priority nodes hostnames through the proxy.
+    /// Peers reached through the proxy are never shared to others peers.
+    #[clap(long)]
+    pub p2p_proxy: Option<String>,
+    /// Maximum upload rate in bytes per second for all peers.
+    #[clap(long)]
+    pub p2p_max_upload_rate: Option<u64>,
+    /// Maximum upload rate in bytes per second for each peer.
+    #[clap(long)]
+    pub p2p_max_peer_upload_rate: Option<u64>,
+    /// Maximum chain and bootstrap chain requests per minute handled for all peers.
+    /// 
+    /// Requests over this limit are ignored.
+    #[clap(long)]
+    pub p2p_max_chain_requests: Option<u64>,
+    /// Maximum chain and bootstrap chain requests per minute handled for each peer.
+    /// 
+    /// Peers going over this limit are penalized.
+    #[clap(long)]
+    pub p2p_max_peer_chain_requests: Option<u64>,
     /// Limit of concurrent tasks accepting new incoming connections.
     #[clap(long, default_value_t = P2P_DEFAULT_CONCURRENCY_TASK_COUNT_LIMIT)]
-    pub p2p_concurrency_task_count_limit: usize
+    pub p2p_concurrency_task_count_limit: usize,
+    /// Disable the mempool persistence.
+    /// 
+    /// By default, the mempool is saved on disk when the daemon stops
+    /// and its transactions are verified again to be reloaded on the next start.
+    #[clap(long)]
+    pub disable_mempool_persistence: bool,
+    /// Interval in seconds to also save the mempool on disk while running.
+    #[clap(long)]
+    pub mempool_save_interval: Option<u64>,
+    /// Maximum count of transactions in mempool.
+    /// 
+    /// Once reached, the transactions with the lowest fee per byte are evicted.
+    #[clap(long)]
+    pub mempool_max_txs: Option<usize>,
+    /// Maximum total size in bytes of the transactions in mempool.
+    /// 
+    /// Once reached, the transactions with the lowest fee per byte are evicted.
+    #[clap(long)]
+    pub mempool_max_size: Option<usize>
+}
+
+// Options to customize a block template
+#[derive(Default)]
+pub struct BlockTemplateOptions {
+    // Maximum size in bytes of the block, capped to MAX_BLOCK_SIZE
+    pub max_size: Option<usize>,
+    // Transactions that must not be included
+    pub exclude_txs: HashSet<Hash>,
+    // Transactions to include first, with their previous nonces
+    pub prioritize_txs: HashSet<Hash>,
+    // Minimum fee per byte for a transaction to be included
+    pub min_fee_per_byte: Option<u64>,
+    // Tips to build the block on instead of the best ones
+    pub tips: Option<IndexSet<Hash>>
 }
 
 pub struct Blockchain<S: Storage> {
@@ -214,6 +292,10 @@ pub struct Blockchain<S: Storage> {
     stable_topoheight: AtomicU64,
     // mempool to retrieve/add all txs
     mempool: RwLock<Mempool>,
+    // file where the mempool is saved, None if persistence is disabled
+    mempool_path: Option<String>,
+    // task saving the mempool periodically
+    mempool_save_task: Mutex<Option<JoinHandle<()>>>,
     // storage to retrieve/add blocks
     storage: RwLock<S>,
     // P2p module
@@ -271,6 +353,10 @@ impl<S: Storage> Blockchain<S> {
             if config.skip_pow_verification {
                 warn!("PoW verification is disabled! This is dangerous in production!");
             }
+
+            if config.disable_mempool_persistence && config.mempool_save_interval.is_some() {
+                warn!("Mempool persistence is disabled, mempool save interval will be ignored");
+            }
         }
 
         let on_disk = storage.has_blocks().await;
@@ -288,7 +374,9 @@ impl<S: Storage> Blockchain<S> {
             topoheight: AtomicU64::new(topoheight),
             stable_height: AtomicU64::new(0),
             stable_topoheight: AtomicU64::new(0),
-            mempool: RwLock::new(Mempool::new(network)),
+            mempool: RwLock::new(Mempool::new(network, config.mempool_max_txs, config.mempool_max_size)),
+            mempool_path: (!config.disable_mempool_persistence).then(|| format!("{}mempool-{}.bin", config.dir_path.as_deref().unwrap_or_default(), network.to_string().to_lowercase())),
+            mempool_save_task: Mutex::new(None),
             storage: RwLock::new(storage),
             p2p: RwLock::new(None),
             rpc: RwLock::new(None),
@@ -325,7 +413,27 @@ impl<S: Storage> Blockchain<S> {
             blockchain.stable_topoheight.store(stable_topoheight, Ordering::SeqCst);
         }
 
+        // reload the TXs saved before the last stop
+        blockchain.load_mempool_from_disk().await;
+
         let arc = Arc::new(blockchain);
+
+        // save the mempool periodically
+        if let (Some(interval), Some(_)) = (config.mempool_save_interval, arc.mempool_path.as_ref()) {
+            let blockchain = Arc::clone(&arc);
+            let handle = spawn_task("mempool-save", async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(interval.max(1)));
+                // first tick is immediate
+                interval.tick().await;
+                loop {
+                    interval.tick().await;
+                    if let Err(e) = blockchain.save_mempool_to_disk().await {
+                        error!("Error while saving mempool on disk: {}", e);
+                    }
+                }
+            });
+            *arc.mempool_save_task.lock().await = Some(handle);
+        }
         // create P2P Server
         if !config.disable_p2p_server {
             info!("Starting P2p server...");
@@ -344,7 +452,13 @@ impl<S: Storage> Blockchain<S> {
                 }
             }
 
-            match P2pServer::new(config.p2p_concurrency_task_count_limit, config.dir_path, config.tag, config.max_peers, config.p2p_bind_address, Arc::clone(&arc), exclusive_nodes.is_empty(), exclusive_nodes, config.allow_fast_sync, config.allow_boost_sync, config.max_chain_response_size, !config.disable_ip_sharing, config.disable_p2p_outgoing_connections) {
+            let rate_limits = RateLimits {
+                upload: config.p2p_max_upload_rate,
+                peer_upload: config.p2p_max_peer_upload_rate,
+                chain_requests: config.p2p_max_chain_requests,
+                peer_chain_requests: config.p2p_max_peer_chain_requests
+            };
+            match P2pServer::new(config.p2p_concurrency_task_count_limit, config.dir_path, config.tag, config.max_peers, config.p2p_bind_address, Arc::clone(&arc), exclusive_nodes.is_empty(), exclusive_nodes, config.allow_fast_sync, config.allow_boost_sync, config.max_chain_response_size, !config.disable_ip_sharing, config.disable_p2p_outgoing_connections, config.disable_compact_blocks, config.p2p_proxy, rate_limits) {
                 Ok(p2p) => {
                     // connect to priority nodes
                     for addr in config.priority_nodes {
@@ -352,18 +466,10 @@ impl<S: Storage> Blockchain<S> {
                             let addr: SocketAddr = match addr.parse() {
                                 Ok(addr) => addr,
                                 Err(e) => {
-                                    match lookup_host(&addr).await {
-                                        Ok(it) => {
-                                            info!("Valid host found for {}", addr);
-                                            for addr in it {
-                                                info!("Trying to connect to priority node with IP from DNS resolution: {}", addr);
-                                                p2p.try_to_connect_to_peer(addr, true).await;
-                                            }
-                                        },
-                                        Err(e2) => {
-                                            error!("Error while parsing priority node address: {}, {}", e, e2);
-                                        }
-                                    };
+                                    info!("Trying to connect to priority node using its hostname: {}", addr);
+                                    if let Err(e2) = p2p.try_to_connect_to_host(addr, true).await {
+                                        error!("Error while parsing priority node address: {}, {}", e, e2);
+                                    }
                                     continue;
                                 }
                             };
@@ -380,7 +486,7 @@ impl<S: Storage> Blockchain<S> {
         // create RPC Server
         if !config.disable_rpc_server {
             info!("RPC Server will listen on: {}", config.rpc_bind_address);
-            match DaemonRpcServer::new(config.rpc_bind_address, Arc::clone(&arc), config.disable_getwork_server).await {
+            match DaemonRpcServer::new(config.rpc_bind_address, Arc::clone(&arc), config.disable_getwork_server, config.getwork_share_difficulty, config.stratum_bind_address).await {
                 Ok(server) => *arc.rpc.write().await = Some(server),
                 Err(e) => error!("Error while starting RPC server: {}", e)
             };
@@ -413,6 +519,10 @@ impl<S: Storage> Blockchain<S> {
     // So no deadlock occurs in case they are linked
     pub async fn stop(&self) {
         info!("Stopping modules...");
+        if let Some(handle) = self.mempool_save_task.lock().await.take() {
+            handle.abort();
+        }
+
         {
             let mut p2p = self.p2p.write().await;
             if let Some(p2p) = p2p.take() {
@@ -435,6 +545,10 @@ impl<S: Storage> Blockchain<S> {
         }
 
         {
+            if let Err(e) = self.save_mempool_to_disk().await {
+                error!("Error while saving mempool on disk: {}", e);
+            }
+
             let mut mempool = self.mempool.write().await;
             mempool.stop().await;
         }
@@ -442,6 +556,70 @@ impl<S: Storage> Blockchain<S> {
         info!("All modules are now stopped!");
     }
 
+    // Save all TXs from mempool on disk to reload them on next start
+    pub async fn save_mempool_to_disk(&self) -> Result<(), BlockchainError> {
+        let Some(path) = self.mempool_path.as_ref() else {
+            return Ok(())
+        };
+
+        let (count, bytes) = {
+            let mempool = self.mempool.read().await;
+            (mempool.size(), mempool.to_persisted_bytes())
+        };
+
+        // write in a temporary file first to never corrupt the previous one
+        let tmp_path = format!("{}.tmp", path);
+        fs::write(&tmp_path, bytes)?;
+        fs::rename(&tmp_path, path)?;
+        debug!("{} TXs from mempool saved in {}", count, path);
+
+        Ok(())
+    }
+
+    // Reload the TXs saved on disk in mempool
+    // Each TX is verified again with the current chain state and dropped if invalid
+    async fn load_mempool_from_disk(&self) {
+        let Some(path) = self.mempool_path.as_ref() else {
+            return
+        };
+
+        let bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return,
+            Err(e) => {
+                warn!("Error while reading mempool from {}: {}", path, e);
+                return
+            }
+        };
+
+        let mut txs = match Mempool::read_persisted_bytes(&bytes) {
+            Ok(txs) => txs,
+            Err(e) => {
+                warn!("Error while decoding mempool from {}: {}", path, e);
+                return
+            }
+        };
+
+        // TXs from the same account must be added by nonce order
+        txs.sort_by_key(|(tx, _)| tx.get_nonce());
+
+        let total = txs.len();
+        let mut loaded = 0;
+        let storage = self.storage.read().await;
+        for (tx, first_seen) in txs {
+            let hash = tx.hash();
+            if let Err(e) = self.add_tx_to_mempool_with_storage_and_hash(&storage, Arc::new(tx), hash.clone(), false).await {
+                debug!("Dropping TX {} from saved mempool: {}", hash, e);
+                continue;
+            }
+
+            self.mempool.write().await.set_first_seen(&hash, first_seen);
+            loaded += 1;
+        }
+
+        info!("{} TXs reloaded in mempool from disk ({} dropped)", loaded, total - loaded);
+    }
+
     // Reload the storage and update all cache values
     // Clear the mempool also in case of not being up-to-date
     pub async fn reload_from_disk(&self) -> Result<(), BlockchainError> {
@@ -664,6 +842,37 @@ impl<S: Storage> Blockchain<S> {
         self.mempool.read().await.size()
     }
 
+    // Get the minimum fee per byte required for a TX to enter the mempool
+    pub async fn get_mempool_min_fee_per_byte(&self) -> u64 {
+        self.mempool.read().await.get_min_fee_per_byte()
+    }
+
+    // Estimate the fee rates per KB based on the mempool depth
+    // and the fee rates of the TXs included in the recent blocks
+    pub async fn estimate_fee_rates(&self, target_blocks: u64) -> Result<FeeRateEstimation, BlockchainError> {
+        let storage = self.storage.read().await;
+        let topoheight = self.get_topo_height();
+        let pruned_topoheight = storage.get_pruned_topoheight().await?.unwrap_or(0);
+        let start = topoheight.saturating_sub(FEE_ESTIMATION_BLOCKS - 1).max(pruned_topoheight);
+
+        let mut recent_rates = Vec::new();
+        for topoheight in start..=topoheight {
+            let hash = storage.get_hash_at_topo_height(topoheight).await?;
+            let block = storage.get_block_by_hash(&hash).await?;
+            for tx in block.get_transactions() {
+                recent_rates.push(get_fee_per_kb(tx.get_fee(), tx.size()));
+            }
+        }
+
+        let mempool = self.mempool.read().await;
+        let txs = mempool.get_txs().values()
+            .map(|sorted_tx| (get_fee_per_kb(sorted_tx.get_fee(), sorted_tx.get_size()), sorted_tx.get_size()))
+            .collect();
+        let min_fee_per_kb = mempool.get_min_fee_per_byte().saturating_mul(1024);
+
+        Ok(estimate_fee_rates(txs, recent_rates, target_blocks, min_fee_per_kb))
+    }
+
     // Get the current top block hash in chain
     pub async fn get_top_block_hash(&self) -> Result<Hash, BlockchainError> {
         let storage = self.storage.read().await;
@@ -1308,7 +1517,7 @@ impl<S: Storage> Blockchain<S> {
             return Err(BlockchainError::TxTooBig(tx_size, MAX_TRANSACTION_SIZE))
         }
 
-        {
+        let evicted_txs = {
             let mut mempool = self.mempool.write().await;
     
             if mempool.contains_tx(&hash) {
@@ -1338,8 +1547,48 @@ impl<S: Storage> Blockchain<S> {
                 }
             }
 
+            // once the mempool is full, the TX must pay more than the cheapest one
+            let min_fee_per_byte = mempool.get_min_fee_per_byte();
+            let required_fee = min_fee_per_byte.saturating_mul(tx_size as u64);
+            if tx.get_fee() < required_fee {
+                return Err(BlockchainError::TxFeeTooLowForMempool(hash, tx.get_fee(), required_fee))
+            }
+
             let version = get_version_at_height(self.get_network(), self.get_height());
             mempool.add_tx(storage, stable_topoheight, current_topoheight, hash.clone(), tx.clone(), tx_size, version).await?;
+            mempool.evict(storage, stable_topoheight, current_topoheight, version).await
+        };
+
+        if !evicted_txs.is_empty() {
+            debug!("{} TXs evicted from mempool", evicted_txs.len());
+            if let Some(rpc) = self.rpc.read().await.as_ref() {
+                if rpc.is_event_tracked(&NotifyEvent::TransactionOrphaned).await {
+                    let events: Vec<Value> = evicted_txs.iter().map(|(tx_hash, sorted_tx)| {
+                        let data = RPCTransaction::from_tx(sorted_tx.get_tx(), tx_hash, storage.is_mainnet());
+                        json!(TransactionResponse {
+                            blocks: None,
+                            executed_in_block: None,
+                            in_mempool: false,
+                            first_seen: Some(sorted_tx.get_first_seen()),
+                            data,
+                        })
+                    }).collect();
+
+                    let rpc = rpc.clone();
+                    spawn_task("rpc-notify-evicted-txs", async move {
+                        for event in events {
+                            if let Err(e) = rpc.notify_clients(&NotifyEvent::TransactionOrphaned, event).await {
+                                debug!("Error while broadcasting event TransactionOrphaned to websocket: {}", e);
+                            }
+                        }
+                    });
+                }
+            }
+
+            // the TX was the cheapest one or depends on it
+            if evicted_txs.iter().any(|(tx_hash, _)| tx_hash.as_ref() == &hash) {
+                return Err(BlockchainError::MempoolFull(hash))
+            }
         }
 
         if broadcast {
@@ -1436,8 +1685,77 @@ impl<S: Storage> Blockchain<S> {
 
     // Generate a block header template without transactions
     pub async fn get_block_header_template_for_storage(&self, storage: &S, address: PublicKey) -> Result<BlockHeader, BlockchainError> {
+        self.get_block_header_template_with_tips(storage, address, None).await
+    }
+
+    // Generate a block header template without transactions on the requested tips
+    // If no tips are requested, the best ones are selected
+    async fn get_block_header_template_with_tips(&self, storage: &S, address: PublicKey, requested_tips: Option<&IndexSet<Hash>>) -> Result<BlockHeader, BlockchainError> {
         trace!("get block header template");
         let extra_nonce: [u8; EXTRA_NONCE_SIZE] = rand::thread_rng().gen::<[u8; EXTRA_NONCE_SIZE]>(); // generate random bytes
+        let current_height = self.get_height();
+        let tips = match requested_tips {
+            Some(tips) => self.verify_template_tips(storage, tips, current_height).await?,
+            None => self.select_template_tips(storage, current_height).await?
+        };
+
+        let mut sorted_tips = blockdag::sort_tips(storage, tips.into_iter()).await?;
+        if sorted_tips.len() > TIPS_LIMIT {
+            let dropped_tips = sorted_tips.drain(TIPS_LIMIT..); // keep only first 3 heavier tips
+            debug!("Dropping tips {} because they are not in the first 3 heavier tips", dropped_tips.map(|h| h.to_string()).collect::<Vec<String>>().join(", "));
+        }
+
+        let height = blockdag::calculate_height_at_tips(storage, sorted_tips.iter()).await?;
+        let block = BlockHeader::new(get_version_at_height(self.get_network(), height), height, get_current_time_in_millis(), sorted_tips, extra_nonce, address, IndexSet::new());
+
+        Ok(block)
+    }
+
+    // Verify the tips requested for a block template
+    // They must follow the same rules as the tips selected by default
+    // Only the current tips or their direct parents are accepted,
+    // as the TXs of the template are verified against the current state
+    async fn verify_template_tips(&self, storage: &S, tips: &IndexSet<Hash>, current_height: u64) -> Result<Vec<Hash>, BlockchainError> {
+        if tips.is_empty() || tips.len() > TIPS_LIMIT {
+            return Err(BlockchainError::InvalidTemplateTipsCount(tips.len()))
+        }
+
+        for hash in tips {
+            if !storage.has_block_with_hash(hash).await? {
+                return Err(BlockchainError::TemplateTipNotFound(hash.clone()))
+            }
+        }
+
+        let mut allowed_tips = HashSet::new();
+        for tip in storage.get_tips().await? {
+            allowed_tips.extend(storage.get_past_blocks_for_block_hash(&tip).await?.iter().cloned());
+            allowed_tips.insert(tip);
+        }
+
+        let stable_height = self.get_stable_height();
+        let best_tip = blockdag::find_best_tip_by_cumulative_difficulty(storage, tips.iter()).await?.clone();
+        for hash in tips {
+            if !allowed_tips.contains(hash) {
+                debug!("Tip {} for block template is not a current tip or one of their parents", hash);
+                return Err(BlockchainError::InvalidTemplateTip(hash.clone()))
+            }
+
+            // the best tip is also verified as it can be the only one requested
+            if storage.get_height_for_block_hash(hash).await? < stable_height || !self.verify_distance_from_mainchain(storage, hash, current_height).await? {
+                debug!("Tip {} for block template is too far from mainchain at height {}", hash, current_height);
+                return Err(BlockchainError::InvalidTemplateTip(hash.clone()))
+            }
+
+            if *hash != best_tip && !self.validate_tips(storage, &best_tip, hash).await? {
+                return Err(BlockchainError::InvalidTemplateTip(hash.clone()))
+            }
+        }
+
+        Ok(tips.iter().cloned().collect())
+    }
+
+    // Select the best tips from storage for a block template
+    async fn select_template_tips(&self, storage: &S, current_height: u64) -> Result<Vec<Hash>, BlockchainError> {
         let tips_set = storage.get_tips().await?;
         let mut tips = Vec::with_capacity(tips_set.len());
         for hash in tips_set {
@@ -1445,7 +1763,6 @@ impl<S: Storage> Blockchain<S> {
             tips.push(hash);
         }
 
-        let current_height = self.get_height();
         if tips.len() > 1 {
             let best_tip = blockdag::find_best_tip_by_cumulative_difficulty(storage, tips.iter()).await?.clone();
             debug!("Best tip selected for this block template is {}", best_tip);
@@ -1472,23 +1789,21 @@ impl<S: Storage> Blockchain<S> {
             }
         }
 
-        let mut sorted_tips = blockdag::sort_tips(storage, tips.into_iter()).await?;
-        if sorted_tips.len() > TIPS_LIMIT {
-            let dropped_tips = sorted_tips.drain(TIPS_LIMIT..); // keep only first 3 heavier tips
-            debug!("Dropping tips {} because they are not in the first 3 heavier tips", dropped_tips.map(|h| h.to_string()).collect::<Vec<String>>().join(", "));
-        }
-
-        let height = blockdag::calculate_height_at_tips(storage, sorted_tips.iter()).await?;
-        let block = BlockHeader::new(get_version_at_height(self.get_network(), height), height, get_current_time_in_millis(), sorted_tips, extra_nonce, address, IndexSet::new());
-
-        Ok(block)
+        Ok(tips)
     }
 
     // Get the mining block template for miners
     // This function is called when a miner request a new block template
     // We create a block candidate with selected TXs from mempool
     pub async fn get_block_template_for_storage(&self, storage: &S, address: PublicKey) -> Result<BlockHeader, BlockchainError> {
-        let mut block = self.get_block_header_template_for_storage(storage, address).await?;
+        let (block, _) = self.get_custom_block_template_for_storage(storage, address, &BlockTemplateOptions::default()).await?;
+        Ok(block)
+    }
+
+    // Same as get_block_template_for_storage but using the requested options
+    // Returns the block template with the total fees of its transactions
+    pub async fn get_custom_block_template_for_storage(&self, storage: &S, address: PublicKey, options: &BlockTemplateOptions) -> Result<(BlockHeader, u64), BlockchainError> {
+        let mut block = self.get_block_header_template_with_tips(storage, address, options.tips.as_ref()).await?;
 
         trace!("Locking mempool for building block template");
         let mempool = self.mempool.read().await;
@@ -1509,11 +1824,13 @@ impl<S: Storage> Blockchain<S> {
         }
 
         // Build the tx selector using the mempool
-        let mut tx_selector = TxSelector::grouped(entries.into_iter());
+        let mut tx_selector = TxSelector::grouped_with_priority(entries.into_iter(), options.prioritize_txs.clone());
 
         // size of block
+        let max_block_size = options.max_size.map_or(MAX_BLOCK_SIZE, |size| size.min(MAX_BLOCK_SIZE));
         let mut block_size = block.size();
         let mut total_txs_size = 0;
+        let mut total_fees = 0;
 
         // data used to verify txs
         let stable_topoheight = self.get_stable_topoheight();
@@ -1523,7 +1840,7 @@ impl<S: Storage> Blockchain<S> {
 
         let mut failed_sources = HashSet::new();
         while let Some(TxSelectorEntry { size, hash, tx }) = tx_selector.next() {
-            if block_size + total_txs_size + size >= MAX_BLOCK_SIZE {
+            if block_size + total_txs_size + size >= max_block_size {
                 break;
             }
 
@@ -1535,6 +1852,19 @@ impl<S: Storage> Blockchain<S> {
                 continue;
             }
 
+            // next nonces of the same source can't be included without it
+            if options.exclude_txs.contains(hash.as_ref()) {
+                debug!("Skipping TX {} because it is excluded", hash);
+                failed_sources.insert(source);
+                continue;
+            }
+
+            if options.min_fee_per_byte.is_some_and(|fee_per_byte| tx.get_fee() < fee_per_byte.saturating_mul(size as u64)) {
+                debug!("Skipping TX {} because its fee per byte is too low", hash);
+                failed_sources.insert(source);
+                continue;
+            }
+
             if let Err(e) = tx.verify(&mut chain_state).await {
                 warn!("TX {} ({}) is not valid for mining: {}", hash, source.as_address(self.network.is_mainnet()), e);
                 failed_sources.insert(source);
@@ -1544,10 +1874,11 @@ impl<S: Storage> Blockchain<S> {
                 block.txs_hashes.insert(hash.as_ref().clone());
                 block_size += HASH_SIZE; // add the hash size
                 total_txs_size += size;
+                total_fees += tx.get_fee();
             }
         }
 
-        Ok(block)
+        Ok((block, total_fees))
     }
 
     // Build a block using the header and search for TXs in mempool and storage
@@ -2633,6 +2964,12 @@ pub fn get_block_dev_fee(height: u64) -> u64 {
 
 #[cfg(test)]
 mod tests {
+    use clap::Parser;
+    use vyridium_common::account::VersionedNonce;
+    use crate::core::{
+        mempool::tests::{add_tx, create_storage, TestAccount},
+        storage::NonceProvider
+    };
     use super::*;
 
     #[test]
@@ -2658,4 +2995,51 @@ mod tests {
         assert_eq!(get_block_dev_fee(DEV_FEES[1].height), 5);
         assert_eq!(get_block_dev_fee(DEV_FEES[1].height + 1), 5);
     }
-}
\ No newline at end of file
+
+    // Config without any server, using the directory for the mempool file
+    fn test_config(dir: &str) -> Config {
+        #[derive(Parser)]
+        struct TestConfig {
+            #[clap(flatten)]
+            config: Config
+        }
+
+        TestConfig::parse_from(["vyridium_daemon", "--disable-p2p-server", "--disable-rpc-server", "--dir-path", dir]).config
+    }
+
+    #[tokio::test]
+    async fn test_load_mempool_from_disk() {
+        let mut alice = TestAccount::new(100 * COIN_VALUE);
+        let mut bob = TestAccount::new(100 * COIN_VALUE);
+        let (mut storage, dir) = create_storage(&[&alice, &bob]).await;
+
+        let mut mempool = Mempool::new(Network::Testnet, None, None);
+        let first = add_tx(&mut mempool, &storage, alice.create_tx(1_000_000)).await;
+        let second = add_tx(&mut mempool, &storage, alice.create_tx(1_000_000)).await;
+        let stale = add_tx(&mut mempool, &storage, bob.create_tx(1_000_000)).await;
+        mempool.set_first_seen(&first, 10);
+        let path = format!("{}mempool-{}.bin", dir, Network::Testnet.to_string().to_lowercase());
+        fs::write(&path, mempool.to_persisted_bytes()).unwrap();
+
+        // the nonce of bob was used by a block before the restart
+        storage.set_last_nonce_to(&bob.key(), 0, &VersionedNonce::new(1, None)).await.unwrap();
+
+        let blockchain = Blockchain::new(test_config(&dir), Network::Testnet, storage).await.unwrap();
+        {
+            let reloaded = blockchain.get_mempool().read().await;
+            assert!(reloaded.contains_tx(&first) && reloaded.contains_tx(&second));
+            assert!(!reloaded.contains_tx(&stale));
+            assert_eq!(reloaded.get_sorted_tx(&first).unwrap().get_first_seen(), 10);
+            assert!(reloaded.get_cache_for(&bob.key()).is_none());
+
+            // sender cache is the same as before the restart
+            let cache = reloaded.get_cache_for(&alice.key()).unwrap();
+            assert_eq!(cache.get_min(), 0);
+            assert_eq!(cache.get_max(), 1);
+            assert_eq!(cache.get_txs().len(), 2);
+        }
+
+        drop(blockchain);
+        let _ = fs::remove_dir_all(dir);
+    }
+}
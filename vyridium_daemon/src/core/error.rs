@@ -145,6 +145,10 @@ pub enum BlockchainError {
     TxNotFoundInSortedList(Hash),
     #[error("Tx {} already in mempool", _0)]
     TxAlreadyInMempool(Hash),
+    #[error("Tx {} fee is too low for the mempool: {} but at least {} is required", _0, _1, _2)]
+    TxFeeTooLowForMempool(Hash, u64, u64),
+    #[error("Mempool is full, tx {} has been evicted", _0)]
+    MempoolFull(Hash),
     #[error("Normal Tx {} is empty", _0)]
     TxEmpty(Hash),
     #[error("Transaction has an invalid reference: block hash not found")]
@@ -259,6 +263,12 @@ pub enum BlockchainError {
     InvalidTipsNotFound(Hash, Hash),
     #[error("Block {0} has invalid tips difficulty: {1}")]
     InvalidTipsDifficulty(Hash, Hash),
+    #[error("Invalid tips count for block template: {0}")]
+    InvalidTemplateTipsCount(usize),
+    #[error("Tip {0} for block template is not present in chain")]
+    TemplateTipNotFound(Hash),
+    #[error("Tip {0} is not valid for block template")]
+    InvalidTemplateTip(Hash),
     #[error("Invalid block version")]
     InvalidBlockVersion,
     #[error("Invalid tx version")]
@@ -375,3 +385,24 @@ impl From<VerificationError<BlockchainError>> for BlockchainError {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use vyridium_common::api::daemon::{
+        MEMPOOL_FULL_ERROR_CODE,
+        TX_ALREADY_IN_BLOCKCHAIN_ERROR_CODE,
+        TX_ALREADY_IN_MEMPOOL_ERROR_CODE,
+        TX_FEE_TOO_LOW_FOR_MEMPOOL_ERROR_CODE
+    };
+    use super::*;
+
+    // Wallets rely on these codes to know if a transaction can be broadcasted again
+    #[test]
+    fn test_submit_transaction_error_codes() {
+        let code = |err: BlockchainError| InternalRpcError::from(err).get_code();
+        assert_eq!(code(BlockchainError::TxAlreadyInMempool(Hash::zero())), TX_ALREADY_IN_MEMPOOL_ERROR_CODE);
+        assert_eq!(code(BlockchainError::TxFeeTooLowForMempool(Hash::zero(), 0, 1)), TX_FEE_TOO_LOW_FOR_MEMPOOL_ERROR_CODE);
+        assert_eq!(code(BlockchainError::MempoolFull(Hash::zero())), MEMPOOL_FULL_ERROR_CODE);
+        assert_eq!(code(BlockchainError::TxAlreadyInBlockchain(Hash::zero())), TX_ALREADY_IN_BLOCKCHAIN_ERROR_CODE);
+    }
+}
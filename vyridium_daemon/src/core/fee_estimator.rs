@@ -0,0 +1,111 @@
+use vyridium_common::config::FEE_PER_KB;
+use crate::config::{FEE_ESTIMATION_BLOCKS, MAX_BLOCK_SIZE};
+
+// Fee rates estimated in atomic units per KB
+#[derive(Debug, PartialEq, Eq)]
+pub struct FeeRateEstimation {
+    // fee per KB to be included in the next block
+    pub next_block: u64,
+    // fee per KB to be included within the requested blocks count
+    pub target: u64,
+    // lowest fee per KB accepted, without any guarantee on the inclusion time
+    pub economy: u64
+}
+
+// Compute the fee per KB paid by a TX based on its size in bytes
+pub fn get_fee_per_kb(fee: u64, size: usize) -> u64 {
+    (fee as u128 * 1024 / size.max(1) as u128).min(u64::MAX as u128) as u64
+}
+
+// Fee per KB needed to be ahead of the mempool depth that fills the requested blocks count
+// Mempool TXs are given as (fee per KB, size in bytes)
+// Returns None if all the TXs in mempool fit in these blocks
+fn get_mempool_fee_rate(txs: &[(u64, usize)], blocks: u64) -> Option<u64> {
+    let capacity = (blocks.max(1) as usize).saturating_mul(MAX_BLOCK_SIZE);
+    let mut total_size = 0usize;
+    for (fee_per_kb, size) in txs {
+        total_size += size;
+        if total_size > capacity {
+            return Some(fee_per_kb.saturating_add(1))
+        }
+    }
+
+    None
+}
+
+// Get the value at the requested percentile of sorted fee rates
+fn get_percentile(sorted_rates: &[u64], percentile: u64) -> Option<u64> {
+    if sorted_rates.is_empty() {
+        return None
+    }
+
+    let index = (sorted_rates.len() - 1) * percentile.min(100) as usize / 100;
+    Some(sorted_rates[index])
+}
+
+// Estimate the fee rates using the current mempool and the fee rates included in recent blocks
+// `mempool` contains the (fee per KB, size in bytes) of each TX in mempool
+// `recent_rates` contains the fee per KB of each TX included in the recent blocks
+// `min_fee_per_kb` is the lowest fee per KB accepted currently by the mempool
+pub fn estimate_fee_rates(mut mempool: Vec<(u64, usize)>, mut recent_rates: Vec<u64>, target_blocks: u64, min_fee_per_kb: u64) -> FeeRateEstimation {
+    let target_blocks = target_blocks.max(1);
+    // highest fee rates are selected first by the block template
+    mempool.sort_by(|a, b| b.0.cmp(&a.0));
+    recent_rates.sort_unstable();
+
+    let floor = min_fee_per_kb.max(FEE_PER_KB);
+    let estimate = |blocks: u64| {
+        // The median of recent blocks is enough for the next block,
+        // lower fee rates are accepted for later blocks
+        let from_recent = get_percentile(&recent_rates, 50 / blocks).unwrap_or_default();
+        let from_mempool = get_mempool_fee_rate(&mempool, blocks).unwrap_or_default();
+        from_recent.max(from_mempool).max(floor)
+    };
+
+    let next_block = estimate(1);
+    let target = estimate(target_blocks).min(next_block);
+    let economy = get_mempool_fee_rate(&mempool, FEE_ESTIMATION_BLOCKS)
+        .unwrap_or_default()
+        .max(floor)
+        .min(target);
+
+    FeeRateEstimation {
+        next_block,
+        target,
+        economy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_estimation() {
+        let estimation = estimate_fee_rates(Vec::new(), Vec::new(), 3, 0);
+        assert_eq!(estimation, FeeRateEstimation {
+            next_block: FEE_PER_KB,
+            target: FEE_PER_KB,
+            economy: FEE_PER_KB
+        });
+    }
+
+    #[test]
+    fn test_mempool_depth() {
+        // two full blocks of TXs paying more than the minimum
+        let mempool = vec![(FEE_PER_KB * 3, MAX_BLOCK_SIZE), (FEE_PER_KB * 2, MAX_BLOCK_SIZE), (FEE_PER_KB, 1024)];
+        let estimation = estimate_fee_rates(mempool, Vec::new(), 2, 0);
+        assert_eq!(estimation.next_block, FEE_PER_KB * 2 + 1);
+        assert_eq!(estimation.target, FEE_PER_KB + 1);
+        assert_eq!(estimation.economy, FEE_PER_KB);
+    }
+
+    #[test]
+    fn test_recent_blocks() {
+        let recent = vec![FEE_PER_KB, FEE_PER_KB * 2, FEE_PER_KB * 4, FEE_PER_KB * 8, FEE_PER_KB * 10];
+        let estimation = estimate_fee_rates(Vec::new(), recent, 2, 0);
+        assert_eq!(estimation.next_block, FEE_PER_KB * 4);
+        assert_eq!(estimation.target, FEE_PER_KB * 2);
+        assert_eq!(estimation.economy, FEE_PER_KB);
+    }
+}
@@ -4,7 +4,8 @@ use super::{
     storage::Storage
 };
 use std::{
-    collections::{HashMap, HashSet},
+    cmp::Ordering,
+    collections::{BTreeSet, HashMap, HashSet},
     sync::Arc,
     mem,
 };
@@ -14,7 +15,7 @@ use log::{debug, info, trace, warn};
 use vyridium_common::{
     time::{TimestampSeconds, get_current_time_in_seconds},
     network::Network,
-    serializer::Serializer,
+    serializer::{Reader, ReaderError, Serializer, Writer},
     crypto::{
         elgamal::Ciphertext,
         Hash,
@@ -33,6 +34,16 @@ pub struct SortedTx {
     size: usize
 }
 
+// Entry of the fee rate index of the mempool
+// Ordered by fee per byte, then the most recent TX first on equal fee rate
+#[derive(PartialEq, Eq)]
+struct FeeRateKey {
+    fee: u64,
+    size: usize,
+    first_seen: TimestampSeconds,
+    hash: Arc<Hash>
+}
+
 // This struct is used to keep nonce cache for a specific key for faster verification
 // But we also include a sorted list of txs for this key, ordered by nonce
 // and a "expected balance" for this key
@@ -57,16 +68,28 @@ pub struct Mempool {
     // store all txs waiting to be included in a block
     txs: HashMap<Arc<Hash>, SortedTx>,
     // store all sender's nonce for faster finding
-    caches: HashMap<PublicKey, AccountCache>
+    caches: HashMap<PublicKey, AccountCache>,
+    // all txs ordered by fee per byte, lowest first
+    fee_rates: BTreeSet<FeeRateKey>,
+    // total size in bytes of all txs
+    total_size: usize,
+    // maximum count of txs before evicting the lowest fee per byte ones
+    max_txs: Option<usize>,
+    // maximum total size in bytes before evicting the lowest fee per byte txs
+    max_size: Option<usize>
 }
 
 impl Mempool {
     // Create a new empty mempool
-    pub fn new(network: Network) -> Self {
+    pub fn new(network: Network, max_txs: Option<usize>, max_size: Option<usize>) -> Self {
         Mempool {
             mainnet: network.is_mainnet(),
             txs: HashMap::new(),
-            caches: HashMap::new()
+            caches: HashMap::new(),
+            fee_rates: BTreeSet::new(),
+            total_size: 0,
+            max_txs,
+            max_size
         }
     }
 
@@ -81,6 +104,8 @@ impl Mempool {
 
         let hash = Arc::new(hash);
         let nonce = tx.get_nonce();
+        // TX using the same nonce to delete from the mempool
+        let mut replaced = None;
         // update the cache for this owner
         let mut must_update = true;
         if let Some(cache) = self.caches.get_mut(tx.get_source()) {
@@ -99,10 +124,7 @@ impl Mempool {
 
                 if let Some(tx_hash) = cache.txs.swap_remove_index(index) {
                     trace!("TX {} with same nonce found in cache, removing it from sorted txs", tx_hash);
-                    // remove the tx hash from sorted txs
-                    if self.txs.remove(&tx_hash).is_none() {
-                        warn!("TX {} not found in mempool while deleting collision with {}", tx_hash, hash);
-                    }
+                    replaced = Some(tx_hash);
                 } else {
                     warn!("No TX found in cache for nonce {} while adding {}", nonce, hash);
                 }
@@ -127,6 +149,13 @@ impl Mempool {
             self.caches.insert(tx.get_source().clone(), cache);
         }
 
+        // remove the tx hash from sorted txs
+        if let Some(tx_hash) = replaced {
+            if self.remove_sorted_tx(&tx_hash).is_none() {
+                warn!("TX {} not found in mempool while deleting collision with {}", tx_hash, hash);
+            }
+        }
+
         let sorted_tx = SortedTx {
             size,
             first_seen: get_current_time_in_seconds(),
@@ -134,15 +163,30 @@ impl Mempool {
         };
 
         // insert in map
-        self.txs.insert(hash, sorted_tx);
+        self.insert_sorted_tx(hash, sorted_tx);
 
         Ok(())
     }
 
+    // Insert a TX in the map and in the fee rate index
+    fn insert_sorted_tx(&mut self, hash: Arc<Hash>, sorted_tx: SortedTx) {
+        self.total_size += sorted_tx.size;
+        self.fee_rates.insert(FeeRateKey::new(&hash, &sorted_tx));
+        self.txs.insert(hash, sorted_tx);
+    }
+
+    // Remove a TX from the map and from the fee rate index
+    fn remove_sorted_tx(&mut self, hash: &Hash) -> Option<SortedTx> {
+        let (hash, sorted_tx) = self.txs.remove_entry(hash)?;
+        self.total_size -= sorted_tx.size;
+        self.fee_rates.remove(&FeeRateKey::new(&hash, &sorted_tx));
+        Some(sorted_tx)
+    }
+
     // Remove a TX using its hash from mempool
     // This will recalculate the cache bounds
     pub fn remove_tx(&mut self, hash: &Hash) -> Result<(), BlockchainError> {
-        let tx = self.txs.remove(hash).ok_or_else(|| BlockchainError::TxNotFound(hash.clone()))?;
+        let tx = self.remove_sorted_tx(hash).ok_or_else(|| BlockchainError::TxNotFound(hash.clone()))?;
         // remove the tx hash from sorted txs
         let key = tx.get_tx().get_source();
         let mut delete = false;
@@ -258,10 +302,117 @@ impl Mempool {
         self.txs.len()
     }
 
+    // Returns the total size in bytes of all txs in mempool
+    pub fn total_size(&self) -> usize {
+        self.total_size
+    }
+
+    // Check if the mempool has reached one of its limits
+    pub fn is_full(&self) -> bool {
+        self.max_txs.is_some_and(|max| self.txs.len() >= max) || self.max_size.is_some_and(|max| self.total_size >= max)
+    }
+
+    // Check if the mempool went over one of its limits
+    fn is_over_limits(&self) -> bool {
+        self.max_txs.is_some_and(|max| self.txs.len() > max) || self.max_size.is_some_and(|max| self.total_size > max)
+    }
+
+    // Get the TX with the lowest fee per byte
+    // On equal fee rate, the most recent TX is returned
+    fn get_lowest_fee_rate_tx(&self) -> Option<(&Arc<Hash>, &SortedTx)> {
+        self.fee_rates.iter().next()
+            .and_then(|key| self.txs.get_key_value(&key.hash))
+    }
+
+    // Minimum fee per byte required for a TX to be accepted
+    // It is zero until the mempool is full, then it must be higher than the lowest fee per byte in mempool
+    pub fn get_min_fee_per_byte(&self) -> u64 {
+        if !self.is_full() {
+            return 0
+        }
+
+        self.get_lowest_fee_rate_tx()
+            .map(|(_, sorted_tx)| sorted_tx.get_fee() / sorted_tx.size.max(1) as u64 + 1)
+            .unwrap_or_default()
+    }
+
+    // Evict the TXs with the lowest fee per byte until the mempool is under its limits
+    // All the TXs from the same sender with a higher nonce are evicted too as they depend on it
+    pub async fn evict<S: Storage>(&mut self, storage: &S, stable_topoheight: u64, topoheight: u64, block_version: BlockVersion) -> Vec<(Arc<Hash>, SortedTx)> {
+        let mut evicted_transactions = Vec::new();
+        while self.is_over_limits() {
+            let (key, nonce) = match self.get_lowest_fee_rate_tx() {
+                Some((hash, sorted_tx)) => {
+                    debug!("Evicting TX {} with fee {} and size {} from mempool", hash, sorted_tx.get_fee(), sorted_tx.size);
+                    (sorted_tx.get_tx().get_source().clone(), sorted_tx.get_tx().get_nonce())
+                },
+                None => break
+            };
+
+            let mut cache = match self.caches.remove(&key) {
+                Some(cache) => cache,
+                None => {
+                    // This should never happen, stop here to not loop forever
+                    warn!("No cache found for owner {} while evicting TXs", key.as_address(self.mainnet));
+                    break;
+                }
+            };
+
+            // txs are ordered by nonce, keep only the ones before the evicted one
+            let mut hashes = Vec::new();
+            cache.txs.retain(|hash| {
+                let keep = self.txs.get(hash).is_some_and(|sorted_tx| sorted_tx.get_tx().get_nonce() < nonce);
+                if !keep {
+                    hashes.push(Arc::clone(hash));
+                }
+                keep
+            });
+
+            let mut delete_cache = cache.txs.is_empty();
+            if !delete_cache {
+                cache.max = nonce - 1;
+                // Expected balances are including the evicted TXs, compute them again
+                let txs: Vec<&Arc<Transaction>> = cache.txs.iter().filter_map(|hash| self.txs.get(hash).map(SortedTx::get_tx)).collect();
+                let mut state = MempoolState::new(&self, storage, stable_topoheight, topoheight, block_version);
+                match Transaction::verify_batch(txs.as_slice(), &mut state).await {
+                    Ok(()) => match state.get_sender_balances(&key) {
+                        Some(balances) => {
+                            let balances = balances.into_iter().map(|(asset, ciphertext)| (asset.clone(), ciphertext)).collect();
+                            cache.set_balances(balances);
+                        },
+                        None => delete_cache = true
+                    },
+                    Err(e) => {
+                        warn!("Error while verifying TXs left for sender {} after eviction: {}", key.as_address(self.mainnet), e);
+                        delete_cache = true;
+                    }
+                }
+            }
+
+            if delete_cache {
+                hashes.extend(cache.txs.drain(..));
+            } else {
+                self.caches.insert(key, cache);
+            }
+
+            for hash in hashes {
+                if let Some(sorted_tx) = self.remove_sorted_tx(&hash) {
+                    evicted_transactions.push((hash, sorted_tx));
+                } else {
+                    warn!("TX {} not found in mempool while evicting", hash);
+                }
+            }
+        }
+
+        evicted_transactions
+    }
+
     // Clear all txs and caches in mempool
     pub fn clear(&mut self) {
         self.txs.clear();
         self.caches.clear();
+        self.fee_rates.clear();
+        self.total_size = 0;
     }
 
     // Drain all txs from mempool
@@ -272,6 +423,8 @@ impl Mempool {
         }
 
         self.caches.clear();
+        self.fee_rates.clear();
+        self.total_size = 0;
 
         txs
     }
@@ -303,7 +456,7 @@ impl Mempool {
 
                     // Delete all txs from this cache
                     for tx in cache.txs {
-                        if let Some(sorted_tx) = self.txs.remove(&tx) {
+                        if let Some(sorted_tx) = self.remove_sorted_tx(&tx) {
                             deleted_transactions.push((tx, sorted_tx));
                         } else {
                             warn!("TX {} not found in mempool while deleting due to nonce error", tx);
@@ -325,7 +478,7 @@ impl Mempool {
 
                 // Don't let ghost TXs in mempool
                 for tx in cache.txs.drain(..) {
-                    if let Some(sorted_tx) = self.txs.remove(&tx) {
+                    if let Some(sorted_tx) = self.remove_sorted_tx(&tx) {
                         deleted_transactions.push((tx, sorted_tx));
                     } else {
                         warn!("TX {} not found in mempool (orphaned due to nonce)", tx);
@@ -442,7 +595,7 @@ impl Mempool {
                 // now delete all necessary txs
                 for hash in hashes {
                     debug!("Deleting TX {} for owner {}", hash, key.as_address(self.mainnet));
-                    if let Some(sorted_tx) = self.txs.remove(&hash) {
+                    if let Some(sorted_tx) = self.remove_sorted_tx(&hash) {
                         deleted_transactions.push((hash, sorted_tx));
                     } else {
                         // This should never happen, but better to put a warning here
@@ -461,6 +614,45 @@ impl Mempool {
         deleted_transactions
     }
 
+    // Update the first seen timestamp of a TX
+    // This is used to restore the TXs saved on disk
+    pub fn set_first_seen(&mut self, hash: &Hash, first_seen: TimestampSeconds) {
+        if let Some(sorted_tx) = self.remove_sorted_tx(hash) {
+            let hash = Arc::new(hash.clone());
+            self.insert_sorted_tx(hash, SortedTx { first_seen, ..sorted_tx });
+        }
+    }
+
+    // Serialize all TXs with their first seen timestamp to be saved on disk
+    pub fn to_persisted_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::new();
+        writer.write_u32(&(self.txs.len() as u32));
+        for sorted_tx in self.txs.values() {
+            writer.write_u64(&sorted_tx.first_seen);
+            sorted_tx.tx.write(&mut writer);
+        }
+        writer.bytes()
+    }
+
+    // Read the TXs saved on disk with their first seen timestamp
+    // They must be verified again before being added to the mempool
+    pub fn read_persisted_bytes(bytes: &[u8]) -> Result<Vec<(Transaction, TimestampSeconds)>, ReaderError> {
+        let mut reader = Reader::new(bytes);
+        let count = reader.read_u32()?;
+        let mut txs = Vec::new();
+        for _ in 0..count {
+            let first_seen = reader.read_u64()?;
+            let tx = Transaction::read(&mut reader)?;
+            txs.push((tx, first_seen));
+        }
+
+        if reader.size() != 0 {
+            return Err(ReaderError::InvalidSize)
+        }
+
+        Ok(txs)
+    }
+
     pub async fn stop(&mut self) {
         info!("Stopping mempool...");
         self.clear();
@@ -489,6 +681,34 @@ impl SortedTx {
     }
 }
 
+impl FeeRateKey {
+    fn new(hash: &Arc<Hash>, sorted_tx: &SortedTx) -> Self {
+        Self {
+            fee: sorted_tx.get_fee(),
+            size: sorted_tx.size,
+            first_seen: sorted_tx.first_seen,
+            hash: Arc::clone(hash)
+        }
+    }
+}
+
+impl Ord for FeeRateKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // compare self.fee / self.size with other.fee / other.size without losing precision
+        let rate = self.fee as u128 * other.size as u128;
+        let other_rate = other.fee as u128 * self.size as u128;
+        rate.cmp(&other_rate)
+            .then_with(|| other.first_seen.cmp(&self.first_seen))
+            .then_with(|| self.hash.cmp(&other.hash))
+    }
+}
+
+impl PartialOrd for FeeRateKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl AccountCache {
     // Get the lowest nonce for this cache
     pub fn get_min(&self) -> u64 {
@@ -550,4 +770,218 @@ impl AccountCache {
         let index = ((nonce - self.min) % (self.max + 1 - self.min)) as usize;
         self.txs.get_index(index)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use vyridium_common::{
+        account::{CiphertextCache, VersionedBalance},
+        asset::AssetData,
+        config::{COIN_DECIMALS, COIN_VALUE, VYRIDIUM_ASSET},
+        crypto::{Hashable, KeyPair},
+        transaction::{
+            builder::{
+                AccountState,
+                FeeBuilder,
+                FeeHelper,
+                TransactionBuilder,
+                TransactionTypeBuilder,
+                TransferBuilder
+            },
+            Reference,
+            TxVersion
+        }
+    };
+    use crate::core::storage::{AssetProvider, BalanceProvider, SledStorage};
+    use super::*;
+
+    pub(crate) struct TestAccount {
+        keypair: KeyPair,
+        balance: u64,
+        ciphertext: CiphertextCache,
+        nonce: u64
+    }
+
+    impl TestAccount {
+        pub(crate) fn new(balance: u64) -> Self {
+            let keypair = KeyPair::new();
+            let ciphertext = CiphertextCache::Decompressed(keypair.get_public_key().encrypt(balance));
+            Self {
+                keypair,
+                balance,
+                ciphertext,
+                nonce: 0
+            }
+        }
+
+        pub(crate) fn key(&self) -> PublicKey {
+            self.keypair.get_public_key().compress()
+        }
+
+        // Build a transfer of one coin to a new account
+        pub(crate) fn create_tx(&mut self, fee: u64) -> Transaction {
+            let destination = KeyPair::new().get_public_key().to_address(false);
+            let data = TransactionTypeBuilder::Transfers(vec![TransferBuilder {
+                asset: VYRIDIUM_ASSET,
+                amount: COIN_VALUE,
+                destination,
+                extra_data: None
+            }]);
+            let keypair = self.keypair.clone();
+            TransactionBuilder::new(TxVersion::V0, self.key(), data, FeeBuilder::Value(fee))
+                .build(self, &keypair)
+                .unwrap()
+        }
+    }
+
+    impl FeeHelper for TestAccount {
+        type Error = ();
+
+        fn account_exists(&self, _: &PublicKey) -> Result<bool, Self::Error> {
+            Ok(false)
+        }
+    }
+
+    impl AccountState for TestAccount {
+        fn is_mainnet(&self) -> bool {
+            false
+        }
+
+        fn get_account_balance(&self, _: &Hash) -> Result<u64, Self::Error> {
+            Ok(self.balance)
+        }
+
+        fn get_reference(&self) -> Reference {
+            Reference {
+                topoheight: 0,
+                hash: Hash::zero()
+            }
+        }
+
+        fn get_account_ciphertext(&self, _: &Hash) -> Result<CiphertextCache, Self::Error> {
+            Ok(self.ciphertext.clone())
+        }
+
+        fn update_account_balance(&mut self, _: &Hash, balance: u64, ciphertext: Ciphertext) -> Result<(), Self::Error> {
+            self.balance = balance;
+            self.ciphertext = CiphertextCache::Decompressed(ciphertext);
+            Ok(())
+        }
+
+        fn get_nonce(&self) -> Result<u64, Self::Error> {
+            Ok(self.nonce)
+        }
+
+        fn update_nonce(&mut self, nonce: u64) -> Result<(), Self::Error> {
+            self.nonce = nonce;
+            Ok(())
+        }
+    }
+
+    // Create a storage in a temporary directory with the current balance of the accounts
+    pub(crate) async fn create_storage(accounts: &[&TestAccount]) -> (SledStorage, String) {
+        let dir = format!("{}/vyridium-mempool-{:x}/", std::env::temp_dir().display(), rand::random::<u64>());
+        let mut storage = SledStorage::new(dir.clone(), None, Network::Testnet).unwrap();
+        storage.add_asset(&VYRIDIUM_ASSET, AssetData::new(0, COIN_DECIMALS)).await.unwrap();
+        for account in accounts {
+            let version = VersionedBalance::new(account.ciphertext.clone(), None);
+            storage.set_last_balance_to(&account.key(), &VYRIDIUM_ASSET, 0, &version).await.unwrap();
+        }
+
+        (storage, dir)
+    }
+
+    pub(crate) async fn add_tx(mempool: &mut Mempool, storage: &SledStorage, tx: Transaction) -> Hash {
+        let hash = tx.hash();
+        let size = tx.size();
+        mempool.add_tx(storage, 0, 0, hash.clone(), Arc::new(tx), size, BlockVersion::V0).await.unwrap();
+        hash
+    }
+
+    #[test]
+    fn test_persisted_bytes() {
+        let mempool = Mempool::new(Network::Mainnet, None, None);
+        let mut bytes = mempool.to_persisted_bytes();
+        assert!(Mempool::read_persisted_bytes(&bytes).unwrap().is_empty());
+
+        // trailing bytes means the file is corrupted
+        bytes.push(0);
+        assert!(Mempool::read_persisted_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_limits() {
+        let mempool = Mempool::new(Network::Mainnet, None, None);
+        assert!(!mempool.is_full());
+        assert_eq!(mempool.get_min_fee_per_byte(), 0);
+
+        // nothing to compare with, any fee is accepted
+        let mempool = Mempool::new(Network::Mainnet, Some(0), None);
+        assert!(mempool.is_full());
+        assert_eq!(mempool.get_min_fee_per_byte(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_evict_later_nonces() {
+        let mut alice = TestAccount::new(100 * COIN_VALUE);
+        let mut bob = TestAccount::new(100 * COIN_VALUE);
+        let (storage, dir) = create_storage(&[&alice, &bob]).await;
+        let mut mempool = Mempool::new(Network::Testnet, Some(2), None);
+
+        // second TX of alice has the lowest fee rate
+        let first = add_tx(&mut mempool, &storage, alice.create_tx(1_000_000)).await;
+        let balance_after_first = alice.balance;
+        let second = add_tx(&mut mempool, &storage, alice.create_tx(200_000)).await;
+        let third = add_tx(&mut mempool, &storage, alice.create_tx(1_000_000)).await;
+        let other = add_tx(&mut mempool, &storage, bob.create_tx(500_000)).await;
+        assert_eq!(mempool.get_cache_for(&alice.key()).unwrap().get_max(), 2);
+
+        // the third TX depends on the second one, both are evicted
+        let evicted = mempool.evict(&storage, 0, 0, BlockVersion::V0).await;
+        let evicted: HashSet<Hash> = evicted.into_iter().map(|(hash, _)| hash.as_ref().clone()).collect();
+        assert_eq!(evicted, HashSet::from([second, third]));
+        assert!(mempool.contains_tx(&first));
+        assert!(mempool.contains_tx(&other));
+
+        let expected_size: usize = mempool.get_txs().values().map(SortedTx::get_size).sum();
+        assert_eq!(mempool.total_size(), expected_size);
+
+        // bounds and expected balances are recomputed without the evicted TXs
+        let cache = mempool.get_cache_for(&alice.key()).unwrap();
+        assert_eq!(cache.get_min(), 0);
+        assert_eq!(cache.get_max(), 0);
+        assert_eq!(cache.get_next_nonce(), 1);
+        assert_eq!(cache.get_txs().len(), 1);
+
+        let balance = cache.get_balances().get(&VYRIDIUM_ASSET).unwrap();
+        let expected = alice.keypair.get_public_key().encrypt(balance_after_first);
+        assert_eq!(alice.keypair.decrypt_to_point(balance), alice.keypair.decrypt_to_point(&expected));
+
+        // mempool is full, the lowest fee rate left is the one of bob
+        let sorted_tx = mempool.get_sorted_tx(&other).unwrap();
+        assert_eq!(mempool.get_min_fee_per_byte(), sorted_tx.get_fee() / sorted_tx.get_size() as u64 + 1);
+
+        drop(storage);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_evict_whole_sender() {
+        let mut alice = TestAccount::new(100 * COIN_VALUE);
+        let (storage, dir) = create_storage(&[&alice]).await;
+        let mut mempool = Mempool::new(Network::Testnet, Some(1), None);
+
+        // first TX has the lowest fee rate, all the next ones depend on it
+        add_tx(&mut mempool, &storage, alice.create_tx(200_000)).await;
+        add_tx(&mut mempool, &storage, alice.create_tx(1_000_000)).await;
+
+        let evicted = mempool.evict(&storage, 0, 0, BlockVersion::V0).await;
+        assert_eq!(evicted.len(), 2);
+        assert_eq!(mempool.size(), 0);
+        assert_eq!(mempool.total_size(), 0);
+        assert!(mempool.get_cache_for(&alice.key()).is_none());
+
+        drop(storage);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -7,6 +7,7 @@ pub mod difficulty;
 pub mod simulator;
 pub mod nonce_checker;
 pub mod tx_selector;
+pub mod fee_estimator;
 pub mod state;
 pub mod merkle;
 
@@ -3,6 +3,7 @@ use std::{
         VecDeque,
         BinaryHeap,
         HashMap,
+        HashSet,
         hash_map::Entry
     },
     sync::Arc,
@@ -37,18 +38,26 @@ impl Eq for TxSelectorEntry<'_> {}
 // this struct is used to store transactions in a queue
 // and to order them by fees
 // Each Transactions is for a specific sender
+// Second field is the number of prioritized transactions left in the group
 #[derive(PartialEq, Eq)]
-struct Transactions<'a>(VecDeque<TxSelectorEntry<'a>>);
+struct Transactions<'a>(VecDeque<TxSelectorEntry<'a>>, usize);
+
+impl Transactions<'_> {
+    // Groups with prioritized transactions are selected first, then by fees
+    fn key(&self) -> (bool, Option<u64>) {
+        (self.1 > 0, self.0.front().map(|e| e.tx.get_fee()))
+    }
+}
 
 impl PartialOrd for Transactions<'_> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.0.front().map(|e| e.tx.get_fee()).partial_cmp(&other.0.front().map(|e| e.tx.get_fee()))
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for Transactions<'_> {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.0.front().map(|e| e.tx.get_fee()).cmp(&other.0.front().map(|e| e.tx.get_fee()))
+        self.key().cmp(&other.key())
     }
 }
 
@@ -56,12 +65,23 @@ impl Ord for Transactions<'_> {
 // It create sub groups of transactions by sender and order them by nonces
 // It joins all sub groups in a queue that is ordered by fees
 pub struct TxSelector<'a> {
-    queue: BinaryHeap<Transactions<'a>>
+    queue: BinaryHeap<Transactions<'a>>,
+    prioritized: HashSet<Hash>
 }
 
 impl<'a> TxSelector<'a> {
     // Create a TxSelector from a list of groups
     pub fn grouped<I>(groups: I) -> Self
+    where
+        I: Iterator<Item = Vec<TxSelectorEntry<'a>>>
+    {
+        Self::grouped_with_priority(groups, HashSet::new())
+    }
+
+    // Create a TxSelector from a list of groups
+    // Groups containing a prioritized transaction are selected first
+    // so its previous nonces are also included before it
+    pub fn grouped_with_priority<I>(groups: I, prioritized: HashSet<Hash>) -> Self
     where
         I: Iterator<Item = Vec<TxSelectorEntry<'a>>>
     {
@@ -69,11 +89,13 @@ impl<'a> TxSelector<'a> {
 
         // push every group to the queue
         for group in groups {
-            queue.push(Transactions(VecDeque::from(group)));
+            let count = group.iter().filter(|e| prioritized.contains(e.hash.as_ref())).count();
+            queue.push(Transactions(VecDeque::from(group), count));
         }
 
         Self {
-            queue
+            queue,
+            prioritized
         }
     }
 
@@ -116,6 +138,9 @@ impl<'a> TxSelector<'a> {
         let mut group = self.queue.pop()?;
         // get the entry with the highest fee from this group
         let entry = group.0.pop_front()?;
+        if group.1 > 0 && self.prioritized.contains(entry.hash.as_ref()) {
+            group.1 -= 1;
+        }
 
         // if its not empty, push it back to the queue
         if !group.0.is_empty() {
@@ -124,4 +149,68 @@ impl<'a> TxSelector<'a> {
 
         Some(entry)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use vyridium_common::{config::COIN_VALUE, crypto::Hashable};
+    use crate::core::mempool::tests::TestAccount;
+    use super::*;
+
+    fn select(txs: &[(Arc<Hash>, Arc<Transaction>)], prioritized: HashSet<Hash>) -> Vec<Hash> {
+        // group the TXs by sender, ordered by nonces
+        let mut groups: Vec<Vec<TxSelectorEntry>> = Vec::new();
+        for (hash, tx) in txs {
+            let entry = TxSelectorEntry { hash, tx, size: 0 };
+            match groups.iter_mut().find(|g| g[0].tx.get_source() == tx.get_source()) {
+                Some(group) => group.push(entry),
+                None => groups.push(vec![entry])
+            }
+        }
+
+        let mut selector = TxSelector::grouped_with_priority(groups.into_iter(), prioritized);
+        let mut selected = Vec::new();
+        while let Some(entry) = selector.next() {
+            selected.push(entry.hash.as_ref().clone());
+        }
+        selected
+    }
+
+    #[test]
+    fn test_grouped_with_priority() {
+        let mut alice = TestAccount::new(100 * COIN_VALUE);
+        let mut bob = TestAccount::new(100 * COIN_VALUE);
+        let mut carol = TestAccount::new(100 * COIN_VALUE);
+        let mut dave = TestAccount::new(100 * COIN_VALUE);
+
+        let txs: Vec<(Arc<Hash>, Arc<Transaction>)> = [
+            alice.create_tx(1000),
+            alice.create_tx(9000),
+            bob.create_tx(5000),
+            carol.create_tx(3000),
+            dave.create_tx(2000)
+        ].into_iter().map(|tx| (Arc::new(tx.hash()), Arc::new(tx))).collect();
+        let hashes: Vec<Hash> = txs.iter().map(|(hash, _)| hash.as_ref().clone()).collect();
+        let (alice_0, alice_1, bob_0, carol_0, dave_0) = (&hashes[0], &hashes[1], &hashes[2], &hashes[3], &hashes[4]);
+
+        // without priority, groups are ordered by the fee of their next TX
+        assert_eq!(
+            select(&txs, HashSet::new()),
+            vec![bob_0.clone(), carol_0.clone(), dave_0.clone(), alice_0.clone(), alice_1.clone()]
+        );
+
+        // the prioritized TX comes first with its previous nonce
+        let prioritized = HashSet::from([alice_1.clone()]);
+        assert_eq!(
+            select(&txs, prioritized),
+            vec![alice_0.clone(), alice_1.clone(), bob_0.clone(), carol_0.clone(), dave_0.clone()]
+        );
+
+        // prioritized groups are still ordered by fees between them
+        let prioritized = HashSet::from([alice_1.clone(), dave_0.clone()]);
+        assert_eq!(
+            select(&txs, prioritized),
+            vec![dave_0.clone(), alice_0.clone(), alice_1.clone(), bob_0.clone(), carol_0.clone()]
+        );
+    }
+}
@@ -9,7 +9,7 @@ use humantime::format_duration;
 use log::{trace, error, info, warn};
 use p2p::P2pServer;
 use rpc::{
-    getwork_server::SharedGetWorkServer,
+    getwork_server::{Miner, SharedGetWorkServer},
     rpc::get_block_response_for_hash
 };
 use vyridium_common::{
@@ -420,7 +420,15 @@ async fn list_miners<S: Storage>(manager: &CommandManager, _: ArgumentManager) -
                 let miners = getwork.get_miners().lock().await;
                 manager.message(format!("Miners ({}):", miners.len()));
                 for miner in miners.values() {
-                    manager.message(format!("- {}", miner));
+                    display_miner(manager, miner);
+                }
+
+                if let Some(stratum) = rpc.stratum_server() {
+                    let miners = stratum.get_miners().lock().await;
+                    manager.message(format!("Stratum miners ({}):", miners.len()));
+                    for miner in miners.values() {
+                        display_miner(manager, miner);
+                    }
                 }
             },
             None => {
@@ -435,6 +443,17 @@ async fn list_miners<S: Storage>(manager: &CommandManager, _: ArgumentManager) -
     Ok(())
 }
 
+fn display_miner(manager: &CommandManager, miner: &Miner) {
+    let hashrate = miner.get_hashrates();
+    manager.message(format!(
+        "- {} | Hashrate: {} (1m), {} (15m), {} (1h)",
+        miner,
+        format_hashrate(hashrate.last_minute),
+        format_hashrate(hashrate.last_15_minutes),
+        format_hashrate(hashrate.last_hour)
+    ));
+}
+
 async fn list_peers<S: Storage>(manager: &CommandManager, _: ArgumentManager) -> Result<(), CommandError> {
     let context = manager.get_context().lock()?;
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
@@ -65,13 +65,15 @@ pub struct Connection {
     rotate_key_out: AtomicUsize,
     // Encryption state used for packets
     encryption: Encryption,
+    // True if the connection was established through a proxy
+    proxied: bool,
 }
 
 // We are rotating every 1GB sent
 const ROTATE_EVERY_N_BYTES: usize = 1024 * 1024 * 1024;
 
 impl Connection {
-    pub fn new(stream: TcpStream, addr: SocketAddr, out: bool) -> Self {
+    pub fn new(stream: TcpStream, addr: SocketAddr, out: bool, proxied: bool) -> Self {
         let (read, write) = stream.into_split();
         Self {
             out,
@@ -87,6 +89,7 @@ impl Connection {
             rotate_key_in: AtomicUsize::new(0),
             rotate_key_out: AtomicUsize::new(0),
             encryption: Encryption::new(),
+            proxied,
         }
     }
 
@@ -148,6 +151,11 @@ impl Connection {
         self.out
     }
 
+    // Verify if the connection goes through a proxy
+    pub fn is_proxied(&self) -> bool {
+        self.proxied
+    }
+
     // This will send to the peer a packet to rotate the key
     async fn rotate_key_packet(&self) -> P2pResult<Bytes> {
         trace!(
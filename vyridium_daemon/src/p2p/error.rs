@@ -6,6 +6,7 @@ use tokio::{
     sync::{
         AcquireError,
         mpsc::error::SendError as TSendError,
+        broadcast::error::RecvError as BroadcastRecvError,
         oneshot::error::RecvError,
     },
     time::error::Elapsed
@@ -27,6 +28,7 @@ use std::{
 use thiserror::Error;
 use super::{
     encryption::EncryptionError,
+    proxy::ProxyError,
     packet::{
         bootstrap_chain::StepKind,
         object::ObjectRequest,
@@ -175,6 +177,26 @@ pub enum P2pError {
     SemaphoreAcquireError(#[from] AcquireError),
     #[error(transparent)]
     EncryptionError(#[from] EncryptionError),
+    #[error(transparent)]
+    ProxyError(#[from] ProxyError),
+    #[error(transparent)]
+    DatabaseError(#[from] sled::Error),
+    #[error("Error while waiting on response blocker: {}", _0)]
+    ResponseBlockerError(#[from] BroadcastRecvError),
+    #[error("Compact blocks were not negotiated with this peer")]
+    CompactBlocksNotNegotiated,
+    #[error("Chain requests rate limit exceeded")]
+    ChainRequestsRateLimited,
+    #[error("Batched objects were not negotiated with this peer")]
+    BatchedObjectsNotNegotiated,
+    #[error("Received a unrequested block transactions response")]
+    UnrequestedBlockTransactionsResponse,
+    #[error("Invalid block transactions response")]
+    InvalidBlockTransactionsResponse,
+    #[error("Invalid block transactions request, index {} is out of bounds", _0)]
+    InvalidBlockTransactionsRequest(u16),
+    #[error("Compact block {} rebuilt has a different hash", _0)]
+    CompactBlockHashMismatch(Hash),
 }
 
 impl From<BlockchainError> for P2pError {
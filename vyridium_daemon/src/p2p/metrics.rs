@@ -0,0 +1,73 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration
+};
+use vyridium_common::api::daemon::BlockPropagationStats;
+
+// Counters to compare the propagation of full blocks against compact blocks
+// Propagation time is measured from the packet reception until the block is added to the chain
+#[derive(Default)]
+pub struct BlockPropagationMetrics {
+    // Full block headers received and added
+    full_blocks: AtomicU64,
+    // Total time spent to add them in milliseconds
+    full_blocks_time: AtomicU64,
+    // Compact blocks received and added
+    compact_blocks: AtomicU64,
+    // Total time spent to add them in milliseconds
+    compact_blocks_time: AtomicU64,
+    // Compact blocks rebuilt only from our mempool
+    compact_blocks_rebuilt: AtomicU64,
+    // TXs we had to request for compact blocks
+    compact_blocks_missing_txs: AtomicU64,
+    // Compact blocks we had to request fully
+    compact_blocks_fallbacks: AtomicU64
+}
+
+impl BlockPropagationMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Track a full block propagated
+    pub fn on_full_block(&self, elapsed: Duration) {
+        self.full_blocks.fetch_add(1, Ordering::Relaxed);
+        self.full_blocks_time.fetch_add(elapsed.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    // Track a compact block propagated
+    pub fn on_compact_block(&self, elapsed: Duration) {
+        self.compact_blocks.fetch_add(1, Ordering::Relaxed);
+        self.compact_blocks_time.fetch_add(elapsed.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    // Track a compact block rebuilt without any round trip
+    pub fn on_compact_block_rebuilt(&self) {
+        self.compact_blocks_rebuilt.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Track the TXs requested for a compact block
+    pub fn on_compact_block_missing_txs(&self, count: usize) {
+        self.compact_blocks_missing_txs.fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    // Track a compact block that couldn't be rebuilt
+    pub fn on_compact_block_fallback(&self) {
+        self.compact_blocks_fallbacks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Build the stats for the API
+    pub fn get_stats(&self) -> BlockPropagationStats {
+        let full_blocks = self.full_blocks.load(Ordering::Relaxed);
+        let compact_blocks = self.compact_blocks.load(Ordering::Relaxed);
+        BlockPropagationStats {
+            full_blocks,
+            full_blocks_avg_time: self.full_blocks_time.load(Ordering::Relaxed).checked_div(full_blocks).unwrap_or(0),
+            compact_blocks,
+            compact_blocks_avg_time: self.compact_blocks_time.load(Ordering::Relaxed).checked_div(compact_blocks).unwrap_or(0),
+            compact_blocks_rebuilt: self.compact_blocks_rebuilt.load(Ordering::Relaxed),
+            compact_blocks_missing_txs: self.compact_blocks_missing_txs.load(Ordering::Relaxed),
+            compact_blocks_fallbacks: self.compact_blocks_fallbacks.load(Ordering::Relaxed)
+        }
+    }
+}
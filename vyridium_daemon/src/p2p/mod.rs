@@ -4,6 +4,9 @@ pub mod error;
 pub mod packet;
 pub mod peer_list;
 pub mod chain_validator;
+pub mod metrics;
+pub mod proxy;
+pub mod traffic;
 mod tracker;
 mod encryption;
 
@@ -25,6 +28,7 @@ use vyridium_common::{
     immutable::Immutable,
     serializer::Serializer,
     tokio::{ThreadPool, spawn_task},
+    transaction::Transaction,
     time::{
         get_current_time_in_millis,
         get_current_time_in_seconds,
@@ -39,7 +43,7 @@ use crate::{
         CHAIN_SYNC_REQUEST_MAX_BLOCKS, CHAIN_SYNC_RESPONSE_MAX_BLOCKS, CHAIN_SYNC_RESPONSE_MIN_BLOCKS,
         CHAIN_SYNC_TOP_BLOCKS, MILLIS_PER_SECOND, NETWORK_ID, P2P_AUTO_CONNECT_PRIORITY_NODES_DELAY,
         P2P_EXTEND_PEERLIST_DELAY, P2P_PING_DELAY, P2P_PING_PEER_LIST_DELAY, P2P_PING_PEER_LIST_LIMIT,
-        PEER_FAIL_LIMIT, PEER_MAX_PACKET_SIZE, PEER_TIMEOUT_INIT_CONNECTION, PEER_TIMEOUT_INIT_OUTGOING_CONNECTION,
+        PEER_FAIL_LIMIT, PEER_MAX_PACKET_CONTENT_SIZE, PEER_MAX_PACKET_SIZE, PEER_TIMEOUT_INIT_CONNECTION, PEER_TIMEOUT_INIT_OUTGOING_CONNECTION,
         PRUNE_SAFETY_LIMIT, STABLE_LIMIT, P2P_PING_TIMEOUT, P2P_HEARTBEAT_INTERVAL
     },
     core::{
@@ -58,6 +62,14 @@ use crate::{
                 MAX_ITEMS_PER_PAGE
             },
             chain::CommonPoint,
+            compact_block::{
+                compute_short_tx_id,
+                BlockTransactionsRequest,
+                BlockTransactionsResponse,
+                CompactBlock,
+                ShortTxId
+            },
+            handshake::{CAPABILITY_BATCHED_OBJECTS, CAPABILITY_COMPACT_BLOCKS},
             inventory::{
                 NotifyInventoryRequest,
                 NotifyInventoryResponse,
@@ -74,18 +86,21 @@ use self::{
     packet::{
         chain::{BlockId, ChainRequest, ChainResponse},
         handshake::Handshake,
-        object::{ObjectRequest, ObjectResponse, OwnedObjectResponse},
+        object::{ObjectRequest, ObjectResponse, ObjectsResponse, OwnedObjectResponse},
         ping::Ping,
         Packet,
         PacketWrapper
     },
+    metrics::BlockPropagationMetrics,
+    proxy::{Socks5Proxy, ProxyTarget},
+    traffic::{RateLimits, TokenBucket},
     peer::{Peer, TaskState, Rx},
     peer_list::{PeerList, SharedPeerList},
     tracker::{ObjectTracker, SharedObjectTracker}
 };
 use tokio::{
     io::AsyncWriteExt,
-    net::{TcpListener, TcpStream},
+    net::{lookup_host, TcpListener, TcpStream},
     select,
     sync::{
         broadcast,
@@ -104,19 +119,48 @@ use tokio::{
 use log::{info, warn, error, debug, trace};
 use std::{
     borrow::Cow,
-    collections::{hash_map::Entry, HashSet},
+    collections::{hash_map::Entry, HashMap, HashSet},
     io,
-    net::{IpAddr, SocketAddr},
+    net::{IpAddr, Ipv4Addr, SocketAddr},
     num::NonZeroUsize,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc
     },
-    time::Duration
+    time::{Duration, Instant}
 };
 use bytes::Bytes;
 use rand::{seq::IteratorRandom, Rng};
 
+// Block propagated by a peer and waiting to be processed
+enum PropagatedBlock {
+    // Full block header, missing TXs are requested one by one
+    Header(BlockHeader),
+    // Compact block, rebuilt using our mempool
+    Compact(CompactBlock)
+}
+
+// Block propagated, its hash and when we received it
+type PropagatedBlockEntry = (Arc<Peer>, PropagatedBlock, Hash, Instant);
+
+// Target of an outgoing connection
+enum OutgoingTarget {
+    Addr(SocketAddr),
+    // Hostname resolved by the proxy, its IP address is never known locally
+    Host(String, u16)
+}
+
+impl OutgoingTarget {
+    // Address used to identify the connection
+    // A hostname has no IP address, so the unspecified one is used
+    fn get_address(&self) -> SocketAddr {
+        match self {
+            Self::Addr(addr) => *addr,
+            Self::Host(_, port) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), *port)
+        }
+    }
+}
+
 // P2pServer is a fully async TCP server
 // Each connection will block on a data to send or to receive
 // useful for low end hardware
@@ -134,7 +178,7 @@ pub struct P2pServer<S: Storage> {
     // reference to the chain to add blocks/txs
     blockchain: Arc<Blockchain<S>>,
     // this sender allows to create a queue system in one task only
-    connections_sender: Sender<(SocketAddr, bool)>,
+    connections_sender: Sender<(OutgoingTarget, bool)>,
     // used to requests objects to peers and avoid requesting the same object to multiple peers
     object_tracker: SharedObjectTracker,
     // used to check if the server is running or not in tasks
@@ -142,7 +186,7 @@ pub struct P2pServer<S: Storage> {
     // Synced cache to prevent concurrent tasks adding the block
     blocks_propagation_queue: Mutex<LruCache<Hash, ()>>,
     // Sender for the blocks processing task to have a ordered queue
-    blocks_processor: Sender<(Arc<Peer>, BlockHeader, Hash)>,
+    blocks_processor: Sender<PropagatedBlockEntry>,
     // allow fast syncing (only balances / assets / Smart Contracts changes)
     // without syncing the history
     allow_fast_sync_mode: bool,
@@ -165,12 +209,24 @@ pub struct P2pServer<S: Storage> {
     outgoing_connections_disabled: AtomicBool,
     // Are we syncing the chain with another peer
     is_syncing: AtomicBool,
+    // Do we send and accept compact blocks with peers supporting it
+    compact_blocks: bool,
+    // Metrics to compare full and compact blocks propagation
+    block_propagation_metrics: BlockPropagationMetrics,
+    // SOCKS5 proxy used for all outgoing connections
+    proxy: Option<Socks5Proxy>,
+    // Limits configured, per peer limits are created from it
+    rate_limits: RateLimits,
+    // Upload limit shared by all peers
+    upload_limiter: Option<TokenBucket>,
+    // Chain requests limit shared by all peers
+    chain_requests_limiter: Option<TokenBucket>,
     // Exit channel to notify all tasks to stop
     exit_sender: broadcast::Sender<()>
 }
 
 impl<S: Storage> P2pServer<S> {
-    pub fn new(concurrency: usize, dir_path: Option<String>, tag: Option<String>, max_peers: usize, bind_address: String, blockchain: Arc<Blockchain<S>>, use_peerlist: bool, exclusive_nodes: Vec<SocketAddr>, allow_fast_sync_mode: bool, allow_boost_sync_mode: bool, max_chain_response_size: Option<usize>, sharable: bool, disable_outgoing_connections: bool) -> Result<Arc<Self>, P2pError> {
+    pub fn new(concurrency: usize, dir_path: Option<String>, tag: Option<String>, max_peers: usize, bind_address: String, blockchain: Arc<Blockchain<S>>, use_peerlist: bool, exclusive_nodes: Vec<SocketAddr>, allow_fast_sync_mode: bool, allow_boost_sync_mode: bool, max_chain_response_size: Option<usize>, sharable: bool, disable_outgoing_connections: bool, disable_compact_blocks: bool, proxy: Option<String>, rate_limits: RateLimits) -> Result<Arc<Self>, P2pError> {
         if tag.as_ref().is_some_and(|tag| tag.len() == 0 || tag.len() > 16) {
             return Err(P2pError::InvalidTag);
         }
@@ -183,6 +239,15 @@ impl<S: Storage> P2pServer<S> {
             return Err(P2pError::InvalidMaxPeers);
         }
 
+        let proxy = match proxy {
+            Some(url) => {
+                let proxy: Socks5Proxy = url.parse()?;
+                info!("All outgoing P2P connections will go through {}", proxy);
+                Some(proxy)
+            },
+            None => None
+        };
+
         // set channel to communicate with listener thread
         let mut rng = rand::thread_rng();
         let peer_id: u64 = rng.gen(); // generate a random peer id for network
@@ -196,7 +261,7 @@ impl<S: Storage> P2pServer<S> {
         let object_tracker = ObjectTracker::new(blockchain.clone(), exit_receiver);
 
         let (sender, event_receiver) = channel::<Arc<Peer>>(max_peers); 
-        let peer_list = PeerList::new(max_peers, format!("{}peerlist-{}.json", dir_path.unwrap_or_default(), blockchain.get_network().to_string().to_lowercase()), Some(sender));
+        let peer_list = PeerList::new(max_peers, format!("{}peerlist-{}", dir_path.unwrap_or_default(), blockchain.get_network().to_string().to_lowercase()), Some(sender))?;
 
 
         let server = Self {
@@ -218,6 +283,12 @@ impl<S: Storage> P2pServer<S> {
             sharable,
             is_syncing: AtomicBool::new(false),
             outgoing_connections_disabled: AtomicBool::new(disable_outgoing_connections),
+            compact_blocks: !disable_compact_blocks,
+            block_propagation_metrics: BlockPropagationMetrics::new(),
+            proxy,
+            upload_limiter: rate_limits.upload.map(TokenBucket::per_second),
+            chain_requests_limiter: rate_limits.chain_requests.map(TokenBucket::per_minute),
+            rate_limits,
             exit_sender,
         };
 
@@ -307,7 +378,7 @@ impl<S: Storage> P2pServer<S> {
 
     // connect to seed nodes, start p2p server
     // and wait on all new connections
-    async fn start(self: &Arc<Self>, receiver: Receiver<(SocketAddr, bool)>, blocks_processor_receiver: Receiver<(Arc<Peer>, BlockHeader, Hash)>, event_receiver: Receiver<Arc<Peer>>, use_peerlist: bool, concurrency: usize) -> Result<(), P2pError> {
+    async fn start(self: &Arc<Self>, receiver: Receiver<(OutgoingTarget, bool)>, blocks_processor_receiver: Receiver<PropagatedBlockEntry>, event_receiver: Receiver<Arc<Peer>>, use_peerlist: bool, concurrency: usize) -> Result<(), P2pError> {
         let listener = TcpListener::bind(self.get_bind_address()).await?;
         info!("P2p Server will listen on: {}", self.get_bind_address());
 
@@ -342,15 +413,24 @@ impl<S: Storage> P2pServer<S> {
         spawn_task("p2p-events", Arc::clone(&self).event_loop(event_receiver));
 
 
+        let (tx, mut rx) = channel(1);
+        spawn_task("p2p-outgoing-connections", Arc::clone(&self).handle_outgoing_connections(priority_connections, receiver, tx.clone()));
+        spawn_task("p2p-incoming-connections", Arc::clone(&self).handle_incoming_connections(listener, tx, concurrency));
+
         // start another task for peerlist loop
         if use_peerlist {
+            // reconnect first to our anchors from the last run
+            let zelf = Arc::clone(self);
+            spawn_task("p2p-anchors", async move {
+                for addr in zelf.peer_list.take_anchors() {
+                    info!("Trying to connect to anchor peer: {}", addr);
+                    zelf.try_to_connect_to_peer(addr, false).await;
+                }
+            });
+
             spawn_task("p2p-peerlist", Arc::clone(&self).peerlist_loop());
         }
 
-        let (tx, mut rx) = channel(1);
-        spawn_task("p2p-outgoing-connections", Arc::clone(&self).handle_outgoing_connections(priority_connections, receiver, tx.clone()));
-        spawn_task("p2p-incoming-connections", Arc::clone(&self).handle_incoming_connections(listener, tx, concurrency));
-
         let mut exit_receiver = self.exit_sender.subscribe();
         loop {
             select! {
@@ -399,12 +479,12 @@ impl<S: Storage> P2pServer<S> {
         Ok(())
     }
 
-    async fn handle_outgoing_connections(self: Arc<Self>, mut priority_connections: Receiver<SocketAddr>, mut receiver: Receiver<(SocketAddr, bool)>, tx: Sender<(Peer, Rx)>) {
+    async fn handle_outgoing_connections(self: Arc<Self>, mut priority_connections: Receiver<SocketAddr>, mut receiver: Receiver<(OutgoingTarget, bool)>, tx: Sender<(Peer, Rx)>) {
         // only allocate one time the buffer for this packet
         let mut handshake_buffer = [0; 512];
         let mut exit_receiver = self.exit_sender.subscribe();
         loop {
-            let (addr, priority) = select! {
+            let (target, priority) = select! {
                 biased;
                 _ = exit_receiver.recv() => {
                     debug!("Received exit message, exiting outgoing connections task");
@@ -413,7 +493,7 @@ impl<S: Storage> P2pServer<S> {
                 res = priority_connections.recv() => {
                     trace!("New priority connection received");
                     match res {
-                        Some(res) => (res, true),
+                        Some(res) => (OutgoingTarget::Addr(res), true),
                         None => {
                             error!("Error while receiving priority connection, exiting task");
                             break;
@@ -431,6 +511,7 @@ impl<S: Storage> P2pServer<S> {
                 }
             };
 
+            let addr = target.get_address();
             trace!("Trying to connect to {}", addr);
             if !priority {
                 trace!("checking if connection can be accepted");
@@ -445,7 +526,7 @@ impl<S: Storage> P2pServer<S> {
                 break;
             }
 
-            let connection = match self.connect_to_peer(addr).await {
+            let connection = match self.connect_to_peer(target).await {
                 Ok(connection) => connection,
                 Err(e) => {
                     debug!("Error while connecting to address {}: {}", addr, e);
@@ -499,7 +580,7 @@ impl<S: Storage> P2pServer<S> {
             return Ok(())
         }
 
-        let connection = Connection::new(stream, addr, false);
+        let connection = Connection::new(stream, addr, false, false);
         let zelf = Arc::clone(&self);
         let tx = tx.clone();
         thread_pool.execute(async move {
@@ -593,7 +674,11 @@ impl<S: Storage> P2pServer<S> {
         let pruned_topoheight = storage.get_pruned_topoheight().await?;
         let cumulative_difficulty = storage.get_cumulative_difficulty_for_block_hash(&top_hash).await.unwrap_or_else(|_| CumulativeDifficulty::zero());
         let genesis_block = get_genesis_block_hash(self.blockchain.get_network());
-        let handshake = Handshake::new(Cow::Owned(VERSION.to_owned()), *self.blockchain.get_network(), Cow::Borrowed(self.get_tag()), Cow::Borrowed(&NETWORK_ID), self.get_peer_id(), self.bind_address.port(), get_current_time_in_seconds(), topoheight, block.get_height(), pruned_topoheight, Cow::Borrowed(&top_hash), Cow::Borrowed(genesis_block), Cow::Borrowed(&cumulative_difficulty), self.sharable);
+        let mut capabilities = CAPABILITY_BATCHED_OBJECTS;
+        if self.compact_blocks {
+            capabilities |= CAPABILITY_COMPACT_BLOCKS;
+        }
+        let handshake = Handshake::new(Cow::Owned(VERSION.to_owned()), *self.blockchain.get_network(), Cow::Borrowed(self.get_tag()), Cow::Borrowed(&NETWORK_ID), self.get_peer_id(), self.bind_address.port(), get_current_time_in_seconds(), topoheight, block.get_height(), pruned_topoheight, Cow::Borrowed(&top_hash), Cow::Borrowed(genesis_block), Cow::Borrowed(&cumulative_difficulty), self.sharable, capabilities);
         Ok(Packet::Handshake(Cow::Owned(handshake)).to_bytes())
     }
 
@@ -608,7 +693,7 @@ impl<S: Storage> P2pServer<S> {
             }
         };
 
-        let (peer, rx) = handshake.create_peer(connection, priority, self.peer_list.clone());
+        let (peer, rx) = handshake.create_peer(connection, priority, self.peer_list.clone(), self.rate_limits.create_peer_limiter());
         Ok((peer, rx))
     }
 
@@ -683,20 +768,46 @@ impl<S: Storage> P2pServer<S> {
     // Buffer is passed in parameter to prevent the re-allocation each time
     // No check is done, this is done at the moment of the connection
     pub async fn try_to_connect_to_peer(&self, addr: SocketAddr, priority: bool) {
+        self.send_outgoing_target(OutgoingTarget::Addr(addr), priority).await;
+    }
+
+    // Connect to a peer using its hostname (host:port)
+    // If the proxy is set to resolve hostnames, we don't leak the DNS request locally
+    // and the proxy connects to the hostname directly
+    pub async fn try_to_connect_to_host(&self, host: &str, priority: bool) -> Result<(), P2pError> {
+        if self.proxy.as_ref().is_some_and(|proxy| proxy.is_remote_dns()) {
+            let (domain, port) = host.rsplit_once(':')
+                .ok_or_else(|| P2pError::InvalidPeerAddress(host.to_owned()))?;
+            let port = port.parse().map_err(|_| P2pError::InvalidPeerAddress(host.to_owned()))?;
+            self.send_outgoing_target(OutgoingTarget::Host(domain.to_owned(), port), priority).await;
+        } else {
+            for addr in lookup_host(host).await? {
+                debug!("{} resolved to {}", host, addr);
+                self.try_to_connect_to_peer(addr, priority).await;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Queue an outgoing connection
+    async fn send_outgoing_target(&self, target: OutgoingTarget, priority: bool) {
+        let addr = target.get_address();
         debug!("try to connect to peer addr {}, priority: {}", addr, priority);
         if self.connections_sender.is_closed() {
             error!("Connection sender is closed, we can't connect to peer {}", addr);
             return;
         }
 
-        if let Err(e) = self.connections_sender.send((addr, priority)).await {
+        if let Err(e) = self.connections_sender.send((target, priority)).await {
             error!("Error while trying to connect to address {} (priority = {}): {}", addr, priority, e);
         }
     }
 
-    // Connect to a new peer using its socket address
+    // Connect to a new peer using its socket address or its hostname
     // Then we send him a handshake
-    async fn connect_to_peer(&self, addr: SocketAddr) -> Result<Connection, P2pError> {
+    async fn connect_to_peer(&self, target: OutgoingTarget) -> Result<Connection, P2pError> {
+        let addr = target.get_address();
         trace!("Trying to connect to {}", addr);
 
         if !self.is_compatible_with_exclusive_nodes(&addr) {
@@ -704,11 +815,12 @@ impl<S: Storage> P2pServer<S> {
             return Err(P2pError::ExclusiveNode);
         }
 
-        {
+        // A hostname has no known address, duplicated peers are detected by their id
+        if let OutgoingTarget::Addr(addr) = &target {
             trace!("peer list locked for trying to connect to peer {}", addr);
-            if self.is_connected_to_addr(&addr).await {
+            if self.is_connected_to_addr(addr).await {
                 debug!("Already connected to peer: {}, skipping", addr);
-                return Err(P2pError::PeerAlreadyConnected(addr));
+                return Err(P2pError::PeerAlreadyConnected(*addr));
             }
 
             if !self.peer_list.is_allowed(&addr.ip()).await {
@@ -717,11 +829,63 @@ impl<S: Storage> P2pServer<S> {
             }
         }
 
-        let stream = timeout(Duration::from_millis(PEER_TIMEOUT_INIT_OUTGOING_CONNECTION), TcpStream::connect(&addr)).await??;
-        let connection = Connection::new(stream, addr, true);
+        let connection = match (self.proxy.as_ref(), &target) {
+            (Some(proxy), OutgoingTarget::Addr(addr)) => {
+                let stream = timeout(Duration::from_millis(PEER_TIMEOUT_INIT_OUTGOING_CONNECTION), proxy.connect(ProxyTarget::Addr(*addr))).await??;
+                Connection::new(stream, *addr, true, true)
+            },
+            (Some(proxy), OutgoingTarget::Host(host, port)) => {
+                debug!("Connecting to {}:{} through proxy {}", host, port, proxy);
+                let stream = timeout(Duration::from_millis(PEER_TIMEOUT_INIT_OUTGOING_CONNECTION), proxy.connect(ProxyTarget::Domain(host, *port))).await??;
+                Connection::new(stream, addr, true, true)
+            },
+            (None, OutgoingTarget::Addr(addr)) => {
+                let stream = timeout(Duration::from_millis(PEER_TIMEOUT_INIT_OUTGOING_CONNECTION), TcpStream::connect(addr)).await??;
+                Connection::new(stream, *addr, true, false)
+            },
+            (None, OutgoingTarget::Host(host, port)) => {
+                let stream = timeout(Duration::from_millis(PEER_TIMEOUT_INIT_OUTGOING_CONNECTION), TcpStream::connect((host.as_str(), *port))).await??;
+                let addr = stream.peer_addr()?;
+                Connection::new(stream, addr, true, false)
+            }
+        };
         Ok(connection)
     }
 
+    // Compute how long we must wait before sending bytes to a peer
+    // Both the global and the peer upload limits are applied
+    fn get_upload_delay(&self, peer: &Peer, bytes: usize) -> Duration {
+        let peer_delay = peer.get_rate_limiter().upload_delay(bytes);
+        let global_delay = self.upload_limiter.as_ref()
+            .map_or(Duration::ZERO, |bucket| bucket.consume(bytes as u64));
+
+        peer_delay.max(global_delay)
+    }
+
+    // Verify that we can handle a chain or bootstrap chain request
+    // A peer going over its own limit is penalized through the returned error
+    // If our global limit is reached, the request is ignored without penalty
+    fn allow_chain_request(&self, peer: &Peer) -> Result<bool, P2pError> {
+        if !peer.get_rate_limiter().allow_chain_request() {
+            debug!("{} exceeded its chain requests limit", peer);
+            return Err(P2pError::ChainRequestsRateLimited)
+        }
+
+        if let Some(bucket) = self.chain_requests_limiter.as_ref() {
+            if !bucket.try_consume(1) {
+                debug!("Global chain requests limit reached, ignoring request from {}", peer);
+                return Ok(false)
+            }
+        }
+
+        Ok(true)
+    }
+
+    // Get the proxy used for outgoing connections
+    pub fn get_proxy(&self) -> Option<&Socks5Proxy> {
+        self.proxy.as_ref()
+    }
+
     // Send a handshake to a connection (this is used to determine if its a potential peer)
     // Handsake is sent only once, when we connect to a new peer, and we get it back from connection to make it a peer
     async fn send_handshake(&self, connection: &Connection) -> Result<(), P2pError> {
@@ -1003,7 +1167,8 @@ impl<S: Storage> P2pServer<S> {
                     for p in all_peers.iter() {
                         // don't send him itself
                         // and don't share a peer that don't want to be shared
-                        if p.get_id() == peer.get_id() || !p.sharable() {
+                        // nor a peer we reached through our proxy
+                        if p.get_id() == peer.get_id() || !p.sharable() || p.is_proxied() {
                             continue;
                         }
 
@@ -1131,8 +1296,138 @@ impl<S: Storage> P2pServer<S> {
         debug!("Event loop task is stopped!");
     }
 
+    // Build the response of an object requested by a peer
+    async fn build_object_response(&self, peer: &Arc<Peer>, request: ObjectRequest) -> ObjectResponse<'static> {
+        match &request {
+            ObjectRequest::Block(hash) => {
+                debug!("{} asked full block {}", peer, hash);
+                let block = {
+                    let storage = self.blockchain.get_storage().read().await;
+                    storage.get_block_by_hash(hash).await
+                };
+
+                match block {
+                    Ok(block) => {
+                        debug!("block {} found, sending it", hash);
+                        ObjectResponse::Block(Cow::Owned(block))
+                    },
+                    Err(e) => {
+                        debug!("{} asked block '{}' but not present in our chain: {}", peer, hash, e);
+                        ObjectResponse::NotFound(request)
+                    }
+                }
+            },
+            ObjectRequest::BlockHeader(hash) => {
+                debug!("{} asked block header {}", peer, hash);
+                let block = {
+                    let storage = self.blockchain.get_storage().read().await;
+                    storage.get_block_header_by_hash(hash).await
+                };
+
+                match block {
+                    Ok(block) => {
+                        debug!("block header {} found, sending it", hash);
+                        ObjectResponse::BlockHeader(Cow::Owned(block.as_ref().clone()))
+                    },
+                    Err(e) => {
+                        debug!("{} asked block header '{}' but not present in our chain: {}", peer, hash, e);
+                        ObjectResponse::NotFound(request)
+                    }
+                }
+            },
+            ObjectRequest::Transaction(hash) => {
+                debug!("{} asked tx {}", peer, hash);
+                match self.blockchain.get_tx(hash).await {
+                    Ok(tx) => {
+                        debug!("tx {} found, sending it", hash);
+                        ObjectResponse::Transaction(Cow::Owned(tx.as_ref().clone()))
+                    },
+                    Err(e) => {
+                        debug!("{} asked tx '{}' but not present in our chain: {}", peer, hash, e);
+                        ObjectResponse::NotFound(request)
+                    }
+                }
+            }
+        }
+    }
+
+    // Handle an object response received from a peer
+    async fn handle_object_response(&self, peer: &Arc<Peer>, response: OwnedObjectResponse) -> Result<(), P2pError> {
+        trace!("Object response received is {}", response.get_hash());
+
+        // check if we requested it from this peer
+        let request = response.get_request();
+        if peer.has_requested_object(&request).await {
+            let sender = peer.remove_object_request(request).await?;
+            // handle the response
+            if sender.send(response).is_err() {
+                error!("Error while sending object response to sender!");
+            }
+        // check if the Object Tracker has requested this object
+        } else if self.object_tracker.has_requested_object(request.get_hash()).await {
+            trace!("Object Tracker requested it, handling it");
+            self.object_tracker.handle_object_response(response).await?;
+        } else if self.object_tracker.is_ignored_request_hash(request.get_hash()).await {
+            debug!("Object {} was ignored by Object Tracker, ignoring response", request.get_hash());
+        } else {
+            return Err(P2pError::ObjectNotRequested(request))
+        }
+
+        Ok(())
+    }
+
+    // Track a block propagated by a peer in the caches
+    // Returns true if the block must be processed
+    async fn track_propagated_block(&self, peer: &Arc<Peer>, header: &BlockHeader, block_hash: &Hash) -> Result<bool, P2pError> {
+        // verify that this block wasn't already sent by him
+        {
+            let mut blocks_propagation = peer.get_blocks_propagation().lock().await;
+            if let Some(direction) = blocks_propagation.get_mut(block_hash) {
+                if !direction.update(Direction::In) {
+                    debug!("{} send us a block ({}) already tracked by him ({:?})", peer, block_hash, direction);
+                    // return Err(P2pError::AlreadyTrackedBlock(block_hash, *direction))
+                }
+            } else {
+                debug!("Saving {} in blocks propagation cache for {}", block_hash, peer);
+                blocks_propagation.put(block_hash.clone(),  Direction::In);
+            }
+        }
+
+        // Avoid sending the same block to a common peer that may have already got it
+        // because we track peerlist of each peers, we can try to determinate it
+        for common_peer in self.get_common_peers_for(peer).await {
+            debug!("{} is a common peer with {}, adding block {} to its propagation cache", common_peer, peer, block_hash);
+            let mut blocks_propagation = common_peer.get_blocks_propagation().lock().await;
+            // Out allow to get "In" again, because it's a prediction, don't block it completely
+            if !blocks_propagation.contains(block_hash) {
+                blocks_propagation.put(block_hash.clone(), Direction::Out);
+            }
+        }
+
+        // check that we don't have this block in our chain
+        {
+            let storage = self.blockchain.get_storage().read().await;
+            if storage.has_block_with_hash(block_hash).await? {
+                debug!("{}: {} with hash {} is already in our chain. Skipping", peer, header, block_hash);
+                return Ok(false)
+            }
+        }
+
+        // Check that we are not already waiting on it
+        {
+            let mut blocks_propagation_queue = self.blocks_propagation_queue.lock().await;
+            if blocks_propagation_queue.contains(block_hash) {
+                debug!("Block {} propagated is already in processing from another peer", block_hash);
+                return Ok(false)
+            }
+            blocks_propagation_queue.put(block_hash.clone(), ());
+        }
+
+        Ok(true)
+    }
+
     // Task for all blocks propagation
-    async fn blocks_processing_task(self: Arc<Self>, mut receiver: Receiver<(Arc<Peer>, BlockHeader, Hash)>) {
+    async fn blocks_processing_task(self: Arc<Self>, mut receiver: Receiver<PropagatedBlockEntry>) {
         debug!("Starting blocks processing task");
         let mut server_exit = self.exit_sender.subscribe();
 
@@ -1144,62 +1439,42 @@ impl<S: Storage> P2pServer<S> {
                     break 'main;
                 }
                 msg = receiver.recv() => {
-                    let Some((peer, header, block_hash)) = msg else {
+                    let Some((peer, block, block_hash, received_at)) = msg else {
                         debug!("No more blocks to process, stopping blocks processing task");
                         break 'main;
                     };
 
-                    let mut response_blockers: Vec<ResponseBlocker> = Vec::new();
-                    for hash in header.get_txs_hashes() {
-                        let contains = { // we don't lock one time because we may wait on p2p response
-                            // Check in ObjectTracker
-                            if let Some(response_blocker) = self.object_tracker.get_response_blocker_for_requested_object(hash).await {
-                                trace!("{} is already requested, waiting on response blocker for block {}", hash, block_hash);
-                                response_blockers.push(response_blocker);
-                                true
-                            } else {
-                                self.blockchain.has_tx(hash).await.unwrap_or(false)
-                            }
-                        };
-
-                        if !contains { // retrieve one by one to prevent acquiring the lock for nothing
-                            debug!("Requesting TX {} to {} for block {}", hash, peer, block_hash);
-                            if let Err(e) = self.object_tracker.request_object_from_peer(Arc::clone(&peer), ObjectRequest::Transaction(hash.clone()), false).await {
-                                    error!("Error while requesting TX {} to {} for block {}: {}", hash, peer, block_hash, e);
-                                    peer.increment_fail_count();
-                                    continue 'main;
-                            }
-
-                            if let Some(response_blocker) = self.object_tracker.get_response_blocker_for_requested_object(hash).await {
-                                response_blockers.push(response_blocker);
-                            }
-                        }
-                    }
-
-                    // Wait on all already requested txs
-                    for mut blocker in response_blockers {
-                        if let Err(e) = blocker.recv().await {
-                            // It's mostly a closed channel error, so we can ignore it
-                            warn!("Error while waiting on response blocker: {}", e);
-                            peer.increment_fail_count();
-                            continue 'main;
-                        }
-                    }
+                    let is_compact = matches!(block, PropagatedBlock::Compact(_));
+                    let res = match block {
+                        PropagatedBlock::Header(header) => self.build_block_from_propagated_header(&peer, header, &block_hash).await,
+                        PropagatedBlock::Compact(compact) => self.build_block_from_compact_block(&peer, compact, &block_hash).await
+                    };
 
                     // add immediately the block to chain as we are synced with
-                    let block = match self.blockchain.build_block_from_header(Immutable::Owned(header)).await {
+                    let block = match res {
                         Ok(block) => block,
                         Err(e) => {
                             error!("Error while building block {} from peer {}: {}", block_hash, peer, e);
                             peer.increment_fail_count();
+                            if is_compact {
+                                // Allow others peers to propagate it to us again
+                                self.blocks_propagation_queue.lock().await.pop(&block_hash);
+                            }
                             continue 'main;
                         }
                     };
-        
+
                     debug!("Adding received block {} from {} to chain", block_hash, peer);
                     if let Err(e) = self.blockchain.add_new_block(block, true, false).await {
                         error!("Error while adding new block from {}: {}", peer, e);
                         peer.increment_fail_count();
+                        continue 'main;
+                    }
+
+                    if is_compact {
+                        self.block_propagation_metrics.on_compact_block(received_at.elapsed());
+                    } else {
+                        self.block_propagation_metrics.on_full_block(received_at.elapsed());
                     }
                 }
             }
@@ -1208,6 +1483,140 @@ impl<S: Storage> P2pServer<S> {
         debug!("Blocks processing task ended");
     }
 
+    // Build a block propagated with its full header
+    // Missing TXs are requested one by one using the object tracker
+    async fn build_block_from_propagated_header(&self, peer: &Arc<Peer>, header: BlockHeader, block_hash: &Hash) -> Result<Block, P2pError> {
+        let mut response_blockers: Vec<ResponseBlocker> = Vec::new();
+        for hash in header.get_txs_hashes() {
+            let contains = { // we don't lock one time because we may wait on p2p response
+                // Check in ObjectTracker
+                if let Some(response_blocker) = self.object_tracker.get_response_blocker_for_requested_object(hash).await {
+                    trace!("{} is already requested, waiting on response blocker for block {}", hash, block_hash);
+                    response_blockers.push(response_blocker);
+                    true
+                } else {
+                    self.blockchain.has_tx(hash).await.unwrap_or(false)
+                }
+            };
+
+            if !contains { // retrieve one by one to prevent acquiring the lock for nothing
+                debug!("Requesting TX {} to {} for block {}", hash, peer, block_hash);
+                if let Err(e) = self.object_tracker.request_object_from_peer(Arc::clone(peer), ObjectRequest::Transaction(hash.clone()), false).await {
+                    error!("Error while requesting TX {} to {} for block {}: {}", hash, peer, block_hash, e);
+                    return Err(e)
+                }
+
+                if let Some(response_blocker) = self.object_tracker.get_response_blocker_for_requested_object(hash).await {
+                    response_blockers.push(response_blocker);
+                }
+            }
+        }
+
+        // Wait on all already requested txs
+        for mut blocker in response_blockers {
+            if let Err(e) = blocker.recv().await {
+                // It's mostly a closed channel error, so we can ignore it
+                warn!("Error while waiting on response blocker: {}", e);
+                return Err(P2pError::ResponseBlockerError(e))
+            }
+        }
+
+        let block = self.blockchain.build_block_from_header(Immutable::Owned(header)).await?;
+        Ok(block)
+    }
+
+    // Build a block propagated as a compact block
+    // If we can't rebuild it, we fallback by requesting the full block to the peer
+    async fn build_block_from_compact_block(&self, peer: &Arc<Peer>, compact: CompactBlock, block_hash: &Hash) -> Result<Block, P2pError> {
+        match self.rebuild_compact_block(peer, &compact, block_hash).await {
+            Ok(block) => Ok(block),
+            Err(e) => {
+                debug!("Couldn't rebuild compact block {} from {}: {}, requesting full block", block_hash, peer, e);
+                self.block_propagation_metrics.on_compact_block_fallback();
+                match peer.request_blocking_object(ObjectRequest::Block(block_hash.clone())).await? {
+                    OwnedObjectResponse::Block(block, _) => Ok(block),
+                    _ => Err(P2pError::ExpectedBlock)
+                }
+            }
+        }
+    }
+
+    // Rebuild a compact block using the TXs from our mempool
+    // All missing TXs are requested in one batch to the peer
+    async fn rebuild_compact_block(&self, peer: &Arc<Peer>, compact: &CompactBlock, block_hash: &Hash) -> Result<Block, P2pError> {
+        let salt = compact.get_salt();
+        let short_ids = compact.get_short_ids();
+
+        // Search all short ids in our mempool
+        // If two TXs have the same short id, we consider it as missing
+        let mut txs: Vec<Option<(Hash, Immutable<Transaction>)>> = {
+            let mempool = self.blockchain.get_mempool().read().await;
+            let mut candidates: HashMap<ShortTxId, Option<(&Arc<Hash>, &Arc<Transaction>)>> = HashMap::with_capacity(mempool.size());
+            for (hash, sorted_tx) in mempool.get_txs() {
+                match candidates.entry(compute_short_tx_id(salt, hash)) {
+                    Entry::Occupied(mut entry) => {
+                        entry.insert(None);
+                    },
+                    Entry::Vacant(entry) => {
+                        entry.insert(Some((hash, sorted_tx.get_tx())));
+                    }
+                }
+            }
+
+            short_ids.iter()
+                .map(|id| candidates.get(id)
+                    .copied()
+                    .flatten()
+                    .map(|(hash, tx)| (Hash::clone(hash), Immutable::Arc(Arc::clone(tx))))
+                )
+                .collect()
+        };
+
+        let missing: IndexSet<u16> = txs.iter()
+            .enumerate()
+            .filter(|(_, tx)| tx.is_none())
+            .map(|(i, _)| i as u16)
+            .collect();
+
+        if missing.is_empty() {
+            self.block_propagation_metrics.on_compact_block_rebuilt();
+        } else {
+            debug!("Compact block {} from {} is missing {} TXs", block_hash, peer, missing.len());
+            self.block_propagation_metrics.on_compact_block_missing_txs(missing.len());
+            let request = BlockTransactionsRequest::new(block_hash.clone(), missing.clone());
+            let (_, response_txs) = peer.request_block_transactions(request).await?.consume();
+            if response_txs.len() != missing.len() {
+                return Err(P2pError::InvalidBlockTransactionsResponse)
+            }
+
+            for (index, tx) in missing.into_iter().zip(response_txs) {
+                // Verify that the TX received is the one expected
+                let hash = tx.hash();
+                if compute_short_tx_id(salt, &hash) != short_ids[index as usize] {
+                    return Err(P2pError::InvalidBlockTransactionsResponse)
+                }
+                txs[index as usize] = Some((hash, Immutable::Owned(tx)));
+            }
+        }
+
+        let mut txs_hashes = IndexSet::with_capacity(txs.len());
+        let mut transactions = Vec::with_capacity(txs.len());
+        for (hash, tx) in txs.into_iter().flatten() {
+            if !txs_hashes.insert(hash) {
+                return Err(P2pError::InvalidBlockTransactionsResponse)
+            }
+            transactions.push(tx);
+        }
+
+        // A short id collision with one of our TXs would give us another block
+        let header = compact.to_header(txs_hashes);
+        if header.hash() != *block_hash {
+            return Err(P2pError::CompactBlockHashMismatch(block_hash.clone()))
+        }
+
+        Ok(Block::new(Immutable::Owned(header), transactions))
+    }
+
     // this function handle the logic to send all packets to the peer
     async fn handle_connection_write_side(&self, peer: &Arc<Peer>, rx: &mut Rx, mut task_rx: oneshot::Receiver<()>) -> Result<(), P2pError> {
         let mut server_exit = self.exit_sender.subscribe();
@@ -1242,6 +1651,16 @@ impl<S: Storage> P2pServer<S> {
                 Some(bytes) = rx.recv() => {
                     // there is a overhead of 4 for each packet (packet size u32 4 bytes, packet id u8 is counted in the packet size)
                     trace!("Sending packet with ID {}, size sent: {}, real size: {}", bytes[4], u32::from_be_bytes(bytes[0..4].try_into()?), bytes.len());
+                    // Respect the upload limits before sending it
+                    let delay = self.get_upload_delay(peer, bytes.len());
+                    if !delay.is_zero() {
+                        trace!("Delaying packet to {} by {:?} due to upload limit", peer, delay);
+                        sleep(delay).await;
+                    }
+
+                    if let Some(id) = bytes.first() {
+                        peer.get_traffic_stats().on_packet_out(*id);
+                    }
                     peer.get_connection().send_bytes(&bytes).await?;
                     trace!("data sucessfully sent!");
                 }
@@ -1444,6 +1863,7 @@ impl<S: Storage> P2pServer<S> {
             },
             Packet::BlockPropagation(packet_wrapper) => {
                 trace!("Received a block propagation packet from {}", peer);
+                let received_at = Instant::now();
                 let (header, ping) = packet_wrapper.consume();
                 ping.into_owned().update_peer(peer, &self.blockchain).await?;
 
@@ -1451,56 +1871,70 @@ impl<S: Storage> P2pServer<S> {
                 let header = header.into_owned();
                 let block_hash = header.hash();
 
-                // verify that this block wasn't already sent by him
-                {
-                    let mut blocks_propagation = peer.get_blocks_propagation().lock().await;
-                    if let Some(direction) = blocks_propagation.get_mut(&block_hash) {
-                        if !direction.update(Direction::In) {
-                            debug!("{} send us a block ({}) already tracked by him ({:?})", peer, block_hash, direction);
-                            // return Err(P2pError::AlreadyTrackedBlock(block_hash, *direction))
-                        }
-                    } else {
-                        debug!("Saving {} in blocks propagation cache for {}", block_hash, peer);
-                        blocks_propagation.put(block_hash.clone(),  Direction::In);
-                    }
+                if !self.track_propagated_block(peer, &header, &block_hash).await? {
+                    return Ok(())
                 }
 
-                // Avoid sending the same block to a common peer that may have already got it
-                // because we track peerlist of each peers, we can try to determinate it
-                for common_peer in self.get_common_peers_for(&peer).await {
-                    debug!("{} is a common peer with {}, adding block {} to its propagation cache", common_peer, peer, block_hash);
-                    let mut blocks_propagation = common_peer.get_blocks_propagation().lock().await;
-                    // Out allow to get "In" again, because it's a prediction, don't block it completely
-                    if !blocks_propagation.contains(&block_hash) {
-                        blocks_propagation.put(block_hash.clone(), Direction::Out);
-                    }
+                let block_height = header.get_height();
+                debug!("Received block at height {} from {}", block_height, peer);
+                let peer = Arc::clone(peer);
+                // This will block the task if the bounded channel is full
+                if let Err(e) = self.blocks_processor.send((peer, PropagatedBlock::Header(header), block_hash, received_at)).await {
+                    error!("Error while sending block propagated to blocks processor task: {}", e);
                 }
-
-                // check that we don't have this block in our chain
-                {
-                    let storage = self.blockchain.get_storage().read().await;
-                    if storage.has_block_with_hash(&block_hash).await? {
-                        debug!("{}: {} with hash {} is already in our chain. Skipping", peer, header, block_hash);
-                        return Ok(())
-                    }
+            },
+            Packet::CompactBlockPropagation(packet_wrapper) => {
+                trace!("Received a compact block propagation packet from {}", peer);
+                if !self.compact_blocks {
+                    return Err(P2pError::CompactBlocksNotNegotiated)
                 }
 
-                // Check that we are not already waiting on it
-                {
-                    let mut blocks_propagation_queue = self.blocks_propagation_queue.lock().await;
-                    if blocks_propagation_queue.contains(&block_hash) {
-                        debug!("Block {} propagated is already in processing from another peer", block_hash);
-                        return Ok(())
-                    }
-                    blocks_propagation_queue.put(block_hash.clone(), ());
+                let received_at = Instant::now();
+                let (compact, ping) = packet_wrapper.consume();
+                ping.into_owned().update_peer(peer, &self.blockchain).await?;
+
+                let compact = compact.into_owned();
+                let block_hash = compact.get_block_hash().clone();
+
+                if !self.track_propagated_block(peer, compact.get_header(), &block_hash).await? {
+                    return Ok(())
                 }
 
-                let block_height = header.get_height();
-                debug!("Received block at height {} from {}", block_height, peer);
+                debug!("Received compact block at height {} with {} TXs from {}", compact.get_header().get_height(), compact.get_txs_count(), peer);
                 let peer = Arc::clone(peer);
                 // This will block the task if the bounded channel is full
-                if let Err(e) = self.blocks_processor.send((peer, header, block_hash)).await {
-                    error!("Error while sending block propagated to blocks processor task: {}", e);
+                if let Err(e) = self.blocks_processor.send((peer, PropagatedBlock::Compact(compact), block_hash, received_at)).await {
+                    error!("Error while sending compact block propagated to blocks processor task: {}", e);
+                }
+            },
+            Packet::BlockTransactionsRequest(request) => {
+                trace!("Received a block transactions request from {}", peer);
+                let (block_hash, indexes) = request.consume();
+                let txs = {
+                    let storage = self.blockchain.get_storage().read().await;
+                    let header = storage.get_block_header_by_hash(&block_hash).await?;
+                    let txs_hashes = header.get_txs_hashes();
+                    let mut txs = Vec::with_capacity(indexes.len());
+                    for index in indexes {
+                        let hash = txs_hashes.get_index(index as usize)
+                            .ok_or(P2pError::InvalidBlockTransactionsRequest(index))?;
+                        let tx = storage.get_transaction(hash).await?;
+                        txs.push(tx.as_ref().clone());
+                    }
+                    txs
+                };
+
+                peer.send_packet(Packet::BlockTransactionsResponse(BlockTransactionsResponse::new(block_hash, txs))).await?;
+            },
+            Packet::BlockTransactionsResponse(response) => {
+                trace!("Received a block transactions response from {}", peer);
+                let sender = peer.get_block_transactions_channel()
+                    .lock().await
+                    .take()
+                    .ok_or(P2pError::UnrequestedBlockTransactionsResponse)?;
+
+                if sender.send(response).is_err() {
+                    error!("Error while sending block transactions response to channel of {}", peer);
                 }
             },
             Packet::ChainRequest(packet_wrapper) => {
@@ -1508,6 +1942,10 @@ impl<S: Storage> P2pServer<S> {
                 let (request, ping) = packet_wrapper.consume();
                 ping.into_owned().update_peer(peer, &self.blockchain).await?;
                 let request = request.into_owned();
+                if !self.allow_chain_request(peer)? {
+                    return Ok(())
+                }
+
                 let last_request = peer.get_last_chain_sync();
                 let time = get_current_time_in_seconds();
                 // Node is trying to ask too fast our chain
@@ -1587,80 +2025,54 @@ impl<S: Storage> P2pServer<S> {
             },
             Packet::ObjectRequest(request) => {
                 trace!("Received a object request from {}", peer);
-                let request = request.into_owned();
-                match &request {
-                    ObjectRequest::Block(hash) => {
-                        debug!("{} asked full block {}", peer, hash);
-                        let block = {
-                            let storage = self.blockchain.get_storage().read().await;
-                            storage.get_block_by_hash(hash).await
-                        };
+                let response = self.build_object_response(peer, request.into_owned()).await;
+                peer.send_packet(Packet::ObjectResponse(response)).await?;
+            },
+            Packet::ObjectsRequest(request) => {
+                trace!("Received a objects request from {}", peer);
+                if !peer.supports_batched_objects() {
+                    return Err(P2pError::BatchedObjectsNotNegotiated)
+                }
+
+                // Split the responses in several packets to never exceed the max packet size
+                // 1 byte for the packet id and 1 byte for the count
+                let mut responses = Vec::new();
+                let mut size = 2;
+                for request in request.into_owned().consume() {
+                    let response = self.build_object_response(peer, request).await;
+                    let response_size = response.size();
+                    if !responses.is_empty() && size + response_size > PEER_MAX_PACKET_CONTENT_SIZE {
+                        peer.send_packet(Packet::ObjectsResponse(ObjectsResponse::new(responses))).await?;
+                        responses = Vec::new();
+                        size = 2;
+                    }
 
-                        match block {
-                            Ok(block) => {
-                                debug!("block {} found, sending it", hash);
-                                peer.send_packet(Packet::ObjectResponse(ObjectResponse::Block(Cow::Borrowed(&block)))).await?;
-                            },
-                            Err(e) => {
-                                debug!("{} asked block '{}' but not present in our chain: {}", peer, hash, e);
-                                peer.send_packet(Packet::ObjectResponse(ObjectResponse::NotFound(request))).await?;
-                            }
-                        };
-                    },
-                    ObjectRequest::BlockHeader(hash) => {
-                        debug!("{} asked block header {}", peer, hash);
-                        let block = {
-                            let storage = self.blockchain.get_storage().read().await;
-                            storage.get_block_header_by_hash(hash).await
-                        };
+                    size += response_size;
+                    responses.push(response);
+                }
 
-                        match block {
-                            Ok(block) => {
-                                debug!("block header {} found, sending it", hash);
-                                peer.send_packet(Packet::ObjectResponse(ObjectResponse::BlockHeader(Cow::Borrowed(&block)))).await?;
-                            },
-                            Err(e) => {
-                                debug!("{} asked block header '{}' but not present in our chain: {}", peer, hash, e);
-                                peer.send_packet(Packet::ObjectResponse(ObjectResponse::NotFound(request))).await?;
-                            }
-                        };
-                    },
-                    ObjectRequest::Transaction(hash) => {
-                        debug!("{} asked tx {}", peer, hash);
-                        match self.blockchain.get_tx(hash).await {
-                            Ok(tx) => {
-                                debug!("tx {} found, sending it", hash);
-                                peer.send_packet(Packet::ObjectResponse(ObjectResponse::Transaction(Cow::Borrowed(&tx)))).await?;
-                            },
-                            Err(e) => {
-                                debug!("{} asked tx '{}' but not present in our chain: {}", peer, hash, e);
-                                peer.send_packet(Packet::ObjectResponse(ObjectResponse::NotFound(request))).await?;
-                            }
-                        }
-                    }
+                if !responses.is_empty() {
+                    peer.send_packet(Packet::ObjectsResponse(ObjectsResponse::new(responses))).await?;
                 }
             },
             Packet::ObjectResponse(response) => {
                 trace!("Received a object response from {}", peer);
-                let response = response.to_owned();
-                trace!("Object response received is {}", response.get_hash());
-
-                // check if we requested it from this peer
-                let request = response.get_request();
-                if peer.has_requested_object(&request).await {
-                    let sender = peer.remove_object_request(request).await?;
-                    // handle the response
-                    if sender.send(response).is_err() {
-                        error!("Error while sending object response to sender!");
+                self.handle_object_response(peer, response.to_owned()).await?;
+            },
+            Packet::ObjectsResponse(response) => {
+                trace!("Received a objects response from {}", peer);
+                // an invalid object must not drop the remaining ones
+                // but the peer is still penalized once the batch is handled
+                let mut error = None;
+                for response in response.consume() {
+                    if let Err(e) = self.handle_object_response(peer, response.to_owned()).await {
+                        warn!("Error while handling an object response from {}: {}", peer, e);
+                        error.get_or_insert(e);
                     }
-                // check if the Object Tracker has requested this object
-                } else if self.object_tracker.has_requested_object(request.get_hash()).await {
-                    trace!("Object Tracker requested it, handling it");
-                    self.object_tracker.handle_object_response(response).await?;
-                } else if self.object_tracker.is_ignored_request_hash(request.get_hash()).await {
-                    debug!("Object {} was ignored by Object Tracker, ignoring response", request.get_hash());
-                } else {
-                    return Err(P2pError::ObjectNotRequested(request))
+                }
+
+                if let Some(e) = error {
+                    return Err(e)
                 }
             },
             Packet::NotifyInventoryRequest(packet_wrapper) => {
@@ -1739,6 +2151,10 @@ impl<S: Storage> P2pServer<S> {
                 }
             },
             Packet::BootstrapChainRequest(request) => {
+                if !self.allow_chain_request(peer)? {
+                    return Ok(())
+                }
+
                 self.handle_bootstrap_chain_request(peer, request.step()).await?;
             },
             Packet::BootstrapChainResponse(response) => {
@@ -1794,6 +2210,7 @@ impl<S: Storage> P2pServer<S> {
         // 16 additional bytes are for AEAD
         let packet = peer.get_connection().read_packet(buf, PEER_MAX_PACKET_SIZE).await?;
         let packet_id = packet.get_id();
+        peer.get_traffic_stats().on_packet_in(packet_id);
         // Handle the packet
         if let Err(e) = self.handle_incoming_packet(&peer, packet).await {
             match e {
@@ -2233,6 +2650,11 @@ impl<S: Storage> P2pServer<S> {
         self.max_peers
     }
 
+    // Get the metrics of blocks propagated to us
+    pub fn get_block_propagation_metrics(&self) -> &BlockPropagationMetrics {
+        &self.block_propagation_metrics
+    }
+
     // Get our unique peer ID
     pub fn get_peer_id(&self) -> u64 {
         self.peer_id
@@ -2335,7 +2757,7 @@ impl<S: Storage> P2pServer<S> {
         let ping = Ping::new(Cow::Borrowed(hash), our_topoheight, our_height, pruned_topoheight, cumulative_difficulty, IndexSet::new());
         let block_packet = Packet::BlockPropagation(PacketWrapper::new(Cow::Borrowed(block), Cow::Borrowed(&ping)));
         let packet_block_bytes = Bytes::from(block_packet.to_bytes());
-        let packet_ping_bytes = Bytes::from(Packet::Ping(Cow::Owned(ping)).to_bytes());
+        let packet_ping_bytes = Bytes::from(Packet::Ping(Cow::Borrowed(&ping)).to_bytes());
 
         trace!("Locking peer list for broadcasting block {}", hash);
         trace!("start broadcasting block {} to all peers", hash);
@@ -2359,7 +2781,16 @@ impl<S: Storage> P2pServer<S> {
                     blocks_propagation.put(hash.clone(), if lock { Direction::Both } else { Direction::Out });
 
                     debug!("Broadcast {} to {} (lock: {})", hash, peer, lock);
-                    if let Err(e) = peer.send_bytes(packet_block_bytes.clone()).await {
+                    // Peers supporting compact blocks receive only the short ids of the TXs
+                    // salted with their own connection salt
+                    let bytes = if self.compact_blocks && peer.supports_compact_blocks() {
+                        let compact = CompactBlock::new(block, hash.clone(), peer.get_compact_block_salt());
+                        Bytes::from(Packet::CompactBlockPropagation(PacketWrapper::new(Cow::Owned(compact), Cow::Borrowed(&ping))).to_bytes())
+                    } else {
+                        packet_block_bytes.clone()
+                    };
+
+                    if let Err(e) = peer.send_bytes(bytes).await {
                         debug!("Error on broadcast block {} to {}: {}", hash, peer, e);
                     }
                     trace!("{} has been broadcasted to {}", hash, peer);
@@ -0,0 +1,282 @@
+use indexmap::IndexSet;
+use vyridium_common::{
+    block::BlockHeader,
+    crypto::{hash, Hash, HASH_SIZE},
+    serializer::{Reader, ReaderError, Serializer, Writer},
+    transaction::Transaction
+};
+use log::debug;
+
+// Short transaction id used in compact blocks
+// It is computed from the TX hash salted with a per-connection value
+// so nobody can craft a collision that would impact the whole network
+pub type ShortTxId = u64;
+
+// Compute the short id of a transaction hash using the salt of the connection
+pub fn compute_short_tx_id(salt: u64, tx_hash: &Hash) -> ShortTxId {
+    let mut bytes = [0u8; 8 + HASH_SIZE];
+    bytes[0..8].copy_from_slice(&salt.to_be_bytes());
+    bytes[8..].copy_from_slice(tx_hash.as_bytes());
+
+    let salted = hash(&bytes);
+    let mut id = [0u8; 8];
+    id.copy_from_slice(&salted.as_bytes()[0..8]);
+    u64::from_be_bytes(id)
+}
+
+// A compact block is a block header without its transactions hashes
+// Each TX is replaced by its short id so the receiver can rebuild
+// the block from its mempool and only request the missing ones
+#[derive(Clone, Debug)]
+pub struct CompactBlock {
+    // Block header with an empty TXs hashes list
+    header: BlockHeader,
+    // Block hash expected once rebuilt
+    // This is used to detect a short id collision
+    block_hash: Hash,
+    // Salt used to compute the short ids
+    salt: u64,
+    // Short ids of all TXs in the same order as the block
+    short_ids: Vec<ShortTxId>
+}
+
+impl CompactBlock {
+    // Build a compact block from a full block header
+    pub fn new(header: &BlockHeader, block_hash: Hash, salt: u64) -> Self {
+        let short_ids = header.get_txs_hashes()
+            .iter()
+            .map(|tx_hash| compute_short_tx_id(salt, tx_hash))
+            .collect();
+
+        let header = BlockHeader {
+            txs_hashes: IndexSet::new(),
+            ..header.clone()
+        };
+
+        Self {
+            header,
+            block_hash,
+            salt,
+            short_ids
+        }
+    }
+
+    pub fn get_header(&self) -> &BlockHeader {
+        &self.header
+    }
+
+    pub fn get_block_hash(&self) -> &Hash {
+        &self.block_hash
+    }
+
+    pub fn get_salt(&self) -> u64 {
+        self.salt
+    }
+
+    pub fn get_short_ids(&self) -> &Vec<ShortTxId> {
+        &self.short_ids
+    }
+
+    pub fn get_txs_count(&self) -> usize {
+        self.short_ids.len()
+    }
+
+    // Rebuild the full block header using the TXs hashes found
+    // TXs hashes must be in the same order as the short ids
+    pub fn to_header(&self, txs_hashes: IndexSet<Hash>) -> BlockHeader {
+        BlockHeader {
+            txs_hashes,
+            ..self.header.clone()
+        }
+    }
+}
+
+impl Serializer for CompactBlock {
+    fn write(&self, writer: &mut Writer) {
+        self.header.write(writer);
+        writer.write_hash(&self.block_hash);
+        writer.write_u64(&self.salt);
+        writer.write_u16(self.short_ids.len() as u16);
+        for id in &self.short_ids {
+            writer.write_u64(id);
+        }
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let header = BlockHeader::read(reader)?;
+        if header.get_txs_count() != 0 {
+            debug!("Compact block header must not contain any TX hash");
+            return Err(ReaderError::InvalidValue)
+        }
+
+        let block_hash = reader.read_hash()?;
+        let salt = reader.read_u64()?;
+        let count = reader.read_u16()?;
+        let mut short_ids = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            short_ids.push(reader.read_u64()?);
+        }
+
+        Ok(Self {
+            header,
+            block_hash,
+            salt,
+            short_ids
+        })
+    }
+
+    fn size(&self) -> usize {
+        self.header.size() + self.block_hash.size() + self.salt.size() + 2 + self.short_ids.len() * 8
+    }
+}
+
+// Request the missing TXs of a compact block using their indexes in the block
+#[derive(Clone, Debug)]
+pub struct BlockTransactionsRequest {
+    block_hash: Hash,
+    indexes: IndexSet<u16>
+}
+
+impl BlockTransactionsRequest {
+    pub fn new(block_hash: Hash, indexes: IndexSet<u16>) -> Self {
+        Self {
+            block_hash,
+            indexes
+        }
+    }
+
+    pub fn get_block_hash(&self) -> &Hash {
+        &self.block_hash
+    }
+
+    pub fn get_indexes(&self) -> &IndexSet<u16> {
+        &self.indexes
+    }
+
+    pub fn consume(self) -> (Hash, IndexSet<u16>) {
+        (self.block_hash, self.indexes)
+    }
+}
+
+impl Serializer for BlockTransactionsRequest {
+    fn write(&self, writer: &mut Writer) {
+        writer.write_hash(&self.block_hash);
+        writer.write_u16(self.indexes.len() as u16);
+        for index in &self.indexes {
+            writer.write_u16(*index);
+        }
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let block_hash = reader.read_hash()?;
+        let count = reader.read_u16()?;
+        let mut indexes = IndexSet::with_capacity(count as usize);
+        for _ in 0..count {
+            if !indexes.insert(reader.read_u16()?) {
+                debug!("Duplicated index in block transactions request");
+                return Err(ReaderError::InvalidValue)
+            }
+        }
+
+        Ok(Self {
+            block_hash,
+            indexes
+        })
+    }
+
+    fn size(&self) -> usize {
+        self.block_hash.size() + 2 + self.indexes.len() * 2
+    }
+}
+
+// Response containing the TXs requested in the same order as the indexes
+#[derive(Clone, Debug)]
+pub struct BlockTransactionsResponse {
+    block_hash: Hash,
+    txs: Vec<Transaction>
+}
+
+impl BlockTransactionsResponse {
+    pub fn new(block_hash: Hash, txs: Vec<Transaction>) -> Self {
+        Self {
+            block_hash,
+            txs
+        }
+    }
+
+    pub fn get_block_hash(&self) -> &Hash {
+        &self.block_hash
+    }
+
+    pub fn consume(self) -> (Hash, Vec<Transaction>) {
+        (self.block_hash, self.txs)
+    }
+}
+
+impl Serializer for BlockTransactionsResponse {
+    fn write(&self, writer: &mut Writer) {
+        writer.write_hash(&self.block_hash);
+        writer.write_u16(self.txs.len() as u16);
+        for tx in &self.txs {
+            tx.write(writer);
+        }
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let block_hash = reader.read_hash()?;
+        let count = reader.read_u16()?;
+        let mut txs = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            txs.push(Transaction::read(reader)?);
+        }
+
+        Ok(Self {
+            block_hash,
+            txs
+        })
+    }
+
+    fn size(&self) -> usize {
+        self.block_hash.size() + 2 + self.txs.iter().map(|tx| tx.size()).sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vyridium_common::{
+        block::{BlockVersion, EXTRA_NONCE_SIZE},
+        crypto::{Hashable, KeyPair}
+    };
+
+    fn create_header(txs: usize) -> BlockHeader {
+        let txs_hashes = (0..txs).map(|i| hash(&(i as u64).to_be_bytes())).collect();
+        let miner = KeyPair::new().get_public_key().compress();
+        BlockHeader::new(BlockVersion::V0, 10, 1000, IndexSet::from([Hash::zero()]), [0u8; EXTRA_NONCE_SIZE], miner, txs_hashes)
+    }
+
+    #[test]
+    fn test_compact_block_rebuild() {
+        let header = create_header(5);
+        let block_hash = header.hash();
+        let compact = CompactBlock::new(&header, block_hash.clone(), 42);
+
+        let bytes = compact.to_bytes();
+        assert_eq!(bytes.len(), compact.size());
+
+        let compact = CompactBlock::from_bytes(&bytes).unwrap();
+        assert_eq!(compact.get_txs_count(), 5);
+        for (tx_hash, id) in header.get_txs_hashes().iter().zip(compact.get_short_ids()) {
+            assert_eq!(compute_short_tx_id(compact.get_salt(), tx_hash), *id);
+        }
+
+        let rebuilt = compact.to_header(header.get_txs_hashes().clone());
+        assert_eq!(rebuilt.hash(), block_hash);
+    }
+
+    #[test]
+    fn test_short_id_salted() {
+        let tx_hash = hash(b"tx");
+        assert_ne!(compute_short_tx_id(1, &tx_hash), compute_short_tx_id(2, &tx_hash));
+    }
+}
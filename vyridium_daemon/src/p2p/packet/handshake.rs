@@ -9,7 +9,8 @@ use vyridium_common::{
 use crate::p2p::{
     connection::Connection,
     peer::{Peer, Rx},
-    peer_list::SharedPeerList
+    peer_list::SharedPeerList,
+    traffic::PeerRateLimiter
 };
 use std::{
     borrow::Cow,
@@ -17,6 +18,11 @@ use std::{
     fmt::{Display, Error, Formatter}
 };
 
+// Capabilities flags announced in the handshake
+// Unknown flags must be ignored to stay compatible with newer nodes
+pub const CAPABILITY_COMPACT_BLOCKS: u8 = 0b0000_0001;
+pub const CAPABILITY_BATCHED_OBJECTS: u8 = 0b0000_0010;
+
 // this Handshake is the first data sent when connecting to the server
 // If handshake is valid, server reply with his own handshake
 // We just have to repeat this request to all peers until we reach max connection
@@ -52,13 +58,16 @@ pub struct Handshake<'a> {
     cumulative_difficulty: Cow<'a, CumulativeDifficulty>,
     // By default it's true, and peer allow to be shared to others and/or through API
     // If false, we must not share it
-    can_be_shared: bool
+    can_be_shared: bool,
+    // Optional protocol features supported by the node
+    // Older nodes don't send it, in this case no capability is set
+    capabilities: u8
 } // Server reply with his own list of peers, but we remove all already known by requester for the response.
 
 impl<'a> Handshake<'a> {
     pub const MAX_LEN: usize = 16;
 
-    pub fn new(version: Cow<'a, String>, network: Network, node_tag: Cow<'a, Option<String>>, network_id: Cow<'a, [u8; 16]>, peer_id: u64, local_port: u16, utc_time: TimestampSeconds, topoheight: u64, height: u64, pruned_topoheight: Option<u64>, top_hash: Cow<'a, Hash>, genesis_hash: Cow<'a, Hash>, cumulative_difficulty: Cow<'a, CumulativeDifficulty>, can_be_shared: bool) -> Self {
+    pub fn new(version: Cow<'a, String>, network: Network, node_tag: Cow<'a, Option<String>>, network_id: Cow<'a, [u8; 16]>, peer_id: u64, local_port: u16, utc_time: TimestampSeconds, topoheight: u64, height: u64, pruned_topoheight: Option<u64>, top_hash: Cow<'a, Hash>, genesis_hash: Cow<'a, Hash>, cumulative_difficulty: Cow<'a, CumulativeDifficulty>, can_be_shared: bool, capabilities: u8) -> Self {
         debug_assert!(version.len() > 0 && version.len() <= Handshake::MAX_LEN);
         // version cannot be greater than 16 chars
         if let Some(node_tag) = node_tag.as_ref() {
@@ -80,14 +89,15 @@ impl<'a> Handshake<'a> {
             top_hash,
             genesis_hash,
             cumulative_difficulty,
-            can_be_shared
+            can_be_shared,
+            capabilities
         }
     }
 
     // Create a new peer using its connection and this handshake packet
-    pub fn create_peer(self, connection: Connection, priority: bool, peer_list: SharedPeerList) -> (Peer, Rx) {
+    pub fn create_peer(self, connection: Connection, priority: bool, peer_list: SharedPeerList, rate_limiter: PeerRateLimiter) -> (Peer, Rx) {
         let peers = HashSet::new();
-        Peer::new(connection, self.get_peer_id(), self.node_tag.into_owned(), self.local_port, self.version.into_owned(), self.top_hash.into_owned(), self.topoheight, self.height, self.pruned_topoheight, priority, self.cumulative_difficulty.into_owned(), peer_list, peers, self.can_be_shared)
+        Peer::new(connection, self.get_peer_id(), self.node_tag.into_owned(), self.local_port, self.version.into_owned(), self.top_hash.into_owned(), self.topoheight, self.height, self.pruned_topoheight, priority, self.cumulative_difficulty.into_owned(), peer_list, peers, self.can_be_shared, self.capabilities, rate_limiter)
     }
 
     pub fn get_version(&self) -> &String {
@@ -133,6 +143,10 @@ impl<'a> Handshake<'a> {
     pub fn get_pruned_topoheight(&self) -> &Option<u64> {
         &self.pruned_topoheight
     }
+
+    pub fn get_capabilities(&self) -> u8 {
+        self.capabilities
+    }
 }
 
 impl Serializer for Handshake<'_> {
@@ -158,6 +172,7 @@ impl Serializer for Handshake<'_> {
         writer.write_hash(&self.genesis_hash); // Genesis Hash
         self.cumulative_difficulty.write(writer); // Cumulative Difficulty
         writer.write_bool(self.can_be_shared); // Can be shared
+        writer.write_u8(self.capabilities); // Capabilities
     }
 
     fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
@@ -199,8 +214,14 @@ impl Serializer for Handshake<'_> {
         let genesis_hash = reader.read_hash()?;
         let cumulative_difficulty = CumulativeDifficulty::read(reader)?;
         let can_be_shared = reader.read_bool()?;
+        // Capabilities were added later, older nodes don't send them
+        let capabilities = if reader.size() > 0 {
+            reader.read_u8()?
+        } else {
+            0
+        };
 
-        Ok(Handshake::new(Cow::Owned(version), network, Cow::Owned(node_tag), Cow::Owned(network_id), peer_id, local_port, utc_time, topoheight, height, pruned_topoheight, Cow::Owned(top_hash), Cow::Owned(genesis_hash), Cow::Owned(cumulative_difficulty), can_be_shared))
+        Ok(Handshake::new(Cow::Owned(version), network, Cow::Owned(node_tag), Cow::Owned(network_id), peer_id, local_port, utc_time, topoheight, height, pruned_topoheight, Cow::Owned(top_hash), Cow::Owned(genesis_hash), Cow::Owned(cumulative_difficulty), can_be_shared, capabilities))
     }
 
     fn size(&self) -> usize {
@@ -231,7 +252,9 @@ impl Serializer for Handshake<'_> {
         // Cumulative Difficulty
         self.cumulative_difficulty.size() +
         // Can be shared
-        self.can_be_shared.size()
+        self.can_be_shared.size() +
+        // Capabilities
+        self.capabilities.size()
     }
 }
 
@@ -5,11 +5,13 @@ pub mod object;
 pub mod inventory;
 pub mod bootstrap_chain;
 pub mod peer_disconnected;
+pub mod compact_block;
 
 use self::bootstrap_chain::{BootstrapChainRequest, BootstrapChainResponse};
 use self::inventory::{NotifyInventoryResponse, NotifyInventoryRequest};
-use self::object::{ObjectRequest, ObjectResponse};
+use self::object::{ObjectRequest, ObjectResponse, ObjectsRequest, ObjectsResponse};
 use self::chain::{ChainRequest, ChainResponse};
+use self::compact_block::{BlockTransactionsRequest, BlockTransactionsResponse, CompactBlock};
 use self::handshake::Handshake;
 use self::peer_disconnected::PacketPeerDisconnected;
 use self::ping::Ping;
@@ -38,6 +40,40 @@ const NOTIFY_INV_RESPONSE_ID: u8 = 10;
 const BOOTSTRAP_CHAIN_REQUEST_ID: u8 = 11;
 const BOOTSTRAP_CHAIN_RESPONSE_ID: u8 = 12;
 const PEER_DISCONNECTED_ID: u8 = 13;
+const COMPACT_BLOCK_PROPAGATION_ID: u8 = 14;
+const BLOCK_TXS_REQUEST_ID: u8 = 15;
+const BLOCK_TXS_RESPONSE_ID: u8 = 16;
+const OBJECTS_REQUEST_ID: u8 = 17;
+const OBJECTS_RESPONSE_ID: u8 = 18;
+
+// Number of packet types, used to count packets per type
+pub const PACKET_TYPES_COUNT: usize = OBJECTS_RESPONSE_ID as usize + 1;
+
+// Get a human readable name for a packet id
+pub fn get_packet_name(id: u8) -> &'static str {
+    match id {
+        KEY_EXCHANGE_ID => "key_exchange",
+        HANDSHAKE_ID => "handshake",
+        TX_PROPAGATION_ID => "transaction_propagation",
+        BLOCK_PROPAGATION_ID => "block_propagation",
+        CHAIN_REQUEST_ID => "chain_request",
+        CHAIN_RESPONSE_ID => "chain_response",
+        PING_ID => "ping",
+        OBJECT_REQUEST_ID => "object_request",
+        OBJECT_RESPONSE_ID => "object_response",
+        NOTIFY_INV_REQUEST_ID => "inventory_request",
+        NOTIFY_INV_RESPONSE_ID => "inventory_response",
+        BOOTSTRAP_CHAIN_REQUEST_ID => "bootstrap_chain_request",
+        BOOTSTRAP_CHAIN_RESPONSE_ID => "bootstrap_chain_response",
+        PEER_DISCONNECTED_ID => "peer_disconnected",
+        COMPACT_BLOCK_PROPAGATION_ID => "compact_block_propagation",
+        BLOCK_TXS_REQUEST_ID => "block_transactions_request",
+        BLOCK_TXS_RESPONSE_ID => "block_transactions_response",
+        OBJECTS_REQUEST_ID => "objects_request",
+        OBJECTS_RESPONSE_ID => "objects_response",
+        _ => "unknown"
+    }
+}
 
 // PacketWrapper allows us to link any Packet to a Ping
 #[derive(Debug)]
@@ -97,6 +133,13 @@ pub enum Packet<'a> {
     BootstrapChainRequest(BootstrapChainRequest<'a>),
     BootstrapChainResponse(BootstrapChainResponse),
     PeerDisconnected(PacketPeerDisconnected),
+    // Compact blocks, only sent to peers that negotiated it in handshake
+    CompactBlockPropagation(PacketWrapper<'a, CompactBlock>),
+    BlockTransactionsRequest(BlockTransactionsRequest),
+    BlockTransactionsResponse(BlockTransactionsResponse),
+    // Batched objects, only sent to peers that negotiated it in handshake
+    ObjectsRequest(Cow<'a, ObjectsRequest>),
+    ObjectsResponse(ObjectsResponse<'a>),
     // Encryption
     KeyExchange(Cow<'a, EncryptionKey>),
 }
@@ -117,6 +160,11 @@ impl Packet<'_> {
             Packet::BootstrapChainRequest(_) => BOOTSTRAP_CHAIN_REQUEST_ID,
             Packet::BootstrapChainResponse(_) => BOOTSTRAP_CHAIN_RESPONSE_ID,
             Packet::PeerDisconnected(_) => PEER_DISCONNECTED_ID,
+            Packet::CompactBlockPropagation(_) => COMPACT_BLOCK_PROPAGATION_ID,
+            Packet::BlockTransactionsRequest(_) => BLOCK_TXS_REQUEST_ID,
+            Packet::BlockTransactionsResponse(_) => BLOCK_TXS_RESPONSE_ID,
+            Packet::ObjectsRequest(_) => OBJECTS_REQUEST_ID,
+            Packet::ObjectsResponse(_) => OBJECTS_RESPONSE_ID,
             Packet::KeyExchange(_) => KEY_EXCHANGE_ID,
         }
     }
@@ -141,6 +189,11 @@ impl<'a> Serializer for Packet<'a> {
             BOOTSTRAP_CHAIN_REQUEST_ID => Packet::BootstrapChainRequest(BootstrapChainRequest::read(reader)?),
             BOOTSTRAP_CHAIN_RESPONSE_ID => Packet::BootstrapChainResponse(BootstrapChainResponse::read(reader)?),
             PEER_DISCONNECTED_ID => Packet::PeerDisconnected(PacketPeerDisconnected::read(reader)?),
+            COMPACT_BLOCK_PROPAGATION_ID => Packet::CompactBlockPropagation(PacketWrapper::read(reader)?),
+            BLOCK_TXS_REQUEST_ID => Packet::BlockTransactionsRequest(BlockTransactionsRequest::read(reader)?),
+            BLOCK_TXS_RESPONSE_ID => Packet::BlockTransactionsResponse(BlockTransactionsResponse::read(reader)?),
+            OBJECTS_REQUEST_ID => Packet::ObjectsRequest(Cow::Owned(ObjectsRequest::read(reader)?)),
+            OBJECTS_RESPONSE_ID => Packet::ObjectsResponse(ObjectsResponse::read(reader)?),
             id => {
                 debug!("invalid packet id received: {}", id);
                 return Err(ReaderError::InvalidValue)
@@ -170,6 +223,11 @@ impl<'a> Serializer for Packet<'a> {
             Packet::BootstrapChainRequest(request) => (BOOTSTRAP_CHAIN_REQUEST_ID, request),
             Packet::BootstrapChainResponse(response) => (BOOTSTRAP_CHAIN_RESPONSE_ID, response),
             Packet::PeerDisconnected(disconnected) => (PEER_DISCONNECTED_ID, disconnected),
+            Packet::CompactBlockPropagation(block) => (COMPACT_BLOCK_PROPAGATION_ID, block),
+            Packet::BlockTransactionsRequest(request) => (BLOCK_TXS_REQUEST_ID, request),
+            Packet::BlockTransactionsResponse(response) => (BLOCK_TXS_RESPONSE_ID, response),
+            Packet::ObjectsRequest(request) => (OBJECTS_REQUEST_ID, request.as_ref()),
+            Packet::ObjectsResponse(response) => (OBJECTS_RESPONSE_ID, response),
         };
 
         let packet = serializer.to_bytes();
@@ -16,6 +16,8 @@ use vyridium_common::{
         Writer
     },
 };
+use crate::config::PEER_MAX_OBJECTS_PER_REQUEST;
+use log::debug;
 use std::{borrow::Cow, fmt::{Display, Formatter, self}};
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
@@ -187,3 +189,136 @@ impl<'a> Serializer for ObjectResponse<'a> {
         }
     }
 }
+
+// Request several objects in one packet
+// Responses can be split in several packets by the peer
+#[derive(Clone, Debug)]
+pub struct ObjectsRequest(Vec<ObjectRequest>);
+
+impl ObjectsRequest {
+    pub fn new(requests: Vec<ObjectRequest>) -> Self {
+        debug_assert!(requests.len() <= PEER_MAX_OBJECTS_PER_REQUEST);
+        Self(requests)
+    }
+
+    pub fn get_requests(&self) -> &Vec<ObjectRequest> {
+        &self.0
+    }
+
+    pub fn consume(self) -> Vec<ObjectRequest> {
+        self.0
+    }
+}
+
+impl Serializer for ObjectsRequest {
+    fn write(&self, writer: &mut Writer) {
+        writer.write_u8(self.0.len() as u8);
+        for request in &self.0 {
+            request.write(writer);
+        }
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let count = reader.read_u8()? as usize;
+        if count == 0 || count > PEER_MAX_OBJECTS_PER_REQUEST {
+            debug!("Invalid objects request count: {}", count);
+            return Err(ReaderError::InvalidSize)
+        }
+
+        let mut requests = Vec::with_capacity(count);
+        for _ in 0..count {
+            requests.push(ObjectRequest::read(reader)?);
+        }
+
+        Ok(Self(requests))
+    }
+
+    fn size(&self) -> usize {
+        1 + self.0.iter().map(|request| request.size()).sum::<usize>()
+    }
+}
+
+// Several objects responses sent in one packet
+#[derive(Debug)]
+pub struct ObjectsResponse<'a>(Vec<ObjectResponse<'a>>);
+
+impl<'a> ObjectsResponse<'a> {
+    pub fn new(responses: Vec<ObjectResponse<'a>>) -> Self {
+        debug_assert!(responses.len() <= PEER_MAX_OBJECTS_PER_REQUEST);
+        Self(responses)
+    }
+
+    pub fn consume(self) -> Vec<ObjectResponse<'a>> {
+        self.0
+    }
+}
+
+impl<'a> Serializer for ObjectsResponse<'a> {
+    fn write(&self, writer: &mut Writer) {
+        writer.write_u8(self.0.len() as u8);
+        for response in &self.0 {
+            response.write(writer);
+        }
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let count = reader.read_u8()? as usize;
+        if count == 0 || count > PEER_MAX_OBJECTS_PER_REQUEST {
+            debug!("Invalid objects response count: {}", count);
+            return Err(ReaderError::InvalidSize)
+        }
+
+        let mut responses = Vec::with_capacity(count);
+        for _ in 0..count {
+            responses.push(ObjectResponse::read(reader)?);
+        }
+
+        Ok(Self(responses))
+    }
+
+    fn size(&self) -> usize {
+        1 + self.0.iter().map(|response| response.size()).sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_objects_request_serialization() {
+        let requests = vec![
+            ObjectRequest::Block(Hash::zero()),
+            ObjectRequest::BlockHeader(Hash::max()),
+            ObjectRequest::Transaction(Hash::zero())
+        ];
+        let request = ObjectsRequest::new(requests.clone());
+        let bytes = request.to_bytes();
+        assert_eq!(bytes.len(), request.size());
+
+        let request = ObjectsRequest::from_bytes(&bytes).unwrap();
+        assert_eq!(*request.get_requests(), requests);
+    }
+
+    #[test]
+    fn test_objects_request_limit() {
+        let requests = vec![ObjectRequest::Transaction(Hash::zero()); PEER_MAX_OBJECTS_PER_REQUEST + 1];
+        let bytes = ObjectsRequest(requests).to_bytes();
+        assert!(ObjectsRequest::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_objects_response_serialization() {
+        let responses = vec![
+            ObjectResponse::NotFound(ObjectRequest::Block(Hash::zero())),
+            ObjectResponse::NotFound(ObjectRequest::Transaction(Hash::max()))
+        ];
+        let response = ObjectsResponse::new(responses);
+        let bytes = response.to_bytes();
+        assert_eq!(bytes.len(), response.size());
+
+        let responses = ObjectsResponse::from_bytes(&bytes).unwrap().consume();
+        assert_eq!(responses.len(), 2);
+        assert_eq!(*responses[1].get_request(), ObjectRequest::Transaction(Hash::max()));
+    }
+}
@@ -28,6 +28,11 @@ use super::{
             ChainRequest,
             ChainResponse
         },
+        compact_block::{
+            BlockTransactionsRequest,
+            BlockTransactionsResponse
+        },
+        handshake::{CAPABILITY_BATCHED_OBJECTS, CAPABILITY_COMPACT_BLOCKS},
         object::{
             ObjectRequest,
             OwnedObjectResponse
@@ -36,7 +41,8 @@ use super::{
     },
     peer_list::SharedPeerList,
     connection::Connection,
-    error::P2pError
+    error::P2pError,
+    traffic::{PeerRateLimiter, TrafficStats}
 };
 use std::{
     num::NonZeroUsize,
@@ -146,6 +152,16 @@ pub struct Peer {
     outgoing_address: SocketAddr,
     // Determine if this peer allows to be shared to others and/or through API
     sharable: bool,
+    // Capabilities announced by the peer during handshake
+    capabilities: u8,
+    // Random salt used to compute short TX ids of compact blocks sent to this peer
+    compact_block_salt: u64,
+    // used to wait on the missing TXs of a compact block
+    block_transactions: Mutex<Option<Sender<BlockTransactionsResponse>>>,
+    // Packets received and sent per type
+    traffic: TrafficStats,
+    // Upload and requests limits applied to this peer
+    rate_limiter: PeerRateLimiter,
     // Channel to send bytes to the writer task
     tx: Tx,
     // Channel to notify the tasks to exit
@@ -156,7 +172,7 @@ pub struct Peer {
 }
 
 impl Peer {
-    pub fn new(connection: Connection, id: u64, node_tag: Option<String>, local_port: u16, version: String, top_hash: Hash, topoheight: u64, height: u64, pruned_topoheight: Option<u64>, priority: bool, cumulative_difficulty: CumulativeDifficulty, peer_list: SharedPeerList, peers_received: HashSet<SocketAddr>, sharable: bool) -> (Self, Rx) {
+    pub fn new(connection: Connection, id: u64, node_tag: Option<String>, local_port: u16, version: String, top_hash: Hash, topoheight: u64, height: u64, pruned_topoheight: Option<u64>, priority: bool, cumulative_difficulty: CumulativeDifficulty, peer_list: SharedPeerList, peers_received: HashSet<SocketAddr>, sharable: bool, capabilities: u8, rate_limiter: PeerRateLimiter) -> (Self, Rx) {
         let mut outgoing_address = *connection.get_address();
         outgoing_address.set_port(local_port);
 
@@ -198,6 +214,11 @@ impl Peer {
             sync_chain: Mutex::new(None),
             outgoing_address,
             sharable,
+            capabilities,
+            compact_block_salt: rand::random(),
+            block_transactions: Mutex::new(None),
+            traffic: TrafficStats::new(),
+            rate_limiter,
             exit_channel,
             tx,
             read_task: Mutex::new(TaskState::Inactive),
@@ -331,6 +352,42 @@ impl Peer {
         self.sharable
     }
 
+    // Get the packets counters of the peer
+    pub fn get_traffic_stats(&self) -> &TrafficStats {
+        &self.traffic
+    }
+
+    // Get the limits applied to the peer
+    pub fn get_rate_limiter(&self) -> &PeerRateLimiter {
+        &self.rate_limiter
+    }
+
+    // Is the peer reached through our proxy
+    // Its address must not be shared to others peers
+    pub fn is_proxied(&self) -> bool {
+        self.connection.is_proxied()
+    }
+
+    // Get the capabilities announced by the peer
+    pub fn get_capabilities(&self) -> u8 {
+        self.capabilities
+    }
+
+    // Check if the peer can receive compact blocks
+    pub fn supports_compact_blocks(&self) -> bool {
+        self.capabilities & CAPABILITY_COMPACT_BLOCKS != 0
+    }
+
+    // Check if the peer can handle several objects in one request
+    pub fn supports_batched_objects(&self) -> bool {
+        self.capabilities & CAPABILITY_BATCHED_OBJECTS != 0
+    }
+
+    // Get the salt used for compact blocks sent to this peer
+    pub fn get_compact_block_salt(&self) -> u64 {
+        self.compact_block_salt
+    }
+
     // Get the last time we got a fail from the peer
     pub fn get_last_fail_count(&self) -> u64 {
         self.last_fail_count.load(Ordering::Acquire)
@@ -497,6 +554,41 @@ impl Peer {
         Ok(response)
     }
 
+    // Request the missing TXs of a compact block from this peer and wait on them until we receive it or until timeout
+    pub async fn request_block_transactions(&self, request: BlockTransactionsRequest) -> Result<BlockTransactionsResponse, P2pError> {
+        debug!("Requesting {} TXs for compact block {}", request.get_indexes().len(), request.get_block_hash());
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        {
+            let mut sender_lock = self.block_transactions.lock().await;
+            *sender_lock = Some(sender);
+        }
+
+        let block_hash = request.get_block_hash().clone();
+        self.send_packet(Packet::BlockTransactionsRequest(request)).await?;
+
+        let response: BlockTransactionsResponse = match timeout(Duration::from_millis(PEER_TIMEOUT_REQUEST_OBJECT), receiver).await {
+            Ok(res) => res?,
+            Err(e) => {
+                debug!("Requested TXs for compact block {} have timed out", block_hash);
+                self.block_transactions.lock().await.take();
+                return Err(P2pError::AsyncTimeOut(e));
+            }
+        };
+
+        // check that the response is for the block we asked for
+        if *response.get_block_hash() != block_hash {
+            return Err(P2pError::InvalidObjectResponse(response.get_block_hash().clone()))
+        }
+
+        Ok(response)
+    }
+
+    // Get the block transactions channel
+    // This is used to wait on the missing TXs of a compact block
+    pub fn get_block_transactions_channel(&self) -> &Mutex<Option<Sender<BlockTransactionsResponse>>> {
+        &self.block_transactions
+    }
+
     // Get the bootstrap chain channel
     // Like the sync chain channel, but for bootstrap (fast sync) syncing
     pub fn get_bootstrap_chain_channel(&self) -> &Mutex<Option<Sender<StepResponse>>> {
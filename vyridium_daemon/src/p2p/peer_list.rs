@@ -1,6 +1,8 @@
 use crate::{
     config::{
         P2P_EXTEND_PEERLIST_DELAY,
+        P2P_MAX_ANCHOR_PEERS,
+        P2P_MAX_OUTGOING_PEERS_PER_GROUP,
         PEER_FAIL_LIMIT,
         PEER_FAIL_TO_CONNECT_LIMIT,
         PEER_TEMP_BAN_TIME_ON_CONNECT,
@@ -14,6 +16,7 @@ use std::{
     fs, net::{IpAddr, SocketAddr},
     time::Duration
 };
+use sled::{Db, Tree};
 use humantime::format_duration;
 use serde::{Serialize, Deserialize};
 use tokio::sync::{mpsc::Sender, RwLock};
@@ -37,13 +40,47 @@ pub struct PeerList {
     // We only keep one "peer" per address in case the peer changes multiple
     // times its local port
     stored_peers: RwLock<HashMap<IpAddr, StoredPeer>>,
-    filename: String,
+    // DB where each stored peer is saved on change
+    db: Db,
+    // Stored peers indexed by their IP
+    stored_peers_tree: Tree,
+    // Last good outgoing peers, reconnected first on restart
+    anchors_tree: Tree,
     // used to notify the server that a peer disconnected
     // this is done through a channel to not have to handle generic types
     // and to be flexible in the future
     peer_disconnect_channel: Option<Sender<Arc<Peer>>>
 }
 
+// Network group of an IP address
+// Used to limit how many outgoing connections can go to the same provider
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum NetworkGroup {
+    // IPv4 /16
+    V4([u8; 2]),
+    // IPv6 /32
+    V6([u8; 4])
+}
+
+impl NetworkGroup {
+    pub fn new(ip: &IpAddr) -> Self {
+        match ip {
+            IpAddr::V4(ip) => {
+                let octets = ip.octets();
+                Self::V4([octets[0], octets[1]])
+            },
+            IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+                // IPv4 mapped addresses are grouped like IPv4
+                Some(ip) => Self::new(&IpAddr::V4(ip)),
+                None => {
+                    let octets = ip.octets();
+                    Self::V6([octets[0], octets[1], octets[2], octets[3]])
+                }
+            }
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Eq)]
 enum StoredPeerState {
     Whitelist,
@@ -64,85 +101,91 @@ pub struct StoredPeer {
 }
 
 impl PeerList {
-    // load all the stored peers from the file
-    fn load_stored_peers(filename: &String) -> Result<HashMap<IpAddr, StoredPeer>, P2pError> {
-        // check that the file exists
-        if fs::metadata(filename).is_err() {
-            info!("Peerlist file not found, creating a new one");
-            let peers = HashMap::new();
-            // write empty set in file
-            fs::write(filename, serde_json::to_string_pretty(&peers)?)?;
-            return Ok(peers);
-        }
-
-        // read the whole file
-        let content = match fs::read_to_string(filename) {
-            Ok(content) => content,
-            Err(e) => {
-                error!("Error while reading peerlist file: {}", e);
-                warn!("Removing peerlist file and creating a new empty one");
-                fs::remove_file(filename)?;
-                let peers = HashMap::new();
-                // write empty set in file
-                fs::write(filename, serde_json::to_string_pretty(&peers)?)?;
-
-                return Ok(peers);
-            }
-        };
+    // load all the stored peers from the DB
+    // If a legacy JSON peerlist file is found, it is imported and deleted
+    fn load_stored_peers(tree: &Tree, legacy_filename: &str) -> Result<HashMap<IpAddr, StoredPeer>, P2pError> {
+        if fs::metadata(legacy_filename).is_ok() {
+            info!("Importing legacy peerlist file {}", legacy_filename);
+            let content = fs::read_to_string(legacy_filename)?;
+            match serde_json::from_str::<HashMap<IpAddr, StoredPeer>>(&content) {
+                Ok(peers) => {
+                    for (ip, stored_peer) in peers.iter() {
+                        tree.insert(ip.to_string().as_bytes(), serde_json::to_vec(stored_peer)?)?;
+                    }
+                },
+                Err(e) => error!("Error while importing legacy peerlist file: {}", e)
+            };
+            fs::remove_file(legacy_filename)?;
+        }
 
-        // deserialize the content
-        let mut peers: HashMap<IpAddr, StoredPeer> = match serde_json::from_str(&content) {
-            Ok(peers) => peers,
-            Err(e) => {
-                error!("Error while deserializing peerlist: {}", e);
-                warn!("Removing peerlist file and creating a new empty one");
-                fs::remove_file(filename)?;
-                let peers = HashMap::new();
-                // write empty set in file
-                fs::write(filename, serde_json::to_string_pretty(&peers)?)?;
-
-                peers
-            }
-        };
+        let mut peers = HashMap::new();
+        for res in tree.iter() {
+            let (key, value) = res?;
+            let ip = match std::str::from_utf8(&key).ok().and_then(|key| key.parse::<IpAddr>().ok()) {
+                Some(ip) => ip,
+                None => {
+                    warn!("Invalid IP key found in stored peerlist, deleting it");
+                    tree.remove(key)?;
+                    continue;
+                }
+            };
+
+            let mut stored_peer: StoredPeer = match serde_json::from_slice(&value) {
+                Ok(stored_peer) => stored_peer,
+                Err(e) => {
+                    warn!("Error while deserializing stored peer {}: {}, deleting it", ip, e);
+                    tree.remove(key)?;
+                    continue;
+                }
+            };
 
-        // reset the fail count of all whitelisted peers
-        for stored_peer in peers.values_mut() {
-            if *stored_peer.get_state() == StoredPeerState::Whitelist {
+            // reset the fail count of all whitelisted peers
+            if *stored_peer.get_state() == StoredPeerState::Whitelist && stored_peer.fail_count != 0 {
                 stored_peer.fail_count = 0;
+                tree.insert(key, serde_json::to_vec(&stored_peer)?)?;
             }
+
+            peers.insert(ip, stored_peer);
         }
 
         Ok(peers)
     }
 
-    pub fn new(capacity: usize, filename: String, peer_disconnect_channel: Option<Sender<Arc<Peer>>>) -> SharedPeerList {
-        let stored_peers = match Self::load_stored_peers(&filename) {
+    pub fn new(capacity: usize, dir_path: String, peer_disconnect_channel: Option<Sender<Arc<Peer>>>) -> Result<SharedPeerList, P2pError> {
+        let db = sled::open(&dir_path)?;
+        let stored_peers_tree = db.open_tree("stored_peers")?;
+        let anchors_tree = db.open_tree("anchors")?;
+
+        let stored_peers = match Self::load_stored_peers(&stored_peers_tree, &format!("{}.json", dir_path)) {
             Ok(peers) => peers,
             Err(e) => {
                 error!("Error while loading peerlist: {}", e);
                 info!("Creating a empty peerlist");
+                stored_peers_tree.clear()?;
                 HashMap::new()
             }
         };
 
-        Arc::new(
+        Ok(Arc::new(
             Self {
                 peers: RwLock::new(HashMap::with_capacity(capacity)),
                 stored_peers: RwLock::new(stored_peers),
-                filename,
+                db,
+                stored_peers_tree,
+                anchors_tree,
                 peer_disconnect_channel
             }
-        )
+        ))
     }
 
-    // Clear the peerlist, this will overwrite the file on disk also
+    // Clear the peerlist, this will clear the DB also
     pub async fn clear_peerlist(&self) {
         trace!("clear peerlist");
         let mut stored_peers = self.stored_peers.write().await;
         stored_peers.clear();
 
-        if let Err(e) = self.save_peers_to_file(&stored_peers) {
-            error!("Error while trying to save peerlist to file: {}", e);
+        if let Err(e) = self.stored_peers_tree.clear() {
+            error!("Error while trying to clear stored peerlist: {}", e);
         }
     }
 
@@ -220,6 +263,12 @@ impl PeerList {
     async fn update_peer(&self, peer: &Peer) {
         let addr = peer.get_outgoing_address();
         let ip = addr.ip();
+        // Peer reached by its hostname through the proxy, its IP is unknown
+        if ip.is_unspecified() {
+            debug!("{} has no known IP, not storing it", peer);
+            return;
+        }
+
         let mut stored_peers = self.stored_peers.write().await;
         if let Some(stored_peer) = stored_peers.get_mut(&ip) {
             debug!("Updating {} in stored peerlist", peer);
@@ -227,9 +276,12 @@ impl PeerList {
             stored_peer.set_fail_count(0);
             stored_peer.set_last_seen(get_current_time_in_seconds());
             stored_peer.set_local_port(peer.get_local_port());
+            self.save_stored_peer(&ip, stored_peer);
         } else {
             debug!("Saving {} in stored peerlist", peer);
-            stored_peers.insert(ip, StoredPeer::new(peer.get_local_port(), StoredPeerState::Graylist));
+            let stored_peer = StoredPeer::new(peer.get_local_port(), StoredPeerState::Graylist);
+            self.save_stored_peer(&ip, &stored_peer);
+            stored_peers.insert(ip, stored_peer);
         }
     }
 
@@ -265,6 +317,8 @@ impl PeerList {
 
     pub async fn close_all(&self) {
         trace!("closing all peers");
+        self.save_anchors().await;
+
         let peers = {
             let mut peers = self.peers.write().await;
             peers.drain().collect::<Vec<(u64, Arc<Peer>)>>()
@@ -279,9 +333,8 @@ impl PeerList {
             }
         }
 
-        let stored_peers = self.stored_peers.read().await;
-        if let Err(e) = self.save_peers_to_file(&stored_peers) {
-            error!("Error while trying to save peerlist to file: {}", e);
+        if let Err(e) = self.db.flush_async().await {
+            error!("Error while trying to flush peerlist: {}", e);
         }
     }
 
@@ -378,11 +431,10 @@ impl PeerList {
 
     async fn set_state_to_address(&self, addr: &IpAddr, state: StoredPeerState) {
         let mut stored_peers = self.stored_peers.write().await;
-        if let Some(stored_peer) = stored_peers.get_mut(addr) {
-            stored_peer.set_state(state);
-        } else {
-            stored_peers.insert(addr.clone(), StoredPeer::new(0, state));
-        }
+        let stored_peer = stored_peers.entry(*addr)
+            .or_insert_with(|| StoredPeer::new(0, StoredPeerState::Graylist));
+        stored_peer.set_state(state);
+        self.save_stored_peer(addr, stored_peer);
     }
 
     // Set a peer to graylist, if its local port is 0, delete it from the stored peerlist
@@ -391,6 +443,7 @@ impl PeerList {
         let mut stored_peers = self.stored_peers.write().await;
         let delete = if let Some(peer) = stored_peers.get_mut(ip) {
             peer.set_state(StoredPeerState::Graylist);
+            self.save_stored_peer(ip, peer);
             peer.get_local_port() == 0
         } else {
             false
@@ -399,6 +452,7 @@ impl PeerList {
         if delete {
             info!("Deleting {} from stored peerlist", ip);
             stored_peers.remove(ip);
+            self.remove_stored_peer(ip);
         }
     }
 
@@ -453,8 +507,11 @@ impl PeerList {
         let mut stored_peers = self.stored_peers.write().await;
         if let Some(stored_peer) = stored_peers.get_mut(ip) {
             stored_peer.set_temp_ban_until(Some(get_current_time_in_seconds() + seconds));
+            self.save_stored_peer(ip, stored_peer);
         } else {
-            stored_peers.insert(ip.clone(), StoredPeer::new(0, StoredPeerState::Graylist));
+            let stored_peer = StoredPeer::new(0, StoredPeerState::Graylist);
+            self.save_stored_peer(ip, &stored_peer);
+            stored_peers.insert(ip.clone(), stored_peer);
         }
     }
 
@@ -469,16 +526,33 @@ impl PeerList {
         // remove all peers that have a high fail count
         let peers = self.peers.read().await;
         let mut stored_peers = self.stored_peers.write().await;
-        stored_peers.retain(|_, stored_peer| *stored_peer.get_state() == StoredPeerState::Whitelist || stored_peer.get_fail_count() < PEER_FAIL_LIMIT);
+        let mut removed = Vec::new();
+        stored_peers.retain(|ip, stored_peer| {
+            let keep = *stored_peer.get_state() == StoredPeerState::Whitelist || stored_peer.get_fail_count() < PEER_FAIL_LIMIT;
+            if !keep {
+                removed.push(*ip);
+            }
+            keep
+        });
+        for ip in removed {
+            self.remove_stored_peer(&ip);
+        }
+
+        // count our outgoing connections per network group
+        let mut groups: HashMap<NetworkGroup, usize> = HashMap::new();
+        for peer in peers.values().filter(|peer| peer.get_connection().is_out()) {
+            *groups.entry(NetworkGroup::new(&peer.get_ip())).or_insert(0) += 1;
+        }
 
         let current_time = get_current_time_in_seconds();
         // first lets check in whitelist
-        if let Some(addr) = self.find_peer_to_connect_to_with_state(&peers, &mut stored_peers, current_time, StoredPeerState::Whitelist) {
+        // whitelisted peers are trusted, they are not limited by their network group
+        if let Some(addr) = self.find_peer_to_connect_to_with_state(&peers, &mut stored_peers, None, current_time, StoredPeerState::Whitelist) {
             return Some(addr);
         }
 
         // then in graylist
-        if let Some(addr) = self.find_peer_to_connect_to_with_state(&peers, &mut stored_peers, current_time, StoredPeerState::Graylist) {
+        if let Some(addr) = self.find_peer_to_connect_to_with_state(&peers, &mut stored_peers, Some(&groups), current_time, StoredPeerState::Graylist) {
             return Some(addr);
         }
 
@@ -487,11 +561,21 @@ impl PeerList {
 
     // find among stored peers a peer to connect to with the requested StoredPeerState
     // we check that we're not already connected to this peer and that we didn't tried to connect to it recently
-    fn find_peer_to_connect_to_with_state(&self, peers: &HashMap<u64, Arc<Peer>>, stored_peers: &mut HashMap<IpAddr, StoredPeer>, current_time: TimestampSeconds, state: StoredPeerState) -> Option<SocketAddr> {
-        for (ip, stored_peer) in stored_peers {
+    // if groups is set, we skip the peers from a network group that already reached the outgoing limit
+    fn find_peer_to_connect_to_with_state(&self, peers: &HashMap<u64, Arc<Peer>>, stored_peers: &mut HashMap<IpAddr, StoredPeer>, groups: Option<&HashMap<NetworkGroup, usize>>, current_time: TimestampSeconds, state: StoredPeerState) -> Option<SocketAddr> {
+        for (ip, stored_peer) in stored_peers.iter_mut() {
             let addr = SocketAddr::new(*ip, stored_peer.get_local_port());
             if *stored_peer.get_state() == state && stored_peer.get_last_connection_try() + (stored_peer.get_fail_count() as u64 * P2P_EXTEND_PEERLIST_DELAY) <= current_time && Self::internal_get_peer_by_addr(peers, &addr).is_none() {
+                if let Some(groups) = groups {
+                    let group = NetworkGroup::new(ip);
+                    if groups.get(&group).is_some_and(|count| *count >= P2P_MAX_OUTGOING_PEERS_PER_GROUP) {
+                        trace!("Skipping {}, too many outgoing peers in network group {:?}", addr, group);
+                        continue;
+                    }
+                }
+
                 stored_peer.set_last_connection_try(current_time);
+                self.save_stored_peer(ip, stored_peer);
                 return Some(addr);
             }
         }
@@ -516,12 +600,13 @@ impl PeerList {
 
             debug!("Increasing fail count for {}", ip);
             stored_peer.set_fail_count(fail_count.wrapping_add(1));
+            self.save_stored_peer(ip, stored_peer);
         } else {
             debug!("{} is whitelisted, not increasing fail count", ip);
         }
     }
 
-    // Store a new peer address into the peerlist file
+    // Store a new peer address into the peerlist
     pub async fn store_peer_address(&self, addr: SocketAddr) -> bool {
         let mut stored_peers = self.stored_peers.write().await;
         let ip: IpAddr = addr.ip();
@@ -529,19 +614,80 @@ impl PeerList {
             return false;
         }
 
-        stored_peers.insert(ip, StoredPeer::new(addr.port(), StoredPeerState::Graylist));
+        let stored_peer = StoredPeer::new(addr.port(), StoredPeerState::Graylist);
+        self.save_stored_peer(&ip, &stored_peer);
+        stored_peers.insert(ip, stored_peer);
 
         true
     }
 
-    // serialize the stored peers to a file
-    fn save_peers_to_file(&self, stored_peers: &HashMap<IpAddr, StoredPeer>) -> Result<(), P2pError> {
-        trace!("saving peerlist to file");
-        let content = serde_json::to_string_pretty(&stored_peers)?;
-        fs::write(&self.filename, content)?;
+    // Save the outgoing peers connected for the longest time as anchors
+    // Priority nodes are skipped as they are always reconnected
+    async fn save_anchors(&self) {
+        let mut outgoing = {
+            let peers = self.peers.read().await;
+            peers.values()
+                .filter(|peer| peer.get_connection().is_out() && !peer.is_priority())
+                .map(|peer| (peer.get_connection().connected_on(), *peer.get_outgoing_address()))
+                .collect::<Vec<_>>()
+        };
+
+        outgoing.sort_by_key(|(connected_on, _)| *connected_on);
+        outgoing.truncate(P2P_MAX_ANCHOR_PEERS);
 
+        if let Err(e) = self.write_anchors(outgoing) {
+            error!("Error while saving anchor peers: {}", e);
+        }
+    }
+
+    // Replace the anchors stored by the new ones
+    fn write_anchors(&self, anchors: Vec<(TimestampSeconds, SocketAddr)>) -> Result<(), P2pError> {
+        self.anchors_tree.clear()?;
+        for (connected_on, addr) in anchors {
+            debug!("Saving {} as anchor peer", addr);
+            self.anchors_tree.insert(addr.to_string().as_bytes(), connected_on.to_be_bytes().to_vec())?;
+        }
+
+        Ok(())
+    }
+
+    // Retrieve the anchors saved during the last shutdown
+    // They are deleted so a bad anchor is not reused forever
+    pub fn take_anchors(&self) -> Vec<SocketAddr> {
+        let anchors = self.anchors_tree.iter()
+            .keys()
+            .filter_map(|key| key.ok())
+            .filter_map(|key| std::str::from_utf8(&key).ok().and_then(|key| key.parse().ok()))
+            .collect();
+
+        if let Err(e) = self.anchors_tree.clear() {
+            error!("Error while clearing anchor peers: {}", e);
+        }
+
+        anchors
+    }
+
+    // Persist a stored peer in the DB
+    fn save_stored_peer(&self, ip: &IpAddr, stored_peer: &StoredPeer) {
+        trace!("saving stored peer {}", ip);
+        if let Err(e) = self.try_save_stored_peer(ip, stored_peer) {
+            error!("Error while saving stored peer {}: {}", ip, e);
+        }
+    }
+
+    fn try_save_stored_peer(&self, ip: &IpAddr, stored_peer: &StoredPeer) -> Result<(), P2pError> {
+        let value = serde_json::to_vec(stored_peer)?;
+        self.stored_peers_tree.insert(ip.to_string().as_bytes(), value)?;
         Ok(())
     }
+
+    // Delete a stored peer from the DB
+    fn remove_stored_peer(&self, ip: &IpAddr) {
+        trace!("removing stored peer {}", ip);
+        if let Err(e) = self.stored_peers_tree.remove(ip.to_string().as_bytes()) {
+            error!("Error while removing stored peer {}: {}", ip, e);
+        }
+    }
 }
 
 impl StoredPeer {
@@ -608,4 +754,127 @@ impl Display for StoredPeer {
         let current_time = get_current_time_in_seconds();
         write!(f, "StoredPeer[first seen: {} ago, last seen: {} ago]", format_duration(Duration::from_secs(current_time - self.first_seen)), format_duration(Duration::from_secs(current_time - self.last_seen)))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_peerlist_path() -> String {
+        format!("{}/vyridium-peerlist-{:x}", std::env::temp_dir().display(), rand::random::<u64>())
+    }
+
+    #[test]
+    fn test_network_group() {
+        let a: IpAddr = "51.15.1.2".parse().unwrap();
+        let b: IpAddr = "51.15.200.3".parse().unwrap();
+        let c: IpAddr = "51.16.1.2".parse().unwrap();
+        assert_eq!(NetworkGroup::new(&a), NetworkGroup::new(&b));
+        assert_ne!(NetworkGroup::new(&a), NetworkGroup::new(&c));
+
+        // IPv4 mapped addresses are in the same group as IPv4
+        let mapped: IpAddr = "::ffff:51.15.9.9".parse().unwrap();
+        assert_eq!(NetworkGroup::new(&a), NetworkGroup::new(&mapped));
+
+        let d: IpAddr = "2001:db8:1::1".parse().unwrap();
+        let e: IpAddr = "2001:db8:ffff::1".parse().unwrap();
+        let f: IpAddr = "2001:db9::1".parse().unwrap();
+        assert_eq!(NetworkGroup::new(&d), NetworkGroup::new(&e));
+        assert_ne!(NetworkGroup::new(&d), NetworkGroup::new(&f));
+    }
+
+    #[tokio::test]
+    async fn test_load_stored_peers() {
+        let path = temp_peerlist_path();
+        let a: IpAddr = "51.15.1.2".parse().unwrap();
+        let b: IpAddr = "62.10.1.2".parse().unwrap();
+        let c: IpAddr = "73.20.1.2".parse().unwrap();
+
+        // legacy JSON peerlist
+        let mut whitelisted = StoredPeer::new(2125, StoredPeerState::Whitelist);
+        whitelisted.set_fail_count(3);
+        let mut legacy = HashMap::new();
+        legacy.insert(a, whitelisted);
+        legacy.insert(b, StoredPeer::new(2126, StoredPeerState::Graylist));
+        let legacy_filename = format!("{}.json", path);
+        fs::write(&legacy_filename, serde_json::to_string(&legacy).unwrap()).unwrap();
+
+        let peer_list = PeerList::new(8, path.clone(), None).unwrap();
+        // legacy file is imported then deleted
+        assert!(fs::metadata(&legacy_filename).is_err());
+        {
+            let stored_peers = peer_list.get_stored_peers().read().await;
+            assert_eq!(stored_peers.len(), 2);
+            // fail count of whitelisted peers is reset on load
+            assert_eq!(stored_peers.get(&a).unwrap().get_fail_count(), 0);
+            assert_eq!(stored_peers.get(&b).unwrap().get_local_port(), 2126);
+        }
+
+        assert!(peer_list.store_peer_address(SocketAddr::new(c, 2127)).await);
+        assert!(!peer_list.store_peer_address(SocketAddr::new(c, 2128)).await);
+        peer_list.db.flush().unwrap();
+        drop(peer_list);
+
+        // everything is reloaded from sled
+        let peer_list = PeerList::new(8, path.clone(), None).unwrap();
+        {
+            let stored_peers = peer_list.get_stored_peers().read().await;
+            assert_eq!(stored_peers.len(), 3);
+            assert!(*stored_peers.get(&a).unwrap().get_state() == StoredPeerState::Whitelist);
+            assert_eq!(stored_peers.get(&c).unwrap().get_local_port(), 2127);
+        }
+        drop(peer_list);
+
+        let _ = fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_outgoing_peers_per_group_limit() {
+        let path = temp_peerlist_path();
+        let peer_list = PeerList::new(8, path.clone(), None).unwrap();
+        let peers = HashMap::new();
+
+        let full: IpAddr = "51.15.1.2".parse().unwrap();
+        let mut stored_peers = HashMap::new();
+        stored_peers.insert(full, StoredPeer::new(2125, StoredPeerState::Graylist));
+
+        let mut groups = HashMap::new();
+        groups.insert(NetworkGroup::new(&full), P2P_MAX_OUTGOING_PEERS_PER_GROUP);
+
+        // the only peer is in a full network group
+        assert!(peer_list.find_peer_to_connect_to_with_state(&peers, &mut stored_peers, Some(&groups), 1000, StoredPeerState::Graylist).is_none());
+        assert_eq!(stored_peers.get(&full).unwrap().get_last_connection_try(), 0);
+
+        // a peer from another network group is selected
+        let other: IpAddr = "51.16.1.2".parse().unwrap();
+        stored_peers.insert(other, StoredPeer::new(2126, StoredPeerState::Graylist));
+        assert_eq!(peer_list.find_peer_to_connect_to_with_state(&peers, &mut stored_peers, Some(&groups), 1000, StoredPeerState::Graylist), Some(SocketAddr::new(other, 2126)));
+
+        // without groups, like for the whitelist, the limit doesn't apply
+        stored_peers.remove(&other);
+        assert_eq!(peer_list.find_peer_to_connect_to_with_state(&peers, &mut stored_peers, None, 1000, StoredPeerState::Graylist), Some(SocketAddr::new(full, 2125)));
+        drop(peer_list);
+
+        let _ = fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_take_anchors() {
+        let path = temp_peerlist_path();
+        let peer_list = PeerList::new(8, path.clone(), None).unwrap();
+
+        let a: SocketAddr = "51.15.1.2:2125".parse().unwrap();
+        let b: SocketAddr = "62.10.1.2:2125".parse().unwrap();
+        peer_list.write_anchors(vec![(10, a), (20, b)]).unwrap();
+
+        let mut anchors = peer_list.take_anchors();
+        anchors.sort();
+        assert_eq!(anchors, vec![a, b]);
+
+        // anchors are deleted once read
+        assert!(peer_list.take_anchors().is_empty());
+        drop(peer_list);
+
+        let _ = fs::remove_dir_all(path);
+    }
+}
@@ -8,6 +8,7 @@ use std::{
     collections::HashMap
 };
 use bytes::Bytes;
+use indexmap::IndexMap;
 use tokio::{
     sync::{
         mpsc::{Sender, Receiver, self},
@@ -30,12 +31,13 @@ use crate::{
         blockchain::Blockchain,
         storage::Storage
     },
-    config::PEER_TIMEOUT_REQUEST_OBJECT
+    config::{PEER_MAX_OBJECTS_PER_REQUEST, PEER_TIMEOUT_REQUEST_OBJECT}
 };
 use super::{
     packet::{
         object::{
             ObjectRequest,
+            ObjectsRequest,
             OwnedObjectResponse
         },
         Packet
@@ -260,6 +262,9 @@ const REQUESTER_CHANNEL_BUFFER: usize = 128;
 // It is set to 1 by default to not be spammed by the peer
 const HANDLER_CHANNEL_BUFFER: usize = 16;
 
+// How many pending requests can be coalesced at once
+const REQUESTER_MAX_COALESCED: usize = REQUESTER_CHANNEL_BUFFER;
+
 // Duration constant for timeout instead of building it at each iteration
 const TIME_OUT: Duration = Duration::from_millis(PEER_TIMEOUT_REQUEST_OBJECT);
 
@@ -422,7 +427,16 @@ impl ObjectTracker {
                 },
                 hash = request_receiver.recv() => {
                     if let Some(hash) = hash {
-                        self.request_object_from_peer_internal(hash).await;
+                        // Coalesce all pending requests to batch them per peer
+                        let mut hashes = vec![hash];
+                        while hashes.len() < REQUESTER_MAX_COALESCED {
+                            match request_receiver.try_recv() {
+                                Ok(hash) => hashes.push(hash),
+                                Err(_) => break
+                            }
+                        }
+
+                        self.request_objects_from_peers_internal(hashes).await;
                     } else {
                         // channel closed
                         break;
@@ -542,34 +556,116 @@ impl ObjectTracker {
         }
     }
 
-    // Request the object from the peer
+    // Request the objects from their peers
+    // All requests for the same peer are sent in batches if the peer supports it
     // This is called from the requester task loop
-    async fn request_object_from_peer_internal(&self, request_hash: Hash) {
-        debug!("Requesting object with hash {}", request_hash);
+    async fn request_objects_from_peers_internal(&self, hashes: Vec<Hash>) {
+        debug!("Requesting {} objects", hashes.len());
         let mut queue = self.queue.write().await;
 
-        let fail = if let Some(request) = queue.get_mut(&request_hash) {
-            request.set_requested();
-            let packet = Bytes::from(Packet::ObjectRequest(Cow::Borrowed(request.get_object())).to_bytes());
-            // send the packet to the Peer
-            let peer = request.get_peer();
-            if peer.get_connection().is_closed() {
-                warn!("Peer {} is disconnected but still has a pending request object {}", peer, request_hash);
-                Some((peer.get_id(), request.get_group_id().map(|v| (v, P2pError::Disconnected))))
-            } else if let Err(e) = peer.send_bytes(packet).await {
-                warn!("Error while requesting object {} using Object Tracker: {}", request_hash, e);
-                Some((peer.get_id(), request.get_group_id().map(|v| (v, e))))
+        let mut pending = Vec::with_capacity(hashes.len());
+        for hash in hashes {
+            if let Some(request) = queue.get_mut(&hash) {
+                request.set_requested();
+                let peer = request.get_peer();
+                pending.push((peer.get_id(), Arc::clone(peer), request.get_object().clone()));
             } else {
-                None
+                trace!("Object {} not requested anymore", hash);
+            }
+        }
+
+        let mut fails = Vec::new();
+        for (_, (peer, objects)) in group_per_peer(pending) {
+            if peer.get_connection().is_closed() {
+                warn!("Peer {} is disconnected but still has {} pending requested objects", peer, objects.len());
+                fails.push((peer, objects, P2pError::Disconnected));
+                continue;
+            }
+
+            let mut error = None;
+            for (count, packet) in build_request_packets(&objects, peer.supports_batched_objects()) {
+                // send the packet to the Peer
+                if let Err(e) = peer.send_bytes(packet).await {
+                    warn!("Error while requesting {} objects using Object Tracker: {}", count, e);
+                    error = Some(e);
+                    break;
+                }
+            }
+
+            if let Some(e) = error {
+                fails.push((peer, objects, e));
             }
+        }
+
+        for (peer, objects, e) in fails {
+            // Retrieve the group of one of the requests to notify it
+            let group = objects.iter()
+                .find_map(|object| queue.get(object.get_hash()).and_then(Request::get_group_id))
+                .map(|v| (v, e));
+
+            warn!("cleaning queue because of failure");
+            self.clean_queue(&mut queue, peer.get_id(), group).await;
+        }
+    }
+}
+
+// Group the requested objects per peer while keeping their order
+fn group_per_peer<P, O>(requests: impl IntoIterator<Item = (u64, P, O)>) -> IndexMap<u64, (P, Vec<O>)> {
+    let mut groups: IndexMap<u64, (P, Vec<O>)> = IndexMap::new();
+    for (id, peer, object) in requests {
+        groups.entry(id)
+            .or_insert_with(|| (peer, Vec::new()))
+            .1
+            .push(object);
+    }
+    groups
+}
+
+// Build the packets requesting the objects with the count of objects in each one
+// Old peers can only receive one object per request
+fn build_request_packets(objects: &[ObjectRequest], batched: bool) -> Vec<(usize, Bytes)> {
+    let chunk_size = if batched {
+        PEER_MAX_OBJECTS_PER_REQUEST
+    } else {
+        1
+    };
+
+    objects.chunks(chunk_size).map(|chunk| {
+        let packet = if let [object] = chunk {
+            Packet::ObjectRequest(Cow::Borrowed(object))
         } else {
-            trace!("Object {} not requested anymore", request_hash);
-            None
+            Packet::ObjectsRequest(Cow::Owned(ObjectsRequest::new(chunk.to_vec())))
         };
+        (chunk.len(), Bytes::from(packet.to_bytes()))
+    }).collect()
+}
 
-        if let Some((peer_id, group)) = fail {
-            warn!("cleaning queue because of failure");
-            self.clean_queue(&mut queue, peer_id, group).await;
+#[cfg(test)]
+mod tests {
+    use crate::config::PEER_MAX_PACKET_SIZE;
+    use super::*;
+
+    #[test]
+    fn test_coalesce_requests_per_peer() {
+        // requests of two peers interleaved
+        let count = PEER_MAX_OBJECTS_PER_REQUEST * 2 + 1;
+        let requests = (0..count).map(|i| (i as u64 % 2, (), ObjectRequest::Transaction(Hash::new([i as u8; 32]))));
+        let groups = group_per_peer(requests);
+        assert_eq!(groups.len(), 2);
+
+        let (_, objects) = &groups[&0];
+        assert_eq!(objects.len(), PEER_MAX_OBJECTS_PER_REQUEST + 1);
+        assert_eq!(objects[1], ObjectRequest::Transaction(Hash::new([2u8; 32])));
+
+        // all objects of the peer are sent in the minimum of packets
+        let packets = build_request_packets(objects, true);
+        assert_eq!(packets.iter().map(|(count, _)| *count).collect::<Vec<_>>(), vec![PEER_MAX_OBJECTS_PER_REQUEST, 1]);
+        for (_, packet) in &packets {
+            assert!(packet.len() <= PEER_MAX_PACKET_SIZE as usize);
         }
+
+        // one packet per object for old peers
+        let (_, objects) = &groups[&1];
+        assert_eq!(build_request_packets(objects, false).len(), PEER_MAX_OBJECTS_PER_REQUEST);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,218 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex
+    },
+    time::{Duration, Instant}
+};
+use super::packet::{get_packet_name, PACKET_TYPES_COUNT};
+
+// Count the packets received and sent per type with a peer
+// Bytes are already counted by the connection
+#[derive(Default)]
+pub struct TrafficStats {
+    packets_in: [AtomicU64; PACKET_TYPES_COUNT],
+    packets_out: [AtomicU64; PACKET_TYPES_COUNT]
+}
+
+impl TrafficStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Track a packet received
+    pub fn on_packet_in(&self, id: u8) {
+        if let Some(counter) = self.packets_in.get(id as usize) {
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // Track a packet sent
+    pub fn on_packet_out(&self, id: u8) {
+        if let Some(counter) = self.packets_out.get(id as usize) {
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // Packets received per type name, types never received are skipped
+    pub fn get_packets_in(&self) -> HashMap<String, u64> {
+        Self::to_map(&self.packets_in)
+    }
+
+    // Packets sent per type name, types never sent are skipped
+    pub fn get_packets_out(&self) -> HashMap<String, u64> {
+        Self::to_map(&self.packets_out)
+    }
+
+    fn to_map(counters: &[AtomicU64; PACKET_TYPES_COUNT]) -> HashMap<String, u64> {
+        counters.iter()
+            .enumerate()
+            .filter_map(|(id, counter)| {
+                let value = counter.load(Ordering::Relaxed);
+                (value > 0).then(|| (get_packet_name(id as u8).to_owned(), value))
+            })
+            .collect()
+    }
+}
+
+struct BucketState {
+    tokens: f64,
+    last_refill: Instant
+}
+
+// Token bucket refilled continuously at a fixed rate
+// It starts full and can't hold more than its capacity
+pub struct TokenBucket {
+    capacity: f64,
+    // Tokens added per second
+    refill_rate: f64,
+    state: Mutex<BucketState>
+}
+
+impl TokenBucket {
+    pub fn new(capacity: u64, refill_rate: f64) -> Self {
+        Self {
+            capacity: capacity as f64,
+            refill_rate,
+            state: Mutex::new(BucketState {
+                tokens: capacity as f64,
+                last_refill: Instant::now()
+            })
+        }
+    }
+
+    // Bucket allowing `amount` tokens per second with a burst of one second
+    pub fn per_second(amount: u64) -> Self {
+        Self::new(amount, amount as f64)
+    }
+
+    // Bucket allowing `amount` tokens per minute with a burst of one minute
+    pub fn per_minute(amount: u64) -> Self {
+        Self::new(amount, amount as f64 / 60.0)
+    }
+
+    fn refill(&self, state: &mut BucketState) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * self.refill_rate).min(self.capacity);
+        state.last_refill = now;
+    }
+
+    // Take the tokens only if they are all available
+    pub fn try_consume(&self, amount: u64) -> bool {
+        let mut state = self.state.lock().expect("token bucket lock poisoned");
+        self.refill(&mut state);
+        if state.tokens < amount as f64 {
+            return false
+        }
+
+        state.tokens -= amount as f64;
+        true
+    }
+
+    // Take the tokens even if not enough are available
+    // Returns how long to wait until the bucket is not in debt anymore
+    // This allows to consume more than the capacity at once, like a big packet
+    pub fn consume(&self, amount: u64) -> Duration {
+        let mut state = self.state.lock().expect("token bucket lock poisoned");
+        self.refill(&mut state);
+        state.tokens -= amount as f64;
+        if state.tokens >= 0.0 || self.refill_rate <= 0.0 {
+            return Duration::ZERO
+        }
+
+        Duration::from_secs_f64(-state.tokens / self.refill_rate)
+    }
+}
+
+// Limits configured for the P2P server
+// None means unlimited
+#[derive(Clone, Debug, Default)]
+pub struct RateLimits {
+    // Upload in bytes per second for all peers
+    pub upload: Option<u64>,
+    // Upload in bytes per second for each peer
+    pub peer_upload: Option<u64>,
+    // Chain and bootstrap chain requests per minute for all peers
+    pub chain_requests: Option<u64>,
+    // Chain and bootstrap chain requests per minute for each peer
+    pub peer_chain_requests: Option<u64>
+}
+
+impl RateLimits {
+    // Build the limiter of a new peer
+    pub fn create_peer_limiter(&self) -> PeerRateLimiter {
+        PeerRateLimiter {
+            upload: self.peer_upload.map(TokenBucket::per_second),
+            chain_requests: self.peer_chain_requests.map(TokenBucket::per_minute)
+        }
+    }
+}
+
+// Limits applied to a single peer
+#[derive(Default)]
+pub struct PeerRateLimiter {
+    upload: Option<TokenBucket>,
+    chain_requests: Option<TokenBucket>
+}
+
+impl PeerRateLimiter {
+    // How long we should wait before sending these bytes to the peer
+    pub fn upload_delay(&self, bytes: usize) -> Duration {
+        self.upload.as_ref().map_or(Duration::ZERO, |bucket| bucket.consume(bytes as u64))
+    }
+
+    // Is the peer allowed to send us a new chain request
+    pub fn allow_chain_request(&self) -> bool {
+        match self.chain_requests.as_ref() {
+            Some(bucket) => bucket.try_consume(1),
+            None => true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_consume() {
+        let bucket = TokenBucket::new(3, 0.0);
+        assert!(bucket.try_consume(2));
+        assert!(bucket.try_consume(1));
+        assert!(!bucket.try_consume(1));
+    }
+
+    #[test]
+    fn test_consume_debt() {
+        let bucket = TokenBucket::per_second(100);
+        assert_eq!(bucket.consume(100), Duration::ZERO);
+        // 50 tokens in debt at 100 tokens per second
+        let delay = bucket.consume(50);
+        assert!(delay > Duration::from_millis(400) && delay <= Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_refill() {
+        let bucket = TokenBucket::new(1, 1000.0);
+        assert!(bucket.try_consume(1));
+        std::thread::sleep(Duration::from_millis(10));
+        assert!(bucket.try_consume(1));
+    }
+
+    #[test]
+    fn test_traffic_stats() {
+        let stats = TrafficStats::new();
+        stats.on_packet_in(6);
+        stats.on_packet_in(6);
+        stats.on_packet_out(4);
+        // Unknown ids are ignored
+        stats.on_packet_in(u8::MAX);
+
+        let packets_in = stats.get_packets_in();
+        assert_eq!(packets_in.len(), 1);
+        assert_eq!(packets_in.get("ping"), Some(&2));
+        assert_eq!(stats.get_packets_out().get("chain_request"), Some(&1));
+    }
+}
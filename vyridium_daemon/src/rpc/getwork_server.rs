@@ -1,6 +1,6 @@
 use std::{
     borrow::Cow,
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     fmt::Display,
     num::NonZeroUsize,
     sync::{
@@ -34,13 +34,17 @@ use rand::{
 };
 use serde::Serialize;
 use serde_json::json;
-use tokio::sync::Mutex;
+use tokio::sync::{broadcast, Mutex};
 use vyridium_common::{
     api::daemon::{
         GetMinerWorkResult,
+        MinerEntry,
+        MinerHashrate,
+        MiningProtocol,
         SubmitMinerWorkParams
     },
     block::{
+        Algorithm,
         BlockHeader,
         MinerWork
     },
@@ -49,7 +53,7 @@ use vyridium_common::{
         Hashable,
         PublicKey
     },
-    difficulty::Difficulty,
+    difficulty::{difficulty_from_hash, Difficulty},
     immutable::Immutable,
     rpc_server::{
         InternalRpcError,
@@ -69,11 +73,17 @@ use crate::{
         storage::Storage
     },
     config::{
+        get_minimum_difficulty,
         DEV_PUBLIC_KEY,
         STABLE_LIMIT
     }
 };
 
+// Accepted shares are kept up to this duration to estimate the hashrate
+const SHARES_WINDOW_MILLIS: u64 = 60 * 60 * 1000;
+// Accepted shares PoW hashes kept to detect duplicated shares
+const SHARES_CACHE_SIZE: usize = 4096;
+
 pub type SharedGetWorkServer<S> = Arc<GetWorkServer<S>>;
 
 #[derive(Serialize, PartialEq)]
@@ -81,13 +91,38 @@ pub type SharedGetWorkServer<S> = Arc<GetWorkServer<S>>;
 pub enum Response {
     NewJob(GetMinerWorkResult),
     BlockAccepted,
-    BlockRejected(String)
+    BlockRejected(String),
+    // Share is valid but is not reaching the network difficulty
+    ShareAccepted,
+    ShareRejected(ShareError)
+}
+
+// Reasons of a share rejected, serialized as a message for the miner
+#[derive(Serialize, PartialEq, Debug, thiserror::Error)]
+pub enum ShareError {
+    #[serde(rename = "Low difficulty share")]
+    #[error("Low difficulty share")]
+    LowDifficulty,
+    #[serde(rename = "Duplicate share")]
+    #[error("Duplicate share")]
+    Duplicate
 }
 
 impl TMessage for Response {
     type Result = Result<(), InternalRpcError>;
 }
 
+// Mining job shared with others mining protocols
+// Work has no miner set and an empty extra nonce
+#[derive(Clone)]
+pub struct MiningJob {
+    pub work: MinerWork<'static>,
+    pub algorithm: Algorithm,
+    pub height: u64,
+    pub topoheight: u64,
+    pub difficulty: Difficulty
+}
+
 pub struct Miner {
     // Used to display correctly its address
     mainnet: bool,
@@ -102,11 +137,19 @@ pub struct Miner {
     // blocks rejected since he is connected
     blocks_rejected: usize,
     // timestamp of the last invalid block received
-    last_invalid_block: TimestampMillis
+    last_invalid_block: TimestampMillis,
+    // difficulty of the jobs sent if lower than the network difficulty
+    share_difficulty: Option<Difficulty>,
+    // shares accepted since he is connected, blocks included
+    shares_accepted: usize,
+    // shares rejected since he is connected
+    shares_rejected: usize,
+    // timestamp and difficulty of the shares accepted in the last window
+    shares: VecDeque<(TimestampMillis, Difficulty)>
 }
 
 impl Miner {
-    pub fn new(mainnet: bool, key: PublicKey, name: String) -> Self {
+    pub fn new(mainnet: bool, key: PublicKey, name: String, share_difficulty: Option<Difficulty>) -> Self {
         Self {
             mainnet,
             first_seen: get_current_time_in_millis(),
@@ -114,7 +157,11 @@ impl Miner {
             name,
             blocks_accepted: IndexSet::new(),
             blocks_rejected: 0,
-            last_invalid_block: 0
+            last_invalid_block: 0,
+            share_difficulty,
+            shares_accepted: 0,
+            shares_rejected: 0,
+            shares: VecDeque::new()
         }
     }
 
@@ -133,12 +180,93 @@ impl Miner {
     pub fn get_blocks_accepted(&self) -> usize {
         self.blocks_accepted.len()
     }
+
+    pub fn get_share_difficulty(&self) -> Option<&Difficulty> {
+        self.share_difficulty.as_ref()
+    }
+
+    // Difficulty of the job to send to this miner
+    pub fn get_job_difficulty(&self, network_difficulty: Difficulty) -> Difficulty {
+        match self.share_difficulty {
+            Some(share_difficulty) => share_difficulty.min(network_difficulty),
+            None => network_difficulty
+        }
+    }
+
+    // Update the stats based on the result of a submitted work
+    // difficulty is the one of the job, which is credited for each valid share
+    pub fn on_work_result(&mut self, response: &Response, hash: Option<Hash>, difficulty: Difficulty) {
+        match response {
+            Response::BlockAccepted => {
+                if let Some(hash) = hash {
+                    self.blocks_accepted.insert(hash);
+                }
+                self.on_share_accepted(difficulty);
+            },
+            Response::BlockRejected(_) => {
+                self.blocks_rejected += 1;
+                self.last_invalid_block = get_current_time_in_millis();
+            },
+            Response::ShareAccepted => self.on_share_accepted(difficulty),
+            Response::ShareRejected(_) => self.shares_rejected += 1,
+            Response::NewJob(_) => {}
+        }
+    }
+
+    fn on_share_accepted(&mut self, difficulty: Difficulty) {
+        let now = get_current_time_in_millis();
+        self.shares_accepted += 1;
+        self.shares.push_back((now, difficulty));
+        while self.shares.front().is_some_and(|(timestamp, _)| *timestamp + SHARES_WINDOW_MILLIS < now) {
+            self.shares.pop_front();
+        }
+    }
+
+    // Estimated hashrate over the last window
+    // Each share required on average as many hashes as its difficulty
+    pub fn get_hashrate(&self, window_millis: u64) -> f64 {
+        let now = get_current_time_in_millis();
+        let hashes: f64 = self.shares.iter()
+            .rev()
+            .take_while(|(timestamp, _)| *timestamp + window_millis >= now)
+            .map(|(_, difficulty)| difficulty.as_ref().low_u128() as f64)
+            .sum();
+
+        // don't underestimate a miner connected for less than the window
+        let elapsed = window_millis.min(now.saturating_sub(self.first_seen)).max(1);
+        hashes * 1000.0 / elapsed as f64
+    }
+
+    // Estimated hashrate over all the windows
+    pub fn get_hashrates(&self) -> MinerHashrate {
+        MinerHashrate {
+            last_minute: self.get_hashrate(60 * 1000),
+            last_15_minutes: self.get_hashrate(15 * 60 * 1000),
+            last_hour: self.get_hashrate(SHARES_WINDOW_MILLIS)
+        }
+    }
+
+    pub fn to_entry(&self, protocol: MiningProtocol) -> MinerEntry<'_> {
+        MinerEntry {
+            address: self.key.as_address(self.mainnet),
+            worker: Cow::Borrowed(&self.name),
+            protocol,
+            first_seen: self.first_seen,
+            blocks_accepted: self.blocks_accepted.len(),
+            blocks_rejected: self.blocks_rejected,
+            shares_accepted: self.shares_accepted,
+            shares_rejected: self.shares_rejected,
+            share_difficulty: self.share_difficulty,
+            last_share: self.shares.back().map(|(timestamp, _)| *timestamp),
+            hashrate: self.get_hashrates()
+        }
+    }
 }
 
 impl Display for Miner {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let valid_blocks = self.blocks_accepted.iter().map(|h| h.to_string()).collect::<Vec<_>>().join(",");
-        write!(f, "Miner[address={}, name={}, accepted={} ({}), rejected={}]", self.key.as_address(self.mainnet), self.name, self.blocks_accepted.len(), valid_blocks, self.blocks_rejected)
+        write!(f, "Miner[address={}, name={}, accepted={} ({}), rejected={}, shares={}/{}]", self.key.as_address(self.mainnet), self.name, self.blocks_accepted.len(), valid_blocks, self.blocks_rejected, self.shares_accepted, self.shares_rejected)
     }
 }
 
@@ -217,21 +345,50 @@ pub struct GetWorkServer<S: Storage> {
     last_header_hash: Mutex<Option<Hash>>,
     // used only when a new TX is received in mempool
     last_notify: AtomicU64,
-    notify_rate_limit_ms: u64
+    notify_rate_limit_ms: u64,
+    // notify others mining protocols of each new job
+    jobs_sender: broadcast::Sender<MiningJob>,
+    // share difficulty used when the miner doesn't request one
+    default_share_difficulty: Option<Difficulty>,
+    // PoW hashes of the last shares accepted
+    shares_seen: Mutex<LruCache<Hash, ()>>
 }
 
 impl<S: Storage> GetWorkServer<S> {
-    pub fn new(blockchain: Arc<Blockchain<S>>) -> Self {
+    pub fn new(blockchain: Arc<Blockchain<S>>, default_share_difficulty: Option<Difficulty>) -> Self {
         Self {
             miners: Mutex::new(HashMap::new()),
             blockchain,
             mining_jobs: Mutex::new(LruCache::new(NonZeroUsize::new(STABLE_LIMIT as usize).unwrap())),
             last_header_hash: Mutex::new(None),
             last_notify: AtomicU64::new(0),
-            notify_rate_limit_ms: 500 // maximum one time every 500ms
+            notify_rate_limit_ms: 500, // maximum one time every 500ms
+            jobs_sender: broadcast::channel(1).0,
+            default_share_difficulty,
+            shares_seen: Mutex::new(LruCache::new(NonZeroUsize::new(SHARES_CACHE_SIZE).unwrap()))
         }
     }
 
+    // Share difficulty to use for a new miner
+    // The requested one can't be lower than the minimum difficulty of the network
+    pub fn get_share_difficulty(&self, requested: Option<u64>) -> Result<Option<Difficulty>, InternalRpcError> {
+        match requested {
+            Some(value) => {
+                let difficulty = Difficulty::from_u64(value);
+                if difficulty < get_minimum_difficulty(self.blockchain.get_network()) {
+                    return Err(InternalRpcError::InvalidParams("Share difficulty is lower than the minimum difficulty"))
+                }
+                Ok(Some(difficulty))
+            },
+            None => Ok(self.default_share_difficulty)
+        }
+    }
+
+    // Subscribe to the new jobs notified to miners
+    pub fn subscribe_jobs(&self) -> broadcast::Receiver<MiningJob> {
+        self.jobs_sender.subscribe()
+    }
+
     // Returns the number of miners connected to the getwork server
     pub async fn count_miners(&self) -> usize {
         trace!("count miners");
@@ -244,14 +401,13 @@ impl<S: Storage> GetWorkServer<S> {
         &self.miners
     }
 
-    // retrieve last mining job and set random extra nonce and miner public key
-    // then, send it
-    async fn send_new_job(self: Arc<Self>, addr: Addr<GetWorkWebSocketHandler<S>>, key: PublicKey) -> Result<(), InternalRpcError> {
-        debug!("Sending new job to miner");
-        let (mut job, version, height, difficulty) = {
+    // retrieve the last mining job if we are rate limited
+    // otherwise, generate a new one
+    pub async fn get_current_job(&self) -> Result<MiningJob, InternalRpcError> {
+        let (work, version, height, difficulty) = {
             let mut hash = self.last_header_hash.lock().await;
             let mut mining_jobs = self.mining_jobs.lock().await;
-            let (version, job, height, difficulty);
+            let (version, work, height, difficulty);
             // if we have a job in cache, and we are rate limited, we can send it
             // otherwise, we generate a new job
             if let Some(hash) = hash.as_ref().filter(|_| self.is_rate_limited().0) {
@@ -259,7 +415,7 @@ impl<S: Storage> GetWorkServer<S> {
                     error!("No mining job found! How is it possible ?");
                     InternalRpcError::InternalError("No mining job found")
                 })?;
-                job = MinerWork::new(header.get_work_hash(), get_current_time_in_millis());
+                work = MinerWork::new(header.get_work_hash(), get_current_time_in_millis());
                 height = header.get_height();
                 version = header.get_version();
                 difficulty = *diff;
@@ -269,36 +425,50 @@ impl<S: Storage> GetWorkServer<S> {
                 let header = self.blockchain.get_block_template_for_storage(&storage, DEV_PUBLIC_KEY.clone()).await.context("Error while retrieving block template")?;
                 (difficulty, _) = self.blockchain.get_difficulty_at_tips(&*storage, header.get_tips().iter()).await.context("Error while retrieving difficulty at tips")?;
 
-                job = MinerWork::new(header.get_work_hash(), get_current_time_in_millis());
+                work = MinerWork::new(header.get_work_hash(), get_current_time_in_millis());
                 height = header.get_height();
                 version = header.get_version();
 
                 // save the mining job, and set it as last job
-                let header_work_hash = job.get_header_work_hash();
+                let header_work_hash = work.get_header_work_hash();
                 *hash = Some(header_work_hash.clone());
                 mining_jobs.put(header_work_hash.clone(), (header, difficulty));
             }
 
-            (job, version, height, difficulty)
+            (work, version, height, difficulty)
         };
 
+        Ok(MiningJob {
+            work,
+            // get the algorithm for the current version
+            algorithm: get_pow_algorithm_for_version(version),
+            height,
+            topoheight: self.blockchain.get_topo_height(),
+            difficulty
+        })
+    }
+
+    // retrieve last mining job and set random extra nonce and miner public key
+    // then, send it
+    async fn send_new_job(self: Arc<Self>, addr: Addr<GetWorkWebSocketHandler<S>>, key: PublicKey, share_difficulty: Option<Difficulty>) -> Result<(), InternalRpcError> {
+        debug!("Sending new job to miner");
+        let MiningJob { work: mut job, algorithm, height, topoheight, difficulty } = self.get_current_job().await?;
+        let difficulty = share_difficulty.map_or(difficulty, |share_difficulty| share_difficulty.min(difficulty));
+
         // set miner key and random extra nonce
         job.set_miner(Cow::Owned(key));
         OsRng.fill_bytes(job.get_extra_nonce());
 
-        // get the algorithm for the current version
-        let algorithm = get_pow_algorithm_for_version(version);
-        let topoheight = self.blockchain.get_topo_height();
         debug!("Sending job to new miner");
         addr.send(Response::NewJob(GetMinerWorkResult { algorithm, miner_work: job.to_hex(), height, topoheight, difficulty })).await.context("error while sending block template")??;
         Ok(())
     }
 
-    pub async fn add_miner(self: &Arc<Self>, addr: Addr<GetWorkWebSocketHandler<S>>, key: PublicKey, worker: String) {
+    pub async fn add_miner(self: &Arc<Self>, addr: Addr<GetWorkWebSocketHandler<S>>, key: PublicKey, worker: String, share_difficulty: Option<Difficulty>) {
         trace!("add miner");
         {
             let mut miners = self.miners.lock().await;
-            let miner = Miner::new(self.blockchain.get_network().is_mainnet(), key.clone(), worker);
+            let miner = Miner::new(self.blockchain.get_network().is_mainnet(), key.clone(), worker, share_difficulty);
             debug!("Adding new miner to GetWork server: {}", miner);
             miners.insert(addr.clone(), miner);
         }
@@ -306,7 +476,7 @@ impl<S: Storage> GetWorkServer<S> {
         // notify the new miner so he can work ASAP
         let zelf = Arc::clone(&self);
         spawn_task("getwork-new-job", async move {
-            if let Err(e) = zelf.send_new_job(addr, key).await {
+            if let Err(e) = zelf.send_new_job(addr, key, share_difficulty).await {
                 error!("Error while sending new job to miner: {}", e);
             }
         });
@@ -320,39 +490,58 @@ impl<S: Storage> GetWorkServer<S> {
         }
     }
 
-    // this function is called when a miner send a new block
+    // this function is called when a miner send a new share
     // we retrieve the block header saved in cache using the mining job "header_work_hash"
     // its used to check that the job come from our server
-    // when it's found, we merge the miner job inside the block header
-    async fn accept_miner_job(&self, job: MinerWork<'_>) -> Result<(Response, Hash), InternalRpcError> {
-        trace!("accept miner job");
+    // when it's found, we merge the miner job inside the block header and verify its PoW
+    // If the share reach the network difficulty, its block is submitted to the chain
+    // Returns the response, the block hash if submitted, and the job difficulty to credit
+    pub async fn accept_miner_share(&self, job: MinerWork<'_>, share_difficulty: Option<&Difficulty>) -> Result<(Response, Option<Hash>, Difficulty), InternalRpcError> {
+        trace!("accept miner share");
         if job.get_miner().is_none() {
             return Err(InternalRpcError::InvalidJSONRequest);
         }
 
-        let mut miner_header;
-        {
+        let (mut miner_header, network_difficulty) = {
             let mining_jobs = self.mining_jobs.lock().await;
-            if let Some((header, _)) = mining_jobs.peek(job.get_header_work_hash()) {
+            if let Some((header, difficulty)) = mining_jobs.peek(job.get_header_work_hash()) {
                 // job is found in cache, clone it and put miner data inside
-                miner_header = header.clone();
-                miner_header.apply_miner_work(job);
+                (header.clone(), *difficulty)
             } else {
                 // really old job, or miner send invalid job
                 debug!("Job {} was not found in cache", job.get_header_work_hash());
                 return Err(InternalRpcError::InvalidParams("Job was not found in cache"))
-            };
+            }
+        };
+        miner_header.apply_miner_work(job);
+
+        let job_difficulty = share_difficulty.map_or(network_difficulty, |share_difficulty| (*share_difficulty).min(network_difficulty));
+        let algorithm = get_pow_algorithm_for_version(miner_header.get_version());
+        let pow_hash = miner_header.get_pow_hash(algorithm).context("Error while computing PoW hash of share")?;
+        let difficulty = difficulty_from_hash(&pow_hash);
+        if difficulty < job_difficulty {
+            debug!("Share {} is below the job difficulty", pow_hash);
+            return Ok((Response::ShareRejected(ShareError::LowDifficulty), None, job_difficulty))
+        }
+
+        if self.shares_seen.lock().await.put(pow_hash, ()).is_some() {
+            return Ok((Response::ShareRejected(ShareError::Duplicate), None, job_difficulty))
+        }
+
+        if difficulty < network_difficulty {
+            return Ok((Response::ShareAccepted, None, job_difficulty))
         }
 
         let block = self.blockchain.build_block_from_header(Immutable::Owned(miner_header)).await.context("Error while building block from header")?;
         let block_hash = block.hash();
-        Ok(match self.blockchain.add_new_block(block, true, true).await {
-            Ok(_) => (Response::BlockAccepted, block_hash),
+        let response = match self.blockchain.add_new_block(block, true, true).await {
+            Ok(_) => Response::BlockAccepted,
             Err(e) => {
                 debug!("Error while accepting miner block: {}", e);
-                (Response::BlockRejected(e.to_string()), block_hash)
+                Response::BlockRejected(e.to_string())
             }
-        })
+        };
+        Ok((response, Some(block_hash), job_difficulty))
     }
 
     // handle the incoming mining job from the miner
@@ -360,17 +549,24 @@ impl<S: Storage> GetWorkServer<S> {
     // if its block is rejected, resend him the job
     pub async fn handle_block_for(self: Arc<Self>, addr: Addr<GetWorkWebSocketHandler<S>>, submitted_work: SubmitMinerWorkParams) {
         trace!("handle block for");
-        let (response, hash) = match MinerWork::from_hex(submitted_work.miner_work) {
-            Ok(job) => match self.accept_miner_job(job).await {
-                Ok((response, hash)) => (response, Some(hash)),
+        let share_difficulty = {
+            let miners = self.miners.lock().await;
+            miners.get(&addr).and_then(|miner| miner.get_share_difficulty().cloned())
+        };
+
+        let (response, hash, difficulty) = match MinerWork::from_hex(submitted_work.miner_work) {
+            Ok(job) => match self.accept_miner_share(job, share_difficulty.as_ref()).await {
+                // without share difficulty, the miner only sends blocks: reject it and resend the job like before
+                Ok((Response::ShareRejected(e), hash, difficulty)) if share_difficulty.is_none() => (Response::BlockRejected(e.to_string()), hash, difficulty),
+                Ok(result) => result,
                 Err(e) => {
                     debug!("Error while accepting miner job: {}", e);
-                    (Response::BlockRejected(e.to_string()), None)
+                    (Response::BlockRejected(e.to_string()), None, Difficulty::zero())
                 }
             },
             Err(e) => {
                 debug!("Error while decoding block miner: {}", e);
-                (Response::BlockRejected(e.to_string()), None)
+                (Response::BlockRejected(e.to_string()), None, Difficulty::zero())
             }
         };
 
@@ -378,19 +574,12 @@ impl<S: Storage> GetWorkServer<S> {
         {
             let mut miners = self.miners.lock().await;
             if let Some(miner) = miners.get_mut(&addr) {
-                match &response {
-                    Response::BlockAccepted => {
-                        let hash = hash.unwrap();
-                        debug!("Miner {} found block {}!", miner, hash);
-                        miner.blocks_accepted.insert(hash);
-                    },
-                    Response::BlockRejected(_) => {
-                        debug!("Miner {} sent an invalid block", miner);
-                        miner.blocks_rejected += 1;
-                        miner.last_invalid_block = get_current_time_in_millis();
-                    },
+                match (&response, &hash) {
+                    (Response::BlockAccepted, Some(hash)) => debug!("Miner {} found block {}!", miner, hash),
+                    (Response::BlockRejected(_), _) => debug!("Miner {} sent an invalid block", miner),
                     _ => {}
                 }
+                miner.on_work_result(&response, hash, difficulty);
             }
         }
 
@@ -416,7 +605,7 @@ impl<S: Storage> GetWorkServer<S> {
                     }
                 };
                 if let Some(key) = key {
-                    if let Err(e) = self.send_new_job(addr, key).await {
+                    if let Err(e) = self.send_new_job(addr, key, share_difficulty).await {
                         error!("Error while sending new job to miner: {}", e);
                     };
                 }
@@ -455,7 +644,7 @@ impl<S: Storage> GetWorkServer<S> {
         // otherwise, no need to build a new job
         {
             let miners = self.miners.lock().await;
-            if miners.is_empty() {
+            if miners.is_empty() && self.jobs_sender.receiver_count() == 0 {
                 debug!("No miners connected, no need to notify them");
                 return Ok(());
             }
@@ -492,6 +681,15 @@ impl<S: Storage> GetWorkServer<S> {
         // This is for visual purposes only
         let topoheight = self.blockchain.get_topo_height();
 
+        // notify others mining protocols
+        if self.jobs_sender.receiver_count() > 0 {
+            let work = MinerWork::new(job.get_header_work_hash().clone(), job.get_timestamp());
+            let mining_job = MiningJob { work, algorithm, height, topoheight, difficulty };
+            if self.jobs_sender.send(mining_job).is_err() {
+                debug!("No subscribers left for the new job");
+            }
+        }
+
         for (addr, miner) in miners.iter() {
             debug!("Notifying {} for new job", miner);
             let addr = addr.clone();
@@ -499,6 +697,7 @@ impl<S: Storage> GetWorkServer<S> {
             job.set_miner(Cow::Borrowed(miner.get_public_key()));
             OsRng.fill_bytes(job.get_extra_nonce());
             let template = job.to_hex();
+            let difficulty = miner.get_job_difficulty(difficulty);
 
             // New task for each miner in case a miner is slow
             // we don't want to wait for him
@@ -517,4 +716,28 @@ impl<S: Storage> GetWorkServer<S> {
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use vyridium_common::crypto::KeyPair;
+    use super::*;
+
+    #[test]
+    fn test_miner_shares() {
+        let key = KeyPair::new().get_public_key().compress();
+        let mut miner = Miner::new(false, key, "test".into(), Some(Difficulty::from_u64(1000)));
+        assert_eq!(miner.get_job_difficulty(Difficulty::from_u64(500)), Difficulty::from_u64(500));
+        assert_eq!(miner.get_job_difficulty(Difficulty::from_u64(5000)), Difficulty::from_u64(1000));
+
+        miner.on_work_result(&Response::ShareAccepted, None, Difficulty::from_u64(1000));
+        miner.on_work_result(&Response::BlockAccepted, Some(Hash::zero()), Difficulty::from_u64(1000));
+        miner.on_work_result(&Response::ShareRejected(ShareError::Duplicate), None, Difficulty::from_u64(1000));
+
+        let entry = miner.to_entry(MiningProtocol::Getwork);
+        assert_eq!(entry.shares_accepted, 2);
+        assert_eq!(entry.shares_rejected, 1);
+        assert_eq!(entry.blocks_accepted, 1);
+        assert!(entry.hashrate.last_minute > 0.0);
+    }
+}
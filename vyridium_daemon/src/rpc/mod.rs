@@ -1,5 +1,6 @@
 pub mod rpc;
 pub mod getwork_server;
+pub mod stratum_server;
 
 use crate::{
     core::{
@@ -20,18 +21,21 @@ use actix_web::{
         self,
         Path,
         Data,
-        Payload
+        Payload,
+        Query
     },
     dev::ServerHandle,
     error::Error
 };
 use actix_web_actors::ws::WsResponseBuilder;
+use serde::Deserialize;
 use serde_json::{Value, json};
 use tokio::sync::Mutex;
 use vyridium_common::{
     api::daemon::NotifyEvent,
     config,
     crypto::Address,
+    difficulty::Difficulty,
     rpc_server::{
         json_rpc,
         websocket,
@@ -58,9 +62,15 @@ use log::{
     warn,
     error,
 };
-use self::getwork_server::{
-    GetWorkWebSocketHandler,
-    SharedGetWorkServer
+use self::{
+    getwork_server::{
+        GetWorkWebSocketHandler,
+        SharedGetWorkServer
+    },
+    stratum_server::{
+        SharedStratumServer,
+        StratumServer
+    }
 };
 
 pub type SharedDaemonRpcServer<S> = Arc<DaemonRpcServer<S>>;
@@ -68,7 +78,8 @@ pub type SharedDaemonRpcServer<S> = Arc<DaemonRpcServer<S>>;
 pub struct DaemonRpcServer<S: Storage> {
     handle: Mutex<Option<ServerHandle>>,
     websocket: WebSocketServerShared<EventWebSocketHandler<Arc<Blockchain<S>>, NotifyEvent>>,
-    getwork: Option<SharedGetWorkServer<S>>
+    getwork: Option<SharedGetWorkServer<S>>,
+    stratum: Option<SharedStratumServer<S>>
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -80,18 +91,30 @@ pub enum ApiError {
     #[error("P2p engine is not running")]
     NoP2p,
     #[error("WebSocket server is not started")]
-    NoWebSocketServer
+    NoWebSocketServer,
+    #[error("Stratum server requires the GetWork server to be enabled")]
+    StratumWithoutGetWork
 }
 
 impl<S: Storage> DaemonRpcServer<S> {
-    pub async fn new(bind_address: String, blockchain: Arc<Blockchain<S>>, disable_getwork_server: bool) -> Result<SharedDaemonRpcServer<S>, BlockchainError> {
+    pub async fn new(bind_address: String, blockchain: Arc<Blockchain<S>>, disable_getwork_server: bool, getwork_share_difficulty: Option<u64>, stratum_bind_address: Option<String>) -> Result<SharedDaemonRpcServer<S>, BlockchainError> {
         let getwork: Option<SharedGetWorkServer<S>> = if !disable_getwork_server {
             info!("Creating GetWork server...");
-            Some(Arc::new(GetWorkServer::new(blockchain.clone())))
+            Some(Arc::new(GetWorkServer::new(blockchain.clone(), getwork_share_difficulty.map(Difficulty::from_u64))))
         } else {
             None
         };
 
+        // Stratum server is sharing the jobs of the getwork server
+        let stratum: Option<SharedStratumServer<S>> = match (stratum_bind_address, &getwork) {
+            (Some(stratum_bind_address), Some(getwork)) => {
+                info!("Creating Stratum server...");
+                Some(StratumServer::new(&stratum_bind_address, blockchain.clone(), getwork.clone()).await?)
+            },
+            (Some(_), None) => return Err(BlockchainError::Any(ApiError::StratumWithoutGetWork.into())),
+            (None, _) => None
+        };
+
         // create the RPC Handler which will register and contains all available methods
         let mut rpc_handler = RPCHandler::new(blockchain);
         rpc::register_methods(&mut rpc_handler, !disable_getwork_server);
@@ -103,6 +126,7 @@ impl<S: Storage> DaemonRpcServer<S> {
             handle: Mutex::new(None),
             websocket: ws,
             getwork,
+            stratum,
         });
 
         {
@@ -152,6 +176,10 @@ impl<S: Storage> DaemonRpcServer<S> {
     }
 
     pub async fn stop(&self) {
+        if let Some(stratum) = self.stratum.as_ref() {
+            stratum.stop();
+        }
+
         info!("Stopping RPC Server...");
         let mut handle = self.handle.lock().await;
         if let Some(handle) = handle.take() {
@@ -165,6 +193,10 @@ impl<S: Storage> DaemonRpcServer<S> {
     pub fn getwork_server(&self) -> &Option<SharedGetWorkServer<S>> {
         &self.getwork
     }
+
+    pub fn stratum_server(&self) -> &Option<SharedStratumServer<S>> {
+        &self.stratum
+    }
 }
 
 impl<S: Storage> WebSocketServerHandler<EventWebSocketHandler<Arc<Blockchain<S>>, NotifyEvent>> for DaemonRpcServer<S> {
@@ -185,7 +217,13 @@ async fn index() -> impl Responder {
     HttpResponse::Ok().body(format!("Hello, world!\nRunning on: {}", config::VERSION))
 }
 
-async fn getwork_endpoint<S: Storage>(server: Data<DaemonRpcServer<S>>, request: HttpRequest, stream: Payload, path: Path<(String, String)>) -> Result<HttpResponse, Error> {
+#[derive(Deserialize)]
+struct GetWorkQuery {
+    // share difficulty requested by the miner
+    share_difficulty: Option<u64>
+}
+
+async fn getwork_endpoint<S: Storage>(server: Data<DaemonRpcServer<S>>, request: HttpRequest, stream: Payload, path: Path<(String, String)>, query: Query<GetWorkQuery>) -> Result<HttpResponse, Error> {
     match &server.getwork {
         Some(getwork) => {
             let (addr, worker) = path.into_inner();
@@ -209,10 +247,15 @@ async fn getwork_endpoint<S: Storage>(server: Data<DaemonRpcServer<S>>, request:
                 return Ok(HttpResponse::BadRequest().body(format!("Address is not in same network state, should be in {} mode", network.to_string().to_lowercase())))
             }
 
+            let share_difficulty = match getwork.get_share_difficulty(query.share_difficulty) {
+                Ok(share_difficulty) => share_difficulty,
+                Err(e) => return Ok(HttpResponse::BadRequest().body(e.to_string()))
+            };
+
             let key = address.to_public_key();
             let (addr, response) = WsResponseBuilder::new(GetWorkWebSocketHandler::new(getwork.clone()), &request, stream).start_with_addr()?;
             trace!("New miner connected to GetWork WebSocket: {:?}", addr);
-            getwork.add_miner(addr, key, worker).await;
+            getwork.add_miner(addr, key, worker, share_difficulty).await;
             Ok(response)
         },
         None => Ok(HttpResponse::NotFound().reason("GetWork server is not enabled").finish()) // getwork server is not started
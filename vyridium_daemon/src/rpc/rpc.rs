@@ -1,8 +1,8 @@
 use super::{ApiError, InternalRpcError};
 use crate::{
-    config::{BLOCK_TIME_MILLIS, DEV_FEES, DEV_PUBLIC_KEY},
+    config::{BLOCK_TIME_MILLIS, DEV_FEES, DEV_PUBLIC_KEY, FEE_ESTIMATION_DEFAULT_TARGET_BLOCKS},
     core::{
-        blockchain::{get_block_dev_fee, get_block_reward, Blockchain},
+        blockchain::{get_block_dev_fee, get_block_reward, BlockTemplateOptions, Blockchain},
         error::BlockchainError,
         hard_fork::get_pow_algorithm_for_version,
         mempool::Mempool,
@@ -334,6 +334,10 @@ pub async fn get_peer_entry(peer: &Peer) -> PeerEntry {
         pruned_topoheight: peer.get_pruned_topoheight(),
         cumulative_difficulty: Cow::Owned(cumulative_difficulty),
         connected_on: peer.get_connection().connected_on(),
+        bytes_in: peer.get_connection().bytes_in(),
+        bytes_out: peer.get_connection().bytes_out(),
+        packets_in: peer.get_traffic_stats().get_packets_in(),
+        packets_out: peer.get_traffic_stats().get_packets_out(),
     }
 }
 
@@ -439,6 +443,7 @@ pub fn register_methods<S: Storage>(
     );
     handler.register_method("get_size_on_disk", async_handler!(get_size_on_disk::<S>));
     handler.register_method("get_mempool_cache", async_handler!(get_mempool_cache::<S>));
+    handler.register_method("estimate_fee_rate", async_handler!(estimate_fee_rate::<S>));
     handler.register_method("get_difficulty", async_handler!(get_difficulty::<S>));
     handler.register_method("validate_address", async_handler!(validate_address::<S>));
     handler.register_method("split_address", async_handler!(split_address::<S>));
@@ -454,6 +459,7 @@ pub fn register_methods<S: Storage>(
         );
         handler.register_method("get_miner_work", async_handler!(get_miner_work::<S>));
         handler.register_method("submit_block", async_handler!(submit_block::<S>));
+        handler.register_method("get_miners", async_handler!(get_miners::<S>));
     }
 }
 
@@ -561,9 +567,17 @@ async fn get_block_template<S: Storage>(
         ));
     }
 
+    let options = BlockTemplateOptions {
+        max_size: params.max_size,
+        exclude_txs: params.exclude_txs.into_iter().collect(),
+        prioritize_txs: params.prioritize_txs.into_iter().collect(),
+        min_fee_per_byte: params.min_fee_per_byte,
+        tips: params.tips
+    };
+
     let storage = blockchain.get_storage().read().await;
-    let block = blockchain
-        .get_block_template_for_storage(&storage, params.address.into_owned().to_public_key())
+    let (block, total_fees) = blockchain
+        .get_custom_block_template_for_storage(&storage, params.address.into_owned().to_public_key(), &options)
         .await
         .context("Error while retrieving block template")?;
     let (difficulty, _) = blockchain
@@ -573,12 +587,25 @@ async fn get_block_template<S: Storage>(
     let height = block.height;
     let algorithm = get_pow_algorithm_for_version(block.version);
     let topoheight = blockchain.get_topo_height();
+
+    // block will be ordered after the current topoheight
+    let past_supply = storage
+        .get_supply_at_topo_height(topoheight)
+        .await
+        .context("Error while retrieving supply")?;
+    let reward = get_block_reward(past_supply);
+    let (dev_reward, miner_reward) = get_block_rewards(height, Some(reward)).unwrap_or_default();
+
     Ok(json!(GetBlockTemplateResult {
         template: block.to_hex(),
         algorithm,
         height,
         topoheight,
-        difficulty
+        difficulty,
+        total_fees,
+        reward,
+        miner_reward,
+        dev_reward
     }))
 }
 
@@ -650,6 +677,32 @@ async fn submit_block<S: Storage>(
     Ok(json!(true))
 }
 
+async fn get_miners<S: Storage>(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
+    if body != Value::Null {
+        return Err(InternalRpcError::UnexpectedParams);
+    }
+
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    let rpc = blockchain.get_rpc().read().await;
+    let rpc = rpc.as_ref().ok_or(InternalRpcError::InternalError("RPC server is not running"))?;
+    let getwork = rpc.getwork_server().as_ref().ok_or(InternalRpcError::InternalError("GetWork server is not running"))?;
+
+    let getwork_miners = getwork.get_miners().lock().await;
+    let mut miners: Vec<MinerEntry> = getwork_miners.values()
+        .map(|miner| miner.to_entry(MiningProtocol::Getwork))
+        .collect();
+
+    let stratum_miners = match rpc.stratum_server() {
+        Some(stratum) => Some(stratum.get_miners().lock().await),
+        None => None
+    };
+    if let Some(stratum_miners) = stratum_miners.as_ref() {
+        miners.extend(stratum_miners.values().map(|miner| miner.to_entry(MiningProtocol::Stratum)));
+    }
+
+    Ok(json!(GetMinersResult { miners }))
+}
+
 async fn get_balance<S: Storage>(
     context: &Context,
     body: Value,
@@ -794,6 +847,7 @@ async fn get_info<S: Storage>(context: &Context, body: Value) -> Result<Value, I
     let block_time_target = BLOCK_TIME_MILLIS;
     let block_reward = get_block_reward(circulating_supply);
     let mempool_size = blockchain.get_mempool_size().await;
+    let mempool_min_fee_per_byte = blockchain.get_mempool_min_fee_per_byte().await;
     let version = VERSION.into();
     let network = *blockchain.get_network();
 
@@ -810,6 +864,7 @@ async fn get_info<S: Storage>(context: &Context, body: Value) -> Result<Value, I
         average_block_time,
         block_reward,
         mempool_size,
+        mempool_min_fee_per_byte,
         version,
         network
     }))
@@ -1073,6 +1128,7 @@ async fn p2p_status<S: Storage>(context: &Context, body: Value) -> Result<Value,
             let max_peers = p2p.get_max_peers();
             let our_topoheight = blockchain.get_topo_height();
             let peer_count = p2p.get_peer_count().await;
+            let block_propagation = p2p.get_block_propagation_metrics().get_stats();
 
             Ok(json!(P2pStatusResult {
                 peer_count,
@@ -1081,7 +1137,8 @@ async fn p2p_status<S: Storage>(context: &Context, body: Value) -> Result<Value,
                 our_topoheight,
                 best_topoheight,
                 median_topoheight,
-                max_peers
+                max_peers,
+                block_propagation
             }))
         }
         None => Err(InternalRpcError::InvalidParamsAny(ApiError::NoP2p.into())),
@@ -1731,6 +1788,35 @@ async fn get_mempool_cache<S: Storage>(
     Ok(json!(cache))
 }
 
+async fn estimate_fee_rate<S: Storage>(
+    context: &Context,
+    body: Value,
+) -> Result<Value, InternalRpcError> {
+    // params are optional
+    let params: EstimateFeeRateParams = if body == Value::Null {
+        EstimateFeeRateParams { target_blocks: None }
+    } else {
+        parse_params(body)?
+    };
+    let target_blocks = params
+        .target_blocks
+        .unwrap_or(FEE_ESTIMATION_DEFAULT_TARGET_BLOCKS)
+        .max(1);
+
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    let estimation = blockchain
+        .estimate_fee_rates(target_blocks)
+        .await
+        .context("Error while estimating fee rates")?;
+
+    Ok(json!(EstimateFeeRateResult {
+        next_block: estimation.next_block,
+        target_blocks,
+        target: estimation.target,
+        economy: estimation.economy,
+    }))
+}
+
 async fn get_difficulty<S: Storage>(
     context: &Context,
     body: Value,
@@ -0,0 +1,405 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc
+    }
+};
+use log::{debug, error, info, trace, warn};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpListener,
+        TcpStream
+    },
+    select,
+    sync::{
+        broadcast::{self, error::RecvError},
+        mpsc,
+        Mutex
+    }
+};
+use vyridium_common::{
+    api::stratum::*,
+    block::MinerWork,
+    crypto::{Address, PublicKey},
+    difficulty::Difficulty,
+    serializer::Serializer,
+    tokio::spawn_task
+};
+use crate::core::{
+    blockchain::Blockchain,
+    storage::Storage
+};
+use super::getwork_server::{
+    Miner,
+    MiningJob,
+    Response,
+    ShareError,
+    SharedGetWorkServer
+};
+
+pub type SharedStratumServer<S> = Arc<StratumServer<S>>;
+
+// Maximum size of a line sent by a miner
+const MAX_LINE_SIZE: u64 = 4096;
+// Maximum size of a worker name, same as the getwork server
+const MAX_WORKER_NAME_SIZE: usize = 32;
+
+// State of a Stratum connection
+struct Session {
+    id: u64,
+    subscribed: bool,
+    // public key of the miner once authorized
+    key: Option<PublicKey>,
+    // share difficulty requested at authorization
+    share_difficulty: Option<Difficulty>,
+    // last difficulty sent to the miner
+    difficulty: Option<Difficulty>
+}
+
+impl Session {
+    // Extra nonce bytes reserved for this connection
+    fn extra_nonce_prefix(&self) -> [u8; EXTRA_NONCE_PREFIX_SIZE] {
+        self.id.to_be_bytes()
+    }
+}
+
+// Stratum TCP server for pool-compatible miners
+// Jobs are the same as the getwork server ones, and blocks found
+// are submitted using the same path
+pub struct StratumServer<S: Storage> {
+    blockchain: Arc<Blockchain<S>>,
+    getwork: SharedGetWorkServer<S>,
+    // used as the extra nonce prefix of each connection
+    next_session_id: AtomicU64,
+    // authorized miners per session id
+    miners: Mutex<HashMap<u64, Miner>>,
+    exit_sender: broadcast::Sender<()>
+}
+
+impl<S: Storage> StratumServer<S> {
+    pub async fn new(bind_address: &str, blockchain: Arc<Blockchain<S>>, getwork: SharedGetWorkServer<S>) -> Result<SharedStratumServer<S>, std::io::Error> {
+        let listener = TcpListener::bind(bind_address).await?;
+        info!("Stratum server listening on {}", bind_address);
+
+        let (exit_sender, _) = broadcast::channel(1);
+        let server = Arc::new(Self {
+            blockchain,
+            getwork,
+            next_session_id: AtomicU64::new(0),
+            miners: Mutex::new(HashMap::new()),
+            exit_sender
+        });
+
+        let zelf = Arc::clone(&server);
+        spawn_task("stratum-server", async move {
+            zelf.listen(listener).await;
+        });
+
+        Ok(server)
+    }
+
+    // Returns the number of miners authorized on the Stratum server
+    pub async fn count_miners(&self) -> usize {
+        trace!("count stratum miners");
+        self.miners.lock().await.len()
+    }
+
+    // Returns the list of miners authorized on the Stratum server
+    pub fn get_miners(&self) -> &Mutex<HashMap<u64, Miner>> {
+        trace!("get stratum miners");
+        &self.miners
+    }
+
+    // Stop the server and close all connections
+    pub fn stop(&self) {
+        info!("Stopping Stratum server...");
+        if self.exit_sender.send(()).is_err() {
+            debug!("Stratum server is not running");
+        }
+    }
+
+    async fn listen(self: Arc<Self>, listener: TcpListener) {
+        let mut exit = self.exit_sender.subscribe();
+        loop {
+            select! {
+                res = listener.accept() => match res {
+                    Ok((stream, addr)) => {
+                        let zelf = Arc::clone(&self);
+                        spawn_task(format!("stratum-{}", addr), async move {
+                            zelf.handle_connection(stream, addr).await;
+                        });
+                    },
+                    Err(e) => {
+                        error!("Error while accepting new Stratum connection: {}", e);
+                    }
+                },
+                _ = exit.recv() => {
+                    debug!("Stratum server listener has stopped");
+                    break;
+                }
+            }
+        }
+    }
+
+    async fn handle_connection(self: Arc<Self>, stream: TcpStream, addr: SocketAddr) {
+        let id = self.next_session_id.fetch_add(1, Ordering::SeqCst);
+        debug!("New Stratum connection #{} from {}", id, addr);
+
+        let (reader, mut writer) = stream.into_split();
+        let (lines_sender, mut lines) = mpsc::channel(16);
+        let reader_task = spawn_task(format!("stratum-reader-{}", addr), Self::read_lines(reader, lines_sender));
+
+        let mut session = Session {
+            id,
+            subscribed: false,
+            key: None,
+            share_difficulty: None,
+            difficulty: None
+        };
+        let mut jobs = self.getwork.subscribe_jobs();
+        let mut exit = self.exit_sender.subscribe();
+
+        loop {
+            let res = select! {
+                line = lines.recv() => match line {
+                    Some(line) => self.handle_line(&mut session, &mut writer, &line).await,
+                    None => break
+                },
+                job = jobs.recv() => match job {
+                    Ok(job) => self.send_job(&mut session, &mut writer, job).await,
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break
+                },
+                _ = exit.recv() => break
+            };
+
+            if let Err(e) = res {
+                debug!("Error on Stratum connection #{} from {}: {}", id, addr, e);
+                break;
+            }
+        }
+
+        reader_task.abort();
+        if let Some(miner) = self.miners.lock().await.remove(&id) {
+            debug!("{} disconnected from Stratum server", miner);
+        }
+    }
+
+    // Read each line sent by the miner
+    // Connection is closed if a line is too long
+    async fn read_lines(reader: OwnedReadHalf, sender: mpsc::Sender<String>) {
+        let mut reader = BufReader::new(reader);
+        loop {
+            let mut line = String::new();
+            match (&mut reader).take(MAX_LINE_SIZE).read_line(&mut line).await {
+                Ok(0) => break,
+                Ok(_) if !line.ends_with('\n') => {
+                    debug!("Stratum line is too long, closing connection");
+                    break;
+                },
+                Ok(_) => {
+                    if sender.send(line).await.is_err() {
+                        break;
+                    }
+                },
+                Err(e) => {
+                    debug!("Error while reading Stratum line: {}", e);
+                    break;
+                }
+            }
+        }
+    }
+
+    async fn write_message<V: serde::Serialize>(writer: &mut OwnedWriteHalf, message: &V) -> Result<(), anyhow::Error> {
+        let mut bytes = serde_json::to_vec(message)?;
+        bytes.push(b'\n');
+        writer.write_all(&bytes).await?;
+        Ok(())
+    }
+
+    async fn handle_line(&self, session: &mut Session, writer: &mut OwnedWriteHalf, line: &str) -> Result<(), anyhow::Error> {
+        let line = line.trim();
+        if line.is_empty() {
+            return Ok(())
+        }
+
+        let request: StratumRequest = serde_json::from_str(line)?;
+        trace!("Stratum request #{}: {}", session.id, request.method);
+        let id = request.id.clone();
+        let authorize = request.method == METHOD_AUTHORIZE;
+        let response = match self.handle_request(session, writer, request).await {
+            Ok(result) => StratumResponse::result(id, result),
+            Err((code, message)) => StratumResponse::error(id, code, message)
+        };
+        Self::write_message(writer, &response).await?;
+
+        // Miner is now authorized, send him a job so he can work ASAP
+        if authorize && response.error.is_none() {
+            let job = self.getwork.get_current_job().await?;
+            self.send_job(session, writer, job).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn handle_request(&self, session: &mut Session, writer: &mut OwnedWriteHalf, request: StratumRequest) -> Result<Value, (i16, String)> {
+        match request.method.as_str() {
+            METHOD_SUBSCRIBE => {
+                session.subscribed = true;
+                let prefix = hex::encode(session.extra_nonce_prefix());
+                let subscriptions = vec![
+                    (METHOD_SET_DIFFICULTY.to_owned(), prefix.clone()),
+                    (METHOD_NOTIFY.to_owned(), prefix.clone())
+                ];
+                Ok(json!(SubscribeResult(subscriptions, prefix, EXTRA_NONCE_MINER_SIZE)))
+            },
+            METHOD_AUTHORIZE => {
+                if !session.subscribed {
+                    return Err((ERROR_NOT_SUBSCRIBED, "Not subscribed".into()))
+                }
+                if session.key.is_some() {
+                    return Err((ERROR_OTHER, "Already authorized".into()))
+                }
+
+                // params are [login, password]
+                let login = request.params.get(0)
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| (ERROR_OTHER, "Invalid params: expected login".to_owned()))?
+                    .to_owned();
+
+                // like most pools, the password can be used to request a share difficulty with "d=<difficulty>"
+                let requested_difficulty = match request.params.get(1).and_then(Value::as_str).and_then(|password| password.strip_prefix("d=")) {
+                    Some(value) => Some(value.parse::<u64>().map_err(|_| (ERROR_OTHER, "Invalid share difficulty".to_owned()))?),
+                    None => None
+                };
+                let share_difficulty = self.getwork.get_share_difficulty(requested_difficulty)
+                    .map_err(|e| (ERROR_OTHER, e.to_string()))?;
+
+                // login can be "address" or "address.worker"
+                let (address, worker) = match login.split_once('.') {
+                    Some((address, worker)) => (address, worker.to_owned()),
+                    None => (login.as_str(), String::new())
+                };
+                if worker.len() > MAX_WORKER_NAME_SIZE {
+                    return Err((ERROR_UNAUTHORIZED_WORKER, format!("Worker name must be less or equal to {} chars", MAX_WORKER_NAME_SIZE)))
+                }
+
+                let key = self.parse_miner_address(address)?;
+                let miner = Miner::new(self.blockchain.get_network().is_mainnet(), key.clone(), worker, share_difficulty);
+                debug!("Adding new miner to Stratum server: {}", miner);
+                self.miners.lock().await.insert(session.id, miner);
+                session.key = Some(key);
+                session.share_difficulty = share_difficulty;
+
+                Ok(Value::Bool(true))
+            },
+            METHOD_SUBMIT => {
+                let key = session.key.as_ref()
+                    .ok_or_else(|| (ERROR_UNAUTHORIZED_WORKER, "Unauthorized worker".to_owned()))?;
+
+                let SubmitParams(_, job_id, miner_work) = serde_json::from_value(request.params)
+                    .map_err(|e| (ERROR_OTHER, format!("Invalid params: {}", e)))?;
+
+                let mut work = MinerWork::from_hex(miner_work)
+                    .map_err(|e| (ERROR_OTHER, format!("Invalid miner work: {}", e)))?;
+
+                if work.get_header_work_hash().to_hex() != job_id {
+                    return Err((ERROR_JOB_NOT_FOUND, "Job not found".into()))
+                }
+
+                if work.get_miner() != Some(key) {
+                    return Err((ERROR_UNAUTHORIZED_WORKER, "Invalid miner in work".into()))
+                }
+
+                if work.get_extra_nonce()[..EXTRA_NONCE_PREFIX_SIZE] != session.extra_nonce_prefix() {
+                    return Err((ERROR_OTHER, "Invalid extra nonce prefix".into()))
+                }
+
+                let (response, hash, difficulty) = self.getwork.accept_miner_share(work, session.share_difficulty.as_ref()).await
+                    .map_err(|e| (ERROR_JOB_NOT_FOUND, e.to_string()))?;
+
+                {
+                    let mut miners = self.miners.lock().await;
+                    if let Some(miner) = miners.get_mut(&session.id) {
+                        match (&response, &hash) {
+                            (Response::BlockAccepted, Some(hash)) => debug!("Miner {} found block {}!", miner, hash),
+                            (Response::BlockRejected(_), _) => debug!("Miner {} sent an invalid block", miner),
+                            _ => {}
+                        }
+                        miner.on_work_result(&response, hash, difficulty);
+                    }
+                }
+
+                match response {
+                    // the block hash tells the miner its share was also a block
+                    Response::BlockAccepted => Ok(hash.map(|hash| json!(hash.to_hex())).unwrap_or(Value::Bool(true))),
+                    Response::ShareAccepted => Ok(Value::Bool(true)),
+                    Response::ShareRejected(ShareError::LowDifficulty) => Err((ERROR_LOW_DIFFICULTY_SHARE, ShareError::LowDifficulty.to_string())),
+                    Response::ShareRejected(ShareError::Duplicate) => Err((ERROR_DUPLICATE_SHARE, ShareError::Duplicate.to_string())),
+                    Response::BlockRejected(reason) => {
+                        // send a fresh job like the getwork server does
+                        match self.getwork.get_current_job().await {
+                            Ok(job) => if let Err(e) = self.send_job(session, writer, job).await {
+                                warn!("Error while sending new job to Stratum miner: {}", e);
+                            },
+                            Err(e) => error!("Error while retrieving job for Stratum miner: {}", e)
+                        };
+                        Err((ERROR_BLOCK_REJECTED, reason))
+                    },
+                    Response::NewJob(_) => Err((ERROR_OTHER, "Unexpected response".into()))
+                }
+            },
+            method => {
+                debug!("Unknown Stratum method: {}", method);
+                Err((ERROR_OTHER, format!("Unknown method {}", method)))
+            }
+        }
+    }
+
+    // Verify that the address can be used to mine on this network
+    fn parse_miner_address(&self, address: &str) -> Result<PublicKey, (i16, String)> {
+        let address = Address::from_string(&address.to_owned())
+            .map_err(|_| (ERROR_UNAUTHORIZED_WORKER, "Invalid miner address".to_owned()))?;
+
+        if !address.is_normal() {
+            return Err((ERROR_UNAUTHORIZED_WORKER, "Address should be in normal format".into()))
+        }
+
+        let network = self.blockchain.get_network();
+        if address.is_mainnet() != network.is_mainnet() {
+            return Err((ERROR_UNAUTHORIZED_WORKER, format!("Address is not in same network state, should be in {} mode", network.to_string().to_lowercase())))
+        }
+
+        Ok(address.to_public_key())
+    }
+
+    // Send the job to an authorized miner with its own key and extra nonce prefix
+    // Difficulty is sent before only if it has changed
+    // Previous jobs are always cleaned as each job is built on the latest tips
+    async fn send_job(&self, session: &mut Session, writer: &mut OwnedWriteHalf, job: MiningJob) -> Result<(), anyhow::Error> {
+        let Some(key) = session.key.as_ref() else {
+            return Ok(())
+        };
+
+        let difficulty = session.share_difficulty.map_or(job.difficulty, |share_difficulty| share_difficulty.min(job.difficulty));
+        if session.difficulty != Some(difficulty) {
+            let notification = StratumRequest::notification(METHOD_SET_DIFFICULTY, json!(SetDifficultyParams(difficulty)));
+            Self::write_message(writer, &notification).await?;
+            session.difficulty = Some(difficulty);
+        }
+
+        let mut work = job.work;
+        work.set_miner(Cow::Owned(key.clone()));
+        work.get_extra_nonce()[..EXTRA_NONCE_PREFIX_SIZE].copy_from_slice(&session.extra_nonce_prefix());
+
+        let job_id = work.get_header_work_hash().to_hex();
+        let params = NotifyParams(job_id, job.algorithm, work.to_hex(), job.height, job.topoheight, true);
+        Self::write_message(writer, &StratumRequest::notification(METHOD_NOTIFY, json!(params))).await
+    }
+}
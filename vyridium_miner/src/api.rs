@@ -0,0 +1,264 @@
+use std::{
+    fmt::Write,
+    sync::atomic::Ordering,
+    time::Duration
+};
+use anyhow::Result;
+use log::{debug, info, warn};
+use serde::Serialize;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    time::timeout
+};
+use vyridium_common::{
+    difficulty::Difficulty,
+    tokio::spawn_task
+};
+use crate::{
+    stats::{self, ThreadStatsEntry},
+    ACTIVE_DAEMON,
+    BLOCKS_FOUND,
+    BLOCKS_REJECTED,
+    SHARES_ACCEPTED,
+    SHARES_REJECTED,
+    CURRENT_DIFFICULTY,
+    CURRENT_HEIGHT,
+    CURRENT_TOPO_HEIGHT,
+    HASHRATE,
+    START_TIME,
+    WEBSOCKET_CONNECTED
+};
+
+// Maximum size of a request, we only need the request line and headers
+const MAX_REQUEST_SIZE: u64 = 8 * 1024;
+// Maximum time for a client to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+const CONTENT_TYPE_JSON: &str = "application/json";
+const CONTENT_TYPE_METRICS: &str = "text/plain; version=0.0.4";
+const CONTENT_TYPE_TEXT: &str = "text/plain";
+
+#[derive(Serialize)]
+pub struct MinerStats {
+    pub hashrate: u64,
+    pub accepted: usize,
+    pub rejected: usize,
+    pub shares_accepted: usize,
+    pub shares_rejected: usize,
+    // uptime in seconds
+    pub uptime: u64,
+    pub connected: bool,
+    // daemon we are currently mining on
+    pub upstream: Option<String>,
+    pub height: u64,
+    pub topoheight: u64,
+    pub difficulty: Option<Difficulty>,
+    pub threads: Vec<ThreadStatsEntry>
+}
+
+impl MinerStats {
+    pub fn current() -> Self {
+        Self {
+            hashrate: HASHRATE.load(Ordering::SeqCst),
+            accepted: BLOCKS_FOUND.load(Ordering::SeqCst),
+            rejected: BLOCKS_REJECTED.load(Ordering::SeqCst),
+            shares_accepted: SHARES_ACCEPTED.load(Ordering::SeqCst),
+            shares_rejected: SHARES_REJECTED.load(Ordering::SeqCst),
+            uptime: START_TIME.elapsed().as_secs(),
+            connected: WEBSOCKET_CONNECTED.load(Ordering::SeqCst),
+            upstream: ACTIVE_DAEMON.read().unwrap().clone(),
+            height: CURRENT_HEIGHT.load(Ordering::SeqCst),
+            topoheight: CURRENT_TOPO_HEIGHT.load(Ordering::SeqCst),
+            difficulty: *CURRENT_DIFFICULTY.read().unwrap(),
+            threads: stats::get_threads_stats()
+        }
+    }
+
+    // Render the stats using the Prometheus text format
+    pub fn to_metrics(&self) -> String {
+        let mut output = String::new();
+        write_metric(&mut output, "vyridium_miner_hashrate", "gauge", "Current hashrate in H/s", &[(None, self.hashrate as f64)]);
+        write_metric(&mut output, "vyridium_miner_blocks_accepted_total", "counter", "Blocks accepted by the network", &[(None, self.accepted as f64)]);
+        write_metric(&mut output, "vyridium_miner_blocks_rejected_total", "counter", "Blocks rejected by the network", &[(None, self.rejected as f64)]);
+        write_metric(&mut output, "vyridium_miner_shares_accepted_total", "counter", "Shares accepted by the daemon", &[(None, self.shares_accepted as f64)]);
+        write_metric(&mut output, "vyridium_miner_shares_rejected_total", "counter", "Shares rejected by the daemon", &[(None, self.shares_rejected as f64)]);
+        write_metric(&mut output, "vyridium_miner_uptime_seconds", "gauge", "Time since the miner started", &[(None, self.uptime as f64)]);
+        write_metric(&mut output, "vyridium_miner_connected", "gauge", "Whether the miner is connected to a daemon", &[(None, if self.connected { 1f64 } else { 0f64 })]);
+        write_metric(&mut output, "vyridium_miner_job_height", "gauge", "Height of the current job", &[(None, self.height as f64)]);
+        write_metric(&mut output, "vyridium_miner_job_topoheight", "gauge", "Topoheight of the current job", &[(None, self.topoheight as f64)]);
+        if let Some(difficulty) = self.difficulty {
+            write_metric(&mut output, "vyridium_miner_job_difficulty", "gauge", "Difficulty of the current job", &[(None, difficulty.into())]);
+        }
+
+        let labels: Vec<String> = self.threads.iter().map(|thread| format!("thread=\"{}\"", thread.id)).collect();
+        let values = |f: fn(&ThreadStatsEntry) -> f64| -> Vec<(Option<&str>, f64)> {
+            self.threads.iter().zip(labels.iter()).map(|(thread, label)| (Some(label.as_str()), f(thread))).collect()
+        };
+        write_metric(&mut output, "vyridium_miner_thread_hashrate", "gauge", "Hashrate of each mining thread in H/s", &values(|thread| thread.hashrate as f64));
+        write_metric(&mut output, "vyridium_miner_thread_blocks_accepted_total", "counter", "Blocks found by each mining thread and accepted", &values(|thread| thread.accepted as f64));
+        write_metric(&mut output, "vyridium_miner_thread_blocks_rejected_total", "counter", "Blocks found by each mining thread and rejected", &values(|thread| thread.rejected as f64));
+
+        output
+    }
+}
+
+fn write_metric(output: &mut String, name: &str, kind: &str, help: &str, values: &[(Option<&str>, f64)]) {
+    if values.is_empty() {
+        return
+    }
+
+    // writing to a String can't fail
+    let _ = writeln!(output, "# HELP {} {}", name, help);
+    let _ = writeln!(output, "# TYPE {} {}", name, kind);
+    for (labels, value) in values {
+        match labels {
+            Some(labels) => { let _ = writeln!(output, "{}{{{}}} {}", name, labels, value); },
+            None => { let _ = writeln!(output, "{} {}", name, value); }
+        };
+    }
+}
+
+struct HttpResponse {
+    status: &'static str,
+    content_type: &'static str,
+    body: String
+}
+
+impl HttpResponse {
+    fn new(status: &'static str, content_type: &'static str, body: String) -> Self {
+        Self { status, content_type, body }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        format!(
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.status,
+            self.content_type,
+            self.body.len(),
+            self.body
+        ).into_bytes()
+    }
+}
+
+// Build the response for the requested route
+fn route(method: &str, target: &str) -> HttpResponse {
+    // query parameters are not used
+    let path = target.split('?').next().unwrap_or_default();
+    if !matches!(path, "/stats" | "/metrics" | "/health") {
+        return HttpResponse::new("404 Not Found", CONTENT_TYPE_TEXT, "Not Found".to_owned())
+    }
+
+    if method != "GET" {
+        return HttpResponse::new("405 Method Not Allowed", CONTENT_TYPE_TEXT, "Method Not Allowed".to_owned())
+    }
+
+    match path {
+        "/stats" => match serde_json::to_string(&MinerStats::current()) {
+            Ok(json) => HttpResponse::new("200 OK", CONTENT_TYPE_JSON, json),
+            Err(e) => HttpResponse::new("500 Internal Server Error", CONTENT_TYPE_TEXT, e.to_string())
+        },
+        "/metrics" => HttpResponse::new("200 OK", CONTENT_TYPE_METRICS, MinerStats::current().to_metrics()),
+        // the miner is alive as long as it answers
+        _ => HttpResponse::new("200 OK", CONTENT_TYPE_JSON, serde_json::json!({
+            "status": "ok",
+            "connected": WEBSOCKET_CONNECTED.load(Ordering::SeqCst)
+        }).to_string())
+    }
+}
+
+// Read the request line and skip the headers, returns the method and target
+async fn read_request(stream: &mut TcpStream) -> Result<Option<(String, String)>> {
+    let mut reader = BufReader::new(stream.take(MAX_REQUEST_SIZE));
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).await? == 0 {
+        return Ok(None)
+    }
+
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).await? == 0 || header.trim_end().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => Ok(Some((method.to_owned(), target.to_owned()))),
+        _ => Ok(None)
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream) -> Result<()> {
+    let response = match timeout(REQUEST_TIMEOUT, read_request(&mut stream)).await {
+        Ok(Ok(Some((method, target)))) => {
+            debug!("Stats API request: {} {}", method, target);
+            route(&method, &target)
+        },
+        Ok(Ok(None)) | Ok(Err(_)) => HttpResponse::new("400 Bad Request", CONTENT_TYPE_TEXT, "Bad Request".to_owned()),
+        Err(_) => HttpResponse::new("408 Request Timeout", CONTENT_TYPE_TEXT, "Request Timeout".to_owned())
+    };
+
+    stream.write_all(&response.to_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+// this Tokio task will run indefinitely until the user stops the miner himself.
+// It serves the stats of the miner over HTTP
+pub async fn stats_api_task(bind_address: String) -> Result<()> {
+    let listener = TcpListener::bind(&bind_address).await?;
+    info!("Stats API listening on {}", bind_address);
+    loop {
+        let (stream, addr) = match listener.accept().await {
+            Ok(value) => value,
+            Err(e) => {
+                warn!("Error while accepting a stats API connection: {}", e);
+                continue;
+            }
+        };
+
+        spawn_task(format!("stats-api-{}", addr), async move {
+            if let Err(e) = handle_connection(stream).await {
+                debug!("Error on stats API connection {}: {}", addr, e);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_routes() {
+        assert_eq!(route("GET", "/unknown").status, "404 Not Found");
+        assert_eq!(route("POST", "/stats").status, "405 Method Not Allowed");
+        assert_eq!(route("GET", "/health?verbose=1").status, "200 OK");
+        assert_eq!(route("GET", "/metrics").content_type, CONTENT_TYPE_METRICS);
+    }
+
+    #[test]
+    fn test_metrics_format() {
+        let stats = MinerStats {
+            hashrate: 1500,
+            accepted: 2,
+            rejected: 1,
+            shares_accepted: 5,
+            shares_rejected: 0,
+            uptime: 60,
+            connected: true,
+            upstream: None,
+            height: 10,
+            topoheight: 12,
+            difficulty: None,
+            threads: vec![ThreadStatsEntry { id: 0, hashrate: 750, accepted: 2, rejected: 0, core: None }]
+        };
+
+        let metrics = stats.to_metrics();
+        assert!(metrics.contains("# TYPE vyridium_miner_hashrate gauge\nvyridium_miner_hashrate 1500\n"));
+        assert!(metrics.contains("vyridium_miner_thread_hashrate{thread=\"0\"} 750\n"));
+        assert!(metrics.contains("vyridium_miner_shares_accepted_total 5\n"));
+        assert!(!metrics.contains("vyridium_miner_job_difficulty"));
+    }
+}
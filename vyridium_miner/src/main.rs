@@ -1,6 +1,11 @@
 pub mod config;
+mod api;
+mod stats;
+mod upstream;
 
 use std::{
+    collections::{HashMap, VecDeque},
+    fs,
     sync::{
         atomic::{
             AtomicBool,
@@ -8,19 +13,30 @@ use std::{
             AtomicUsize,
             Ordering
         },
+        Arc,
         RwLock
     },
     thread,
     time::Duration
 };
-use crate::config::DEFAULT_DAEMON_ADDRESS;
+use crate::{
+    config::DEFAULT_DAEMON_ADDRESS,
+    upstream::{
+        stratum_address,
+        ConnectionEnd,
+        ConnectionWatcher,
+        UpstreamConfig
+    }
+};
 use fern::colors::Color;
 use futures_util::{StreamExt, SinkExt};
 use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
 use tokio::{
-    io::AsyncWriteExt, net::TcpListener, select, sync::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader}, net::{tcp::OwnedWriteHalf, TcpStream}, select, sync::{
         broadcast,
         mpsc,
+        watch,
         Mutex
     }, time::Instant
 };
@@ -33,13 +49,30 @@ use tokio_tungstenite::{
 };
 use vyridium_common::{
     tokio::spawn_task,
-    api::daemon::{
-        GetMinerWorkResult,
-        SubmitMinerWorkParams,
+    api::{
+        daemon::{
+            GetMinerWorkResult,
+            SubmitMinerWorkParams,
+        },
+        stratum::{
+            NotifyParams,
+            SetDifficultyParams,
+            StratumMessage,
+            StratumRequest,
+            SubmitParams,
+            METHOD_AUTHORIZE,
+            METHOD_NOTIFY,
+            METHOD_SET_DIFFICULTY,
+            is_block_submit_result,
+            ERROR_BLOCK_REJECTED,
+            METHOD_SUBMIT,
+            METHOD_SUBSCRIBE
+        }
     },
     async_handler,
     block::{MinerWork, Worker, Algorithm},
     config::VERSION,
+    context::Context as CommandContext,
     crypto::{
         Address,
         Hash,
@@ -51,7 +84,13 @@ use vyridium_common::{
         Difficulty
     },
     prompt::{
-        command::CommandManager,
+        argument::ArgumentManager,
+        command::{
+            Command,
+            CommandError,
+            CommandHandler,
+            CommandManager
+        },
         LogLevel,
         Prompt,
         ShareablePrompt,
@@ -65,7 +104,13 @@ use vyridium_common::{
         sanitize_daemon_address
     }
 };
-use clap::Parser;
+use clap::{
+    parser::ValueSource,
+    ArgMatches,
+    CommandFactory,
+    FromArgMatches,
+    Parser
+};
 use log::{
     debug,
     info,
@@ -79,17 +124,37 @@ use anyhow::{
 };
 use lazy_static::lazy_static;
 
-#[derive(Parser)]
+#[derive(Parser, Deserialize)]
 #[clap(version = VERSION, about = "XELIS: An innovate cryptocurrency with BlockDAG and Homomorphic Encryption enabling Smart Contracts")]
 #[command(styles = vyridium_common::get_cli_styles())]
+#[serde(default, deny_unknown_fields)]
 pub struct MinerConfig {
+    /// Path to a TOML config file
+    /// Options explicitly set on the command line override the ones of this file.
+    /// Send SIGHUP or use the reload command to apply a new thread count or worker name.
+    #[clap(long)]
+    #[serde(skip)]
+    config: Option<String>,
     /// Wallet address to mine and receive block rewards on
     #[clap(short, long)]
     miner_address: Option<Address>,
     /// Daemon address to connect to for mining
-    #[clap(long, default_value_t = String::from(DEFAULT_DAEMON_ADDRESS))]
-    daemon_address: String,
-    /// Broadcast address for stats
+    /// Multiple addresses can be set by priority order (repeated or comma separated),
+    /// the miner will fail over to the next one and fail back when a previous one is available again.
+    #[clap(long, default_value = DEFAULT_DAEMON_ADDRESS, value_delimiter = ',')]
+    daemon_address: Vec<String>,
+    /// Seconds without the topoheight advancing before failing over to the next daemon
+    #[clap(long, default_value_t = 300)]
+    stale_job_timeout: u64,
+    /// Interval in seconds to check if a daemon with a higher priority is available again
+    #[clap(long, default_value_t = 60)]
+    failback_interval: u64,
+    /// Protocol to use with the daemon
+    /// Stratum requires the daemon address to be its Stratum server bind address.
+    #[clap(long, value_enum, default_value_t = Protocol::Getwork)]
+    protocol: Protocol,
+    /// Bind address of the stats HTTP API
+    /// It serves /stats in JSON, /metrics for Prometheus and /health.
     #[clap(long)]
     broadcast_address: Option<String>,
     /// Set log level
@@ -135,15 +200,97 @@ pub struct MinerConfig {
     /// By default, this will try to detect the number of threads available on your CPU.
     #[clap(short, long)]
     num_threads: Option<u16>,
+    /// Run the benchmark at startup and use the lowest thread count within 2% of the best total hashrate
+    /// Number of threads set is used as the maximum to try.
+    #[clap(long)]
+    auto_tune: bool,
+    /// Pin each mining thread to a CPU core (Linux only)
+    #[clap(long)]
+    cpu_affinity: bool,
     /// Worker name to be displayed on daemon side
     #[clap(short, long, default_value_t = String::from("default"))]
     worker: String,
+    /// Share difficulty to request to the daemon
+    /// Shares below the network difficulty are only used for the stats on daemon side.
+    #[clap(long)]
+    share_difficulty: Option<u64>,
+}
+
+// Default values are the same as the command line ones
+impl Default for MinerConfig {
+    fn default() -> Self {
+        Self::parse_from([env!("CARGO_PKG_NAME")])
+    }
+}
+
+impl MinerConfig {
+    // Load the config from a TOML file
+    fn from_file(path: &str) -> Result<Self> {
+        let content = fs::read_to_string(path).with_context(|| format!("Error while reading config file {}", path))?;
+        let mut config: Self = toml::from_str(&content).with_context(|| format!("Error while parsing config file {}", path))?;
+        config.config = Some(path.to_owned());
+        Ok(config)
+    }
+
+    // Load the config from a TOML file and apply the options explicitly set on the command line
+    fn from_file_with_matches(path: &str, matches: &ArgMatches) -> Result<Self> {
+        let cli = Self::from_arg_matches(matches)?;
+        Ok(Self::from_file(path)?.merge(cli, matches))
+    }
+
+    // Replace the options that were explicitly set on the command line
+    fn merge(mut self, cli: Self, matches: &ArgMatches) -> Self {
+        macro_rules! merge {
+            ($($field:ident),*) => {
+                $(
+                    if matches.value_source(stringify!($field)) == Some(ValueSource::CommandLine) {
+                        self.$field = cli.$field;
+                    }
+                )*
+            };
+        }
+
+        merge!(
+            miner_address,
+            daemon_address,
+            stale_job_timeout,
+            failback_interval,
+            protocol,
+            broadcast_address,
+            log_level,
+            benchmark,
+            iterations,
+            disable_file_logging,
+            disable_file_log_date_based,
+            disable_log_color,
+            disable_interactive_mode,
+            filename_log,
+            logs_path,
+            logs_modules,
+            num_threads,
+            auto_tune,
+            cpu_affinity,
+            worker,
+            share_difficulty
+        );
+        self
+    }
+}
+
+#[derive(Clone, Copy, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    // WebSocket getwork server of the daemon
+    Getwork,
+    // Stratum TCP server of the daemon
+    Stratum
 }
 
 #[derive(Clone)]
 enum ThreadNotification<'a> {
-    NewJob(Algorithm, MinerWork<'a>, Difficulty, u64), // POW algorithm, block work, difficulty, height
+    NewJob(Algorithm, MinerWork<'a>, Difficulty, u64, bool), // POW algorithm, block work, difficulty, height, share mode
     WebSocketClosed, // WebSocket connection has been closed
+    Stop(u16), // threads with an id greater or equal must stop
     Exit // all threads must stop
 }
 
@@ -152,27 +299,109 @@ enum ThreadNotification<'a> {
 pub enum SocketMessage {
     NewJob(GetMinerWorkResult),
     BlockAccepted,
-    BlockRejected(String)
+    BlockRejected(String),
+    ShareAccepted,
+    ShareRejected(String)
 }
 
 static WEBSOCKET_CONNECTED: AtomicBool = AtomicBool::new(false);
 static CURRENT_TOPO_HEIGHT: AtomicU64 = AtomicU64::new(0);
+static CURRENT_HEIGHT: AtomicU64 = AtomicU64::new(0);
+static CURRENT_DIFFICULTY: RwLock<Option<Difficulty>> = RwLock::new(None);
 static BLOCKS_FOUND: AtomicUsize = AtomicUsize::new(0);
 static BLOCKS_REJECTED: AtomicUsize = AtomicUsize::new(0);
+static SHARES_ACCEPTED: AtomicUsize = AtomicUsize::new(0);
+static SHARES_REJECTED: AtomicUsize = AtomicUsize::new(0);
 static HASHRATE_COUNTER: AtomicUsize = AtomicUsize::new(0);
 static HASHRATE: AtomicU64 = AtomicU64::new(0);
 static JOB_ELAPSED: RwLock<Option<Instant>> = RwLock::new(None);
+// Daemon we are currently mining on
+static ACTIVE_DAEMON: RwLock<Option<String>> = RwLock::new(None);
 
 lazy_static! {
     static ref HASHRATE_LAST_TIME: Mutex<Instant> = Mutex::new(Instant::now());
+    static ref START_TIME: Instant = Instant::now();
 }
 
 // After how many iterations we update the timestamp of the block to avoid too much CPU usage 
 const UPDATE_EVERY_NONCE: u64 = 10;
+// Auto tune keeps the lowest thread count with at least this ratio of the best hashrate
+const AUTO_TUNE_TOLERANCE: f64 = 0.98;
+// Interval to check if the jobs of the active daemon are stale
+const STALE_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+// Mining threads and settings that can be reloaded from the config file
+struct MinerState {
+    config_path: Option<String>,
+    // command line options are kept to override the config file on reload
+    matches: ArgMatches,
+    job_sender: broadcast::Sender<ThreadNotification<'static>>,
+    block_sender: mpsc::Sender<MinerWork<'static>>,
+    worker: watch::Sender<String>,
+    // number of mining threads running
+    threads: Mutex<u16>,
+    detected_threads: u16,
+    cpu_affinity: bool
+}
+
+impl MinerState {
+    // Start or stop mining threads to have the requested count running
+    // New threads start to mine on the next job received
+    async fn set_threads(&self, count: u16) {
+        let mut threads = self.threads.lock().await;
+        if count > *threads {
+            for id in *threads..count {
+                debug!("Starting thread #{}", id);
+                // spread the threads over all the cores available
+                let core = self.cpu_affinity.then_some(id as usize % self.detected_threads as usize);
+                if let Err(e) = start_thread(id, self.job_sender.subscribe(), self.block_sender.clone(), core) {
+                    error!("Error while creating Mining Thread #{}: {}", id, e);
+                }
+            }
+        } else if count < *threads {
+            if self.job_sender.send(ThreadNotification::Stop(count)).is_err() {
+                debug!("Error while sending stop message to threads");
+            }
+            stats::truncate_threads(count);
+        }
+        *threads = count;
+    }
+
+    // Read again the config file and apply the thread count and worker name
+    async fn reload(&self) -> Result<()> {
+        let path = self.config_path.as_ref().context("Miner has not been started with a config file")?;
+        let config = MinerConfig::from_file_with_matches(path, &self.matches)?;
+
+        let threads = match config.num_threads {
+            Some(value) => value,
+            // keep the thread count selected at startup
+            None if config.auto_tune => *self.threads.lock().await,
+            None => self.detected_threads
+        };
+        info!("Reloading config with {} threads and worker name {}", threads, config.worker);
+        self.set_threads(threads).await;
+
+        // connection is restarted only if the worker name has changed
+        self.worker.send_if_modified(|worker| {
+            if *worker == config.worker {
+                return false
+            }
+            *worker = config.worker;
+            true
+        });
+        Ok(())
+    }
+}
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<()> {
-    let config: MinerConfig = MinerConfig::parse();
+    let matches = MinerConfig::command().get_matches();
+    let mut config = MinerConfig::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if let Some(path) = config.config.take() {
+        config = MinerConfig::from_file_with_matches(&path, &matches)?;
+    }
+    // uptime is computed from the start of the miner
+    lazy_static::initialize(&START_TIME);
     let prompt = Prompt::new(config.log_level, &config.logs_path, &config.filename_log, config.disable_file_logging, config.disable_file_log_date_based, config.disable_log_color, !config.disable_interactive_mode, config.logs_modules)?;
 
     let detected_threads = match thread::available_parallelism() {
@@ -183,7 +412,7 @@ async fn main() -> Result<()> {
         }
     };
 
-    let threads = match config.num_threads {
+    let mut threads = match config.num_threads {
         Some(value) => value,
         None => detected_threads
     };
@@ -200,31 +429,61 @@ async fn main() -> Result<()> {
 
     let address = config.miner_address.ok_or_else(|| Error::msg("No miner address specified"))?;
     info!("Miner address: {}", address);    
-    if threads != detected_threads {
+
+    if config.auto_tune {
+        info!("Auto tune enabled, miner will try up to {} threads", threads);
+        let results = benchmark(threads as usize, config.iterations, Algorithm::V1);
+        if let Some(best) = select_best_threads(&results) {
+            info!("Auto tune selected {} threads", best);
+            threads = best as u16;
+        }
+    } else if threads != detected_threads {
         warn!("Attention, the number of threads used may not be optimal, recommended is: {}", detected_threads);
     }
 
+    if config.cpu_affinity && cfg!(not(target_os = "linux")) {
+        warn!("CPU affinity is only supported on Linux, it will be ignored");
+    }
+
     // broadcast channel to send new jobs / exit command to all threads
     let (sender, _) = broadcast::channel::<ThreadNotification>(threads as usize);
     // mpsc channel to send from threads to the "communication" task.
     let (block_sender, block_receiver) = mpsc::channel::<MinerWork>(threads as usize);
-    for id in 0..threads {
-        debug!("Starting thread #{}", id);
-        if let Err(e) = start_thread(id, sender.subscribe(), block_sender.clone()) {
-            error!("Error while creating Mining Thread #{}: {}", id, e);
-        }
-    }
+    // worker name can be changed by reloading the config
+    let (worker_sender, worker_receiver) = watch::channel(config.worker);
+    let state = Arc::new(MinerState {
+        config_path: config.config,
+        matches,
+        job_sender: sender.clone(),
+        block_sender,
+        worker: worker_sender,
+        threads: Mutex::new(0),
+        detected_threads,
+        cpu_affinity: config.cpu_affinity
+    });
+    state.set_threads(threads).await;
 
     // start communication task
-    let task = spawn_task("communication", communication_task(config.daemon_address, sender.clone(), block_receiver, address, config.worker));
+    info!("Daemons by priority: {}", config.daemon_address.join(", "));
+    let upstreams = UpstreamConfig {
+        addresses: config.daemon_address,
+        protocol: config.protocol,
+        stale_job_timeout: Duration::from_secs(config.stale_job_timeout),
+        failback_interval: Duration::from_secs(config.failback_interval)
+    };
+    let task = spawn_task("communication", communication_task(upstreams, sender.clone(), block_receiver, address, worker_receiver, config.share_difficulty));
+
+    // reload the config file on SIGHUP
+    #[cfg(unix)]
+    let reload_task = state.config_path.is_some().then(|| spawn_task("reload", reload_on_sighup(state.clone())));
 
     // start stats task
     let stats = match config.broadcast_address {
-        Some(addr) => Some(spawn_task("broadcast", broadcast_stats_task(addr))),
+        Some(addr) => Some(spawn_task("stats-api", api::stats_api_task(addr))),
         None => None,
     };
 
-    if let Err(e) = run_prompt(prompt).await {
+    if let Err(e) = run_prompt(prompt, state).await {
         error!("Error on running prompt: {}", e);
     }
 
@@ -236,6 +495,13 @@ async fn main() -> Result<()> {
     // stop the communication task
     task.abort();
 
+    #[cfg(unix)]
+    {
+        if let Some(reload_handle) = reload_task {
+            reload_handle.abort();
+        }
+    }
+
         // stop the stats broadcast task
         if let Some(stats_handle) = stats {
             stats_handle.abort()
@@ -244,35 +510,23 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-// this Tokio task will run indefinitely until the user stops the miner himself.
-// It maintains a http listener and sends stats on connection in json.
-async fn broadcast_stats_task(broadcast_address: String) -> Result<()> {
-    info!("Starting broadcast task");
-    loop {
-        let listener = TcpListener::bind(broadcast_address).await?;
-        loop {
-            let (mut socket, _) = listener.accept().await?;
-            let data = serde_json::json!({
-                "accepted": BLOCKS_FOUND,
-                "rejected": BLOCKS_REJECTED,
-                "hashrate": HASHRATE
-            });
-
-            let status_line = "HTTP/1.1 200 OK\r\n";
-            let content_type = "Content-Type: application/json\r\n";
-            let contents = data.to_string();
-            let length = contents.len();
-
-            let response = format!("{status_line}{content_type}Content-Length: {length}\r\n\r\n{contents}");
-            tokio::io::AsyncWriteExt::write_all(&mut socket, response.as_bytes()).await?;
-            socket.shutdown().await?;
+#[cfg(unix)]
+async fn reload_on_sighup(state: Arc<MinerState>) -> Result<()> {
+    let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+    while hangup.recv().await.is_some() {
+        info!("SIGHUP received, reloading config");
+        if let Err(e) = state.reload().await {
+            error!("Error while reloading config: {:#}", e);
         }
     }
+    Ok(())
 }
 
 // Benchmark the miner with the specified number of threads and iterations
 // It will output the total time, total iterations, time per PoW and hashrate for each number of threads
-fn benchmark(threads: usize, iterations: usize, algorithm: Algorithm) {
+// Returns the hashrate measured for each number of threads
+fn benchmark(threads: usize, iterations: usize, algorithm: Algorithm) -> Vec<(usize, f64)> {
+    let mut results = Vec::with_capacity(threads);
     info!("{0: <10} | {1: <10} | {2: <16} | {3: <13} | {4: <13}", "Threads", "Total Time", "Total Iterations", "Time/PoW (ms)", "Hashrate");
 
     for bench in 1..=threads {
@@ -296,91 +550,172 @@ fn benchmark(threads: usize, iterations: usize, algorithm: Algorithm) {
             handle.join().unwrap();
         }
         let duration = start.elapsed().as_millis();
-        let hashrate = format_hashrate(1000f64 / (duration as f64 / (bench*iterations) as f64));
-        info!("{0: <10} | {1: <10} | {2: <16} | {3: <13} | {4: <13}", bench, duration, bench*iterations, duration/(bench*iterations) as u128, hashrate);
+        let hashrate = 1000f64 / (duration as f64 / (bench*iterations) as f64);
+        info!("{0: <10} | {1: <10} | {2: <16} | {3: <13} | {4: <13}", bench, duration, bench*iterations, duration/(bench*iterations) as u128, format_hashrate(hashrate));
+        results.push((bench, hashrate));
     }
+
+    results
+}
+
+// Select the lowest thread count reaching AUTO_TUNE_TOLERANCE of the best total hashrate
+// The hashrate per core is always the best with one thread, so it can't be used alone:
+// threads that don't increase the total hashrate anymore are only left free
+fn select_best_threads(results: &[(usize, f64)]) -> Option<usize> {
+    let best = results.iter()
+        .map(|(_, hashrate)| *hashrate)
+        .filter(|hashrate| hashrate.is_finite())
+        .fold(0f64, f64::max);
+
+    results.iter()
+        .find(|(_, hashrate)| hashrate.is_finite() && *hashrate >= best * AUTO_TUNE_TOLERANCE)
+        .map(|(threads, _)| *threads)
 }
 
 // this Tokio task will run indefinitely until the user stops the miner himself.
-// It maintains a WebSocket connection with the daemon and notify all threads when it receive a new job.
+// It maintains a connection with a daemon and notify all threads when it receive a new job.
 // Its also the task who have the job to send directly the new block found by one of the threads.
 // This allow mining threads to only focus on mining and receiving jobs through memory channels.
-async fn communication_task(daemon_address: String, job_sender: broadcast::Sender<ThreadNotification<'_>>, mut block_receiver: mpsc::Receiver<MinerWork<'_>>, address: Address, worker: String) {
+// Daemons are used by priority order: we fail over to the next one on disconnection or stale jobs,
+// and fail back to a daemon with a higher priority as soon as it is available again.
+async fn communication_task(upstreams: UpstreamConfig, job_sender: broadcast::Sender<ThreadNotification<'_>>, mut block_receiver: mpsc::Receiver<MinerWork<'_>>, address: Address, mut worker: watch::Receiver<String>, share_difficulty: Option<u64>) {
     info!("Starting communication task");
-    let daemon_address = sanitize_daemon_address(&daemon_address);
-    'main: loop {
-        info!("Trying to connect to {}", daemon_address);
-        let client = match connect_async(format!("{}/getwork/{}/{}", daemon_address, address.to_string(), worker)).await {
-            Ok((client, response)) => {
-                let status = response.status();
-                if status.is_server_error() || status.is_client_error() {
-                    error!("Error while connecting to {}, got an unexpected response: {}", daemon_address, status.as_str());
-                    warn!("Trying to connect to WebSocket again in 10 seconds...");
-                    tokio::time::sleep(Duration::from_secs(10)).await;
-                    continue 'main;
-                }
-                client
-            },
-            Err(e) => {
-                if let TungsteniteError::Http(e) = e {
-                    let body: String = e.into_body()
-                        .map_or(
-                            "Unknown error".to_owned(),
-                            |v| String::from_utf8_lossy(&v).to_string()
-                        );
-                    error!("Error while connecting to {}, got an unexpected response: {}", daemon_address, body);
-                } else {
-                    error!("Error while connecting to {}: {}", daemon_address, e);
-                }
+    let mut index = 0;
+    // consecutive daemons we failed to connect to
+    let mut failures = 0;
+    loop {
+        let daemon_address = &upstreams.addresses[index];
+        let watcher = ConnectionWatcher::new(&upstreams, index);
+        let end = match upstreams.protocol {
+            Protocol::Getwork => getwork_connection(daemon_address, watcher, &job_sender, &mut block_receiver, &address, &mut worker, share_difficulty).await,
+            Protocol::Stratum => stratum_connection(daemon_address, watcher, &job_sender, &mut block_receiver, &address, &mut worker, share_difficulty).await
+        };
 
-                warn!("Trying to connect to WebSocket again in 10 seconds...");
-                tokio::time::sleep(Duration::from_secs(10)).await;
-                continue 'main;
+        if !matches!(end, ConnectionEnd::Failed) {
+            failures = 0;
+            WEBSOCKET_CONNECTED.store(false, Ordering::SeqCst);
+            ACTIVE_DAEMON.write().unwrap().take();
+            if job_sender.send(ThreadNotification::WebSocketClosed).is_err() {
+                error!("Error while sending WebSocketClosed message to threads");
             }
+        }
+
+        let next_index = upstreams.next_index(index, &end);
+        match end {
+            ConnectionEnd::FailBack(_) => {
+                info!("Failing back to daemon {}", upstreams.addresses[next_index]);
+                index = next_index;
+                continue;
+            },
+            ConnectionEnd::Reload => {
+                info!("Reconnecting to daemon {} with the new worker name", daemon_address);
+                continue;
+            },
+            ConnectionEnd::StaleJob => warn!("Topoheight is not advancing on daemon {} since {}s", daemon_address, upstreams.stale_job_timeout.as_secs()),
+            ConnectionEnd::Failed => failures += 1,
+            ConnectionEnd::Disconnected => {}
         };
-        WEBSOCKET_CONNECTED.store(true, Ordering::SeqCst);
-        info!("Connected successfully to {}", daemon_address);
-        let (mut write, mut read) = client.split();
-        loop {
-            select! {
-                Some(message) = read.next() => { // read all messages from daemon
-                    debug!("Received message from daemon: {:?}", message);
-                    match handle_websocket_message(message, &job_sender).await {
-                        Ok(exit) => {
-                            if exit {
-                                debug!("Exiting communication task");
-                                break;
-                            }
-                        },
-                        Err(e) => {
-                            error!("Error while handling message from WebSocket: {}", e);
-                            break;
-                        }
-                    }
-                },
-                Some(work) = block_receiver.recv() => { // send all valid blocks found to the daemon
-                    info!("submitting new block found...");
-                    let submit = serde_json::json!(SubmitMinerWorkParams { miner_work: work.to_hex() }).to_string();
-                    if let Err(e) = write.send(Message::Text(submit)).await {
-                        error!("Error while sending the block found to the daemon: {}", e);
-                        break;
-                    }
-                    debug!("Block found has been sent to daemon");
-                }
+
+        if upstreams.has_failover() {
+            index = next_index;
+            warn!("Failing over to daemon {}", upstreams.addresses[index]);
+            // don't spam the daemons if none is available
+            if failures < upstreams.addresses.len() {
+                continue;
             }
+            failures = 0;
         }
 
-        WEBSOCKET_CONNECTED.store(false, Ordering::SeqCst);
-        if job_sender.send(ThreadNotification::WebSocketClosed).is_err() {
-            error!("Error while sending WebSocketClosed message to threads");
+        warn!("Trying to connect again in 10 seconds...");
+        tokio::time::sleep(Duration::from_secs(10)).await;
+    }
+}
+
+// Mark the daemon as the active one once connected
+fn on_daemon_connected(daemon_address: &str) {
+    WEBSOCKET_CONNECTED.store(true, Ordering::SeqCst);
+    ACTIVE_DAEMON.write().unwrap().replace(daemon_address.to_owned());
+    info!("Connected successfully to {}", daemon_address);
+}
+
+// Mine using the WebSocket getwork server of the daemon
+async fn getwork_connection(daemon_address: &str, mut watcher: ConnectionWatcher, job_sender: &broadcast::Sender<ThreadNotification<'_>>, block_receiver: &mut mpsc::Receiver<MinerWork<'_>>, address: &Address, worker: &mut watch::Receiver<String>, share_difficulty: Option<u64>) -> ConnectionEnd {
+    let daemon_address = sanitize_daemon_address(daemon_address);
+    let worker_name = worker.borrow_and_update().clone();
+    let mut url = format!("{}/getwork/{}/{}", daemon_address, address.to_string(), worker_name);
+    if let Some(share_difficulty) = share_difficulty {
+        url.push_str(&format!("?share_difficulty={}", share_difficulty));
+    }
+
+    info!("Trying to connect to {}", daemon_address);
+    let client = match connect_async(&url).await {
+        Ok((client, response)) => {
+            let status = response.status();
+            if status.is_server_error() || status.is_client_error() {
+                error!("Error while connecting to {}, got an unexpected response: {}", daemon_address, status.as_str());
+                return ConnectionEnd::Failed;
+            }
+            client
+        },
+        Err(e) => {
+            if let TungsteniteError::Http(e) = e {
+                let body: String = e.into_body()
+                    .map_or(
+                        "Unknown error".to_owned(),
+                        |v| String::from_utf8_lossy(&v).to_string()
+                    );
+                error!("Error while connecting to {}, got an unexpected response: {}", daemon_address, body);
+            } else {
+                error!("Error while connecting to {}: {}", daemon_address, e);
+            }
+            return ConnectionEnd::Failed;
         }
+    };
+    on_daemon_connected(&daemon_address);
 
-        warn!("Trying to connect to WebSocket again in 10 seconds...");
-        tokio::time::sleep(Duration::from_secs(10)).await;
+    let (mut write, mut read) = client.split();
+    let mut stale_check = tokio::time::interval(STALE_CHECK_INTERVAL);
+    // daemon answers each submitted work in order, keep the thread that found it
+    let mut pending_works = VecDeque::new();
+    loop {
+        select! {
+            Some(message) = read.next() => { // read all messages from daemon
+                debug!("Received message from daemon: {:?}", message);
+                match handle_websocket_message(message, job_sender, &mut pending_works, share_difficulty.is_some()).await {
+                    Ok(exit) => {
+                        if exit {
+                            debug!("Exiting communication task");
+                            break ConnectionEnd::Disconnected;
+                        }
+                    },
+                    Err(e) => {
+                        error!("Error while handling message from WebSocket: {}", e);
+                        break ConnectionEnd::Disconnected;
+                    }
+                }
+            },
+            Some(mut work) = block_receiver.recv() => { // send all valid blocks found to the daemon
+                info!("submitting new block found...");
+                let submit = serde_json::json!(SubmitMinerWorkParams { miner_work: work.to_hex() }).to_string();
+                if let Err(e) = write.send(Message::Text(submit)).await {
+                    error!("Error while sending the block found to the daemon: {}", e);
+                    break ConnectionEnd::Disconnected;
+                }
+                pending_works.push_back(stats::get_thread_id(&mut work));
+                debug!("Block found has been sent to daemon");
+            },
+            _ = stale_check.tick() => {
+                if watcher.is_stale() {
+                    break ConnectionEnd::StaleJob;
+                }
+            },
+            index = watcher.wait_failback() => break ConnectionEnd::FailBack(index),
+            Ok(()) = worker.changed() => break ConnectionEnd::Reload
+        }
     }
 }
 
-async fn handle_websocket_message(message: Result<Message, TungsteniteError>, job_sender: &broadcast::Sender<ThreadNotification<'_>>) -> Result<bool, Error> {
+async fn handle_websocket_message(message: Result<Message, TungsteniteError>, job_sender: &broadcast::Sender<ThreadNotification<'_>>, pending_works: &mut VecDeque<u16>, share: bool) -> Result<bool, Error> {
     match message? {
         Message::Text(text) => {
             debug!("new message from daemon: {}", text);
@@ -389,19 +724,37 @@ async fn handle_websocket_message(message: Result<Message, TungsteniteError>, jo
                     info!("New job received: difficulty {} at height {}", format_difficulty(job.difficulty), job.height);
                     let block = MinerWork::from_hex(job.miner_work).context("Error while decoding new job received from daemon")?;
                     CURRENT_TOPO_HEIGHT.store(job.topoheight, Ordering::SeqCst);
+                    CURRENT_HEIGHT.store(job.height, Ordering::SeqCst);
+                    CURRENT_DIFFICULTY.write().unwrap().replace(job.difficulty);
                     JOB_ELAPSED.write().unwrap().replace(Instant::now());
 
-                    if let Err(e) = job_sender.send(ThreadNotification::NewJob(job.algorithm, block, job.difficulty, job.height)) {
+                    if let Err(e) = job_sender.send(ThreadNotification::NewJob(job.algorithm, block, job.difficulty, job.height, share)) {
                         error!("Error while sending new job to threads: {}", e);
                     }
                 },
                 SocketMessage::BlockAccepted => {
                     BLOCKS_FOUND.fetch_add(1, Ordering::SeqCst);
+                    if let Some(thread_id) = pending_works.pop_front() {
+                        stats::on_work_result(thread_id, true);
+                    }
                     info!("Block submitted has been accepted by network !");
                 },
                 SocketMessage::BlockRejected(err) => {
                     BLOCKS_REJECTED.fetch_add(1, Ordering::SeqCst);
+                    if let Some(thread_id) = pending_works.pop_front() {
+                        stats::on_work_result(thread_id, false);
+                    }
                     error!("Block submitted has been rejected by network: {}", err);
+                },
+                SocketMessage::ShareAccepted => {
+                    SHARES_ACCEPTED.fetch_add(1, Ordering::SeqCst);
+                    pending_works.pop_front();
+                    debug!("Share submitted has been accepted by daemon");
+                },
+                SocketMessage::ShareRejected(err) => {
+                    SHARES_REJECTED.fetch_add(1, Ordering::SeqCst);
+                    pending_works.pop_front();
+                    warn!("Share submitted has been rejected by daemon: {}", err);
                 }
             }
         },
@@ -423,98 +776,348 @@ async fn handle_websocket_message(message: Result<Message, TungsteniteError>, jo
     Ok(false)
 }
 
-fn start_thread(id: u16, mut job_receiver: broadcast::Receiver<ThreadNotification<'static>>, block_sender: mpsc::Sender<MinerWork<'static>>) -> Result<(), Error> {
+// Mine using the Stratum server of the daemon over TCP.
+// Jobs are notified with the extra nonce prefix of our connection already set.
+async fn stratum_connection(daemon_address: &str, mut watcher: ConnectionWatcher, job_sender: &broadcast::Sender<ThreadNotification<'_>>, block_receiver: &mut mpsc::Receiver<MinerWork<'_>>, address: &Address, worker: &mut watch::Receiver<String>, share_difficulty: Option<u64>) -> ConnectionEnd {
+    let daemon_address = stratum_address(daemon_address);
+    let worker_name = worker.borrow_and_update().clone();
+    let login = format!("{}.{}", address, worker_name);
+    // share difficulty is requested using the password
+    let password = share_difficulty.map_or_else(|| "x".to_owned(), |difficulty| format!("d={}", difficulty));
+
+    info!("Trying to connect to {}", daemon_address);
+    let stream = match TcpStream::connect(daemon_address).await {
+        Ok(stream) => stream,
+        Err(e) => {
+            error!("Error while connecting to {}: {}", daemon_address, e);
+            return ConnectionEnd::Failed;
+        }
+    };
+
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    let mut difficulty = None;
+    // ids 1 and 2 are used by subscribe and authorize
+    let mut next_id = 3;
+    // thread that found each submitted work by request id
+    let mut pending_works = HashMap::new();
+
+    let res = async {
+        send_stratum_request(&mut writer, StratumRequest::new(json!(1), METHOD_SUBSCRIBE, json!([format!("vyridium-miner/{}", VERSION)]))).await?;
+        send_stratum_request(&mut writer, StratumRequest::new(json!(2), METHOD_AUTHORIZE, json!([login, password]))).await
+    }.await;
+
+    if let Err(e) = res {
+        error!("Error while subscribing to {}: {}", daemon_address, e);
+        return ConnectionEnd::Failed;
+    }
+    on_daemon_connected(daemon_address);
+
+    let mut stale_check = tokio::time::interval(STALE_CHECK_INTERVAL);
+    loop {
+        select! {
+            line = lines.next_line() => match line {
+                Ok(Some(line)) => match handle_stratum_message(&line, job_sender, &mut difficulty, &mut pending_works, share_difficulty.is_some()) {
+                    Ok(exit) => {
+                        if exit {
+                            debug!("Exiting Stratum communication task");
+                            break ConnectionEnd::Disconnected;
+                        }
+                    },
+                    Err(e) => {
+                        error!("Error while handling message from Stratum server: {}", e);
+                        break ConnectionEnd::Disconnected;
+                    }
+                },
+                Ok(None) => {
+                    warn!("Stratum server has closed the connection with us");
+                    break ConnectionEnd::Disconnected;
+                },
+                Err(e) => {
+                    error!("Error while reading from Stratum server: {}", e);
+                    break ConnectionEnd::Disconnected;
+                }
+            },
+            Some(mut work) = block_receiver.recv() => { // send all valid blocks found to the daemon
+                info!("submitting new block found...");
+                let params = SubmitParams(worker_name.clone(), work.get_header_work_hash().to_hex(), work.to_hex());
+                if let Err(e) = send_stratum_request(&mut writer, StratumRequest::new(json!(next_id), METHOD_SUBMIT, json!(params))).await {
+                    error!("Error while sending the block found to the daemon: {}", e);
+                    break ConnectionEnd::Disconnected;
+                }
+                pending_works.insert(next_id, stats::get_thread_id(&mut work));
+                next_id += 1;
+                debug!("Block found has been sent to daemon");
+            },
+            _ = stale_check.tick() => {
+                if watcher.is_stale() {
+                    break ConnectionEnd::StaleJob;
+                }
+            },
+            index = watcher.wait_failback() => break ConnectionEnd::FailBack(index),
+            Ok(()) = worker.changed() => break ConnectionEnd::Reload
+        }
+    }
+}
+
+async fn send_stratum_request(writer: &mut OwnedWriteHalf, request: StratumRequest) -> Result<(), Error> {
+    let mut bytes = serde_json::to_vec(&request)?;
+    bytes.push(b'\n');
+    writer.write_all(&bytes).await?;
+    Ok(())
+}
+
+// Returns true if the connection must be closed
+fn handle_stratum_message(line: &str, job_sender: &broadcast::Sender<ThreadNotification<'_>>, difficulty: &mut Option<Difficulty>, pending_works: &mut HashMap<u64, u16>, share: bool) -> Result<bool, Error> {
+    debug!("new message from Stratum server: {}", line);
+    match serde_json::from_str::<StratumMessage>(line)? {
+        StratumMessage::Request(request) => match request.method.as_str() {
+            METHOD_SET_DIFFICULTY => {
+                let SetDifficultyParams(value) = serde_json::from_value(request.params)?;
+                *difficulty = Some(value);
+            },
+            METHOD_NOTIFY => {
+                let NotifyParams(_, algorithm, miner_work, height, topoheight, _) = serde_json::from_value(request.params)?;
+                let Some(difficulty) = *difficulty else {
+                    warn!("Job received before its difficulty, skipping it");
+                    return Ok(false)
+                };
+
+                info!("New job received: difficulty {} at height {}", format_difficulty(difficulty), height);
+                let block = MinerWork::from_hex(miner_work).context("Error while decoding new job received from daemon")?;
+                CURRENT_TOPO_HEIGHT.store(topoheight, Ordering::SeqCst);
+                CURRENT_HEIGHT.store(height, Ordering::SeqCst);
+                CURRENT_DIFFICULTY.write().unwrap().replace(difficulty);
+                JOB_ELAPSED.write().unwrap().replace(Instant::now());
+
+                if let Err(e) = job_sender.send(ThreadNotification::NewJob(algorithm, block, difficulty, height, share)) {
+                    error!("Error while sending new job to threads: {}", e);
+                }
+            },
+            method => warn!("Unexpected method from Stratum server: {}", method)
+        },
+        StratumMessage::Response(response) => match response.id.as_u64() {
+            Some(1) => match response.error {
+                Some(error) => {
+                    error!("Subscription has been rejected by Stratum server: {}", error.1);
+                    return Ok(true)
+                },
+                None => debug!("Subscribed to Stratum server: {}", response.result)
+            },
+            Some(2) => match response.error {
+                Some(error) => {
+                    error!("Authorization has been rejected by Stratum server: {}", error.1);
+                    return Ok(true)
+                },
+                None => info!("Worker has been authorized by Stratum server")
+            },
+            id => match response.error {
+                Some(error) if error.0 == ERROR_BLOCK_REJECTED => {
+                    BLOCKS_REJECTED.fetch_add(1, Ordering::SeqCst);
+                    if let Some(thread_id) = id.and_then(|id| pending_works.remove(&id)) {
+                        stats::on_work_result(thread_id, false);
+                    }
+                    error!("Block submitted has been rejected by network: {}", error.1);
+                },
+                Some(error) => {
+                    SHARES_REJECTED.fetch_add(1, Ordering::SeqCst);
+                    if let Some(id) = id {
+                        pending_works.remove(&id);
+                    }
+                    warn!("Share submitted has been rejected by Stratum server: {}", error.1);
+                },
+                None if is_block_submit_result(&response.result) => {
+                    BLOCKS_FOUND.fetch_add(1, Ordering::SeqCst);
+                    if let Some(thread_id) = id.and_then(|id| pending_works.remove(&id)) {
+                        stats::on_work_result(thread_id, true);
+                    }
+                    info!("Block {} submitted has been accepted by network !", response.result);
+                },
+                None if response.result == Value::Bool(true) => {
+                    SHARES_ACCEPTED.fetch_add(1, Ordering::SeqCst);
+                    if let Some(id) = id {
+                        pending_works.remove(&id);
+                    }
+                    debug!("Share submitted has been accepted by Stratum server");
+                },
+                None => warn!("Unexpected response from Stratum server: {}", response.result)
+            }
+        }
+    };
+
+    Ok(false)
+}
+
+fn start_thread(id: u16, mut job_receiver: broadcast::Receiver<ThreadNotification<'static>>, block_sender: mpsc::Sender<MinerWork<'static>>, core: Option<usize>) -> Result<(), Error> {
+    let stats = stats::register_thread(id);
     let builder = thread::Builder::new().name(format!("Mining Thread #{}", id));
     builder.spawn(move || {
         let mut worker = Worker::new();
         let mut hash: Hash;
 
-        info!("Mining Thread #{}: started", id);
-        'main: loop {
-            let message = match job_receiver.blocking_recv() {
-                Ok(message) => message,
-                Err(e) => {
-                    error!("Error on thread #{} while waiting on new job: {}", id, e);
-                    // Channel is maybe lagging, try to empty it
-                    while job_receiver.len() > 1 {
-                        let _ = job_receiver.blocking_recv();
-                    }
-                    thread::sleep(Duration::from_millis(100));
-                    continue;
-                }
+        if let Some(core) = core {
+            match stats::pin_current_thread(core) {
+                Ok(()) => {
+                    debug!("Mining Thread #{}: pinned to core #{}", id, core);
+                    stats.set_core(core);
+                },
+                Err(e) => warn!("Mining Thread #{}: couldn't be pinned to core #{}: {}", id, core, e)
             };
+        }
 
-            match message {
-                ThreadNotification::WebSocketClosed => {
-                    // wait until we receive a new job, check every 100ms
-                    while job_receiver.is_empty() {
+        info!("Mining Thread #{}: started", id);
+        // difficulty target, initial timestamp, height and share mode of the job being mined
+        let mut current_job = None;
+        'main: loop {
+            // keep mining the current job until a notification is pending
+            if current_job.is_none() || !job_receiver.is_empty() {
+                let message = match job_receiver.blocking_recv() {
+                    Ok(message) => message,
+                    Err(e) => {
+                        error!("Error on thread #{} while waiting on new job: {}", id, e);
+                        // Channel is maybe lagging, try to empty it
+                        while job_receiver.len() > 1 {
+                            let _ = job_receiver.blocking_recv();
+                        }
                         thread::sleep(Duration::from_millis(100));
+                        continue;
                     }
-                }
-                ThreadNotification::Exit => {
-                    info!("Exiting Mining Thread #{}...", id);
-                    break 'main;
-                },
-                ThreadNotification::NewJob(algorithm, mut new_job, expected_difficulty, height) => {
-                    debug!("Mining Thread #{} received a new job", id);
-                    // set thread id in extra nonce for more work spread between threads
-                    // u16 support up to 65535 threads
-                    new_job.set_thread_id_u16(id);
-                    let initial_timestamp = new_job.get_timestamp();
-                    worker.set_work(new_job, algorithm).unwrap();
-
-                    let difficulty_target = match compute_difficulty_target(&expected_difficulty) {
-                        Ok(value) => value,
-                        Err(e) => {
-                            error!("Mining Thread #{}: error on difficulty target computation: {}", id, e);
-                            continue 'main;
+                };
+
+                match message {
+                    ThreadNotification::WebSocketClosed => {
+                        current_job = None;
+                        // wait until we receive a new job, check every 100ms
+                        while job_receiver.is_empty() {
+                            thread::sleep(Duration::from_millis(100));
                         }
-                    };
-
-                    // Solve block
-                    hash = worker.get_pow_hash().unwrap();
-                    let mut tries = 0;
-                    while !check_difficulty_against_target(&hash, &difficulty_target) {
-                        worker.increase_nonce().unwrap();
-                        // check if we have a new job pending
-                        // Only update every N iterations to avoid too much CPU usage
-                        if tries % UPDATE_EVERY_NONCE == 0 {
-                            if !job_receiver.is_empty() {
-                                continue 'main;
-                            }
-                            if let Ok(instant) = JOB_ELAPSED.read() {
-                                if let Some(instant) = instant.as_ref() {
-                                    worker.set_timestamp(initial_timestamp + instant.elapsed().as_millis() as u64).unwrap();
-                                }
-                            }
-                            HASHRATE_COUNTER.fetch_add(UPDATE_EVERY_NONCE as usize, Ordering::SeqCst);
+                        continue 'main;
+                    }
+                    ThreadNotification::Stop(count) => {
+                        if id >= count {
+                            info!("Stopping Mining Thread #{}...", id);
+                            break 'main;
                         }
+                        // not for us, resume the current job
+                        continue 'main;
+                    },
+                    ThreadNotification::Exit => {
+                        info!("Exiting Mining Thread #{}...", id);
+                        break 'main;
+                    },
+                    ThreadNotification::NewJob(algorithm, mut new_job, expected_difficulty, height, share) => {
+                        debug!("Mining Thread #{} received a new job", id);
+                        // set thread id in extra nonce for more work spread between threads
+                        // u16 support up to 65535 threads
+                        new_job.set_thread_id_u16(id);
+                        let initial_timestamp = new_job.get_timestamp();
+                        worker.set_work(new_job, algorithm).unwrap();
 
-                        hash = worker.get_pow_hash().unwrap();
-                        tries += 1;
+                        current_job = match compute_difficulty_target(&expected_difficulty) {
+                            Ok(difficulty_target) => Some((difficulty_target, initial_timestamp, height, share)),
+                            Err(e) => {
+                                error!("Mining Thread #{}: error on difficulty target computation: {}", id, e);
+                                None
+                            }
+                        };
                     }
+                };
+            }
 
-                    // compute the reference hash for easier finding of the block
-                    let block_hash = worker.get_block_hash().unwrap();
-                    info!("Thread #{}: block {} found at height {} with difficulty {}", id, block_hash, height, format_difficulty(difficulty_from_hash(&hash)));
+            let (difficulty_target, initial_timestamp, height, share) = match current_job.as_ref() {
+                Some(job) => *job,
+                None => continue 'main
+            };
 
-                    let job = worker.take_work().unwrap();
-                    if let Err(_) = block_sender.blocking_send(job) {
-                        error!("Mining Thread #{}: error while sending block found with hash {}", id, block_hash);
+            // Solve block
+            hash = worker.get_pow_hash().unwrap();
+            let mut tries = 0;
+            while !check_difficulty_against_target(&hash, &difficulty_target) {
+                worker.increase_nonce().unwrap();
+                // check if we have a new job pending
+                // Only update every N iterations to avoid too much CPU usage
+                if tries % UPDATE_EVERY_NONCE == 0 {
+                    if !job_receiver.is_empty() {
                         continue 'main;
                     }
-                    debug!("Job sent to communication task");
+                    if let Ok(instant) = JOB_ELAPSED.read() {
+                        if let Some(instant) = instant.as_ref() {
+                            worker.set_timestamp(initial_timestamp + instant.elapsed().as_millis() as u64).unwrap();
+                        }
+                    }
+                    HASHRATE_COUNTER.fetch_add(UPDATE_EVERY_NONCE as usize, Ordering::SeqCst);
+                    stats.on_hashes(UPDATE_EVERY_NONCE);
                 }
-            };
+
+                hash = worker.get_pow_hash().unwrap();
+                tries += 1;
+            }
+
+            // compute the reference hash for easier finding of the block
+            let block_hash = worker.get_block_hash().unwrap();
+            if share {
+                debug!("Thread #{}: share {} found at height {} with difficulty {}", id, block_hash, height, format_difficulty(difficulty_from_hash(&hash)));
+            } else {
+                info!("Thread #{}: block {} found at height {} with difficulty {}", id, block_hash, height, format_difficulty(difficulty_from_hash(&hash)));
+                // the work is taken below, wait for the next job
+                current_job = None;
+            }
+
+            let job = take_found_work(&mut worker, share).unwrap();
+            if let Err(_) = block_sender.blocking_send(job) {
+                error!("Mining Thread #{}: error while sending block found with hash {}", id, block_hash);
+                continue 'main;
+            }
+            debug!("Job sent to communication task");
         }
         info!("Mining Thread #{}: stopped", id);
     })?;
     Ok(())
 }
 
-async fn run_prompt(prompt: ShareablePrompt) -> Result<()> {
-    let command_manager = CommandManager::new(prompt.clone());
+// Take the work found to submit it
+// In share mode, no new job is sent after an accepted share:
+// the same job keeps being mined from the next nonce
+fn take_found_work<'a>(worker: &mut Worker<'a>, share: bool) -> Option<MinerWork<'a>> {
+    if !share {
+        return worker.take_work()
+    }
+
+    let work = worker.get_work()?.clone();
+    worker.increase_nonce().ok()?;
+    Some(work)
+}
+
+async fn list_threads(manager: &CommandManager, _: ArgumentManager) -> Result<(), CommandError> {
+    let threads = stats::get_threads_stats();
+    manager.message(format!("Mining threads ({}):", threads.len()));
+    for thread in threads {
+        let core = thread.core.map_or_else(|| "none".to_owned(), |core| format!("#{}", core));
+        manager.message(format!("- Thread #{} | Hashrate: {} | Accepted: {} | Rejected: {} | Core: {}", thread.id, format_hashrate(thread.hashrate as f64), thread.accepted, thread.rejected, core));
+    }
+    Ok(())
+}
+
+async fn reload(manager: &CommandManager, _: ArgumentManager) -> Result<(), CommandError> {
+    let state = {
+        let context = manager.get_context().lock()?;
+        let state: &Arc<MinerState> = context.get()?;
+        state.clone()
+    };
+
+    state.reload().await.context("Error while reloading config")?;
+    manager.message("Config has been reloaded");
+    Ok(())
+}
+
+async fn run_prompt(prompt: ShareablePrompt, state: Arc<MinerState>) -> Result<()> {
+    let mut context = CommandContext::default();
+    context.store(state);
+
+    let command_manager = CommandManager::with_context(context, prompt.clone());
     command_manager.register_default_commands()?;
+    command_manager.add_command(Command::new("list_threads", "List all mining threads with their stats", CommandHandler::Async(async_handler!(list_threads))))?;
+    command_manager.add_command(Command::new("reload", "Reload the config file to apply the thread count and worker name", CommandHandler::Async(async_handler!(reload))))?;
 
     let closure = |_: &_, _: _| async {
         let topoheight_str = format!(
@@ -532,8 +1135,17 @@ async fn run_prompt(prompt: ShareablePrompt) -> Result<()> {
             prompt.colorize_str(Color::Yellow, "Rejected"),
             prompt.colorize_string(Color::Green, &format!("{}", BLOCKS_REJECTED.load(Ordering::SeqCst))),
         );
+        let shares = format!(
+            "{}: {}/{}",
+            prompt.colorize_str(Color::Yellow, "Shares"),
+            prompt.colorize_string(Color::Green, &format!("{}", SHARES_ACCEPTED.load(Ordering::SeqCst))),
+            prompt.colorize_string(Color::Red, &format!("{}", SHARES_REJECTED.load(Ordering::SeqCst))),
+        );
         let status = if WEBSOCKET_CONNECTED.load(Ordering::SeqCst) {
-            prompt.colorize_str(Color::Green, "Online")
+            match ACTIVE_DAEMON.read().unwrap().as_ref() {
+                Some(daemon) => prompt.colorize_string(Color::Green, &format!("Online ({})", daemon)),
+                None => prompt.colorize_str(Color::Green, "Online")
+            }
         } else {
             prompt.colorize_str(Color::Red, "Offline")
         };
@@ -541,22 +1153,37 @@ async fn run_prompt(prompt: ShareablePrompt) -> Result<()> {
             let mut last_time = HASHRATE_LAST_TIME.lock().await;
             let counter = HASHRATE_COUNTER.swap(0, Ordering::SeqCst);
 
-            let hashrate = 1000f64 / (last_time.elapsed().as_millis() as f64 / counter as f64);
+            let elapsed = last_time.elapsed().as_millis();
+            let hashrate = 1000f64 / (elapsed as f64 / counter as f64);
+            stats::update_hashrates(elapsed);
             *last_time = Instant::now();
 
             HASHRATE.store(hashrate as u64, Ordering::SeqCst);
 
             prompt.colorize_string(Color::Green, &format!("{}", format_hashrate(hashrate)))
         };
+        // hashrate and accepted/rejected counters of each thread
+        let threads = stats::get_threads_stats()
+            .iter()
+            .map(|thread| format!("#{} {} {}/{}", thread.id, format_hashrate(thread.hashrate as f64), thread.accepted, thread.rejected))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let threads = format!(
+            "{}: {}",
+            prompt.colorize_str(Color::Yellow, "Threads"),
+            prompt.colorize_string(Color::Green, &threads),
+        );
 
         Ok(
             format!(
-                "{} | {} | {} | {} | {} | {} {} ",
+                "{} | {} | {} | {} | {} | {} | {} | {} {} ",
                 prompt.colorize_str(Color::Blue, "XELIS Miner"),
                 topoheight_str,
                 blocks_found,
                 blocks_rejected,
+                shares,
                 hashrate,
+                threads,
                 status,
                 prompt.colorize_str(Color::BrightBlack, ">>")
             )
@@ -566,3 +1193,83 @@ async fn run_prompt(prompt: ShareablePrompt) -> Result<()> {
     prompt.start(Duration::from_millis(1000), Box::new(async_handler!(closure)), Some(&command_manager)).await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_best_threads() {
+        // 4 threads is almost the same as 6 threads
+        let results = [(1, 100.0), (2, 195.0), (4, 380.0), (6, 385.0), (8, 300.0)];
+        assert_eq!(select_best_threads(&results), Some(4));
+        assert_eq!(select_best_threads(&[]), None);
+    }
+
+    #[test]
+    fn test_several_shares_on_one_job() {
+        let mut worker = Worker::new();
+        worker.set_work(MinerWork::new(Hash::zero(), 0), Algorithm::V1).unwrap();
+        // every hash is a valid share at the lowest difficulty
+        let target = compute_difficulty_target(&Difficulty::from_u64(1)).unwrap();
+
+        let mut nonces = Vec::new();
+        for _ in 0..3 {
+            let hash = worker.get_pow_hash().unwrap();
+            assert!(check_difficulty_against_target(&hash, &target));
+            let work = take_found_work(&mut worker, true).unwrap();
+            assert_eq!(work.get_header_work_hash(), &Hash::zero());
+            nonces.push(work.nonce());
+        }
+
+        // the same job is still mined from the next nonce
+        assert_eq!(nonces, vec![0, 1, 2]);
+        assert_eq!(worker.get_work().unwrap().nonce(), 3);
+
+        // a block ends the job until the next one is received
+        assert_eq!(take_found_work(&mut worker, false).unwrap().nonce(), 3);
+        assert!(worker.get_work().is_none());
+    }
+
+    #[test]
+    fn test_config_file() {
+        let config: MinerConfig = toml::from_str(r#"
+            daemon_address = ["127.0.0.1:8080", "127.0.0.1:8081"]
+            protocol = "stratum"
+            worker = "rig-1"
+            num_threads = 4
+            logs_modules = ["vyridium_miner=debug"]
+        "#).unwrap();
+
+        assert_eq!(config.daemon_address.len(), 2);
+        assert!(matches!(config.protocol, Protocol::Stratum));
+        assert_eq!(config.worker, "rig-1");
+        assert_eq!(config.num_threads, Some(4));
+        assert_eq!(config.logs_modules[0].level, LogLevel::Debug);
+        // missing fields use the command line defaults
+        assert_eq!(config.stale_job_timeout, 300);
+        assert_eq!(config.log_level, LogLevel::Info);
+
+        assert!(toml::from_str::<MinerConfig>("unknown_field = 1").is_err());
+    }
+
+    #[test]
+    fn test_config_merge() {
+        let file: MinerConfig = toml::from_str(r#"
+            protocol = "stratum"
+            worker = "rig-1"
+            num_threads = 4
+        "#).unwrap();
+
+        let matches = MinerConfig::command().try_get_matches_from([env!("CARGO_PKG_NAME"), "--config", "miner.toml", "--worker", "rig-2", "--cpu-affinity"]).unwrap();
+        let cli = MinerConfig::from_arg_matches(&matches).unwrap();
+        let config = file.merge(cli, &matches);
+
+        // explicitly set on the command line
+        assert_eq!(config.worker, "rig-2");
+        assert!(config.cpu_affinity);
+        // defaults of the command line don't override the file
+        assert_eq!(config.num_threads, Some(4));
+        assert!(matches!(config.protocol, Protocol::Stratum));
+    }
+}
@@ -0,0 +1,139 @@
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Arc,
+    Mutex,
+    RwLock
+};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use vyridium_common::block::{MinerWork, EXTRA_NONCE_SIZE};
+
+lazy_static! {
+    // Stats of each mining thread, indexed by thread id
+    static ref THREADS_STATS: RwLock<Vec<Arc<ThreadStats>>> = RwLock::new(Vec::new());
+}
+
+// Statistics of a mining thread
+#[derive(Default)]
+pub struct ThreadStats {
+    // hashes computed since the last hashrate update
+    hashes: AtomicU64,
+    hashrate: AtomicU64,
+    accepted: AtomicUsize,
+    rejected: AtomicUsize,
+    // CPU core the thread is pinned to
+    core: Mutex<Option<usize>>
+}
+
+#[derive(Serialize)]
+pub struct ThreadStatsEntry {
+    pub id: u16,
+    pub hashrate: u64,
+    pub accepted: usize,
+    pub rejected: usize,
+    pub core: Option<usize>
+}
+
+impl ThreadStats {
+    pub fn on_hashes(&self, count: u64) {
+        self.hashes.fetch_add(count, Ordering::SeqCst);
+    }
+
+    pub fn set_core(&self, core: usize) {
+        *self.core.lock().unwrap() = Some(core);
+    }
+}
+
+// Create the stats of a new thread
+// Previous stats are replaced if the thread id was already used
+pub fn register_thread(id: u16) -> Arc<ThreadStats> {
+    let stats = Arc::new(ThreadStats::default());
+    let mut threads = THREADS_STATS.write().unwrap();
+    let index = id as usize;
+    if threads.len() <= index {
+        threads.resize_with(index + 1, Default::default);
+    }
+    threads[index] = stats.clone();
+    stats
+}
+
+// Remove the stats of the threads that have been stopped
+pub fn truncate_threads(count: u16) {
+    THREADS_STATS.write().unwrap().truncate(count as usize);
+}
+
+// Each thread write its id in the extra nonce of the work
+pub fn get_thread_id(work: &mut MinerWork) -> u16 {
+    let extra_nonce = work.get_extra_nonce();
+    u16::from_be_bytes([extra_nonce[EXTRA_NONCE_SIZE - 2], extra_nonce[EXTRA_NONCE_SIZE - 1]])
+}
+
+// Update the counters of the thread that found the submitted work
+pub fn on_work_result(thread_id: u16, accepted: bool) {
+    let threads = THREADS_STATS.read().unwrap();
+    if let Some(stats) = threads.get(thread_id as usize) {
+        if accepted {
+            stats.accepted.fetch_add(1, Ordering::SeqCst);
+        } else {
+            stats.rejected.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
+
+// Compute the hashrate of each thread based on the hashes since the last update
+pub fn update_hashrates(elapsed_millis: u128) {
+    let threads = THREADS_STATS.read().unwrap();
+    for stats in threads.iter() {
+        let hashes = stats.hashes.swap(0, Ordering::SeqCst);
+        let hashrate = 1000f64 / (elapsed_millis as f64 / hashes as f64);
+        stats.hashrate.store(hashrate as u64, Ordering::SeqCst);
+    }
+}
+
+pub fn get_threads_stats() -> Vec<ThreadStatsEntry> {
+    let threads = THREADS_STATS.read().unwrap();
+    threads.iter()
+        .enumerate()
+        .map(|(id, stats)| ThreadStatsEntry {
+            id: id as u16,
+            hashrate: stats.hashrate.load(Ordering::SeqCst),
+            accepted: stats.accepted.load(Ordering::SeqCst),
+            rejected: stats.rejected.load(Ordering::SeqCst),
+            core: *stats.core.lock().unwrap()
+        })
+        .collect()
+}
+
+// Pin the current thread to a CPU core
+#[cfg(target_os = "linux")]
+pub fn pin_current_thread(core: usize) -> Result<(), std::io::Error> {
+    // SAFETY: cpu_set_t is a plain bitmask, zeroed is an empty set
+    let res = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(core, &mut set);
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+
+    if res != 0 {
+        return Err(std::io::Error::last_os_error())
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn pin_current_thread(_: usize) -> Result<(), std::io::Error> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "CPU affinity is only supported on Linux"))
+}
+
+#[cfg(test)]
+mod tests {
+    use vyridium_common::crypto::Hash;
+    use super::*;
+
+    #[test]
+    fn test_thread_id_from_work() {
+        let mut work = MinerWork::new(Hash::zero(), 0);
+        work.set_thread_id_u16(300);
+        assert_eq!(get_thread_id(&mut work), 300);
+    }
+}
@@ -0,0 +1,239 @@
+use std::{
+    future::pending,
+    sync::atomic::Ordering,
+    time::Duration
+};
+use futures_util::{SinkExt, StreamExt};
+use log::{debug, info};
+use serde_json::{json, Value};
+use tokio::{
+    net::TcpStream,
+    task::JoinHandle,
+    time::{sleep, timeout, Instant}
+};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use vyridium_common::{
+    tokio::spawn_task,
+    utils::sanitize_daemon_address
+};
+use crate::{Protocol, CURRENT_TOPO_HEIGHT};
+
+// Maximum time to wait for a daemon to answer a health check
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Daemons to mine on, by priority order
+pub struct UpstreamConfig {
+    pub addresses: Vec<String>,
+    pub protocol: Protocol,
+    // switch to the next daemon if the topoheight is not advancing during this time
+    pub stale_job_timeout: Duration,
+    // interval to check if a daemon with a higher priority is available again
+    pub failback_interval: Duration
+}
+
+impl UpstreamConfig {
+    pub fn has_failover(&self) -> bool {
+        self.addresses.len() > 1
+    }
+
+    // Index of the daemon to use once the connection with the daemon at index has ended
+    pub fn next_index(&self, index: usize, end: &ConnectionEnd) -> usize {
+        match end {
+            ConnectionEnd::FailBack(primary) => *primary,
+            ConnectionEnd::Reload => index,
+            // next daemon by priority order, back to the primary after the last one
+            _ if self.has_failover() => (index + 1) % self.addresses.len(),
+            _ => index
+        }
+    }
+}
+
+// Why the connection with a daemon has ended
+pub enum ConnectionEnd {
+    // We couldn't connect to the daemon
+    Failed,
+    // Connection has been closed after being established
+    Disconnected,
+    // Topoheight is not advancing anymore
+    StaleJob,
+    // A daemon with a higher priority is available again
+    FailBack(usize),
+    // Worker name has changed, reconnect to the same daemon
+    Reload
+}
+
+// Watch the connection with the active daemon
+// It detects stale jobs and checks in background
+// if a daemon with a higher priority is available again
+pub struct ConnectionWatcher {
+    stale_job_timeout: Option<Duration>,
+    last_topoheight: u64,
+    last_progress: Instant,
+    failback: Option<JoinHandle<usize>>
+}
+
+impl ConnectionWatcher {
+    pub fn new(config: &UpstreamConfig, index: usize) -> Self {
+        // nothing to switch to with only one daemon
+        let stale_job_timeout = config.has_failover().then_some(config.stale_job_timeout);
+        let failback = (index > 0).then(|| {
+            let addresses = config.addresses[..index].to_vec();
+            spawn_task("failback", failback_task(addresses, config.protocol, config.failback_interval))
+        });
+
+        Self {
+            stale_job_timeout,
+            last_topoheight: CURRENT_TOPO_HEIGHT.load(Ordering::SeqCst),
+            last_progress: Instant::now(),
+            failback
+        }
+    }
+
+    // Must be called periodically, returns true if the jobs are stale
+    pub fn is_stale(&mut self) -> bool {
+        let topoheight = CURRENT_TOPO_HEIGHT.load(Ordering::SeqCst);
+        if topoheight != self.last_topoheight {
+            self.last_topoheight = topoheight;
+            self.last_progress = Instant::now();
+            return false
+        }
+
+        self.stale_job_timeout.is_some_and(|timeout| self.last_progress.elapsed() > timeout)
+    }
+
+    // Wait until a daemon with a higher priority is available again
+    // Never returns if we are on the primary daemon
+    pub async fn wait_failback(&mut self) -> usize {
+        match self.failback.as_mut() {
+            Some(handle) => match handle.await {
+                Ok(index) => index,
+                Err(_) => pending().await
+            },
+            None => pending().await
+        }
+    }
+}
+
+impl Drop for ConnectionWatcher {
+    fn drop(&mut self) {
+        if let Some(handle) = self.failback.take() {
+            handle.abort();
+        }
+    }
+}
+
+// Check every interval the daemons with a higher priority than the active one
+// Returns the index of the first one available
+async fn failback_task(addresses: Vec<String>, protocol: Protocol, interval: Duration) -> usize {
+    loop {
+        sleep(interval).await;
+        for (index, address) in addresses.iter().enumerate() {
+            if is_daemon_healthy(address, protocol).await {
+                info!("Daemon {} is available again", address);
+                return index
+            }
+            debug!("Daemon {} is still unavailable", address);
+        }
+    }
+}
+
+// Stratum is using a raw TCP connection, remove the scheme if any
+pub fn stratum_address(address: &str) -> &str {
+    address.split_once("://").map_or(address, |(_, address)| address)
+}
+
+// A daemon is healthy if it answers and is not behind the topoheight we are mining on
+pub async fn is_daemon_healthy(address: &str, protocol: Protocol) -> bool {
+    let check = async {
+        match protocol {
+            Protocol::Getwork => {
+                let (mut client, _) = connect_async(format!("{}/json_rpc", sanitize_daemon_address(address))).await.ok()?;
+                let request = json!({ "jsonrpc": "2.0", "id": 1, "method": "get_info" });
+                client.send(Message::Text(request.to_string())).await.ok()?;
+                while let Some(message) = client.next().await {
+                    if let Message::Text(text) = message.ok()? {
+                        let response: Value = serde_json::from_str(&text).ok()?;
+                        let topoheight = response["result"]["topoheight"].as_u64()?;
+                        return Some(topoheight >= CURRENT_TOPO_HEIGHT.load(Ordering::SeqCst))
+                    }
+                }
+                None
+            },
+            // Stratum has no method to get the chain state
+            Protocol::Stratum => Some(TcpStream::connect(stratum_address(address)).await.is_ok())
+        }
+    };
+
+    matches!(timeout(HEALTH_CHECK_TIMEOUT, check).await, Ok(Some(true)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    fn test_config(addresses: &[&str], stale_job_timeout: Duration) -> UpstreamConfig {
+        UpstreamConfig {
+            addresses: addresses.iter().map(|address| address.to_string()).collect(),
+            protocol: Protocol::Stratum,
+            stale_job_timeout,
+            failback_interval: Duration::from_millis(10)
+        }
+    }
+
+    #[test]
+    fn test_stratum_address() {
+        assert_eq!(stratum_address("stratum+tcp://127.0.0.1:3333"), "127.0.0.1:3333");
+        assert_eq!(stratum_address("127.0.0.1:3333"), "127.0.0.1:3333");
+    }
+
+    #[test]
+    fn test_stale_job_detection() {
+        let stale_job_timeout = Duration::from_millis(50);
+        let mut watcher = ConnectionWatcher::new(&test_config(&["a", "b"], stale_job_timeout), 0);
+        assert!(!watcher.is_stale());
+
+        std::thread::sleep(stale_job_timeout * 2);
+        assert!(watcher.is_stale());
+
+        // a new topoheight resets the timer
+        CURRENT_TOPO_HEIGHT.fetch_add(1, Ordering::SeqCst);
+        assert!(!watcher.is_stale());
+        assert!(!watcher.is_stale());
+
+        // nothing to switch to with only one daemon
+        let mut watcher = ConnectionWatcher::new(&test_config(&["a"], stale_job_timeout), 0);
+        std::thread::sleep(stale_job_timeout * 2);
+        assert!(!watcher.is_stale());
+    }
+
+    #[test]
+    fn test_next_index() {
+        let config = test_config(&["a", "b", "c"], Duration::from_secs(60));
+        // fail over by priority order
+        assert_eq!(config.next_index(0, &ConnectionEnd::Failed), 1);
+        assert_eq!(config.next_index(1, &ConnectionEnd::StaleJob), 2);
+        // back to the primary after the last one
+        assert_eq!(config.next_index(2, &ConnectionEnd::Disconnected), 0);
+        // fail back to the daemon available again
+        assert_eq!(config.next_index(2, &ConnectionEnd::FailBack(1)), 1);
+        assert_eq!(config.next_index(2, &ConnectionEnd::Reload), 2);
+
+        let config = test_config(&["a"], Duration::from_secs(60));
+        assert_eq!(config.next_index(0, &ConnectionEnd::Failed), 0);
+    }
+
+    #[tokio::test]
+    async fn test_failback_to_highest_priority() {
+        let primary = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let secondary = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addresses = vec![primary.local_addr().unwrap().to_string(), secondary.local_addr().unwrap().to_string()];
+
+        // the primary is preferred when both are available
+        assert_eq!(failback_task(addresses.clone(), Protocol::Stratum, Duration::from_millis(10)).await, 0);
+
+        // the next one by priority order is used while the primary is down
+        drop(primary);
+        assert_eq!(failback_task(addresses, Protocol::Stratum, Duration::from_millis(10)).await, 1);
+    }
+}
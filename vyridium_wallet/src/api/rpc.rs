@@ -3,21 +3,35 @@ use anyhow::Context as AnyContext;
 use vyridium_common::{
     api::{
         wallet::{
+            AddContactParams,
+            BatchPayoutParams,
             BuildTransactionParams,
+            ContactLabelParams,
+            CreateAccountParams,
+            CreateInvoiceParams,
             DeleteParams,
             EstimateFeesParams,
+            ExportHistoryParams,
+            ExportHistoryResult,
             GetAddressParams,
             GetAssetPrecisionParams,
             GetBalanceParams,
+            GetInvoiceParams,
             GetMatchingKeysParams,
             CountMatchingEntriesParams,
             GetTransactionParams,
             GetValueFromKeyParams,
             HasKeyParams,
+            ListInvoicesParams,
             ListTransactionsParams,
+            ParsePaymentRequestParams,
+            PaymentRequestResult,
             QueryDBParams,
             RescanParams,
+            ScheduledPaymentParams,
+            SchedulePaymentParams,
             StoreParams,
+            SwitchAccountParams,
             TransactionResponse,
             SetOnlineModeParams,
             EstimateExtraDataSizeParams,
@@ -31,7 +45,7 @@ use vyridium_common::{
     async_handler,
     config::{VERSION, VYRIDIUM_ASSET},
     context::Context,
-    crypto::Hashable,
+    crypto::{Hashable, PaymentRequest},
     rpc_server::{
         parse_params,
         websocket::WebSocketSessionShared,
@@ -43,6 +57,8 @@ use vyridium_common::{
 };
 use serde_json::{Value, json};
 use crate::{
+    contact::Contact,
+    export::export_history_records,
     wallet::Wallet,
     error::WalletError
 };
@@ -65,13 +81,33 @@ pub fn register_methods(handler: &mut RPCHandler<Arc<Wallet>>) {
     handler.register_method("get_asset_precision", async_handler!(get_asset_precision));
     handler.register_method("get_transaction", async_handler!(get_transaction));
     handler.register_method("build_transaction", async_handler!(build_transaction));
+    handler.register_method("batch_payout", async_handler!(batch_payout));
     handler.register_method("list_transactions", async_handler!(list_transactions));
+    handler.register_method("get_pending_transactions", async_handler!(get_pending_transactions));
+    handler.register_method("export_history", async_handler!(export_history));
     handler.register_method("is_online", async_handler!(is_online));
     handler.register_method("set_online_mode", async_handler!(set_online_mode));
     handler.register_method("set_offline_mode", async_handler!(set_offline_mode));
     handler.register_method("sign_data", async_handler!(sign_data));
     handler.register_method("estimate_fees", async_handler!(estimate_fees));
     handler.register_method("estimate_extra_data_size", async_handler!(estimate_extra_data_size));
+    handler.register_method("create_account", async_handler!(create_account));
+    handler.register_method("switch_account", async_handler!(switch_account));
+    handler.register_method("list_accounts", async_handler!(list_accounts));
+    handler.register_method("get_active_account", async_handler!(get_active_account));
+    handler.register_method("add_contact", async_handler!(add_contact));
+    handler.register_method("get_contact", async_handler!(get_contact));
+    handler.register_method("list_contacts", async_handler!(list_contacts));
+    handler.register_method("remove_contact", async_handler!(remove_contact));
+    handler.register_method("create_invoice", async_handler!(create_invoice));
+    handler.register_method("get_invoice", async_handler!(get_invoice));
+    handler.register_method("list_invoices", async_handler!(list_invoices));
+    handler.register_method("parse_payment_request", async_handler!(parse_payment_request));
+    handler.register_method("schedule_payment", async_handler!(schedule_payment));
+    handler.register_method("list_scheduled_payments", async_handler!(list_scheduled_payments));
+    handler.register_method("pause_scheduled_payment", async_handler!(pause_scheduled_payment));
+    handler.register_method("resume_scheduled_payment", async_handler!(resume_scheduled_payment));
+    handler.register_method("cancel_scheduled_payment", async_handler!(cancel_scheduled_payment));
 
     // These functions allow to have an encrypted DB directly in the wallet storage
     // You can retrieve keys, values, have differents trees, and store values
@@ -134,6 +170,9 @@ async fn get_address(context: &Context, body: Value) -> Result<Value, InternalRp
     let params: GetAddressParams = parse_params(body)?;
 
     let wallet: &Arc<Wallet> = context.get()?;
+    // The lock prevents the account from being switched while deriving the address
+    let storage = wallet.get_storage().read().await;
+    storage.verify_active_account(params.account)?;
     let address = if let Some(data) = params.integrated_data {
         wallet.get_address_with(data)
     } else {
@@ -190,6 +229,7 @@ async fn get_balance(context: &Context, body: Value) -> Result<Value, InternalRp
     let asset = params.asset.unwrap_or(VYRIDIUM_ASSET);
     let wallet: &Arc<Wallet> = context.get()?;
     let storage = wallet.get_storage().read().await;
+    storage.verify_active_account(params.account)?;
 
     // If the asset is not found, it will returns 0
     // Use has_balance below to check if the wallet has a balance for a specific asset
@@ -203,6 +243,7 @@ async fn has_balance(context: &Context, body: Value) -> Result<Value, InternalRp
     let asset = params.asset.unwrap_or(VYRIDIUM_ASSET);
     let wallet: &Arc<Wallet> = context.get()?;
     let storage = wallet.get_storage().read().await;
+    storage.verify_active_account(params.account)?;
 
     let exist = storage.has_balance_for(&asset).await.context("Error while checking if balance exists")?;
     Ok(json!(exist))
@@ -237,6 +278,7 @@ async fn get_transaction(context: &Context, body: Value) -> Result<Value, Intern
 
     let wallet: &Arc<Wallet> = context.get()?;
     let storage = wallet.get_storage().read().await;
+    storage.verify_active_account(params.account)?;
     let transaction = storage.get_transaction(&params.hash)?;
 
     Ok(json!(transaction.serializable(wallet.get_network().is_mainnet())))
@@ -259,11 +301,12 @@ async fn build_transaction(context: &Context, body: Value) -> Result<Value, Inte
     // The lock is kept until the TX is applied to the storage
     // So even if we have few requests building a TX, they wait for the previous one to be applied
     let mut storage = wallet.get_storage().write().await;
+    storage.verify_active_account(params.account)?;
     let (mut state, tx) = wallet.create_transaction_with_storage(&storage, params.tx_type, params.fee.unwrap_or(FeeBuilder::Multiplier(1f64))).await?;
 
     // if requested, broadcast the TX ourself
     if params.broadcast {
-        if let Err(e) = wallet.submit_transaction(&tx).await {
+        if let Err(e) = wallet.submit_transaction_with_storage(&mut storage, &tx).await {
             warn!("Clearing Tx cache & unconfirmed balances because of broadcasting error: {}", e);
             storage.clear_tx_cache();
             storage.delete_unconfirmed_balances().await;
@@ -288,10 +331,24 @@ async fn build_transaction(context: &Context, body: Value) -> Result<Value, Inte
     }))
 }
 
+// Send many transfers, possibly of different assets, in the minimum of transactions
+// Each transfer has its own result with the hash of its transaction or the error
+async fn batch_payout(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: BatchPayoutParams = parse_params(body)?;
+    if params.transfers.is_empty() {
+        return Err(InternalRpcError::InvalidParams("Invalid params, no transfers"))
+    }
+
+    let wallet: &Arc<Wallet> = context.get()?;
+    let result = wallet.batch_payout(params.transfers, params.fee.unwrap_or(FeeBuilder::Multiplier(1f64)), params.account).await?;
+    Ok(json!(result))
+}
+
 // Estimate fees for a transaction
 async fn estimate_fees(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
     let params: EstimateFeesParams = parse_params(body)?;
     let wallet: &Arc<Wallet> = context.get()?;
+    wallet.get_storage().read().await.verify_active_account(params.account)?;
     let fees = wallet.estimate_fees(params.tx_type).await?;
 
     Ok(json!(fees))
@@ -308,6 +365,7 @@ async fn list_transactions(context: &Context, body: Value) -> Result<Value, Inte
 
     let wallet: &Arc<Wallet> = context.get()?;
     let storage = wallet.get_storage().read().await;
+    storage.verify_active_account(params.account)?;
     let opt_key = params.address.map(|addr| addr.to_public_key());
     
     let mainnet = wallet.get_network().is_mainnet();
@@ -319,6 +377,37 @@ async fn list_transactions(context: &Context, body: Value) -> Result<Value, Inte
     Ok(json!(txs))
 }
 
+// Retrieve the submitted transactions not yet executed in a stable block
+async fn get_pending_transactions(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
+    if body != Value::Null {
+        return Err(InternalRpcError::UnexpectedParams)
+    }
+
+    let wallet: &Arc<Wallet> = context.get()?;
+    let transactions = wallet.get_pending_transactions().await?;
+    Ok(json!(transactions))
+}
+
+// Export the transactions history in CSV or JSON format
+// The content is returned instead of being written on disk
+async fn export_history(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: ExportHistoryParams = parse_params(body)?;
+    if let Some(addr) = &params.filters.address {
+        if !addr.is_normal() {
+            return Err(InternalRpcError::InvalidParams("Address should be in normal format (not integrated address)"))
+        }
+    }
+
+    let wallet: &Arc<Wallet> = context.get()?;
+    let records = wallet.get_history_records(&params.filters).await?;
+    let content = export_history_records(&records, params.format)?;
+
+    Ok(json!(ExportHistoryResult {
+        count: records.len(),
+        content
+    }))
+}
+
 // Check if the wallet is currently connected to a daemon
 async fn is_online(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
     if body != Value::Null {
@@ -360,6 +449,193 @@ async fn set_offline_mode(context: &Context, body: Value) -> Result<Value, Inter
     Ok(json!(true))
 }
 
+// Create a new account derived from the wallet seed
+async fn create_account(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: CreateAccountParams = parse_params(body)?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    let account = wallet.create_account(params.name).await?;
+
+    Ok(json!(account))
+}
+
+// Switch the wallet to another account
+async fn switch_account(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: SwitchAccountParams = parse_params(body)?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    wallet.switch_account(params.account).await?;
+
+    Ok(json!(true))
+}
+
+// List all the accounts derived from the wallet seed
+async fn list_accounts(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
+    if body != Value::Null {
+        return Err(InternalRpcError::UnexpectedParams)
+    }
+
+    let wallet: &Arc<Wallet> = context.get()?;
+    let accounts = wallet.list_accounts().await?;
+
+    Ok(json!(accounts))
+}
+
+// Retrieve the index of the account currently used
+async fn get_active_account(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
+    if body != Value::Null {
+        return Err(InternalRpcError::UnexpectedParams)
+    }
+
+    let wallet: &Arc<Wallet> = context.get()?;
+    Ok(json!(wallet.get_active_account().await))
+}
+
+// Add a contact to the wallet address book
+async fn add_contact(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: AddContactParams = parse_params(body)?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    if params.address.is_mainnet() != wallet.get_network().is_mainnet() {
+        return Err(InternalRpcError::InvalidParams("Address is not for the wallet network"))
+    }
+
+    let contact = Contact::new(params.label, params.address, params.notes);
+    let mut storage = wallet.get_storage().write().await;
+    storage.add_contact(&contact)?;
+
+    Ok(json!(true))
+}
+
+// Retrieve a contact from the address book using its label
+async fn get_contact(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: ContactLabelParams = parse_params(body)?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    let storage = wallet.get_storage().read().await;
+    let contact = storage.get_contact(&params.label)?;
+
+    Ok(json!(contact.serializable(wallet.get_network().is_mainnet())))
+}
+
+// List all contacts from the address book
+async fn list_contacts(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
+    if body != Value::Null {
+        return Err(InternalRpcError::UnexpectedParams)
+    }
+
+    let wallet: &Arc<Wallet> = context.get()?;
+    let storage = wallet.get_storage().read().await;
+    let mainnet = wallet.get_network().is_mainnet();
+    let contacts = storage.get_contacts()?
+        .into_iter()
+        .map(|contact| contact.serializable(mainnet))
+        .collect::<Vec<_>>();
+
+    Ok(json!(contacts))
+}
+
+// Remove a contact from the address book using its label
+async fn remove_contact(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: ContactLabelParams = parse_params(body)?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    let mut storage = wallet.get_storage().write().await;
+    storage.delete_contact(&params.label)?;
+
+    Ok(json!(true))
+}
+
+// Create an invoice to be paid on an integrated address of the active account
+async fn create_invoice(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: CreateInvoiceParams = parse_params(body)?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    let invoice = wallet.create_invoice(params).await?;
+
+    Ok(json!(invoice))
+}
+
+// Retrieve an invoice of the active account using its ID
+async fn get_invoice(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: GetInvoiceParams = parse_params(body)?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    let invoice = wallet.get_invoice(&params.id).await?;
+
+    Ok(json!(invoice))
+}
+
+// List the invoices of the active account, optionally filtered by status
+async fn list_invoices(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: ListInvoicesParams = parse_params(body)?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    let invoices = wallet.list_invoices(params.status).await?;
+
+    Ok(json!(invoices))
+}
+
+// Parse a payment request URI to build the transaction paying it
+async fn parse_payment_request(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: ParsePaymentRequestParams = parse_params(body)?;
+    let request = PaymentRequest::from_uri(&params.uri).map_err(|e| InternalRpcError::InvalidParamsAny(e.into()))?;
+
+    let wallet: &Arc<Wallet> = context.get()?;
+    if request.address.is_mainnet() != wallet.get_network().is_mainnet() {
+        return Err(InternalRpcError::InvalidParams("Address is not for the wallet network"))
+    }
+
+    let extra_data = request.get_extra_data();
+    Ok(json!(PaymentRequestResult {
+        address: request.address,
+        asset: request.asset,
+        amount: request.amount,
+        invoice_id: request.invoice_id,
+        memo: request.memo,
+        extra_data
+    }))
+}
+
+// Schedule a payment submitted automatically while the wallet is online
+async fn schedule_payment(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: SchedulePaymentParams = parse_params(body)?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    let payment = wallet.schedule_payment(params).await?;
+
+    Ok(json!(payment))
+}
+
+// List all scheduled payments of the active account
+async fn list_scheduled_payments(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
+    if body != Value::Null {
+        return Err(InternalRpcError::UnexpectedParams)
+    }
+
+    let wallet: &Arc<Wallet> = context.get()?;
+    let payments = wallet.list_scheduled_payments().await?;
+    Ok(json!(payments))
+}
+
+// Pause a scheduled payment, its runs are skipped until resumed
+async fn pause_scheduled_payment(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: ScheduledPaymentParams = parse_params(body)?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    let payment = wallet.set_scheduled_payment_paused(params.id, true).await?;
+
+    Ok(json!(payment))
+}
+
+// Resume a paused scheduled payment
+async fn resume_scheduled_payment(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: ScheduledPaymentParams = parse_params(body)?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    let payment = wallet.set_scheduled_payment_paused(params.id, false).await?;
+
+    Ok(json!(payment))
+}
+
+// Cancel a scheduled payment
+async fn cancel_scheduled_payment(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: ScheduledPaymentParams = parse_params(body)?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    wallet.cancel_scheduled_payment(params.id).await?;
+
+    Ok(json!(true))
+}
+
 // Sign any data converted in bytes format
 async fn sign_data(context: &Context, body: Value) -> Result<Value, InternalRpcError> {
     let params: DataElement = parse_params(body)?;
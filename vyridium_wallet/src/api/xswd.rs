@@ -3,12 +3,14 @@ use std::{
         Arc,
         atomic::{
             AtomicBool,
+            AtomicU64,
             Ordering
         }
     },
     collections::{
         HashMap,
-        HashSet
+        HashSet,
+        VecDeque
     },
     borrow::Cow
 };
@@ -50,6 +52,7 @@ use vyridium_common::{
     context::Context,
     crypto::{
         elgamal::PublicKey as DecompressedPublicKey,
+        Hash,
         Signature,
         SIGNATURE_SIZE
     },
@@ -71,10 +74,19 @@ use vyridium_common::{
         ReaderError,
         Serializer,
         Writer
+    },
+    time::{
+        get_current_time_in_millis,
+        get_current_time_in_seconds,
+        TimestampMillis,
+        TimestampSeconds
     }
 };
 use serde::{Deserialize, Serialize};
-use crate::config::XSWD_BIND_ADDRESS;
+use crate::{
+    application::{ApplicationPermissions, ApplicationPolicy, Permission, PolicyError, RequestSpending},
+    config::{XSWD_BIND_ADDRESS, XSWD_RATE_LIMIT_PERIOD}
+};
 use log::{
     debug,
     info,
@@ -149,7 +161,17 @@ pub trait XSWDPermissionHandler {
     // Handler function to cancel the request permission from app (app has disconnected)
     async fn cancel_request_permission(&self, app_state: &AppStateShared) -> Result<(), Error>;
     // Public key to use to verify the signature
-    async fn get_public_key(&self) -> Result<&DecompressedPublicKey, Error>;
+    async fn get_public_key(&self) -> Result<DecompressedPublicKey, Error>;
+    // Permissions granted to the application during its previous sessions
+    async fn get_saved_permissions(&self, app_state: &AppStateShared) -> Result<Option<ApplicationPermissions>, Error>;
+    // Save the permissions granted to the application for its next sessions
+    // Returns when the saved permissions expire
+    async fn save_permissions(&self, app_state: &AppStateShared, permissions: &HashMap<String, Permission>) -> Result<TimestampSeconds, Error>;
+    // Limits set by the user for the application
+    // An error means a policy may exist and couldn't be loaded
+    async fn get_policy(&self, app_state: &AppStateShared) -> Result<Option<ApplicationPolicy>, Error>;
+    // Save the amounts sent by the application for its daily limits
+    async fn record_spending(&self, app_state: &AppStateShared, amounts: &HashMap<Hash, u64>) -> Result<(), Error>;
 }
 
 #[async_trait]
@@ -168,6 +190,10 @@ pub struct AppState {
     url: Option<String>,
     // All permissions for each method
     permissions: Mutex<HashMap<String, Permission>>,
+    // Expiration of the saved permissions, 0 if none were restored or saved
+    permissions_expire_at: AtomicU64,
+    // Timestamps of the last calls for each method, used by the rate limits
+    calls: Mutex<HashMap<String, VecDeque<TimestampMillis>>>,
     is_requesting: AtomicBool
 }
 
@@ -181,6 +207,8 @@ impl AppState {
             description: data.description,
             url: data.url,
             permissions: Mutex::new(data.permissions),
+            permissions_expire_at: AtomicU64::new(0),
+            calls: Mutex::new(HashMap::new()),
             is_requesting: AtomicBool::new(false)
         }
     }
@@ -205,6 +233,29 @@ impl AppState {
         &self.permissions
     }
 
+    pub fn set_permissions_expire_at(&self, expire_at: TimestampSeconds) {
+        self.permissions_expire_at.store(expire_at, Ordering::SeqCst);
+    }
+
+    // Check if the saved permissions this session relies on have expired
+    pub fn have_permissions_expired(&self, now: TimestampSeconds) -> bool {
+        let expire_at = self.permissions_expire_at.load(Ordering::SeqCst);
+        expire_at != 0 && now >= expire_at
+    }
+
+    // Register a call to a method
+    // Returns the number of calls to it during the last minute, including this one
+    pub async fn register_call(&self, method: &str) -> usize {
+        let now = get_current_time_in_millis();
+        let mut calls = self.calls.lock().await;
+        let timestamps = calls.entry(method.to_owned()).or_insert_with(VecDeque::new);
+        while timestamps.front().map_or(false, |timestamp| timestamp + XSWD_RATE_LIMIT_PERIOD <= now) {
+            timestamps.pop_front();
+        }
+        timestamps.push_back(now);
+        timestamps.len()
+    }
+
     pub fn is_requesting(&self) -> bool {
         self.is_requesting.load(Ordering::SeqCst)
     }
@@ -343,37 +394,12 @@ where
     }
 }
 
-#[derive(Clone, Copy, Serialize, Deserialize, Debug)]
-#[serde(rename_all = "snake_case")]
-pub enum Permission {
-    Ask,
-    AcceptAlways,
-    DenyAlways
-}
-
-impl Permission {
-    pub fn get_id(&self) -> u8 {
-        match self {
-            Self::Ask => 0,
-            Self::AcceptAlways => 1,
-            Self::DenyAlways => 2
-        }
-    }
-
-    pub fn from_id(id: u8) -> Option<Self> {
-        Some(match id {
-            0 => Self::Ask,
-            1 => Self::AcceptAlways,
-            2 => Self::DenyAlways,
-            _ => return None
-        })
-    }
-}
-
 pub enum PermissionRequest<'a> {
     // bool tell if it was already signed or not
     Application(bool),
-    Request(&'a RpcRequest)
+    Request(&'a RpcRequest),
+    // Request exceeding a limit of the application policy
+    PolicyExceeded(&'a RpcRequest, &'a PolicyError)
 }
 
 pub enum PermissionResult {
@@ -457,8 +483,56 @@ where
         }
     }
 
+    // Save the permissions of the application so they are restored on its next connection
+    // A failure is only logged, permissions stay in memory for the current session
+    async fn save_permissions(&self, app: &AppStateShared, permissions: &HashMap<String, Permission>) {
+        match self.handler.get_data().save_permissions(app, permissions).await {
+            Ok(expire_at) => app.set_permissions_expire_at(expire_at),
+            Err(e) => error!("Error while saving permissions of application {}: {}", app.get_name(), e)
+        }
+    }
+
+    // Close all the sessions of an application
+    // Returns the number of sessions closed
+    pub async fn close_application_sessions(&self, id: &str) -> usize {
+        let sessions: Vec<_> = {
+            let applications = self.applications.read().await;
+            applications.iter()
+                .filter(|(_, app)| app.get_id() == id)
+                .map(|(session, _)| session.clone())
+                .collect()
+        };
+
+        for session in sessions.iter() {
+            if let Err(e) = session.close(None).await {
+                error!("Error while closing session of application {}: {}", id, e);
+            }
+        }
+
+        sessions.len()
+    }
+
+    // Verify the request against the policy of the application
+    // Returns the funds sent by the request and the limit exceeded if any
+    // If the policy can't be loaded, the user must accept the request
+    async fn verify_policy_for_request(&self, app: &AppStateShared, request: &RpcRequest) -> (Option<RequestSpending>, Option<PolicyError>) {
+        let calls = app.register_call(&request.method).await;
+        let policy = match self.handler.get_data().get_policy(app).await {
+            Ok(Some(policy)) => policy,
+            Ok(None) => return (None, None),
+            Err(e) => {
+                error!("Error while loading policy of application {}: {}", app.get_name(), e);
+                return (None, Some(PolicyError::PolicyUnavailable))
+            }
+        };
+
+        let (spending, result) = policy.verify_request(&request.method, &request.params, calls, get_current_time_in_seconds());
+        (spending, result.err())
+    }
+
     // verify the permission for a request
     // if the permission is not set, it will request it to the user
+    // if the request exceeds a limit of the application policy, the user is asked even if the method is always accepted
     async fn verify_permission_for_request(&self, app: &AppStateShared, request: &RpcRequest) -> Result<(), RpcResponseError> {
         let _permit = self.permission_handler_semaphore.acquire().await
             .map_err(|_| RpcResponseError::new(request.id.clone(), InternalRpcError::InternalError("Permission handler semaphore error")))?;
@@ -469,7 +543,48 @@ where
             return Err(RpcResponseError::new(request.id.clone(), XSWDError::ApplicationNotFound))
         }
 
+        // Methods always accepted are asked again once the saved permissions have expired
+        if app.have_permissions_expired(get_current_time_in_seconds()) {
+            debug!("Saved permissions of application {} have expired", app.get_name());
+            permissions.retain(|_, permission| *permission != Permission::AcceptAlways);
+            app.set_permissions_expire_at(0);
+        }
+
         let permission = permissions.get(&request.method).map(|v| *v).unwrap_or(Permission::Ask);
+        if permission == Permission::DenyAlways {
+            return Err(RpcResponseError::new(request.id.clone(), XSWDError::PermissionDenied))
+        }
+
+        let (spending, exceeded) = self.verify_policy_for_request(app, request).await;
+        let result = if let Some(exceeded) = exceeded {
+            debug!("Request {} of application {} exceeds its policy: {}", request.method, app.get_name(), exceeded);
+            let result = self.handler.get_data()
+                .request_permission(app, PermissionRequest::PolicyExceeded(request, &exceeded)).await
+                .map_err(|err| RpcResponseError::new(request.id.clone(), InternalRpcError::CustomAny(0, err)))?;
+
+            // Limits are only bypassed for this request, nothing is saved
+            if result.is_positive() {
+                Ok(())
+            } else {
+                Err(RpcResponseError::new(request.id.clone(), XSWDError::PermissionDenied))
+            }
+        } else {
+            self.verify_permission(app, request, permission, &mut permissions).await
+        };
+
+        // Spending is recorded before the request is executed
+        // so concurrent requests can't exceed the daily limits
+        if let (Ok(()), Some(spending)) = (&result, spending) {
+            self.handler.get_data().record_spending(app, spending.get_amounts()).await
+                .map_err(|err| RpcResponseError::new(request.id.clone(), InternalRpcError::CustomAny(0, err)))?;
+        }
+
+        result
+    }
+
+    // Verify the permission set for the method
+    // if it is not set, it will request it to the user
+    async fn verify_permission(&self, app: &AppStateShared, request: &RpcRequest, permission: Permission, permissions: &mut HashMap<String, Permission>) -> Result<(), RpcResponseError> {
         match permission {
             // Request permission from user
             Permission::Ask => {
@@ -482,12 +597,14 @@ where
                     PermissionResult::Deny => Err(RpcResponseError::new(request.id.clone(), XSWDError::PermissionDenied)),
                     PermissionResult::AlwaysAllow => {
                         permissions.insert(request.method.clone(), Permission::AcceptAlways);
+                        self.save_permissions(app, permissions).await;
                         Ok(())
                     },
                     PermissionResult::AlwaysDeny => {
-                        permissions.insert(request.method.clone(), Permission::AcceptAlways);
+                        permissions.insert(request.method.clone(), Permission::DenyAlways);
+                        self.save_permissions(app, permissions).await;
                         Err(RpcResponseError::new(request.id.clone(), XSWDError::PermissionDenied))
-                    }   
+                    }
                 }
             }
             // User has already accepted this method
@@ -561,7 +678,7 @@ where
                     RpcResponseError::new(None, InternalRpcError::InternalError("Error while retrieving wallet public key"))
                 })?;
 
-            if signature.verify(bytes, key) {
+            if signature.verify(bytes, &key) {
                 return Err(RpcResponseError::new(None, XSWDError::InvalidSignatureForApplicationData));
             }
         }
@@ -599,6 +716,17 @@ where
             return Err(RpcResponseError::new(None, XSWDError::PermissionDenied))
         }
 
+        // Restore the permissions granted during its previous sessions
+        match wallet.get_saved_permissions(&state).await {
+            Ok(Some(saved)) => {
+                info!("Restoring {} saved permissions for application {}", saved.get_permissions().len(), state.get_name());
+                state.set_permissions_expire_at(saved.get_expire_at());
+                state.get_permissions().lock().await.extend(saved.get_permissions().clone());
+            },
+            Ok(None) => {},
+            Err(e) => error!("Error while loading saved permissions of application {}: {}", state.get_name(), e)
+        }
+
         Ok(json!({
             "jsonrpc": "2.0",
             "id": Value::Null,
@@ -0,0 +1,589 @@
+use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use vyridium_common::{
+    api::wallet::{BatchPayoutParams, BuildTransactionParams},
+    crypto::{Address, Hash, PublicKey},
+    serializer::{Reader, ReaderError, Serializer, Writer},
+    config::VYRIDIUM_ASSET,
+    time::TimestampSeconds,
+    transaction::builder::{FeeBuilder, TransactionTypeBuilder}
+};
+use crate::{
+    batch::split_transfers,
+    config::{XSWD_PERMISSIONS_EXPIRATION, XSWD_SPENDING_PERIOD}
+};
+
+// Methods spending funds later, their spending can't be verified when they are called
+const DEFERRED_SPENDING_METHODS: [&str; 2] = ["schedule_payment", "resume_scheduled_payment"];
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    Ask,
+    AcceptAlways,
+    DenyAlways
+}
+
+impl Permission {
+    pub fn get_id(&self) -> u8 {
+        match self {
+            Self::Ask => 0,
+            Self::AcceptAlways => 1,
+            Self::DenyAlways => 2
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        Some(match id {
+            0 => Self::Ask,
+            1 => Self::AcceptAlways,
+            2 => Self::DenyAlways,
+            _ => return None
+        })
+    }
+}
+
+// Permissions granted to an XSWD application
+// They are restored on its next connections until they expire
+#[derive(Debug, Clone)]
+pub struct ApplicationPermissions {
+    // Application ID in hexadecimal format
+    id: String,
+    // Name and URL of the app when the permissions were granted
+    name: String,
+    url: Option<String>,
+    // Permission for each method
+    permissions: HashMap<String, Permission>,
+    updated_at: TimestampSeconds,
+    expire_at: TimestampSeconds
+}
+
+impl ApplicationPermissions {
+    // Expiration is reset each time the permissions are updated
+    pub fn new(id: String, name: String, url: Option<String>, permissions: HashMap<String, Permission>, now: TimestampSeconds) -> Self {
+        Self {
+            id,
+            name,
+            url,
+            permissions,
+            updated_at: now,
+            expire_at: now.saturating_add(XSWD_PERMISSIONS_EXPIRATION)
+        }
+    }
+
+    // Replace the permissions and reset the expiration
+    pub fn set_permissions(&mut self, permissions: HashMap<String, Permission>, now: TimestampSeconds) {
+        self.permissions = permissions;
+        self.updated_at = now;
+        self.expire_at = now.saturating_add(XSWD_PERMISSIONS_EXPIRATION);
+    }
+
+    pub fn get_id(&self) -> &String {
+        &self.id
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_url(&self) -> &Option<String> {
+        &self.url
+    }
+
+    pub fn get_permissions(&self) -> &HashMap<String, Permission> {
+        &self.permissions
+    }
+
+    pub fn get_updated_at(&self) -> TimestampSeconds {
+        self.updated_at
+    }
+
+    pub fn get_expire_at(&self) -> TimestampSeconds {
+        self.expire_at
+    }
+
+    pub fn is_expired(&self, now: TimestampSeconds) -> bool {
+        now >= self.expire_at
+    }
+
+    // Permissions are only restored for the same application
+    // and not for another one reusing its ID
+    pub fn is_same_application(&self, name: &String, url: &Option<String>) -> bool {
+        self.name == *name && self.url == *url
+    }
+}
+
+impl Serializer for ApplicationPermissions {
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let id = reader.read_string()?;
+        let name = reader.read_string()?;
+        let url = reader.read_optional_string()?;
+        let permissions_count = reader.read_u8()?;
+        let mut permissions = HashMap::with_capacity(permissions_count as usize);
+        for _ in 0..permissions_count {
+            permissions.insert(reader.read_string()?, Permission::from_id(reader.read_u8()?).ok_or(ReaderError::InvalidValue)?);
+        }
+        let updated_at = reader.read_u64()?;
+        let expire_at = reader.read_u64()?;
+
+        Ok(Self {
+            id,
+            name,
+            url,
+            permissions,
+            updated_at,
+            expire_at
+        })
+    }
+
+    fn write(&self, writer: &mut Writer) {
+        writer.write_string(&self.id);
+        writer.write_string(&self.name);
+        writer.write_optional_string(&self.url);
+        writer.write_u8(self.permissions.len() as u8);
+        for (method, permission) in &self.permissions {
+            writer.write_string(method);
+            writer.write_u8(permission.get_id());
+        }
+        writer.write_u64(&self.updated_at);
+        writer.write_u64(&self.expire_at);
+    }
+
+    fn size(&self) -> usize {
+        // url is written with only one byte for its length
+        let url_size = 1 + self.url.as_ref().map_or(0, |url| url.len());
+        self.id.size() + self.name.size() + url_size + 1
+            + self.permissions.keys().map(|method| method.size() + 1).sum::<usize>()
+            + 8 + 8
+    }
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum PolicyError {
+    #[error("Method {} was called more than {} times in the last minute", _0, _1)]
+    RateLimitExceeded(String, u16),
+    #[error("Amount {} of asset {} is above the limit of {} per transaction", _1, _0, _2)]
+    MaxAmountExceeded(Hash, u64, u64),
+    #[error("Spending {} of asset {} would exceed the daily limit of {}, already spent {}", _1, _0, _2, _3)]
+    DailyLimitExceeded(Hash, u64, u64, u64),
+    // Burns are reported with "burn" as destination
+    #[error("Destination {} is not allowed", _0)]
+    DestinationNotAllowed(String),
+    #[error("Spending of method {} can't be verified against the limits", _0)]
+    UnverifiableSpending(String),
+    #[error("Fee of method {} is not a fixed value and can't be verified against the limits", _0)]
+    UnverifiableFee(String),
+    // The policy saved can't be loaded, requests must be accepted by the user
+    #[error("Policy of the application can't be loaded")]
+    PolicyUnavailable
+}
+
+// Funds sent by a request
+#[derive(Debug, Default)]
+pub struct RequestSpending {
+    method: String,
+    amounts: HashMap<Hash, u64>,
+    destinations: Vec<Address>,
+    // Funds are destroyed by a burn
+    burn: bool,
+    // Fee is computed by the wallet, only a fixed value can be counted
+    unknown_fee: bool
+}
+
+impl RequestSpending {
+    fn add(&mut self, asset: &Hash, amount: u64) {
+        let total = self.amounts.entry(asset.clone()).or_insert(0);
+        *total = total.saturating_add(amount);
+    }
+
+    // Count the fee paid by each transaction of the request
+    // No fee set is the default multiplier of the wallet
+    fn add_fee(&mut self, fee: Option<FeeBuilder>, transactions: usize) {
+        match fee {
+            Some(FeeBuilder::Value(value)) => self.add(&VYRIDIUM_ASSET, value.saturating_mul(transactions as u64)),
+            Some(FeeBuilder::Multiplier(_)) | Some(FeeBuilder::Target(_)) => self.unknown_fee = true,
+            None => {}
+        }
+    }
+
+    // Retrieve the funds sent by a wallet RPC request
+    // Returns None if the method doesn't send funds or if its params are invalid,
+    // in which case the method will fail without sending anything
+    pub fn from_request(method: &str, params: &Option<Value>) -> Result<Option<Self>, PolicyError> {
+        if DEFERRED_SPENDING_METHODS.contains(&method) {
+            return Err(PolicyError::UnverifiableSpending(method.to_owned()))
+        }
+
+        let params = match params {
+            Some(params) => params.clone(),
+            None => return Ok(None)
+        };
+
+        let mut spending = Self {
+            method: method.to_owned(),
+            ..Default::default()
+        };
+        let transfers = match method {
+            "build_transaction" => match serde_json::from_value::<BuildTransactionParams>(params) {
+                Ok(params) => {
+                    spending.add_fee(params.fee, 1);
+                    match params.tx_type {
+                        TransactionTypeBuilder::Transfers(transfers) => transfers,
+                        TransactionTypeBuilder::Burn(payload) => {
+                            spending.add(&payload.asset, payload.amount);
+                            spending.burn = true;
+                            return Ok(Some(spending))
+                        }
+                    }
+                },
+                Err(_) => return Ok(None)
+            },
+            "batch_payout" => match serde_json::from_value::<BatchPayoutParams>(params) {
+                Ok(params) => {
+                    // Each transaction of the batch pays the fee
+                    let transactions = split_transfers(params.transfers.iter().cloned().enumerate()).len();
+                    spending.add_fee(params.fee, transactions);
+                    params.transfers
+                },
+                Err(_) => return Ok(None)
+            },
+            _ => return Ok(None)
+        };
+
+        for transfer in transfers {
+            spending.add(&transfer.asset, transfer.amount);
+            spending.destinations.push(transfer.destination);
+        }
+
+        Ok(Some(spending))
+    }
+
+    pub fn get_amounts(&self) -> &HashMap<Hash, u64> {
+        &self.amounts
+    }
+}
+
+// Limits set by the user on an XSWD application
+// They are verified even for the methods always accepted
+// and are kept until the application is revoked
+#[derive(Debug, Clone, Default)]
+pub struct ApplicationPolicy {
+    // Maximum amount per asset sent by a single request
+    max_amounts: HashMap<Hash, u64>,
+    // Maximum amount per asset sent over the last 24 hours
+    daily_limits: HashMap<Hash, u64>,
+    // Only these destinations can receive funds, any if empty
+    allowed_destinations: HashSet<PublicKey>,
+    // Maximum calls per minute for each method
+    rate_limits: HashMap<String, u16>,
+    // Amounts sent with their timestamp, only kept for the assets with a daily limit
+    spendings: Vec<(TimestampSeconds, Hash, u64)>
+}
+
+impl ApplicationPolicy {
+    pub fn get_max_amounts(&self) -> &HashMap<Hash, u64> {
+        &self.max_amounts
+    }
+
+    pub fn get_daily_limits(&self) -> &HashMap<Hash, u64> {
+        &self.daily_limits
+    }
+
+    pub fn get_allowed_destinations(&self) -> &HashSet<PublicKey> {
+        &self.allowed_destinations
+    }
+
+    pub fn get_rate_limits(&self) -> &HashMap<String, u16> {
+        &self.rate_limits
+    }
+
+    // None removes the limit
+    pub fn set_max_amount(&mut self, asset: Hash, amount: Option<u64>) {
+        match amount {
+            Some(amount) => self.max_amounts.insert(asset, amount),
+            None => self.max_amounts.remove(&asset)
+        };
+    }
+
+    // None removes the limit
+    pub fn set_daily_limit(&mut self, asset: Hash, amount: Option<u64>) {
+        match amount {
+            Some(amount) => self.daily_limits.insert(asset, amount),
+            None => self.daily_limits.remove(&asset)
+        };
+    }
+
+    pub fn set_allowed_destinations(&mut self, destinations: HashSet<PublicKey>) {
+        self.allowed_destinations = destinations;
+    }
+
+    pub fn set_rate_limits(&mut self, rate_limits: HashMap<String, u16>) {
+        self.rate_limits = rate_limits;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.max_amounts.is_empty() && self.daily_limits.is_empty() && self.allowed_destinations.is_empty() && self.rate_limits.is_empty()
+    }
+
+    fn has_spending_limits(&self) -> bool {
+        !self.max_amounts.is_empty() || !self.daily_limits.is_empty() || !self.allowed_destinations.is_empty()
+    }
+
+    // Amount of an asset sent during the last 24 hours
+    pub fn get_daily_spent(&self, asset: &Hash, now: TimestampSeconds) -> u64 {
+        self.spendings.iter()
+            .filter(|(timestamp, spent_asset, _)| spent_asset == asset && timestamp.saturating_add(XSWD_SPENDING_PERIOD) > now)
+            .fold(0u64, |total, (_, _, amount)| total.saturating_add(*amount))
+    }
+
+    // Verify a request against the limits
+    // calls is the number of calls to the method during the last minute, including this one
+    // Returns the funds sent by the request even if a limit is exceeded
+    pub fn verify_request(&self, method: &str, params: &Option<Value>, calls: usize, now: TimestampSeconds) -> (Option<RequestSpending>, Result<(), PolicyError>) {
+        let (spending, result) = match RequestSpending::from_request(method, params) {
+            Ok(spending) => (spending, Ok(())),
+            // Without spending limits, the funds sent are not needed
+            Err(_) if !self.has_spending_limits() => (None, Ok(())),
+            Err(e) => (None, Err(e))
+        };
+
+        if let Some(limit) = self.rate_limits.get(method) {
+            if calls > *limit as usize {
+                return (spending, Err(PolicyError::RateLimitExceeded(method.to_owned(), *limit)))
+            }
+        }
+
+        if result.is_err() {
+            return (spending, result)
+        }
+
+        let result = match spending.as_ref() {
+            Some(spending) => self.verify_spending(spending, now),
+            None => Ok(())
+        };
+        (spending, result)
+    }
+
+    fn verify_spending(&self, spending: &RequestSpending, now: TimestampSeconds) -> Result<(), PolicyError> {
+        if !self.allowed_destinations.is_empty() {
+            if spending.burn {
+                return Err(PolicyError::DestinationNotAllowed("burn".to_owned()))
+            }
+
+            if let Some(destination) = spending.destinations.iter().find(|destination| !self.allowed_destinations.contains(destination.get_public_key())) {
+                return Err(PolicyError::DestinationNotAllowed(destination.to_string()))
+            }
+        }
+
+        for (asset, amount) in spending.amounts.iter() {
+            if let Some(max) = self.max_amounts.get(asset) {
+                if amount > max {
+                    return Err(PolicyError::MaxAmountExceeded(asset.clone(), *amount, *max))
+                }
+            }
+
+            if let Some(limit) = self.daily_limits.get(asset) {
+                let spent = self.get_daily_spent(asset, now);
+                if spent.saturating_add(*amount) > *limit {
+                    return Err(PolicyError::DailyLimitExceeded(asset.clone(), *amount, *limit, spent))
+                }
+            }
+        }
+
+        // The fee paid could exceed the limits of the native asset
+        if spending.unknown_fee {
+            return Err(PolicyError::UnverifiableFee(spending.method.clone()))
+        }
+
+        Ok(())
+    }
+
+    // Save the amounts sent for the daily limits
+    // Spendings older than 24 hours are removed
+    pub fn record_spending(&mut self, amounts: &HashMap<Hash, u64>, now: TimestampSeconds) {
+        self.spendings.retain(|(timestamp, asset, _)| timestamp.saturating_add(XSWD_SPENDING_PERIOD) > now && self.daily_limits.contains_key(asset));
+        for (asset, amount) in amounts {
+            if self.daily_limits.contains_key(asset) {
+                self.spendings.push((now, asset.clone(), *amount));
+            }
+        }
+    }
+}
+
+impl Serializer for ApplicationPolicy {
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let count = reader.read_u16()?;
+        let mut max_amounts = HashMap::with_capacity(count as usize);
+        for _ in 0..count {
+            max_amounts.insert(Hash::read(reader)?, reader.read_u64()?);
+        }
+
+        let count = reader.read_u16()?;
+        let mut daily_limits = HashMap::with_capacity(count as usize);
+        for _ in 0..count {
+            daily_limits.insert(Hash::read(reader)?, reader.read_u64()?);
+        }
+
+        let count = reader.read_u16()?;
+        let mut allowed_destinations = HashSet::with_capacity(count as usize);
+        for _ in 0..count {
+            allowed_destinations.insert(PublicKey::read(reader)?);
+        }
+
+        let count = reader.read_u16()?;
+        let mut rate_limits = HashMap::with_capacity(count as usize);
+        for _ in 0..count {
+            rate_limits.insert(reader.read_string()?, reader.read_u16()?);
+        }
+
+        let count = reader.read_u32()?;
+        let mut spendings = Vec::new();
+        for _ in 0..count {
+            spendings.push((reader.read_u64()?, Hash::read(reader)?, reader.read_u64()?));
+        }
+
+        Ok(Self {
+            max_amounts,
+            daily_limits,
+            allowed_destinations,
+            rate_limits,
+            spendings
+        })
+    }
+
+    fn write(&self, writer: &mut Writer) {
+        writer.write_u16(self.max_amounts.len() as u16);
+        for (asset, amount) in &self.max_amounts {
+            asset.write(writer);
+            writer.write_u64(amount);
+        }
+
+        writer.write_u16(self.daily_limits.len() as u16);
+        for (asset, amount) in &self.daily_limits {
+            asset.write(writer);
+            writer.write_u64(amount);
+        }
+
+        writer.write_u16(self.allowed_destinations.len() as u16);
+        for destination in &self.allowed_destinations {
+            destination.write(writer);
+        }
+
+        writer.write_u16(self.rate_limits.len() as u16);
+        for (method, limit) in &self.rate_limits {
+            writer.write_string(method);
+            writer.write_u16(*limit);
+        }
+
+        writer.write_u32(&(self.spendings.len() as u32));
+        for (timestamp, asset, amount) in &self.spendings {
+            writer.write_u64(timestamp);
+            asset.write(writer);
+            writer.write_u64(amount);
+        }
+    }
+
+    fn size(&self) -> usize {
+        2 + self.max_amounts.keys().map(|asset| asset.size() + 8).sum::<usize>()
+            + 2 + self.daily_limits.keys().map(|asset| asset.size() + 8).sum::<usize>()
+            + 2 + self.allowed_destinations.iter().map(|destination| destination.size()).sum::<usize>()
+            + 2 + self.rate_limits.keys().map(|method| method.size() + 2).sum::<usize>()
+            + 4 + self.spendings.iter().map(|(_, asset, _)| 8 + asset.size() + 8).sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+    use serde_json::json;
+    use vyridium_common::{
+        config::VYRIDIUM_ASSET,
+        crypto::{Address, KeyPair},
+        serializer::Serializer
+    };
+    use crate::config::{XSWD_PERMISSIONS_EXPIRATION, XSWD_SPENDING_PERIOD};
+    use super::{ApplicationPermissions, ApplicationPolicy, Permission, PolicyError};
+
+    #[test]
+    fn test_application_permissions() {
+        let mut permissions = HashMap::new();
+        permissions.insert("get_balance".to_owned(), Permission::AcceptAlways);
+        permissions.insert("build_transaction".to_owned(), Permission::DenyAlways);
+
+        let url = Some("https://example.com".to_owned());
+        let app = ApplicationPermissions::new("00".repeat(32), "Example".to_owned(), url.clone(), permissions, 1000);
+        assert!(!app.is_expired(1000 + XSWD_PERMISSIONS_EXPIRATION - 1));
+        assert!(app.is_expired(1000 + XSWD_PERMISSIONS_EXPIRATION));
+        assert!(app.is_same_application(&"Example".to_owned(), &url));
+        assert!(!app.is_same_application(&"Example".to_owned(), &None));
+
+        let bytes = app.to_bytes();
+        assert_eq!(bytes.len(), app.size());
+        let read = ApplicationPermissions::from_bytes(&bytes).unwrap();
+        assert_eq!(read.get_permissions(), app.get_permissions());
+        assert_eq!(read.get_expire_at(), app.get_expire_at());
+    }
+
+    #[test]
+    fn test_application_policy() {
+        let allowed = KeyPair::new().get_public_key().to_address(false);
+        let other = KeyPair::new().get_public_key().to_address(false);
+        let mut policy = ApplicationPolicy::default();
+        policy.set_max_amount(VYRIDIUM_ASSET, Some(100));
+        policy.set_daily_limit(VYRIDIUM_ASSET, Some(150));
+        policy.set_allowed_destinations(HashSet::from([allowed.get_public_key().clone()]));
+        policy.set_rate_limits(HashMap::from([("build_transaction".to_owned(), 2)]));
+
+        let transfer = |destination: &Address, amount: u64| Some(json!({
+            "transfers": [{ "asset": VYRIDIUM_ASSET, "amount": amount, "destination": destination }],
+            "broadcast": true
+        }));
+
+        let (spending, result) = policy.verify_request("build_transaction", &transfer(&allowed, 100), 1, 1000);
+        assert_eq!(result, Ok(()));
+        policy.record_spending(spending.unwrap().get_amounts(), 1000);
+
+        let (_, result) = policy.verify_request("build_transaction", &transfer(&allowed, 101), 1, 1000);
+        assert_eq!(result, Err(PolicyError::MaxAmountExceeded(VYRIDIUM_ASSET, 101, 100)));
+        let (_, result) = policy.verify_request("build_transaction", &transfer(&allowed, 60), 1, 1000);
+        assert_eq!(result, Err(PolicyError::DailyLimitExceeded(VYRIDIUM_ASSET, 60, 150, 100)));
+        let (_, result) = policy.verify_request("build_transaction", &transfer(&other, 10), 1, 1000);
+        assert_eq!(result, Err(PolicyError::DestinationNotAllowed(other.to_string())));
+        let (_, result) = policy.verify_request("build_transaction", &transfer(&allowed, 10), 3, 1000);
+        assert_eq!(result, Err(PolicyError::RateLimitExceeded("build_transaction".to_owned(), 2)));
+
+        // a fixed fee is spent in the native asset
+        let mut request = transfer(&allowed, 10).unwrap();
+        request["fee"] = json!({ "value": 95 });
+        let (_, result) = policy.verify_request("build_transaction", &Some(request), 1, 1000);
+        assert_eq!(result, Err(PolicyError::MaxAmountExceeded(VYRIDIUM_ASSET, 105, 100)));
+        // a computed fee can't be verified
+        let mut request = transfer(&allowed, 10).unwrap();
+        request["fee"] = json!({ "multiplier": 1000.0 });
+        let (_, result) = policy.verify_request("build_transaction", &Some(request), 1, 1000);
+        assert_eq!(result, Err(PolicyError::UnverifiableFee("build_transaction".to_owned())));
+        // a burn has no allowed destination
+        let burn = Some(json!({ "burn": { "asset": VYRIDIUM_ASSET, "amount": 10 }, "broadcast": true }));
+        let (_, result) = policy.verify_request("build_transaction", &burn, 1, 1000);
+        assert_eq!(result, Err(PolicyError::DestinationNotAllowed("burn".to_owned())));
+
+        let (_, result) = policy.verify_request("schedule_payment", &None, 1, 1000);
+        assert_eq!(result, Err(PolicyError::UnverifiableSpending("schedule_payment".to_owned())));
+        let (_, result) = policy.verify_request("get_balance", &None, 1, 1000);
+        assert_eq!(result, Ok(()));
+
+        // spending is no longer counted after 24 hours
+        assert_eq!(policy.get_daily_spent(&VYRIDIUM_ASSET, 1000 + XSWD_SPENDING_PERIOD), 0);
+        let (_, result) = policy.verify_request("build_transaction", &transfer(&allowed, 60), 1, 1000 + XSWD_SPENDING_PERIOD);
+        assert_eq!(result, Ok(()));
+
+        let bytes = policy.to_bytes();
+        assert_eq!(bytes.len(), policy.size());
+        let read = ApplicationPolicy::from_bytes(&bytes).unwrap();
+        assert_eq!(read.get_daily_spent(&VYRIDIUM_ASSET, 1000), 100);
+        assert_eq!(read.get_allowed_destinations().len(), 1);
+        assert_eq!(read.get_rate_limits(), policy.get_rate_limits());
+    }
+}
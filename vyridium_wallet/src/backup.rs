@@ -0,0 +1,256 @@
+use rand::{rngs::OsRng, RngCore};
+use vyridium_common::{
+    network::Network,
+    serializer::{Reader, ReaderError, Serializer, Writer},
+    time::TimestampSeconds
+};
+use crate::{
+    cipher::Cipher,
+    config::SALT_SIZE,
+    error::WalletError,
+    wallet::hash_password
+};
+
+// Magic bytes at the start of a backup file
+const BACKUP_MAGIC: &[u8; 8] = b"VYRIBKUP";
+// Current version of the backup file format
+pub const BACKUP_VERSION: u8 = 1;
+
+// Raw content of a storage tree
+// Keys and values are kept encrypted by the storage key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupTree {
+    pub name: Vec<u8>,
+    pub entries: Vec<(Vec<u8>, Vec<u8>)>
+}
+
+// Full copy of a wallet storage
+// It contains all the accounts with their keys, history, assets, sync state
+// and the custom trees used through the `store` API
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalletBackup {
+    network: Network,
+    created_at: TimestampSeconds,
+    // Topoheight until which the active account was synced
+    synced_topoheight: Option<u64>,
+    // Key and salt used to encrypt the storage
+    storage_key: Vec<u8>,
+    storage_salt: [u8; SALT_SIZE],
+    trees: Vec<BackupTree>
+}
+
+fn write_blob(writer: &mut Writer, bytes: &[u8]) {
+    writer.write_u32(&(bytes.len() as u32));
+    writer.write_bytes(bytes);
+}
+
+fn read_blob(reader: &mut Reader) -> Result<Vec<u8>, ReaderError> {
+    let len = reader.read_u32()? as usize;
+    Ok(reader.read_bytes_ref(len)?.to_vec())
+}
+
+impl WalletBackup {
+    pub fn new(network: Network, created_at: TimestampSeconds, synced_topoheight: Option<u64>, storage_key: Vec<u8>, storage_salt: [u8; SALT_SIZE], trees: Vec<BackupTree>) -> Self {
+        Self {
+            network,
+            created_at,
+            synced_topoheight,
+            storage_key,
+            storage_salt,
+            trees
+        }
+    }
+
+    pub fn get_network(&self) -> Network {
+        self.network
+    }
+
+    pub fn get_created_at(&self) -> TimestampSeconds {
+        self.created_at
+    }
+
+    pub fn get_synced_topoheight(&self) -> Option<u64> {
+        self.synced_topoheight
+    }
+
+    pub fn get_storage_key(&self) -> &[u8] {
+        &self.storage_key
+    }
+
+    pub fn get_storage_salt(&self) -> &[u8; SALT_SIZE] {
+        &self.storage_salt
+    }
+
+    pub fn get_trees(&self) -> &Vec<BackupTree> {
+        &self.trees
+    }
+
+    // Encrypt the backup using a key derived from the password
+    // Magic bytes, version and password salt are kept in plaintext before the encrypted content
+    pub fn encrypt(&self, password: String) -> Result<Vec<u8>, WalletError> {
+        let mut salt = [0u8; SALT_SIZE];
+        OsRng.fill_bytes(&mut salt);
+
+        let hashed_password = hash_password(password, &salt)?;
+        let cipher = Cipher::new(&hashed_password, None)?;
+        let encrypted = cipher.encrypt_value(&self.to_bytes())?;
+
+        let mut writer = Writer::new();
+        writer.write_bytes(BACKUP_MAGIC);
+        writer.write_u8(BACKUP_VERSION);
+        writer.write_bytes(&salt);
+        writer.write_bytes(&encrypted);
+        Ok(writer.bytes())
+    }
+
+    // Decrypt a backup file content using its password
+    pub fn decrypt(bytes: &[u8], password: String) -> Result<Self, WalletError> {
+        let mut reader = Reader::new(bytes);
+        let magic: [u8; 8] = reader.read_bytes(BACKUP_MAGIC.len()).map_err(|_| WalletError::InvalidBackup)?;
+        if magic != *BACKUP_MAGIC {
+            return Err(WalletError::InvalidBackup)
+        }
+
+        let version = reader.read_u8().map_err(|_| WalletError::InvalidBackup)?;
+        if version != BACKUP_VERSION {
+            return Err(WalletError::UnsupportedBackupVersion(version))
+        }
+
+        let salt: [u8; SALT_SIZE] = reader.read_bytes(SALT_SIZE).map_err(|_| WalletError::InvalidBackup)?;
+        let encrypted = reader.read_bytes_ref(reader.size()).map_err(|_| WalletError::InvalidBackup)?;
+
+        let hashed_password = hash_password(password, &salt)?;
+        let cipher = Cipher::new(&hashed_password, None)?;
+        let decrypted = cipher.decrypt_value(encrypted).map_err(|_| WalletError::InvalidBackupPassword)?;
+
+        Self::from_bytes(&decrypted).map_err(|_| WalletError::InvalidBackup)
+    }
+}
+
+impl Serializer for WalletBackup {
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let network = Network::read(reader)?;
+        let created_at = reader.read_u64()?;
+        let synced_topoheight = Option::read(reader)?;
+        let storage_key = read_blob(reader)?;
+        let storage_salt = reader.read_bytes(SALT_SIZE)?;
+
+        let trees_count = reader.read_u32()?;
+        let mut trees = Vec::new();
+        for _ in 0..trees_count {
+            let name = read_blob(reader)?;
+            let entries_count = reader.read_u32()?;
+            let mut entries = Vec::new();
+            for _ in 0..entries_count {
+                entries.push((read_blob(reader)?, read_blob(reader)?));
+            }
+            trees.push(BackupTree { name, entries });
+        }
+
+        Ok(Self {
+            network,
+            created_at,
+            synced_topoheight,
+            storage_key,
+            storage_salt,
+            trees
+        })
+    }
+
+    fn write(&self, writer: &mut Writer) {
+        self.network.write(writer);
+        writer.write_u64(&self.created_at);
+        self.synced_topoheight.write(writer);
+        write_blob(writer, &self.storage_key);
+        writer.write_bytes(&self.storage_salt);
+
+        writer.write_u32(&(self.trees.len() as u32));
+        for tree in &self.trees {
+            write_blob(writer, &tree.name);
+            writer.write_u32(&(tree.entries.len() as u32));
+            for (key, value) in &tree.entries {
+                write_blob(writer, key);
+                write_blob(writer, value);
+            }
+        }
+    }
+
+    fn size(&self) -> usize {
+        let trees_size: usize = self.trees.iter()
+            .map(|tree| 4 + tree.name.len() + 4 + tree.entries.iter().map(|(key, value)| 4 + key.len() + 4 + value.len()).sum::<usize>())
+            .sum();
+
+        self.network.size() + 8 + self.synced_topoheight.size() + 4 + self.storage_key.len() + SALT_SIZE + 4 + trees_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use vyridium_common::{
+        crypto::Hash,
+        network::Network,
+        serializer::Serializer,
+        tokio::runtime::Runtime
+    };
+    use crate::{
+        config::SALT_SIZE,
+        entry::{EntryData, TransactionEntry},
+        error::WalletError,
+        precomputed_tables::PrecomputedTables,
+        wallet::Wallet
+    };
+    use super::{BackupTree, WalletBackup};
+
+    #[test]
+    fn test_backup_encryption() {
+        let tree = BackupTree {
+            name: vec![1u8; 32],
+            entries: vec![(vec![2u8; 32], vec![3u8; 64])]
+        };
+        let backup = WalletBackup::new(Network::Testnet, 1000, Some(42), vec![4u8; 32], [5u8; SALT_SIZE], vec![tree]);
+        assert_eq!(backup.to_bytes().len(), backup.size());
+
+        let encrypted = backup.encrypt("password".to_owned()).unwrap();
+        assert_eq!(WalletBackup::decrypt(&encrypted, "password".to_owned()).unwrap(), backup);
+        assert!(matches!(WalletBackup::decrypt(&encrypted, "wrong".to_owned()), Err(WalletError::InvalidBackupPassword)));
+
+        // unknown version
+        let mut invalid = encrypted.clone();
+        invalid[8] = 0;
+        assert!(matches!(WalletBackup::decrypt(&invalid, "password".to_owned()), Err(WalletError::UnsupportedBackupVersion(0))));
+
+        // not a backup file
+        assert!(matches!(WalletBackup::decrypt(&encrypted[1..], "password".to_owned()), Err(WalletError::InvalidBackup)));
+    }
+
+    #[test]
+    fn test_restore_backup() {
+        let dir = format!("{}/vyridium-backup-{:x}", std::env::temp_dir().display(), rand::random::<u64>());
+        // tables are not used as no balance is decrypted
+        let precomputed_tables = Arc::new(PrecomputedTables::with_bytes(&[], 0));
+        let hash = Hash::new([1u8; 32]);
+
+        Runtime::new().unwrap().block_on(async {
+            let wallet = Wallet::create(format!("{}/wallet", dir), "password".to_owned(), None, Network::Testnet, precomputed_tables.clone()).unwrap();
+            {
+                let mut storage = wallet.get_storage().write().await;
+                storage.save_transaction(&hash, &TransactionEntry::new(hash.clone(), 10, EntryData::Coinbase { reward: 50 })).unwrap();
+                storage.set_synced_topoheight(10).unwrap();
+            }
+
+            let backup = wallet.export_backup("password".to_owned(), "backup".to_owned()).await.unwrap();
+            drop(wallet);
+
+            assert!(Wallet::restore(format!("{}/mainnet", dir), "restored".to_owned(), &backup, "backup".to_owned(), Network::Mainnet, precomputed_tables.clone()).is_err());
+
+            // history and sync state are kept, so no rescan is needed
+            let restored = Wallet::restore(format!("{}/restored", dir), "restored".to_owned(), &backup, "backup".to_owned(), Network::Testnet, precomputed_tables).unwrap();
+            let storage = restored.get_storage().read().await;
+            assert_eq!(storage.get_synced_topoheight().unwrap(), 10);
+            assert_eq!(storage.get_transaction(&hash).unwrap().get_topoheight(), 10);
+        });
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,146 @@
+use thiserror::Error;
+use vyridium_common::{
+    crypto::PublicKey,
+    transaction::{
+        builder::TransferBuilder,
+        extra_data::ExtraData,
+        EXTRA_DATA_LIMIT_SIZE,
+        EXTRA_DATA_LIMIT_SUM_SIZE,
+        MAX_TRANSFER_COUNT
+    }
+};
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum BatchError {
+    #[error("Amount must be greater than zero")]
+    ZeroAmount,
+    #[error("Destination is not on the wallet network")]
+    InvalidNetwork,
+    #[error("Destination is the wallet itself")]
+    SenderIsReceiver,
+    #[error("Extra data can't be set with an integrated address")]
+    ExtraDataAndIntegratedAddress,
+    #[error("Extra data is too big: {} bytes, max is {} bytes", _0, EXTRA_DATA_LIMIT_SIZE)]
+    ExtraDataTooBig(usize)
+}
+
+// Recipients packed in a single transaction
+// Each transfer is kept with its index in the requested list
+pub type BatchChunk = Vec<(usize, TransferBuilder)>;
+
+// Estimated size of the encrypted extra data of a transfer
+// Integrated data of the destination is counted as extra data
+fn get_extra_data_size(transfer: &TransferBuilder) -> usize {
+    transfer.extra_data.as_ref()
+        .or(transfer.destination.get_extra_data())
+        .map_or(0, ExtraData::estimate_size)
+}
+
+// Verify that a transfer can be included in a transaction
+pub fn verify_transfer(transfer: &TransferBuilder, source: &PublicKey, mainnet: bool) -> Result<(), BatchError> {
+    if transfer.amount == 0 {
+        return Err(BatchError::ZeroAmount)
+    }
+
+    if transfer.destination.is_mainnet() != mainnet {
+        return Err(BatchError::InvalidNetwork)
+    }
+
+    if transfer.destination.get_public_key() == source {
+        return Err(BatchError::SenderIsReceiver)
+    }
+
+    if transfer.extra_data.is_some() && !transfer.destination.is_normal() {
+        return Err(BatchError::ExtraDataAndIntegratedAddress)
+    }
+
+    let size = get_extra_data_size(transfer);
+    if size > EXTRA_DATA_LIMIT_SIZE {
+        return Err(BatchError::ExtraDataTooBig(size))
+    }
+
+    Ok(())
+}
+
+// Split the transfers in the minimum of transactions in the requested order
+// A transaction can't have more than MAX_TRANSFER_COUNT transfers
+// and its extra data can't exceed EXTRA_DATA_LIMIT_SUM_SIZE bytes
+pub fn split_transfers(transfers: impl IntoIterator<Item = (usize, TransferBuilder)>) -> Vec<BatchChunk> {
+    let mut chunks = Vec::new();
+    let mut chunk: BatchChunk = Vec::new();
+    let mut extra_data_size = 0;
+    for (index, transfer) in transfers {
+        let size = get_extra_data_size(&transfer);
+        if chunk.len() >= MAX_TRANSFER_COUNT || extra_data_size + size > EXTRA_DATA_LIMIT_SUM_SIZE {
+            chunks.push(std::mem::take(&mut chunk));
+            extra_data_size = 0;
+        }
+
+        extra_data_size += size;
+        chunk.push((index, transfer));
+    }
+
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use vyridium_common::{
+        api::{DataElement, DataValue},
+        config::VYRIDIUM_ASSET,
+        crypto::KeyPair
+    };
+    use super::*;
+
+    fn transfer(extra_data: Option<DataElement>) -> TransferBuilder {
+        TransferBuilder {
+            asset: VYRIDIUM_ASSET,
+            amount: 1,
+            destination: KeyPair::new().get_public_key().to_address(false),
+            extra_data
+        }
+    }
+
+    #[test]
+    fn test_split_by_transfer_count() {
+        let transfers = (0..MAX_TRANSFER_COUNT * 2 + 1).map(|i| (i, transfer(None)));
+        let chunks = split_transfers(transfers);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].len(), MAX_TRANSFER_COUNT);
+        assert_eq!(chunks[2].len(), 1);
+        assert_eq!(chunks[2][0].0, MAX_TRANSFER_COUNT * 2);
+    }
+
+    #[test]
+    fn test_split_by_extra_data_size() {
+        let data = DataElement::Value(DataValue::Blob(vec![0u8; 900]));
+        let size = ExtraData::estimate_size(&data);
+        let per_chunk = EXTRA_DATA_LIMIT_SUM_SIZE / size;
+
+        let transfers = (0..per_chunk + 1).map(|i| (i, transfer(Some(data.clone()))));
+        let chunks = split_transfers(transfers);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].len(), per_chunk);
+    }
+
+    #[test]
+    fn test_verify_transfer() {
+        let keypair = KeyPair::new();
+        let source = keypair.get_public_key().compress();
+        assert_eq!(verify_transfer(&transfer(None), &source, false), Ok(()));
+        assert_eq!(verify_transfer(&transfer(None), &source, true), Err(BatchError::InvalidNetwork));
+
+        let mut own = transfer(None);
+        own.destination = keypair.get_public_key().to_address(false);
+        assert_eq!(verify_transfer(&own, &source, false), Err(BatchError::SenderIsReceiver));
+
+        // plaintext fits but not once encrypted
+        let data = DataElement::Value(DataValue::Blob(vec![0u8; EXTRA_DATA_LIMIT_SIZE - 16]));
+        let size = ExtraData::estimate_size(&data);
+        assert_eq!(verify_transfer(&transfer(Some(data)), &source, false), Err(BatchError::ExtraDataTooBig(size)));
+    }
+}
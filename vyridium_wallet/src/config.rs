@@ -11,6 +11,24 @@ pub const KEY_SIZE: usize = 32;
 pub const DEFAULT_DAEMON_ADDRESS: &str = "http://127.0.0.1:8080";
 // Auto reconnect interval in seconds for Network Handler
 pub const AUTO_RECONNECT_INTERVAL: u64 = 5;
+// Maximum distance between the topoheights of a blocks range requested to the daemon
+pub const MAX_BLOCKS_RANGE: u64 = 20;
+// Maximum size in bytes of a contact label in the address book
+pub const MAX_CONTACT_LABEL_SIZE: usize = 64;
+// Maximum size in bytes of the notes of a contact
+pub const MAX_CONTACT_NOTES_SIZE: usize = 255;
+// Maximum size in bytes of an invoice ID
+pub const MAX_INVOICE_ID_SIZE: usize = 64;
+// Maximum size in bytes of the memo of an invoice
+pub const MAX_INVOICE_MEMO_SIZE: usize = 255;
+// Maximum payments tracked by an invoice, next ones are not applied
+pub const MAX_INVOICE_PAYMENTS: usize = 64;
+// Time in seconds after which the permissions saved for an XSWD application expire
+pub const XSWD_PERMISSIONS_EXPIRATION: u64 = 30 * 24 * 60 * 60;
+// Period in seconds over which the daily spending limit of an XSWD application applies
+pub const XSWD_SPENDING_PERIOD: u64 = 24 * 60 * 60;
+// Period in milliseconds over which the calls to a method are rate limited
+pub const XSWD_RATE_LIMIT_PERIOD: u64 = 60 * 1000;
 
 lazy_static! {
     pub static ref PASSWORD_ALGORITHM: Argon2<'static> = {
@@ -0,0 +1,135 @@
+use vyridium_common::{
+    api::{
+        wallet::ContactEntry as RPCContactEntry,
+        DataElement,
+    },
+    crypto::{Address, PublicKey},
+    serializer::{Reader, ReaderError, Serializer, Writer},
+};
+use crate::config::{MAX_CONTACT_LABEL_SIZE, MAX_CONTACT_NOTES_SIZE};
+
+// Entry of the address book
+#[derive(Debug, Clone)]
+pub struct Contact {
+    // Unique label to identify the contact
+    label: String,
+    // Public key of the contact
+    key: PublicKey,
+    // Data to integrate in the address when sending to this contact
+    integrated_data: Option<DataElement>,
+    // Notes written by the user
+    notes: Option<String>,
+}
+
+impl Contact {
+    // Create a contact from an address
+    // If the address is an integrated address, its data is kept
+    pub fn new(label: String, address: Address, notes: Option<String>) -> Self {
+        let (integrated_data, address) = address.extract_data();
+        Self {
+            label,
+            key: address.to_public_key(),
+            integrated_data,
+            notes,
+        }
+    }
+
+    // Verify the label and notes sizes
+    pub fn is_valid(&self) -> bool {
+        !self.label.trim().is_empty()
+            && self.label.len() <= MAX_CONTACT_LABEL_SIZE
+            && self.notes.as_ref().map_or(true, |notes| notes.len() <= MAX_CONTACT_NOTES_SIZE)
+    }
+
+    pub fn get_label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn get_key(&self) -> &PublicKey {
+        &self.key
+    }
+
+    pub fn get_integrated_data(&self) -> &Option<DataElement> {
+        &self.integrated_data
+    }
+
+    pub fn get_notes(&self) -> &Option<String> {
+        &self.notes
+    }
+
+    // Build the address to use to send funds to this contact
+    // Integrated data is included if set
+    pub fn to_address(&self, mainnet: bool) -> Address {
+        match &self.integrated_data {
+            Some(data) => self.key.clone().to_address_with(mainnet, data.clone()),
+            None => self.key.clone().to_address(mainnet),
+        }
+    }
+
+    // Convert to RPC Contact Entry
+    pub fn serializable(self, mainnet: bool) -> RPCContactEntry {
+        RPCContactEntry {
+            label: self.label,
+            address: self.key.to_address(mainnet),
+            integrated_data: self.integrated_data,
+            notes: self.notes,
+        }
+    }
+}
+
+impl Serializer for Contact {
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let label = reader.read_string()?;
+        let key = PublicKey::read(reader)?;
+        let integrated_data = Option::read(reader)?;
+        let notes = reader.read_optional_string()?;
+
+        Ok(Self {
+            label,
+            key,
+            integrated_data,
+            notes,
+        })
+    }
+
+    fn write(&self, writer: &mut Writer) {
+        writer.write_string(&self.label);
+        self.key.write(writer);
+        self.integrated_data.write(writer);
+        writer.write_optional_string(&self.notes);
+    }
+
+    fn size(&self) -> usize {
+        // notes are written with only one byte for their length
+        let notes_size = 1 + self.notes.as_ref().map_or(0, |notes| notes.len());
+        self.label.size() + self.key.size() + self.integrated_data.size() + notes_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vyridium_common::{
+        api::{DataElement, DataValue},
+        crypto::KeyPair,
+        serializer::Serializer,
+    };
+    use super::Contact;
+
+    #[test]
+    fn test_serializer() {
+        let key = KeyPair::new().get_public_key().compress();
+        let address = key.to_address_with(false, DataElement::Value(DataValue::U64(42)));
+        let contact = Contact::new("alice".to_owned(), address, Some("exchange deposit".to_owned()));
+        assert!(contact.is_valid());
+        assert!(contact.get_integrated_data().is_some());
+
+        let bytes = contact.to_bytes();
+        assert_eq!(bytes.len(), contact.size());
+
+        let read = Contact::from_bytes(&bytes).unwrap();
+        assert_eq!(read.get_label(), "alice");
+        assert_eq!(read.get_key(), contact.get_key());
+        assert_eq!(read.get_notes(), contact.get_notes());
+        assert_eq!(read.to_address(false), contact.to_address(false));
+    }
+}
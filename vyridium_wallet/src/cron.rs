@@ -0,0 +1,184 @@
+use std::str::FromStr;
+use thiserror::Error;
+use vyridium_common::time::TimestampSeconds;
+
+// How many days are searched to find the next run of an expression
+// Enough to reach the next 29 of February
+const MAX_SEARCH_DAYS: u64 = 366 * 8;
+const MINUTES_PER_DAY: u64 = 24 * 60;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum CronError {
+    #[error("Expected 5 fields in cron expression, got {}", _0)]
+    InvalidFieldsCount(usize),
+    #[error("Invalid cron field '{}'", _0)]
+    InvalidField(String)
+}
+
+// Cron expression evaluated in UTC with 5 fields:
+// minute (0-59), hour (0-23), day of month (1-31), month (1-12), day of week (0-7, 0 and 7 are sunday)
+// Each field supports `*`, values `5`, ranges `1-5`, lists `1,15` and steps `*/10` or `0-30/5`
+// Like the standard cron, if both day of month and day of week are restricted,
+// a day matches if any of them matches
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronExpression {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    days_restricted: bool,
+    weekdays_restricted: bool
+}
+
+// Parse a field into a bitmask of the allowed values
+fn parse_field(field: &str, min: u64, max: u64) -> Result<u64, CronError> {
+    let invalid = || CronError::InvalidField(field.to_owned());
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, Some(step.parse::<u64>().map_err(|_| invalid())?)),
+            None => (part, None)
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (start.parse().map_err(|_| invalid())?, end.parse().map_err(|_| invalid())?)
+        } else {
+            let value = range.parse().map_err(|_| invalid())?;
+            // `5/10` means every 10 starting at 5
+            (value, if step.is_some() { max } else { value })
+        };
+
+        if start < min || end > max || start > end || step == Some(0) {
+            return Err(invalid())
+        }
+
+        let step = step.unwrap_or(1);
+        let mut value = start;
+        while value <= end {
+            mask |= 1 << value;
+            value += step;
+        }
+    }
+
+    Ok(mask)
+}
+
+// Convert days since 1970-01-01 to (year, month, day)
+// Algorithm from http://howardhinnant.github.io/date_algorithms.html
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+impl CronExpression {
+    fn matches_day(&self, days: u64) -> bool {
+        let (_, month, day) = civil_from_days(days);
+        if self.months & (1 << month) == 0 {
+            return false
+        }
+
+        // 1970-01-01 was a thursday
+        let weekday = (days + 4) % 7;
+        let day_match = self.days & (1 << day) != 0;
+        let weekday_match = self.weekdays & (1 << weekday) != 0;
+        if self.days_restricted && self.weekdays_restricted {
+            day_match || weekday_match
+        } else {
+            day_match && weekday_match
+        }
+    }
+
+    // Timestamp in seconds of the first run strictly after the one given
+    pub fn next_after(&self, timestamp: TimestampSeconds) -> Option<TimestampSeconds> {
+        let start = timestamp / 60 + 1;
+        let mut days = start / MINUTES_PER_DAY;
+        let mut first_minute = start % MINUTES_PER_DAY;
+        for _ in 0..MAX_SEARCH_DAYS {
+            if self.matches_day(days) {
+                for minute in first_minute..MINUTES_PER_DAY {
+                    if self.hours & (1 << (minute / 60)) != 0 && self.minutes & (1 << (minute % 60)) != 0 {
+                        return Some((days * MINUTES_PER_DAY + minute) * 60)
+                    }
+                }
+            }
+            days += 1;
+            first_minute = 0;
+        }
+
+        None
+    }
+}
+
+impl FromStr for CronExpression {
+    type Err = CronError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields = s.split_whitespace().collect::<Vec<_>>();
+        if fields.len() != 5 {
+            return Err(CronError::InvalidFieldsCount(fields.len()))
+        }
+
+        let mut weekdays = parse_field(fields[4], 0, 7)?;
+        // 7 is also sunday
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays & !(1 << 7)) | 1;
+        }
+
+        Ok(Self {
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            weekdays,
+            days_restricted: fields[2] != "*",
+            weekdays_restricted: fields[4] != "*"
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2024-01-01 00:00:00 UTC, a monday
+    const JAN_1_2024: u64 = 1704067200;
+
+    #[test]
+    fn test_parse() {
+        assert!("*/5 * * * *".parse::<CronExpression>().is_ok());
+        assert!("0 9-17 * 1,6 1-5".parse::<CronExpression>().is_ok());
+        assert_eq!("* * * *".parse::<CronExpression>(), Err(CronError::InvalidFieldsCount(4)));
+        assert_eq!("60 * * * *".parse::<CronExpression>(), Err(CronError::InvalidField("60".to_owned())));
+        assert_eq!("*/0 * * * *".parse::<CronExpression>(), Err(CronError::InvalidField("*/0".to_owned())));
+    }
+
+    #[test]
+    fn test_next_after() {
+        let every_hour: CronExpression = "0 * * * *".parse().unwrap();
+        assert_eq!(every_hour.next_after(JAN_1_2024), Some(JAN_1_2024 + 3600));
+        assert_eq!(every_hour.next_after(JAN_1_2024 - 1), Some(JAN_1_2024));
+
+        // first day of each month at noon
+        let monthly: CronExpression = "0 12 1 * *".parse().unwrap();
+        assert_eq!(monthly.next_after(JAN_1_2024 + 13 * 3600), Some(JAN_1_2024 + 31 * 86400 + 12 * 3600));
+
+        // every friday at midnight, 2024-01-05
+        let friday: CronExpression = "0 0 * * 5".parse().unwrap();
+        assert_eq!(friday.next_after(JAN_1_2024), Some(JAN_1_2024 + 4 * 86400));
+
+        // 29 of february
+        let leap: CronExpression = "0 0 29 2 *".parse().unwrap();
+        assert_eq!(leap.next_after(JAN_1_2024), Some(JAN_1_2024 + (31 + 28) * 86400));
+    }
+}
@@ -1,6 +1,6 @@
 use std::{borrow::Cow, collections::HashSet, sync::Arc};
 
-use anyhow::Result;
+use anyhow::{Error, Result};
 use serde::Serialize;
 use serde_json::Value;
 use vyridium_common::{
@@ -8,6 +8,7 @@ use vyridium_common::{
     json_rpc::{
         WebSocketJsonRPCClient,
         WebSocketJsonRPCClientImpl,
+        JsonRPCError,
         JsonRPCResult,
         EventReceiver
     },
@@ -19,7 +20,9 @@ use vyridium_common::{
         SubmitTransactionParams,
         BlockResponse,
         GetBlockAtTopoHeightParams,
+        GetTopoHeightRangeParams,
         GetTransactionParams,
+        TransactionResponse,
         GetNonceParams,
         GetNonceResult,
         GetAssetsParams,
@@ -37,7 +40,9 @@ use vyridium_common::{
         TransactionOrphanedEvent,
         GetTransactionExecutorParams,
         GetTransactionExecutorResult,
-        GetStableBalanceResult
+        GetStableBalanceResult,
+        EstimateFeeRateParams,
+        EstimateFeeRateResult
     },
     account::VersionedBalance,
     crypto::{
@@ -226,6 +231,16 @@ impl DaemonAPI {
         Ok(block)
     }
 
+    // Both bounds are included, the daemon accepts at most MAX_BLOCKS_RANGE between them
+    pub async fn get_blocks_range_by_topoheight(&self, start_topoheight: u64, end_topoheight: u64) -> Result<Vec<BlockResponse>> {
+        trace!("get_blocks_range_by_topoheight");
+        let blocks = self.client.call_with("get_blocks_range_by_topoheight", &GetTopoHeightRangeParams {
+            start_topoheight: Some(start_topoheight),
+            end_topoheight: Some(end_topoheight)
+        }).await?;
+        Ok(blocks)
+    }
+
     pub async fn get_block_with_txs_at_topoheight(&self, topoheight: u64) -> Result<BlockResponse> {
         trace!("get_block_with_txs_at_topoheight");
         let block = self.client.call_with("get_block_at_topoheight", &GetBlockAtTopoHeightParams {
@@ -243,6 +258,15 @@ impl DaemonAPI {
         Ok(tx)
     }
 
+    // Retrieve the state of a transaction in the daemon mempool and chain
+    pub async fn get_transaction_state(&self, hash: &Hash) -> Result<TransactionResponse<'static>> {
+        trace!("get_transaction_state");
+        let response = self.client.call_with("get_transaction", &GetTransactionParams {
+            hash: Cow::Borrowed(hash)
+        }).await?;
+        Ok(response)
+    }
+
     pub async fn get_transaction_executor(&self, hash: &Hash) -> Result<GetTransactionExecutorResult> {
         trace!("get_transaction_executor");
         let executor = self.client.call_with("get_transaction_executor", &GetTransactionExecutorParams {
@@ -293,6 +317,14 @@ impl DaemonAPI {
         Ok(is_registered)
     }
 
+    pub async fn estimate_fee_rate(&self, target_blocks: u64) -> Result<EstimateFeeRateResult> {
+        trace!("estimate_fee_rate");
+        let estimation = self.client.call_with("estimate_fee_rate", &EstimateFeeRateParams {
+            target_blocks: Some(target_blocks)
+        }).await?;
+        Ok(estimation)
+    }
+
     pub async fn get_stable_topoheight(&self) -> Result<u64> {
         trace!("get_stable_topoheight");
         let topoheight = self.client.call("get_stable_topoheight").await?;
@@ -307,4 +339,18 @@ impl DaemonAPI {
         }).await?;
         Ok(balance)
     }
-}
\ No newline at end of file
+}
+
+// Check if an error is an answer of the daemon to the request
+// and not a failure to communicate with it
+pub fn is_daemon_answer(e: &Error) -> bool {
+    get_daemon_error_code(e).is_some()
+}
+
+// JSON-RPC error code returned by the daemon to the request, if it answered
+pub fn get_daemon_error_code(e: &Error) -> Option<i16> {
+    match e.downcast_ref::<JsonRPCError>() {
+        Some(JsonRPCError::ServerError { code, .. }) => Some(*code),
+        _ => None
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use crate::storage::EncryptedStorage;
 use anyhow::Result;
 use vyridium_common::{
@@ -342,7 +343,17 @@ impl TransactionEntry {
         }
     }
 
-    pub fn summary(&self, mainnet: bool, storage: &EncryptedStorage) -> Result<String> {
+    // Format a key as its address, prefixed by its label if it's a known contact
+    fn format_key(key: &PublicKey, mainnet: bool, labels: &HashMap<PublicKey, String>) -> String {
+        match labels.get(key) {
+            Some(label) => format!("{} ({})", label, key.as_address(mainnet)),
+            None => key.as_address(mainnet).to_string()
+        }
+    }
+
+    // Human readable summary of the entry
+    // Keys found in `labels` are displayed with their contact label
+    pub fn summary(&self, mainnet: bool, storage: &EncryptedStorage, labels: &HashMap<PublicKey, String>) -> Result<String> {
         let entry_str = match self.get_entry() {
             EntryData::Coinbase { reward } => format!("Coinbase {} XELIS", format_vyridium(*reward)),
             EntryData::Burn { asset, amount } => {
@@ -356,7 +367,7 @@ impl TransactionEntry {
                         str.push_str(&format!(
                             "Received {} XELIS from {}",
                             format_vyridium(transfer.get_amount()),
-                            from.as_address(mainnet)
+                            Self::format_key(from, mainnet, labels)
                         ));
                     } else {
                         let decimals = storage.get_asset_decimals(transfer.get_asset())?;
@@ -364,7 +375,7 @@ impl TransactionEntry {
                             "Received {} {} from {}",
                             format_coin(transfer.get_amount(), decimals),
                             transfer.get_asset(),
-                            from.as_address(mainnet)
+                            Self::format_key(from, mainnet, labels)
                         ));
                     }
                 }
@@ -381,7 +392,7 @@ impl TransactionEntry {
                         str.push_str(&format!(
                             "Sent {} XELIS to {}",
                             format_vyridium(transfer.get_amount()),
-                            transfer.get_destination().as_address(mainnet)
+                            Self::format_key(transfer.get_destination(), mainnet, labels)
                         ));
                     } else {
                         let decimals = storage.get_asset_decimals(transfer.get_asset())?;
@@ -389,7 +400,7 @@ impl TransactionEntry {
                             "Sent {} {} to {}",
                             format_coin(transfer.get_amount(), decimals),
                             transfer.get_asset(),
-                            transfer.get_destination().as_address(mainnet)
+                            Self::format_key(transfer.get_destination(), mainnet, labels)
                         ));
                     }
                 }
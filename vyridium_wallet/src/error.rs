@@ -1,11 +1,13 @@
 #[cfg(feature = "network_handler")]
 use super::network_handler::NetworkError;
 use chacha20poly1305::Error as CryptoError;
+use crate::cron::CronError;
 use thiserror::Error;
 #[cfg(feature = "api_server")]
 use vyridium_common::rpc_server::InternalRpcError;
 use vyridium_common::{
     crypto::Hash,
+    network::Network,
     transaction::extra_data::CipherFormatError,
     utils::{format_coin, format_vyridium},
 };
@@ -92,6 +94,38 @@ pub enum WalletError {
     AEADCipherFormatError(#[from] CipherFormatError),
     #[error("No network handler available")]
     NoNetworkHandler,
+    #[error("Account {} was not found", _0)]
+    AccountNotFound(u32),
+    #[error("Account {} is not the active account {}", _0, _1)]
+    AccountNotActive(u32, u32),
+    #[error("Contact {} was not found", _0)]
+    ContactNotFound(String),
+    #[error("Contact {} already exists", _0)]
+    ContactAlreadyExists(String),
+    #[error("Invalid contact label or notes size")]
+    InvalidContact,
+    #[error("Invoice {} was not found", _0)]
+    InvoiceNotFound(String),
+    #[error("Invoice {} already exists", _0)]
+    InvoiceAlreadyExists(String),
+    #[error("Invalid invoice ID, memo or amount")]
+    InvalidInvoice,
+    #[error("Scheduled payment {} was not found", _0)]
+    ScheduledPaymentNotFound(u64),
+    #[error("Invalid scheduled payment amount, interval or extra data")]
+    InvalidScheduledPayment,
+    #[error("Invalid cron expression: {}", _0)]
+    InvalidCronExpression(#[from] CronError),
+    #[error("Invalid backup file")]
+    InvalidBackup,
+    #[error("Unsupported backup version {}", _0)]
+    UnsupportedBackupVersion(u8),
+    #[error("Invalid password provided for this backup")]
+    InvalidBackupPassword,
+    #[error("Backup is for network {} but wallet is on {}", _0, _1)]
+    BackupNetworkMismatch(Network, Network),
+    #[error("XSWD application {} was not found", _0)]
+    XSWDApplicationNotFound(String),
 }
 
 impl WalletError {
@@ -0,0 +1,224 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter}
+};
+use anyhow::Result;
+use serde::Serialize;
+use vyridium_common::{
+    api::{
+        wallet::ExportFormat,
+        DataElement
+    },
+    config::{COIN_DECIMALS, VYRIDIUM_ASSET},
+    crypto::{Address, Hash},
+    time::TimestampMillis,
+    utils::format_coin
+};
+use crate::{
+    entry::{EntryData, TransactionEntry},
+    storage::EncryptedStorage
+};
+
+// Columns of the CSV export, in the same order as the record fields
+const CSV_HEADER: &str = "hash,topoheight,timestamp,direction,counterparty,asset,amount,fee,nonce,extra_data";
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Coinbase,
+    Burn,
+    Incoming,
+    Outgoing
+}
+
+impl Display for Direction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let str = match self {
+            Direction::Coinbase => "coinbase",
+            Direction::Burn => "burn",
+            Direction::Incoming => "incoming",
+            Direction::Outgoing => "outgoing"
+        };
+        write!(f, "{}", str)
+    }
+}
+
+// One line of the exported history
+// A transaction with several transfers is exported as one record per transfer
+#[derive(Serialize, Debug)]
+pub struct HistoryRecord {
+    pub hash: Hash,
+    pub topoheight: u64,
+    // Timestamp of the block at this topoheight, only known when the wallet is online
+    pub timestamp: Option<TimestampMillis>,
+    pub direction: Direction,
+    // Sender for incoming transfers and destination for outgoing ones
+    pub counterparty: Option<Address>,
+    pub asset: Hash,
+    // Amount formatted using the asset decimals
+    pub amount: String,
+    // Fee is only set on the first record of an outgoing transaction
+    // so it is not counted several times
+    pub fee: Option<String>,
+    pub nonce: Option<u64>,
+    pub extra_data: Option<DataElement>
+}
+
+// Decimals of the asset, native asset is not stored in wallet
+fn get_asset_decimals(storage: &EncryptedStorage, asset: &Hash) -> Result<u8> {
+    if *asset == VYRIDIUM_ASSET {
+        return Ok(COIN_DECIMALS)
+    }
+
+    storage.get_asset_decimals(asset)
+}
+
+// Build the history records of the transactions ordered by topoheight
+pub fn build_history_records(storage: &EncryptedStorage, mut transactions: Vec<TransactionEntry>, timestamps: &HashMap<u64, TimestampMillis>, mainnet: bool) -> Result<Vec<HistoryRecord>> {
+    transactions.sort_by(|a, b| a.get_topoheight().cmp(&b.get_topoheight()));
+
+    let mut records = Vec::new();
+    for transaction in transactions {
+        let topoheight = transaction.get_topoheight();
+        let timestamp = timestamps.get(&topoheight).copied();
+        let record = |direction, counterparty, asset: &Hash, amount: String, fee, nonce, extra_data| HistoryRecord {
+            hash: transaction.get_hash().clone(),
+            topoheight,
+            timestamp,
+            direction,
+            counterparty,
+            asset: asset.clone(),
+            amount,
+            fee,
+            nonce,
+            extra_data
+        };
+
+        match transaction.get_entry() {
+            EntryData::Coinbase { reward } => {
+                records.push(record(Direction::Coinbase, None, &VYRIDIUM_ASSET, format_coin(*reward, COIN_DECIMALS), None, None, None));
+            },
+            EntryData::Burn { asset, amount } => {
+                let decimals = get_asset_decimals(storage, asset)?;
+                records.push(record(Direction::Burn, None, asset, format_coin(*amount, decimals), None, None, None));
+            },
+            EntryData::Incoming { from, transfers } => {
+                for transfer in transfers {
+                    let decimals = get_asset_decimals(storage, transfer.get_asset())?;
+                    records.push(record(
+                        Direction::Incoming,
+                        Some(from.as_address(mainnet)),
+                        transfer.get_asset(),
+                        format_coin(transfer.get_amount(), decimals),
+                        None,
+                        None,
+                        transfer.get_extra_data().clone()
+                    ));
+                }
+            },
+            EntryData::Outgoing { transfers, fee, nonce } => {
+                for (i, transfer) in transfers.iter().enumerate() {
+                    let decimals = get_asset_decimals(storage, transfer.get_asset())?;
+                    records.push(record(
+                        Direction::Outgoing,
+                        Some(transfer.get_destination().as_address(mainnet)),
+                        transfer.get_asset(),
+                        format_coin(transfer.get_amount(), decimals),
+                        (i == 0).then(|| format_coin(*fee, COIN_DECIMALS)),
+                        Some(*nonce),
+                        transfer.get_extra_data().clone()
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(records)
+}
+
+// Escape a CSV field if it contains a separator, a quote or a new line
+fn escape_csv(value: &str) -> String {
+    if value.contains(|c: char| matches!(c, ',' | '"' | '\n' | '\r')) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+// Write the records in CSV format with a header line
+// Extra data is written in its JSON format
+pub fn to_csv(records: &[HistoryRecord]) -> Result<String> {
+    let mut csv = String::from(CSV_HEADER);
+    csv.push('\n');
+
+    for record in records {
+        let extra_data = match &record.extra_data {
+            Some(data) => serde_json::to_string(data)?,
+            None => String::new()
+        };
+
+        let fields = [
+            record.hash.to_string(),
+            record.topoheight.to_string(),
+            record.timestamp.map(|v| v.to_string()).unwrap_or_default(),
+            record.direction.to_string(),
+            record.counterparty.as_ref().map(|v| v.to_string()).unwrap_or_default(),
+            record.asset.to_string(),
+            record.amount.clone(),
+            record.fee.clone().unwrap_or_default(),
+            record.nonce.map(|v| v.to_string()).unwrap_or_default(),
+            extra_data
+        ];
+
+        let line = fields.iter()
+            .map(|field| escape_csv(field))
+            .collect::<Vec<_>>()
+            .join(",");
+        csv.push_str(&line);
+        csv.push('\n');
+    }
+
+    Ok(csv)
+}
+
+// Write the records in the requested format
+pub fn export_history_records(records: &[HistoryRecord], format: ExportFormat) -> Result<String> {
+    match format {
+        ExportFormat::Csv => to_csv(records),
+        ExportFormat::Json => Ok(serde_json::to_string_pretty(records)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_csv() {
+        assert_eq!(escape_csv("hello"), "hello");
+        assert_eq!(escape_csv("hello, world"), "\"hello, world\"");
+        assert_eq!(escape_csv("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn test_csv_export() {
+        let record = HistoryRecord {
+            hash: Hash::zero(),
+            topoheight: 10,
+            timestamp: Some(1000),
+            direction: Direction::Burn,
+            counterparty: None,
+            asset: VYRIDIUM_ASSET,
+            amount: format_coin(100, 8),
+            fee: None,
+            nonce: None,
+            extra_data: None
+        };
+
+        let csv = to_csv(&[record]).unwrap();
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(lines[1], format!("{},10,1000,burn,,{},0.00000100,,,", Hash::zero(), VYRIDIUM_ASSET));
+    }
+}
@@ -0,0 +1,341 @@
+use vyridium_common::{
+    api::{
+        wallet::{InvoiceEntry as RPCInvoiceEntry, InvoiceStatus},
+        DataElement,
+        ValueType
+    },
+    crypto::{
+        Hash,
+        PaymentRequest,
+        PublicKey,
+        INVOICE_ID_KEY
+    },
+    serializer::{Reader, ReaderError, Serializer, Writer},
+    time::TimestampSeconds
+};
+use crate::config::{MAX_INVOICE_ID_SIZE, MAX_INVOICE_MEMO_SIZE, MAX_INVOICE_PAYMENTS};
+
+// Retrieve the invoice ID from the extra data of a transfer
+pub fn get_invoice_id(extra_data: &DataElement) -> Option<&String> {
+    extra_data.get_value_by_string_key(INVOICE_ID_KEY.to_owned(), ValueType::String)
+        .and_then(|value| value.as_string().ok())
+}
+
+// Payment requested by the wallet
+// It is paid by transfers having its ID in their extra data
+#[derive(Debug, Clone)]
+pub struct Invoice {
+    id: String,
+    asset: Hash,
+    // Amount requested in atomic units
+    amount: u64,
+    // Amount received in atomic units
+    received: u64,
+    memo: Option<String>,
+    created_at: TimestampSeconds,
+    expire_at: Option<TimestampSeconds>,
+    status: InvoiceStatus,
+    // Transactions that paid this invoice with the amount credited
+    // and the topoheight at which they were executed
+    payments: Vec<(Hash, u64, u64)>
+}
+
+impl Invoice {
+    pub fn new(id: String, asset: Hash, amount: u64, memo: Option<String>, created_at: TimestampSeconds, expire_at: Option<TimestampSeconds>) -> Self {
+        Self {
+            id,
+            asset,
+            amount,
+            received: 0,
+            memo,
+            created_at,
+            expire_at,
+            status: InvoiceStatus::Pending,
+            payments: Vec::new()
+        }
+    }
+
+    // Verify the ID, memo and amount
+    pub fn is_valid(&self) -> bool {
+        !self.id.is_empty()
+            && self.id.len() <= MAX_INVOICE_ID_SIZE
+            && self.amount > 0
+            && self.memo.as_ref().map_or(true, |memo| memo.len() <= MAX_INVOICE_MEMO_SIZE)
+    }
+
+    pub fn get_id(&self) -> &String {
+        &self.id
+    }
+
+    pub fn get_asset(&self) -> &Hash {
+        &self.asset
+    }
+
+    pub fn get_amount(&self) -> u64 {
+        self.amount
+    }
+
+    pub fn get_received(&self) -> u64 {
+        self.received
+    }
+
+    pub fn get_created_at(&self) -> TimestampSeconds {
+        self.created_at
+    }
+
+    pub fn get_status(&self) -> InvoiceStatus {
+        self.status
+    }
+
+    pub fn get_payments(&self) -> &Vec<(Hash, u64, u64)> {
+        &self.payments
+    }
+
+    // Highest topoheight of the transactions that paid it
+    pub fn get_last_payment_topoheight(&self) -> Option<u64> {
+        self.payments.iter().map(|(_, _, topoheight)| *topoheight).max()
+    }
+
+    // Paid and expired invoices don't accept new payments
+    pub fn is_open(&self) -> bool {
+        matches!(self.status, InvoiceStatus::Pending | InvoiceStatus::PartiallyPaid)
+    }
+
+    // Register a payment for this invoice
+    // All the transfers of a transaction must be summed in one payment
+    // Returns false if the payment can't be applied to it
+    pub fn apply_payment(&mut self, tx_hash: &Hash, asset: &Hash, amount: u64, topoheight: u64, now: TimestampSeconds) -> bool {
+        if !self.is_open() || *asset != self.asset || self.payments.len() >= MAX_INVOICE_PAYMENTS || self.payments.iter().any(|(hash, _, _)| hash == tx_hash) {
+            return false
+        }
+
+        self.received = self.received.saturating_add(amount);
+        self.payments.push((tx_hash.clone(), amount, topoheight));
+        self.update_status(now);
+        true
+    }
+
+    // Remove the payment of a transaction that is not executed anymore
+    // The invoice is reopened so the transaction can be applied again once re-executed,
+    // it expires on the next status update if it is not paid until then
+    // Returns false if this transaction didn't pay the invoice
+    pub fn revert_payment(&mut self, tx_hash: &Hash) -> bool {
+        let position = match self.payments.iter().position(|(hash, _, _)| hash == tx_hash) {
+            Some(position) => position,
+            None => return false
+        };
+
+        let (_, amount, _) = self.payments.remove(position);
+        self.received = self.received.saturating_sub(amount);
+        self.status = if self.received > 0 {
+            InvoiceStatus::PartiallyPaid
+        } else {
+            InvoiceStatus::Pending
+        };
+        true
+    }
+
+    // Compute the status based on the received amount and the expiration
+    // Returns true if the status has changed
+    pub fn update_status(&mut self, now: TimestampSeconds) -> bool {
+        let status = if self.received >= self.amount {
+            InvoiceStatus::Paid
+        } else if self.expire_at.map_or(false, |expire_at| now >= expire_at) {
+            InvoiceStatus::Expired
+        } else if self.received > 0 {
+            InvoiceStatus::PartiallyPaid
+        } else {
+            InvoiceStatus::Pending
+        };
+
+        let changed = status != self.status;
+        self.status = status;
+        changed
+    }
+
+    // Payment request to share with the payer
+    pub fn to_payment_request(&self, key: &PublicKey, mainnet: bool) -> PaymentRequest {
+        let mut request = PaymentRequest::new(key.clone().to_address(mainnet));
+        request.asset = Some(self.asset.clone());
+        request.amount = Some(self.amount - self.received.min(self.amount));
+        request.invoice_id = Some(self.id.clone());
+        request.memo = self.memo.clone();
+        request
+    }
+
+    // Convert to RPC Invoice Entry
+    pub fn serializable(self, key: &PublicKey, mainnet: bool) -> RPCInvoiceEntry {
+        let request = self.to_payment_request(key, mainnet);
+        let uri = request.to_uri();
+        let last_payment_topoheight = self.get_last_payment_topoheight();
+        // integrate the invoice ID in the address to pay
+        let address = match request.get_extra_data() {
+            Some(extra_data) => key.clone().to_address_with(mainnet, extra_data),
+            None => request.address
+        };
+        RPCInvoiceEntry {
+            id: self.id,
+            asset: self.asset,
+            amount: self.amount,
+            received: self.received,
+            memo: self.memo,
+            created_at: self.created_at,
+            expire_at: self.expire_at,
+            status: self.status,
+            transactions: self.payments.into_iter().map(|(hash, _, _)| hash).collect(),
+            last_payment_topoheight,
+            address,
+            uri
+        }
+    }
+}
+
+impl Serializer for Invoice {
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let id = reader.read_string()?;
+        let asset = Hash::read(reader)?;
+        let amount = reader.read_u64()?;
+        let received = reader.read_u64()?;
+        let memo = reader.read_optional_string()?;
+        let created_at = reader.read_u64()?;
+        let expire_at = Option::read(reader)?;
+        let status = match reader.read_u8()? {
+            0 => InvoiceStatus::Pending,
+            1 => InvoiceStatus::PartiallyPaid,
+            2 => InvoiceStatus::Paid,
+            3 => InvoiceStatus::Expired,
+            _ => return Err(ReaderError::InvalidValue)
+        };
+        let payments: Vec<(Hash, u64, u64)> = Vec::read(reader)?;
+        if payments.len() > MAX_INVOICE_PAYMENTS {
+            return Err(ReaderError::InvalidSize)
+        }
+
+        Ok(Self {
+            id,
+            asset,
+            amount,
+            received,
+            memo,
+            created_at,
+            expire_at,
+            status,
+            payments
+        })
+    }
+
+    fn write(&self, writer: &mut Writer) {
+        writer.write_string(&self.id);
+        self.asset.write(writer);
+        writer.write_u64(&self.amount);
+        writer.write_u64(&self.received);
+        writer.write_optional_string(&self.memo);
+        writer.write_u64(&self.created_at);
+        self.expire_at.write(writer);
+        writer.write_u8(match self.status {
+            InvoiceStatus::Pending => 0,
+            InvoiceStatus::PartiallyPaid => 1,
+            InvoiceStatus::Paid => 2,
+            InvoiceStatus::Expired => 3
+        });
+        self.payments.write(writer);
+    }
+
+    fn size(&self) -> usize {
+        // memo is written with only one byte for its length
+        let memo_size = 1 + self.memo.as_ref().map_or(0, |memo| memo.len());
+        self.id.size() + self.asset.size() + 8 + 8 + memo_size + 8 + self.expire_at.size() + 1 + self.payments.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vyridium_common::{
+        api::wallet::InvoiceStatus,
+        config::VYRIDIUM_ASSET,
+        crypto::{Hash, KeyPair},
+        serializer::Serializer
+    };
+    use crate::config::MAX_INVOICE_PAYMENTS;
+    use super::{get_invoice_id, Invoice};
+
+    #[test]
+    fn test_invoice_payments() {
+        let mut invoice = Invoice::new("order-1".to_owned(), VYRIDIUM_ASSET, 100, None, 0, Some(1000));
+        assert!(invoice.is_valid());
+        let key = KeyPair::new().get_public_key().compress();
+        let extra_data = invoice.to_payment_request(&key, false).get_extra_data().unwrap();
+        assert_eq!(get_invoice_id(&extra_data), Some(invoice.get_id()));
+
+        // wrong asset is ignored
+        assert!(!invoice.apply_payment(&Hash::new([1u8; 32]), &Hash::new([2u8; 32]), 100, 5, 10));
+
+        assert!(invoice.apply_payment(&Hash::new([1u8; 32]), &VYRIDIUM_ASSET, 40, 5, 10));
+        assert_eq!(invoice.get_status(), InvoiceStatus::PartiallyPaid);
+
+        // same transaction can't be applied twice
+        assert!(!invoice.apply_payment(&Hash::new([1u8; 32]), &VYRIDIUM_ASSET, 40, 5, 10));
+
+        assert!(invoice.apply_payment(&Hash::new([3u8; 32]), &VYRIDIUM_ASSET, 60, 8, 20));
+        assert_eq!(invoice.get_status(), InvoiceStatus::Paid);
+        assert_eq!(invoice.get_received(), 100);
+
+        // a paid invoice never expires
+        assert!(!invoice.update_status(2000));
+
+        let bytes = invoice.to_bytes();
+        assert_eq!(bytes.len(), invoice.size());
+        let read = Invoice::from_bytes(&bytes).unwrap();
+        assert_eq!(read.get_status(), InvoiceStatus::Paid);
+        assert_eq!(read.get_payments().len(), 2);
+        assert_eq!(read.get_last_payment_topoheight(), Some(8));
+    }
+
+    #[test]
+    fn test_invoice_revert_payment() {
+        let mut invoice = Invoice::new("order-3".to_owned(), VYRIDIUM_ASSET, 100, None, 0, Some(1000));
+        assert!(invoice.apply_payment(&Hash::new([1u8; 32]), &VYRIDIUM_ASSET, 30, 5, 10));
+        assert!(invoice.apply_payment(&Hash::new([2u8; 32]), &VYRIDIUM_ASSET, 70, 5, 10));
+        assert_eq!(invoice.get_status(), InvoiceStatus::Paid);
+
+        // orphaned transaction is removed from the received amount
+        assert!(invoice.revert_payment(&Hash::new([2u8; 32])));
+        assert!(!invoice.revert_payment(&Hash::new([2u8; 32])));
+        assert_eq!(invoice.get_received(), 30);
+        assert_eq!(invoice.get_status(), InvoiceStatus::PartiallyPaid);
+
+        // once executed again, it can be applied again even after the expiration
+        assert!(invoice.apply_payment(&Hash::new([2u8; 32]), &VYRIDIUM_ASSET, 70, 12, 2000));
+        assert_eq!(invoice.get_status(), InvoiceStatus::Paid);
+        assert_eq!(invoice.get_last_payment_topoheight(), Some(12));
+
+        // not executed again, it expires
+        assert!(invoice.revert_payment(&Hash::new([2u8; 32])));
+        assert_eq!(invoice.get_last_payment_topoheight(), Some(5));
+        assert!(invoice.update_status(2000));
+        assert_eq!(invoice.get_status(), InvoiceStatus::Expired);
+    }
+
+    #[test]
+    fn test_invoice_max_payments() {
+        let mut invoice = Invoice::new("order-4".to_owned(), VYRIDIUM_ASSET, u64::MAX, None, 0, None);
+        for i in 0..MAX_INVOICE_PAYMENTS {
+            assert!(invoice.apply_payment(&Hash::new([i as u8; 32]), &VYRIDIUM_ASSET, 1, 5, 10));
+        }
+        assert!(!invoice.apply_payment(&Hash::max(), &VYRIDIUM_ASSET, 1, 5, 10));
+
+        let read = Invoice::from_bytes(&invoice.to_bytes()).unwrap();
+        assert_eq!(read.get_payments().len(), MAX_INVOICE_PAYMENTS);
+    }
+
+    #[test]
+    fn test_invoice_expiration() {
+        let mut invoice = Invoice::new("order-2".to_owned(), VYRIDIUM_ASSET, 100, Some("coffee".to_owned()), 0, Some(1000));
+        assert!(!invoice.update_status(999));
+        assert!(invoice.update_status(1000));
+        assert_eq!(invoice.get_status(), InvoiceStatus::Expired);
+
+        // late payments are not applied
+        assert!(!invoice.apply_payment(&Hash::zero(), &VYRIDIUM_ASSET, 100, 5, 1001));
+    }
+}
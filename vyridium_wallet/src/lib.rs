@@ -3,6 +3,15 @@ pub mod wallet;
 pub mod config;
 pub mod cipher;
 pub mod entry;
+pub mod application;
+pub mod backup;
+pub mod batch;
+pub mod contact;
+pub mod cron;
+pub mod export;
+pub mod invoice;
+pub mod pending_transaction;
+pub mod scheduled_payment;
 pub mod mnemonics;
 pub mod transaction_builder;
 pub mod error;
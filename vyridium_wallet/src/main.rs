@@ -4,9 +4,10 @@ use fern::colors::Color;
 use log::{error, info};
 use std::{ops::ControlFlow, path::Path, sync::Arc, time::Duration};
 use vyridium_common::{
+    api::wallet::{ExportFormat, ListTransactionsParams, PaymentSchedule, SchedulePaymentParams},
     async_handler,
     config::{COIN_DECIMALS, VERSION, VYRIDIUM_ASSET},
-    crypto::{ecdlp, Address, Hashable},
+    crypto::{ecdlp, Address, Hash, Hashable},
     network::Network,
     prompt::{
         argument::{Arg, ArgType, ArgumentManager},
@@ -20,7 +21,12 @@ use vyridium_common::{
     },
     utils::{format_coin, format_vyridium},
 };
-use vyridium_wallet::{config::DIR_PATH, wallet::Wallet};
+use vyridium_wallet::{
+    config::DIR_PATH,
+    contact::Contact,
+    export::export_history_records,
+    wallet::Wallet,
+};
 
 #[cfg(feature = "network_handler")]
 use vyridium_wallet::config::DEFAULT_DAEMON_ADDRESS;
@@ -31,8 +37,11 @@ use {
     vyridium_common::{
         prompt::ShareablePrompt,
         rpc_server::RpcRequest,
+        time::get_current_time_in_seconds,
         tokio::{spawn_task, sync::mpsc::UnboundedReceiver},
+        utils::from_coin,
     },
+    std::collections::{HashMap, HashSet},
     vyridium_wallet::{
         api::{AppStateShared, AuthConfig, PermissionResult},
         wallet::XSWDEvent,
@@ -115,6 +124,11 @@ pub struct Config {
     /// Restore wallet using seed
     #[clap(long)]
     seed: Option<String>,
+    /// Restore wallet using a backup file
+    ///
+    /// Only used when the wallet path doesn't exist yet.
+    #[clap(long)]
+    restore_backup: Option<String>,
     /// Network selected for chain
     #[clap(long, value_enum, default_value_t = Network::Mainnet)]
     network: Network,
@@ -211,6 +225,20 @@ async fn main() -> Result<()> {
         let wallet = if p.exists() && p.is_dir() {
             info!("Opening wallet {}", path);
             Wallet::open(path, password, config.network, precomputed_tables)?
+        } else if let Some(backup_path) = config.restore_backup {
+            info!("Restoring wallet {} from backup {}", path, backup_path);
+            let backup = std::fs::read(&backup_path).context("Error while reading backup file")?;
+            let backup_password = prompt
+                .read_input(format!("Enter Password for backup '{}': ", backup_path), true)
+                .await?;
+            Wallet::restore(
+                path,
+                password,
+                &backup,
+                backup_password,
+                config.network,
+                precomputed_tables,
+            )?
         } else {
             info!("Creating a new wallet at {}", path);
             Wallet::create(
@@ -245,6 +273,11 @@ async fn main() -> Result<()> {
             "Recover a wallet using a seed",
             CommandHandler::Async(async_handler!(recover_wallet)),
         ))?;
+        command_manager.add_command(Command::new(
+            "restore",
+            "Restore a wallet from a backup file",
+            CommandHandler::Async(async_handler!(restore_wallet)),
+        ))?;
 
         // Display available commands
         command_manager.display_commands()?;
@@ -296,6 +329,12 @@ async fn xswd_handler(mut receiver: UnboundedReceiver<XSWDEvent>, prompt: Sharea
                     error!("Error while sending permission response back to XSWD");
                 }
             }
+            XSWDEvent::RequestPolicyExceeded(app_state, request, exceeded, callback) => {
+                let res = xswd_handle_policy_exceeded(&prompt, app_state, request, exceeded).await;
+                if callback.send(res).is_err() {
+                    error!("Error while sending permission response back to XSWD");
+                }
+            }
         };
     }
 }
@@ -365,6 +404,44 @@ async fn xswd_handle_request_permission(
     })
 }
 
+#[cfg(feature = "api_server")]
+async fn xswd_handle_policy_exceeded(
+    prompt: &ShareablePrompt,
+    app_state: AppStateShared,
+    request: RpcRequest,
+    exceeded: String,
+) -> Result<PermissionResult, Error> {
+    let params = if let Some(params) = request.params {
+        params.to_string()
+    } else {
+        "".to_string()
+    };
+
+    let message = format!(
+        "XSWD: Request from {}: {}\r\nParams: {}\r\n",
+        app_state.get_name(),
+        request.method,
+        params
+    );
+    let warning = format!("Request exceeds the application policy: {}\r\n", exceeded);
+    let question = "Do you want to allow this request anyway ?\r\n(Y/N): ";
+
+    let answer = prompt
+        .read_valid_str_value(
+            prompt.colorize_string(Color::Blue, &message)
+                + &prompt.colorize_string(Color::BrightYellow, &warning)
+                + &prompt.colorize_str(Color::Blue, question),
+            vec!["y", "n"],
+        )
+        .await?;
+    // Limits are only bypassed for this request
+    if answer == "y" {
+        Ok(PermissionResult::Allow)
+    } else {
+        Ok(PermissionResult::Deny)
+    }
+}
+
 // Apply the config passed in params
 async fn apply_config(
     wallet: &Arc<Wallet>,
@@ -435,6 +512,7 @@ async fn setup_wallet_command_manager(
     command_manager.remove_command("open")?;
     command_manager.remove_command("recover")?;
     command_manager.remove_command("create")?;
+    command_manager.remove_command("restore")?;
 
     // Add wallet commands
     command_manager.add_command(Command::new(
@@ -454,6 +532,12 @@ async fn setup_wallet_command_manager(
         vec![Arg::new("asset", ArgType::Hash)],
         CommandHandler::Async(async_handler!(transfer_all)),
     ))?;
+    command_manager.add_command(Command::with_required_arguments(
+        "batch_payout",
+        "Send the transfers listed in a csv file (address,amount[,asset])",
+        vec![Arg::new("path", ArgType::String)],
+        CommandHandler::Async(async_handler!(batch_payout)),
+    ))?;
     command_manager.add_command(Command::with_required_arguments(
         "burn",
         "Burn amount of asset",
@@ -480,6 +564,23 @@ async fn setup_wallet_command_manager(
         vec![Arg::new("page", ArgType::Number)],
         CommandHandler::Async(async_handler!(history)),
     ))?;
+    command_manager.add_command(Command::with_required_arguments(
+        "export_backup",
+        "Export an encrypted backup of the whole wallet to a file",
+        vec![Arg::new("path", ArgType::String)],
+        CommandHandler::Async(async_handler!(export_backup)),
+    ))?;
+    command_manager.add_command(Command::with_arguments(
+        "export_history",
+        "Export all your transactions to a file (csv or json)",
+        vec![Arg::new("path", ArgType::String)],
+        vec![
+            Arg::new("format", ArgType::String),
+            Arg::new("min_topoheight", ArgType::Number),
+            Arg::new("max_topoheight", ArgType::Number),
+        ],
+        CommandHandler::Async(async_handler!(export_history)),
+    ))?;
     command_manager.add_command(Command::with_optional_arguments(
         "seed",
         "Show seed of selected language",
@@ -496,6 +597,37 @@ async fn setup_wallet_command_manager(
         "Set new nonce",
         CommandHandler::Async(async_handler!(set_nonce)),
     ))?;
+    command_manager.add_command(Command::with_arguments(
+        "contact",
+        "Manage your address book (add, list, remove)",
+        vec![Arg::new("action", ArgType::String)],
+        vec![Arg::new("label", ArgType::String)],
+        CommandHandler::Async(async_handler!(contact)),
+    ))?;
+    command_manager.add_command(Command::with_arguments(
+        "schedule",
+        "Manage your scheduled payments (add, list, pause, resume, cancel)",
+        vec![Arg::new("action", ArgType::String)],
+        vec![Arg::new("id", ArgType::Number)],
+        CommandHandler::Async(async_handler!(schedule)),
+    ))?;
+    command_manager.add_command(Command::with_optional_arguments(
+        "create_account",
+        "Create a new account derived from your seed",
+        vec![Arg::new("name", ArgType::String)],
+        CommandHandler::Async(async_handler!(create_account)),
+    ))?;
+    command_manager.add_command(Command::with_required_arguments(
+        "switch_account",
+        "Switch to another account",
+        vec![Arg::new("account", ArgType::Number)],
+        CommandHandler::Async(async_handler!(switch_account)),
+    ))?;
+    command_manager.add_command(Command::new(
+        "list_accounts",
+        "List all accounts derived from your seed",
+        CommandHandler::Async(async_handler!(list_accounts)),
+    ))?;
 
     #[cfg(feature = "network_handler")]
     {
@@ -544,6 +676,24 @@ async fn setup_wallet_command_manager(
             "Stop the API (XSWD/RPC) Server",
             CommandHandler::Async(async_handler!(stop_api_server)),
         ))?;
+
+        command_manager.add_command(Command::new(
+            "list_applications",
+            "List the XSWD applications with saved permissions",
+            CommandHandler::Async(async_handler!(list_applications)),
+        ))?;
+        command_manager.add_command(Command::with_required_arguments(
+            "revoke_application",
+            "Revoke the permissions of an XSWD application and close its sessions",
+            vec![Arg::new("id", ArgType::String)],
+            CommandHandler::Async(async_handler!(revoke_application)),
+        ))?;
+        command_manager.add_command(Command::with_required_arguments(
+            "set_application_policy",
+            "Set the spending and rate limits of an XSWD application",
+            vec![Arg::new("id", ArgType::String)],
+            CommandHandler::Async(async_handler!(set_application_policy)),
+        ))?;
     }
 
     let mut context = command_manager.get_context().lock()?;
@@ -808,6 +958,76 @@ async fn recover_wallet(manager: &CommandManager, _: ArgumentManager) -> Result<
     Ok(())
 }
 
+// Restore a wallet from a backup file by requesting its path, name and password
+async fn restore_wallet(manager: &CommandManager, _: ArgumentManager) -> Result<(), CommandError> {
+    let prompt = manager.get_prompt();
+
+    let path = prompt
+        .read_input("Backup file: ", false)
+        .await
+        .context("Error while reading backup path")?;
+    let backup = std::fs::read(&path).context("Error while reading backup file")?;
+
+    let backup_password = prompt
+        .read_input("Backup password: ", true)
+        .await
+        .context("Error while reading backup password")?;
+
+    let name = prompt
+        .read_input("Wallet name: ", false)
+        .await
+        .context("Error while reading wallet name")?;
+
+    if name.is_empty() {
+        manager.error("Wallet name cannot be empty");
+        return Ok(());
+    }
+
+    let dir = format!("{}{}", DIR_PATH, name);
+    // check if it doesn't exists yet
+    if Path::new(&dir).is_dir() {
+        manager.message("Wallet already exist with this name!");
+        return Ok(());
+    }
+
+    // ask and verify password
+    let password = prompt
+        .read_input("Password: ", true)
+        .await
+        .context("Error while reading password")?;
+    let confirm_password = prompt
+        .read_input("Confirm Password: ", true)
+        .await
+        .context("Error while reading password")?;
+
+    if password != confirm_password {
+        manager.message("Confirm password doesn't match password");
+        return Ok(());
+    }
+
+    let wallet = {
+        let context = manager.get_context().lock()?;
+        let network = context.get::<Network>()?;
+        let precomputed_tables = Wallet::read_or_generate_precomputed_tables(
+            None,
+            LogProgressTableGenerationReportFunction,
+        )?;
+        Wallet::restore(dir, password, &backup, backup_password, *network, precomputed_tables)?
+    };
+
+    manager.message("Wallet sucessfully restored");
+    apply_config(
+        &wallet,
+        #[cfg(feature = "api_server")]
+        prompt,
+    )
+    .await;
+
+    setup_wallet_command_manager(wallet, manager).await?;
+
+    Ok(())
+}
+
 // Change wallet password
 async fn change_password(manager: &CommandManager, _: ArgumentManager) -> Result<(), CommandError> {
     let context = manager.get_context().lock()?;
@@ -837,6 +1057,25 @@ async fn change_password(manager: &CommandManager, _: ArgumentManager) -> Result
     Ok(())
 }
 
+// Read an address from the prompt
+// A contact label from the address book can be used instead
+async fn read_address(prompt: &Prompt, wallet: &Wallet) -> Result<Address, CommandError> {
+    let input = prompt
+        .read_input(prompt.colorize_str(Color::Green, "Address or contact: "), false)
+        .await
+        .context("Error while reading address")?;
+
+    if let Ok(address) = Address::from_string(&input) {
+        return Ok(address);
+    }
+
+    let storage = wallet.get_storage().read().await;
+    let contact = storage
+        .get_contact(input.trim())
+        .context("Invalid address or unknown contact")?;
+    Ok(contact.to_address(wallet.get_network().is_mainnet()))
+}
+
 // Create a new transfer to a specified address
 async fn transfer(manager: &CommandManager, _: ArgumentManager) -> Result<(), CommandError> {
     let prompt = manager.get_prompt();
@@ -844,11 +1083,7 @@ async fn transfer(manager: &CommandManager, _: ArgumentManager) -> Result<(), Co
     let wallet: &Arc<Wallet> = context.get()?;
 
     // read address
-    let str_address = prompt
-        .read_input(prompt.colorize_str(Color::Green, "Address: "), false)
-        .await
-        .context("Error while reading address")?;
-    let address = Address::from_string(&str_address).context("Invalid address")?;
+    let address = read_address(prompt, wallet).await?;
 
     let asset = prompt
         .read_hash(prompt.colorize_str(Color::Green, "Asset (default XELIS): "))
@@ -910,6 +1145,82 @@ async fn transfer(manager: &CommandManager, _: ArgumentManager) -> Result<(), Co
     Ok(())
 }
 
+// Send all transfers from a csv file in the minimum of transactions
+// Each line is address,amount[,asset] with the amount in the asset decimals
+async fn batch_payout(
+    manager: &CommandManager,
+    mut arguments: ArgumentManager,
+) -> Result<(), CommandError> {
+    let path = arguments.get_value("path")?.to_string_value()?;
+    let content = std::fs::read_to_string(&path).context("Error while reading batch file")?;
+
+    let prompt = manager.get_prompt();
+    let context = manager.get_context().lock()?;
+    let wallet: &Arc<Wallet> = context.get()?;
+
+    let mut transfers = Vec::new();
+    {
+        let storage = wallet.get_storage().read().await;
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let invalid = || CommandError::InvalidArgument(format!("Invalid line {}: {}", i + 1, line));
+            let fields = line.split(',').map(str::trim).collect::<Vec<_>>();
+            if fields.len() < 2 || fields.len() > 3 {
+                return Err(invalid());
+            }
+
+            let destination = Address::from_string(&fields[0].to_owned()).map_err(|_| invalid())?;
+            let float_amount: f64 = fields[1].parse().map_err(|_| invalid())?;
+            let asset = match fields.get(2) {
+                Some(asset) => Hash::from_hex(asset.to_string()).map_err(|_| invalid())?,
+                None => VYRIDIUM_ASSET,
+            };
+
+            let decimals = storage.get_asset_decimals(&asset).unwrap_or(COIN_DECIMALS);
+            let amount = (float_amount * 10u32.pow(decimals as u32) as f64) as u64;
+            transfers.push(TransferBuilder {
+                destination,
+                amount,
+                asset,
+                extra_data: None,
+            });
+        }
+    }
+
+    if transfers.is_empty() {
+        return Err(CommandError::InvalidArgument("No transfers found in batch file".to_owned()));
+    }
+
+    manager.message(format!("Sending {} transfers from {}", transfers.len(), path));
+    if !prompt
+        .ask_confirmation()
+        .await
+        .context("Error while confirming action")?
+    {
+        manager.message("Batch payout has been aborted");
+        return Ok(());
+    }
+
+    let result = wallet
+        .batch_payout(transfers, FeeBuilder::default(), None)
+        .await
+        .context("Error while sending batch payout")?;
+
+    for (i, recipient) in result.recipients.iter().enumerate() {
+        match (&recipient.tx_hash, &recipient.error) {
+            (Some(hash), _) => manager.message(format!("#{}: sent in {}", i + 1, hash)),
+            (None, Some(error)) => manager.warn(format!("#{}: failed: {}", i + 1, error)),
+            (None, None) => manager.warn(format!("#{}: not sent", i + 1)),
+        }
+    }
+    manager.message(format!("{} transactions submitted", result.transactions.len()));
+    Ok(())
+}
+
 // Send the whole balance to a specified address
 async fn transfer_all(
     manager: &CommandManager,
@@ -920,11 +1231,7 @@ async fn transfer_all(
     let wallet: &Arc<Wallet> = context.get()?;
 
     // read address
-    let str_address = prompt
-        .read_input(prompt.colorize_str(Color::Green, "Address: "), false)
-        .await
-        .context("Error while reading address")?;
-    let address = Address::from_string(&str_address).context("Invalid address")?;
+    let address = read_address(prompt, wallet).await?;
 
     let mut asset = args.get_value("asset").and_then(|v| v.to_hash()).ok();
     if asset.is_none() {
@@ -1116,6 +1423,8 @@ async fn history(
         page,
         max_pages
     ));
+    // resolve the contacts labels only once for the whole page
+    let labels = storage.get_contacts_labels()?;
     for tx in transactions
         .iter()
         .skip((page - 1) * TXS_PER_PAGE)
@@ -1123,13 +1432,118 @@ async fn history(
     {
         manager.message(format!(
             "- {}",
-            tx.summary(wallet.get_network().is_mainnet(), &*storage)?
+            tx.summary(wallet.get_network().is_mainnet(), &*storage, &labels)?
         ));
     }
 
     Ok(())
 }
 
+// Export the transactions history to a file
+async fn export_history(
+    manager: &CommandManager,
+    mut arguments: ArgumentManager,
+) -> Result<(), CommandError> {
+    let path = arguments.get_value("path")?.to_string_value()?;
+    let format = if arguments.has_argument("format") {
+        match arguments.get_value("format")?.to_string_value()?.to_lowercase().as_str() {
+            "csv" => ExportFormat::Csv,
+            "json" => ExportFormat::Json,
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "Format must be csv or json".to_owned(),
+                ))
+            }
+        }
+    } else {
+        ExportFormat::Csv
+    };
+
+    let min_topoheight = if arguments.has_argument("min_topoheight") {
+        Some(arguments.get_value("min_topoheight")?.to_number()?)
+    } else {
+        None
+    };
+    let max_topoheight = if arguments.has_argument("max_topoheight") {
+        Some(arguments.get_value("max_topoheight")?.to_number()?)
+    } else {
+        None
+    };
+
+    let filters = ListTransactionsParams {
+        min_topoheight,
+        max_topoheight,
+        address: None,
+        accept_incoming: true,
+        accept_outgoing: true,
+        accept_coinbase: true,
+        accept_burn: true,
+        query: None,
+        account: None,
+    };
+
+    let context = manager.get_context().lock()?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    let records = wallet
+        .get_history_records(&filters)
+        .await
+        .context("Error while building history")?;
+    let content = export_history_records(&records, format)?;
+    std::fs::write(&path, content).context("Error while writing history file")?;
+
+    manager.message(format!("{} records exported to {}", records.len(), path));
+    Ok(())
+}
+
+// Export an encrypted backup of the wallet to a file
+async fn export_backup(
+    manager: &CommandManager,
+    mut arguments: ArgumentManager,
+) -> Result<(), CommandError> {
+    let path = arguments.get_value("path")?.to_string_value()?;
+    let prompt = manager.get_prompt();
+    let context = manager.get_context().lock()?;
+    let wallet: &Arc<Wallet> = context.get()?;
+
+    let password = prompt
+        .read_input(
+            prompt.colorize_str(Color::BrightRed, "Current Password: "),
+            true,
+        )
+        .await
+        .context("Error while asking password")?;
+
+    let backup_password = prompt
+        .read_input(
+            prompt.colorize_str(Color::BrightRed, "Backup Password: "),
+            true,
+        )
+        .await
+        .context("Error while asking backup password")?;
+    let confirm_password = prompt
+        .read_input(
+            prompt.colorize_str(Color::BrightRed, "Confirm Backup Password: "),
+            true,
+        )
+        .await
+        .context("Error while asking backup password")?;
+
+    if backup_password != confirm_password {
+        manager.message("Confirm password doesn't match backup password");
+        return Ok(());
+    }
+
+    manager.message("Exporting backup...");
+    let content = wallet
+        .export_backup(password, backup_password)
+        .await
+        .context("Error while exporting backup")?;
+    std::fs::write(&path, content).context("Error while writing backup file")?;
+
+    manager.message(format!("Backup exported to {}", path));
+    Ok(())
+}
+
 // Set your wallet in online mode
 #[cfg(feature = "network_handler")]
 async fn online_mode(
@@ -1261,6 +1675,298 @@ async fn set_nonce(manager: &CommandManager, _: ArgumentManager) -> Result<(), C
     Ok(())
 }
 
+// Add, list or remove contacts from the address book
+async fn contact(
+    manager: &CommandManager,
+    mut arguments: ArgumentManager,
+) -> Result<(), CommandError> {
+    let prompt = manager.get_prompt();
+    let context = manager.get_context().lock()?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    let mainnet = wallet.get_network().is_mainnet();
+
+    let action = arguments.get_value("action")?.to_string_value()?;
+    let label = if arguments.has_argument("label") {
+        Some(arguments.get_value("label")?.to_string_value()?)
+    } else {
+        None
+    };
+
+    match action.as_str() {
+        "add" => {
+            let label = match label {
+                Some(label) => label,
+                None => prompt
+                    .read_input(prompt.colorize_str(Color::Green, "Label: "), false)
+                    .await
+                    .context("Error while reading label")?,
+            };
+            let str_address = prompt
+                .read_input(prompt.colorize_str(Color::Green, "Address: "), false)
+                .await
+                .context("Error while reading address")?;
+            let address = Address::from_string(&str_address).context("Invalid address")?;
+            if address.is_mainnet() != mainnet {
+                return Err(CommandError::InvalidArgument("Address is not for this network".to_owned()));
+            }
+
+            let notes = prompt
+                .read_input(prompt.colorize_str(Color::Green, "Notes (optional): "), false)
+                .await
+                .context("Error while reading notes")?;
+            let notes = if notes.is_empty() { None } else { Some(notes) };
+
+            let contact = Contact::new(label, address, notes);
+            let mut storage = wallet.get_storage().write().await;
+            storage.add_contact(&contact).context("Error while adding contact")?;
+            manager.message(format!("Contact {} has been added", contact.get_label()));
+        }
+        "list" => {
+            let storage = wallet.get_storage().read().await;
+            let contacts = storage.get_contacts().context("Error while retrieving contacts")?;
+            if contacts.is_empty() {
+                manager.message("No contacts available");
+                return Ok(());
+            }
+
+            manager.message(format!("Contacts ({}):", contacts.len()));
+            for contact in contacts {
+                let mut line = format!("- {}: {}", contact.get_label(), contact.to_address(mainnet));
+                if let Some(notes) = contact.get_notes() {
+                    line.push_str(&format!(" ({})", notes));
+                }
+                manager.message(line);
+            }
+        }
+        "remove" => {
+            let label = match label {
+                Some(label) => label,
+                None => prompt
+                    .read_input(prompt.colorize_str(Color::Green, "Label: "), false)
+                    .await
+                    .context("Error while reading label")?,
+            };
+
+            let mut storage = wallet.get_storage().write().await;
+            storage.delete_contact(&label).context("Error while removing contact")?;
+            manager.message(format!("Contact {} has been removed", label));
+        }
+        _ => {
+            return Err(CommandError::InvalidArgument(format!(
+                "Unknown action {}, expected add, list or remove",
+                action
+            )))
+        }
+    }
+
+    Ok(())
+}
+
+// Use the ID provided in arguments or ask it
+async fn read_scheduled_payment_id(prompt: &Prompt, id: Option<u64>) -> Result<u64, CommandError> {
+    match id {
+        Some(id) => Ok(id),
+        None => Ok(prompt
+            .read(prompt.colorize_str(Color::Green, "Scheduled payment ID: "))
+            .await
+            .context("Error while reading scheduled payment ID")?),
+    }
+}
+
+// Add, list, pause, resume or cancel scheduled payments
+async fn schedule(
+    manager: &CommandManager,
+    mut arguments: ArgumentManager,
+) -> Result<(), CommandError> {
+    let prompt = manager.get_prompt();
+    let context = manager.get_context().lock()?;
+    let wallet: &Arc<Wallet> = context.get()?;
+
+    let action = arguments.get_value("action")?.to_string_value()?;
+    let id = if arguments.has_argument("id") {
+        Some(arguments.get_value("id")?.to_number()?)
+    } else {
+        None
+    };
+    match action.as_str() {
+        "add" => {
+            let destination = read_address(prompt, wallet).await?;
+            let asset = prompt
+                .read_hash(prompt.colorize_str(Color::Green, "Asset (default XELIS): "))
+                .await
+                .ok()
+                .unwrap_or(VYRIDIUM_ASSET);
+            let decimals = {
+                let storage = wallet.get_storage().read().await;
+                storage.get_asset_decimals(&asset).unwrap_or(COIN_DECIMALS)
+            };
+
+            let float_amount: f64 = prompt
+                .read(prompt.colorize_str(Color::Green, "Amount: "))
+                .await
+                .context("Error while reading amount")?;
+            let amount = (float_amount * 10u32.pow(decimals as u32) as f64) as u64;
+
+            let input = prompt
+                .read_input(
+                    prompt.colorize_str(Color::Green, "Schedule (interval in blocks or cron expression): "),
+                    false,
+                )
+                .await
+                .context("Error while reading schedule")?;
+            let schedule = match input.trim().parse() {
+                Ok(interval) => PaymentSchedule::Topoheight { interval },
+                Err(_) => PaymentSchedule::Cron {
+                    expression: input.trim().to_owned(),
+                },
+            };
+
+            let payment = wallet
+                .schedule_payment(SchedulePaymentParams {
+                    destination,
+                    asset: Some(asset),
+                    amount,
+                    extra_data: None,
+                    schedule,
+                    account: None,
+                })
+                .await
+                .context("Error while scheduling payment")?;
+            manager.message(format!(
+                "Payment {} of {} scheduled, next run at {}",
+                payment.id,
+                format_coin(amount, decimals),
+                payment.next_run
+            ));
+        }
+        "list" => {
+            let payments = wallet
+                .list_scheduled_payments()
+                .await
+                .context("Error while retrieving scheduled payments")?;
+            if payments.is_empty() {
+                manager.message("No scheduled payments");
+                return Ok(());
+            }
+
+            let storage = wallet.get_storage().read().await;
+            manager.message(format!("Scheduled payments ({}):", payments.len()));
+            for payment in payments {
+                let decimals = storage.get_asset_decimals(&payment.asset).unwrap_or(COIN_DECIMALS);
+                let schedule = match &payment.schedule {
+                    PaymentSchedule::Topoheight { interval } => format!("every {} blocks", interval),
+                    PaymentSchedule::Cron { expression } => format!("cron '{}'", expression),
+                };
+                let mut line = format!(
+                    "- #{}: {} of {} to {} ({}, next run at {}, {} runs, {} skipped)",
+                    payment.id,
+                    format_coin(payment.amount, decimals),
+                    payment.asset,
+                    payment.destination,
+                    schedule,
+                    payment.next_run,
+                    payment.runs,
+                    payment.skipped
+                );
+                if payment.paused {
+                    line.push_str(" [paused]");
+                }
+                if let Some(error) = payment.last_error {
+                    line.push_str(&format!(" last error: {}", error));
+                }
+                manager.message(line);
+            }
+        }
+        "pause" | "resume" => {
+            let id = read_scheduled_payment_id(prompt, id).await?;
+            wallet
+                .set_scheduled_payment_paused(id, action == "pause")
+                .await
+                .context("Error while updating scheduled payment")?;
+            manager.message(format!("Scheduled payment {} has been {}d", id, action));
+        }
+        "cancel" => {
+            let id = read_scheduled_payment_id(prompt, id).await?;
+            wallet
+                .cancel_scheduled_payment(id)
+                .await
+                .context("Error while cancelling scheduled payment")?;
+            manager.message(format!("Scheduled payment {} has been cancelled", id));
+        }
+        _ => {
+            return Err(CommandError::InvalidArgument(format!(
+                "Unknown action {}, expected add, list, pause, resume or cancel",
+                action
+            )))
+        }
+    }
+
+    Ok(())
+}
+
+async fn create_account(
+    manager: &CommandManager,
+    mut arguments: ArgumentManager,
+) -> Result<(), CommandError> {
+    let name = if arguments.has_argument("name") {
+        Some(arguments.get_value("name")?.to_string_value()?)
+    } else {
+        None
+    };
+
+    let context = manager.get_context().lock()?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    let account = wallet
+        .create_account(name)
+        .await
+        .context("Error while creating account")?;
+
+    manager.message(format!("Account {} created with address {}", account.index, account.address));
+    Ok(())
+}
+
+async fn switch_account(
+    manager: &CommandManager,
+    mut arguments: ArgumentManager,
+) -> Result<(), CommandError> {
+    let account = arguments.get_value("account")?.to_number()?;
+    let account = u32::try_from(account).context("Invalid account index")?;
+
+    let context = manager.get_context().lock()?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    wallet
+        .switch_account(account)
+        .await
+        .context("Error while switching account")?;
+
+    manager.message(format!("Switched to account {} with address {}", account, wallet.get_address()));
+    Ok(())
+}
+
+async fn list_accounts(manager: &CommandManager, _: ArgumentManager) -> Result<(), CommandError> {
+    let context = manager.get_context().lock()?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    let active = wallet.get_active_account().await;
+    let accounts = wallet
+        .list_accounts()
+        .await
+        .context("Error while listing accounts")?;
+
+    manager.message(format!("Accounts ({}):", accounts.len()));
+    for account in accounts {
+        let marker = if account.index == active { "*" } else { " " };
+        manager.message(format!(
+            "{} {} {} {}",
+            marker,
+            account.index,
+            account.name.as_deref().unwrap_or("-"),
+            account.address
+        ));
+    }
+
+    Ok(())
+}
+
 #[cfg(feature = "api_server")]
 async fn stop_api_server(manager: &CommandManager, _: ArgumentManager) -> Result<(), CommandError> {
     let context = manager.get_context().lock()?;
@@ -1294,6 +2000,234 @@ async fn start_rpc_server(
     Ok(())
 }
 
+#[cfg(feature = "api_server")]
+async fn list_applications(manager: &CommandManager, _: ArgumentManager) -> Result<(), CommandError> {
+    let context = manager.get_context().lock()?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    let applications = wallet
+        .list_xswd_applications()
+        .await
+        .context("Error while retrieving applications")?;
+    if applications.is_empty() {
+        manager.message("No applications with saved permissions");
+        return Ok(());
+    }
+
+    let now = get_current_time_in_seconds();
+    manager.message(format!("Applications ({}):", applications.len()));
+    for app in applications {
+        let status = if app.is_expired(now) { "expired" } else { "expire" };
+        manager.message(format!(
+            "- {} ({}): {} permissions, {} at {}",
+            app.get_name(),
+            app.get_id(),
+            app.get_permissions().len(),
+            status,
+            app.get_expire_at()
+        ));
+        if let Some(url) = app.get_url() {
+            manager.message(format!("  {}", url));
+        }
+        for (method, permission) in app.get_permissions() {
+            manager.message(format!("  {}: {:?}", method, permission));
+        }
+
+        let policy = wallet
+            .get_xswd_application_policy(app.get_id())
+            .await
+            .context("Error while retrieving application policy")?;
+        let storage = wallet.get_storage().read().await;
+        for (asset, amount) in policy.get_max_amounts() {
+            let decimals = storage.get_asset_decimals(asset).unwrap_or(COIN_DECIMALS);
+            manager.message(format!(
+                "  Max amount per request of {}: {}",
+                asset,
+                format_coin(*amount, decimals)
+            ));
+        }
+        for (asset, amount) in policy.get_daily_limits() {
+            let decimals = storage.get_asset_decimals(asset).unwrap_or(COIN_DECIMALS);
+            let spent = policy.get_daily_spent(asset, now);
+            manager.message(format!(
+                "  Daily limit of {}: {} ({} spent)",
+                asset,
+                format_coin(*amount, decimals),
+                format_coin(spent, decimals)
+            ));
+        }
+        if !policy.get_allowed_destinations().is_empty() {
+            manager.message(format!(
+                "  Allowed destinations: {}",
+                policy.get_allowed_destinations().len()
+            ));
+        }
+        for (method, limit) in policy.get_rate_limits() {
+            manager.message(format!("  Rate limit of {}: {} calls per minute", method, limit));
+        }
+    }
+
+    Ok(())
+}
+
+// Read a limit of a policy
+// Empty input keeps the current value and 'none' removes it
+#[cfg(feature = "api_server")]
+async fn read_policy_amount(
+    prompt: &Prompt,
+    name: &str,
+    current: Option<u64>,
+    decimals: u8,
+) -> Result<Option<u64>, CommandError> {
+    let current_str = current.map_or("none".to_string(), |amount| format_coin(amount, decimals));
+    let input = prompt
+        .read_input(
+            prompt.colorize_string(
+                Color::Green,
+                &format!("{} (current: {}, 'none' to remove): ", name, current_str),
+            ),
+            false,
+        )
+        .await
+        .context("Error while reading limit")?;
+
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(current);
+    }
+    if input == "none" {
+        return Ok(None);
+    }
+
+    from_coin(input, decimals)
+        .map(Some)
+        .ok_or_else(|| CommandError::InvalidArgument(format!("Invalid amount: {}", input)))
+}
+
+// Update the policy of an XSWD application
+// Amount limits are set for one asset at a time
+#[cfg(feature = "api_server")]
+async fn set_application_policy(
+    manager: &CommandManager,
+    mut arguments: ArgumentManager,
+) -> Result<(), CommandError> {
+    let id = arguments.get_value("id")?.to_string_value()?;
+    let prompt = manager.get_prompt();
+    let context = manager.get_context().lock()?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    let mut policy = wallet
+        .get_xswd_application_policy(&id)
+        .await
+        .context("Error while retrieving application policy")?;
+
+    let asset = prompt
+        .read_hash(prompt.colorize_str(Color::Green, "Asset (default XELIS): "))
+        .await
+        .ok()
+        .unwrap_or(VYRIDIUM_ASSET);
+    let decimals = {
+        let storage = wallet.get_storage().read().await;
+        storage.get_asset_decimals(&asset).unwrap_or(COIN_DECIMALS)
+    };
+
+    let max_amount = read_policy_amount(
+        prompt,
+        "Max amount per request",
+        policy.get_max_amounts().get(&asset).copied(),
+        decimals,
+    )
+    .await?;
+    policy.set_max_amount(asset.clone(), max_amount);
+
+    let daily_limit = read_policy_amount(
+        prompt,
+        "Daily limit",
+        policy.get_daily_limits().get(&asset).copied(),
+        decimals,
+    )
+    .await?;
+    policy.set_daily_limit(asset, daily_limit);
+
+    let input = prompt
+        .read_input(
+            prompt.colorize_string(
+                Color::Green,
+                &format!(
+                    "Allowed destinations, comma separated (current: {}, 'none' to allow any): ",
+                    policy.get_allowed_destinations().len()
+                ),
+            ),
+            false,
+        )
+        .await
+        .context("Error while reading destinations")?;
+    let input = input.trim();
+    if input == "none" {
+        policy.set_allowed_destinations(HashSet::new());
+    } else if !input.is_empty() {
+        let mainnet = wallet.get_network().is_mainnet();
+        let mut destinations = HashSet::new();
+        for value in input.split(',') {
+            let address = Address::from_string(&value.trim().to_string())
+                .ok()
+                .filter(|address| address.is_mainnet() == mainnet)
+                .ok_or_else(|| CommandError::InvalidArgument(format!("Invalid address: {}", value)))?;
+            destinations.insert(address.to_public_key());
+        }
+        policy.set_allowed_destinations(destinations);
+    }
+
+    let input = prompt
+        .read_input(
+            prompt.colorize_string(
+                Color::Green,
+                &format!(
+                    "Rate limits as method=calls per minute, comma separated (current: {}, 'none' to remove): ",
+                    policy.get_rate_limits().len()
+                ),
+            ),
+            false,
+        )
+        .await
+        .context("Error while reading rate limits")?;
+    let input = input.trim();
+    if input == "none" {
+        policy.set_rate_limits(HashMap::new());
+    } else if !input.is_empty() {
+        let mut rate_limits = HashMap::new();
+        for value in input.split(',') {
+            let (method, limit) = value
+                .split_once('=')
+                .and_then(|(method, limit)| Some((method.trim().to_string(), limit.trim().parse::<u16>().ok()?)))
+                .ok_or_else(|| CommandError::InvalidArgument(format!("Invalid rate limit: {}", value)))?;
+            rate_limits.insert(method, limit);
+        }
+        policy.set_rate_limits(rate_limits);
+    }
+
+    wallet
+        .set_xswd_application_policy(&id, policy)
+        .await
+        .context("Error while saving policy")?;
+    manager.message(format!("Policy of application {} has been updated", id));
+    Ok(())
+}
+
+#[cfg(feature = "api_server")]
+async fn revoke_application(
+    manager: &CommandManager,
+    mut arguments: ArgumentManager,
+) -> Result<(), CommandError> {
+    let id = arguments.get_value("id")?.to_string_value()?;
+    let context = manager.get_context().lock()?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    wallet
+        .revoke_xswd_application(&id)
+        .await
+        .context("Error while revoking application")?;
+    manager.message(format!("Application {} has been revoked", id));
+    Ok(())
+}
+
 #[cfg(feature = "api_server")]
 async fn start_xswd(manager: &CommandManager, _: ArgumentManager) -> Result<(), CommandError> {
     let context = manager.get_context().lock()?;
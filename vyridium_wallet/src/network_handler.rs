@@ -3,7 +3,10 @@ use std::{
         HashMap,
         HashSet
     },
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc
+    },
     time::Duration
 };
 use thiserror::Error;
@@ -22,7 +25,7 @@ use vyridium_common::{
             BlockResponse,
             NewBlockEvent
         },
-        wallet::BalanceChanged,
+        wallet::{BalanceChanged, PendingTransactionStatus},
         RPCTransactionType
     },
     asset::AssetWithData,
@@ -38,7 +41,7 @@ use vyridium_common::{
 };
 use crate::{
     config::AUTO_RECONNECT_INTERVAL,
-    daemon_api::DaemonAPI,
+    daemon_api::{get_daemon_error_code, is_daemon_answer, DaemonAPI},
     entry::{
         EntryData,
         TransactionEntry,
@@ -76,7 +79,10 @@ pub struct NetworkHandler {
     // api to communicate with daemon
     // It is behind a Arc to be shared across several wallets
     // in case someone make a custom service and don't want to create a new connection
-    api: Arc<DaemonAPI>
+    api: Arc<DaemonAPI>,
+    // auto reconnect option used by the last start
+    // so it can be restarted with the same behavior
+    auto_reconnect: AtomicBool
 }
 
 impl NetworkHandler {
@@ -97,7 +103,8 @@ impl NetworkHandler {
         Ok(Arc::new(Self {
             task: Mutex::new(None),
             wallet,
-            api
+            api,
+            auto_reconnect: AtomicBool::new(false)
         }))
     }
 
@@ -117,6 +124,7 @@ impl NetworkHandler {
             }
         }
 
+        self.auto_reconnect.store(auto_reconnect, Ordering::SeqCst);
         let zelf = Arc::clone(&self);
         *self.task.lock().await = Some(spawn_task("network-handler", async move {
             loop {
@@ -185,11 +193,22 @@ impl NetworkHandler {
         }
     }
 
+    // Check if the network handler was started with auto reconnect
+    pub fn is_auto_reconnect(&self) -> bool {
+        self.auto_reconnect.load(Ordering::SeqCst)
+    }
+
     // Retrieve the daemon API used
     pub fn get_api(&self) -> &DaemonAPI {
         &self.api
     }
 
+    // Retrieve a shared reference to the daemon API
+    // It can be used without holding the network handler lock
+    pub fn get_shared_api(&self) -> Arc<DaemonAPI> {
+        self.api.clone()
+    }
+
     // check if the network handler is running (that we have a task and its not finished)
     pub async fn is_running(&self) -> bool {
         let task = self.task.lock().await;
@@ -395,6 +414,11 @@ impl NetworkHandler {
                     }
                 }
 
+                // Update the invoices paid by this transaction
+                if let Err(e) = self.wallet.process_invoice_payments(&entry).await {
+                    error!("Error while processing invoice payments of transaction {}: {}", entry.get_hash(), e);
+                }
+
                 // Propagate the event to the wallet
                 self.wallet.propagate_event(Event::NewTransaction(entry.serializable(self.wallet.get_network().is_mainnet()))).await;
             }
@@ -610,7 +634,8 @@ impl NetworkHandler {
         if storage.delete_changes_above_topoheight(maximum)? {
             warn!("Cleaning transactions above topoheight {}", maximum);
             // Changes were deleted, we should also delete transactions
-            storage.delete_transactions_above_topoheight(maximum)?;
+            let reverted = storage.delete_transactions_above_topoheight(maximum)?;
+            self.wallet.propagate_invoices_updated(reverted).await;
         }
 
         // Save the new values
@@ -825,10 +850,123 @@ impl NetworkHandler {
 
         // Propagate the event
         self.wallet.propagate_event(Event::NewTopoHeight { topoheight: daemon_topoheight }).await;
+
+        // Invoices not paid in time are now expired
+        if let Err(e) = self.wallet.expire_invoices().await {
+            error!("Error while expiring invoices: {}", e);
+        }
+
+        // Verify that our submitted transactions are still known by the daemon
+        if let Err(e) = self.check_pending_transactions(address).await {
+            error!("Error while checking pending transactions: {}", e);
+        }
+
+        // Blocks are frequent enough to also trigger the payments based on the time
+        if let Err(e) = self.wallet.run_scheduled_payments(daemon_topoheight).await {
+            error!("Error while running scheduled payments: {}", e);
+        }
+
         debug!("Synced to topoheight {}", daemon_topoheight);
         Ok(())
     }
 
+    // Verify the state of the transactions submitted by the wallet
+    // Transactions dropped by the daemon are broadcasted again, and rejected ones
+    // are no longer tracked and their unconfirmed balances and nonce are reverted
+    // A transaction is only considered dropped when the daemon answers it doesn't know it,
+    // it is checked again on next block if the daemon couldn't be reached
+    async fn check_pending_transactions(&self, address: &Address) -> Result<(), Error> {
+        trace!("check pending transactions");
+        let transactions = {
+            let storage = self.wallet.get_storage().read().await;
+            storage.get_pending_transactions()?
+        };
+
+        if transactions.is_empty() {
+            return Ok(())
+        }
+
+        let stable_topoheight = self.api.get_stable_topoheight().await?;
+        let mut has_invalid = false;
+        for mut transaction in transactions {
+            let hash = transaction.get_hash().clone();
+            let state = match self.api.get_transaction_state(&hash).await {
+                Ok(state) => Some(state),
+                // Daemon doesn't know the transaction
+                Err(e) if is_daemon_answer(&e) => None,
+                Err(e) => {
+                    debug!("Error while retrieving state of transaction {}: {}", hash, e);
+                    continue;
+                }
+            };
+
+            let updated = match state {
+                Some(state) if state.executed_in_block.is_some() => match self.api.get_transaction_executor(&hash).await {
+                    Ok(executor) if executor.block_topoheight <= stable_topoheight => {
+                        debug!("Transaction {} is now in stable topoheight {}", hash, executor.block_topoheight);
+                        transaction.set_status(PendingTransactionStatus::Confirmed, Some(executor.block_topoheight))
+                    },
+                    Ok(executor) => transaction.set_status(PendingTransactionStatus::Executed, Some(executor.block_topoheight)),
+                    Err(e) => {
+                        debug!("Error while retrieving executor of transaction {}: {}", hash, e);
+                        continue;
+                    }
+                },
+                // Still in the mempool or in blocks not executed yet
+                Some(_) => transaction.set_status(PendingTransactionStatus::Pending, None),
+                None => match self.api.submit_transaction(transaction.get_transaction()).await {
+                    Ok(()) => {
+                        warn!("Transaction {} was dropped by the daemon, broadcasting it again", hash);
+                        transaction.mark_rebroadcasted();
+                        true
+                    },
+                    Err(e) => match get_daemon_error_code(&e) {
+                        Some(code) => {
+                            let updated = transaction.apply_rebroadcast_error(code);
+                            if transaction.get_status() == PendingTransactionStatus::Invalid {
+                                warn!("Transaction {} was rejected by the daemon: {}", hash, e);
+                                has_invalid = true;
+                            } else {
+                                debug!("Transaction {} was not broadcasted again: {}", hash, e);
+                            }
+                            updated
+                        },
+                        None => {
+                            debug!("Error while broadcasting again transaction {}: {}", hash, e);
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            if !updated {
+                continue;
+            }
+
+            {
+                let mut storage = self.wallet.get_storage().write().await;
+                match transaction.get_status() {
+                    PendingTransactionStatus::Confirmed | PendingTransactionStatus::Invalid => storage.delete_pending_transaction(&hash)?,
+                    _ => storage.save_pending_transaction(&transaction)?
+                }
+            }
+
+            self.wallet.propagate_event(Event::PendingTransactionUpdated(transaction.serializable())).await;
+        }
+
+        if has_invalid {
+            // Balances and nonce built on top of the rejected transactions can't be used anymore
+            let mut storage = self.wallet.get_storage().write().await;
+            storage.delete_unconfirmed_balances().await;
+            match self.api.get_nonce(address).await {
+                Ok(result) => storage.set_nonce(result.version.get_nonce())?,
+                Err(e) => warn!("Error while fetching nonce after a rejected transaction: {}", e)
+            }
+        }
+
+        Ok(())
+    }
+
     // Runs an infinite loop to sync on each new block added in chain
     // Because of potential forks and DAG reorg during attacks,
     // we verify the last valid topoheight where changes happened
@@ -870,15 +1008,16 @@ impl NetworkHandler {
                             if hash != *event.block_hash {
                                 warn!("DAG reorg detected at topoheight {}, deleting changes at this topoheight", topoheight);
                                 storage.delete_changes_at_topoheight(topoheight)?;
-                                if topoheight == 0 {
+                                let reverted = if topoheight == 0 {
                                     debug!("Deleting all transactions due to reorg until 0");
-                                    storage.delete_transactions()?;
+                                    storage.delete_transactions()?
                                 } else {
                                     // TODO we should make a faster way to delete all TXs above this topoheight
                                     // Otherwise in future with millions of TXs, this may take few seconds.
                                     debug!("Deleting transactions above {} due to DAG reorg", topoheight);
-                                    storage.delete_transactions_above_topoheight(topoheight)?;
-                                }
+                                    storage.delete_transactions_above_topoheight(topoheight)?
+                                };
+                                self.wallet.propagate_invoices_updated(reverted).await;
                             }
                         } else {
                             debug!("No block hash found for topoheight {}, syncing block {}", topoheight, event.block_hash);
@@ -901,13 +1040,23 @@ impl NetworkHandler {
                     let mut storage = self.wallet.get_storage().write().await;
                     if storage.has_transaction(&tx.hash)? {
                         warn!("Transaction {} was orphaned, deleting it", tx.hash);
-                        storage.delete_transaction(&tx.hash)?;
+                        let reverted = storage.delete_transaction(&tx.hash)?;
+                        self.wallet.propagate_invoices_updated(reverted).await;
                     }
 
                     if storage.get_tx_cache().is_some_and(|cache| cache.last_tx_hash_created == *tx.hash) {
                         warn!("Transaction {} was orphaned, deleting it from cache", tx.hash);
                         storage.clear_tx_cache();
                     }
+
+                    // Broadcast it again if it was submitted by us
+                    let is_pending = storage.has_pending_transaction(&tx.hash)?;
+                    drop(storage);
+                    if is_pending {
+                        if let Err(e) = self.check_pending_transactions(&address).await {
+                            error!("Error while checking pending transactions: {}", e);
+                        }
+                    }
                 },
                 // Detect network events
                 res = on_connection.recv() => {
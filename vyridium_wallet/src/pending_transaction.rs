@@ -0,0 +1,221 @@
+use vyridium_common::{
+    api::{
+        daemon::{
+            MEMPOOL_FULL_ERROR_CODE,
+            TX_ALREADY_IN_BLOCKCHAIN_ERROR_CODE,
+            TX_ALREADY_IN_MEMPOOL_ERROR_CODE,
+            TX_FEE_TOO_LOW_FOR_MEMPOOL_ERROR_CODE
+        },
+        wallet::{PendingTransactionEntry as RPCPendingTransactionEntry, PendingTransactionStatus}
+    },
+    crypto::{Hash, Hashable},
+    serializer::{Reader, ReaderError, Serializer, Writer},
+    time::TimestampSeconds,
+    transaction::Transaction
+};
+
+// Transaction submitted by the wallet
+// It is tracked until it is executed in a stable block
+// so it can be broadcasted again if the daemon dropped it
+#[derive(Debug, Clone)]
+pub struct PendingTransaction {
+    hash: Hash,
+    // Full transaction to be able to rebroadcast it
+    transaction: Transaction,
+    submitted_at: TimestampSeconds,
+    status: PendingTransactionStatus,
+    // Topoheight of the block that executed it
+    topoheight: Option<u64>,
+    rebroadcasts: u32
+}
+
+impl PendingTransaction {
+    pub fn new(transaction: Transaction, submitted_at: TimestampSeconds) -> Self {
+        Self {
+            hash: transaction.hash(),
+            transaction,
+            submitted_at,
+            status: PendingTransactionStatus::Pending,
+            topoheight: None,
+            rebroadcasts: 0
+        }
+    }
+
+    pub fn get_hash(&self) -> &Hash {
+        &self.hash
+    }
+
+    pub fn get_transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    pub fn get_status(&self) -> PendingTransactionStatus {
+        self.status
+    }
+
+    pub fn get_topoheight(&self) -> Option<u64> {
+        self.topoheight
+    }
+
+    // Set the status and the topoheight at which it was executed
+    // Returns true if something changed
+    pub fn set_status(&mut self, status: PendingTransactionStatus, topoheight: Option<u64>) -> bool {
+        let changed = self.status != status || self.topoheight != topoheight;
+        self.status = status;
+        self.topoheight = topoheight;
+        changed
+    }
+
+    // Mark it as broadcasted again, it is waiting to be included in a block
+    pub fn mark_rebroadcasted(&mut self) {
+        self.rebroadcasts += 1;
+        self.status = PendingTransactionStatus::Pending;
+        self.topoheight = None;
+    }
+
+    // Update the status from the error code returned by the daemon when broadcasting it again
+    // Returns true if something changed
+    pub fn apply_rebroadcast_error(&mut self, code: i16) -> bool {
+        let status = match code {
+            // It was included in the chain since it was checked
+            TX_ALREADY_IN_BLOCKCHAIN_ERROR_CODE => PendingTransactionStatus::Executed,
+            // The mempool may accept it later, it is broadcasted again on the next check
+            TX_ALREADY_IN_MEMPOOL_ERROR_CODE
+            | TX_FEE_TOO_LOW_FOR_MEMPOOL_ERROR_CODE
+            | MEMPOOL_FULL_ERROR_CODE => PendingTransactionStatus::Pending,
+            _ => PendingTransactionStatus::Invalid
+        };
+        self.set_status(status, None)
+    }
+
+    // Convert to RPC Pending Transaction Entry
+    pub fn serializable(&self) -> RPCPendingTransactionEntry {
+        RPCPendingTransactionEntry {
+            hash: self.hash.clone(),
+            nonce: self.transaction.get_nonce(),
+            status: self.status,
+            submitted_at: self.submitted_at,
+            topoheight: self.topoheight,
+            rebroadcasts: self.rebroadcasts
+        }
+    }
+}
+
+impl Serializer for PendingTransaction {
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let transaction = Transaction::read(reader)?;
+        let submitted_at = reader.read_u64()?;
+        let status = match reader.read_u8()? {
+            0 => PendingTransactionStatus::Pending,
+            1 => PendingTransactionStatus::Executed,
+            2 => PendingTransactionStatus::Confirmed,
+            3 => PendingTransactionStatus::Invalid,
+            _ => return Err(ReaderError::InvalidValue)
+        };
+        let topoheight = Option::read(reader)?;
+        let rebroadcasts = reader.read_u32()?;
+
+        Ok(Self {
+            hash: transaction.hash(),
+            transaction,
+            submitted_at,
+            status,
+            topoheight,
+            rebroadcasts
+        })
+    }
+
+    fn write(&self, writer: &mut Writer) {
+        self.transaction.write(writer);
+        writer.write_u64(&self.submitted_at);
+        writer.write_u8(match self.status {
+            PendingTransactionStatus::Pending => 0,
+            PendingTransactionStatus::Executed => 1,
+            PendingTransactionStatus::Confirmed => 2,
+            PendingTransactionStatus::Invalid => 3
+        });
+        self.topoheight.write(writer);
+        writer.write_u32(&self.rebroadcasts);
+    }
+
+    fn size(&self) -> usize {
+        self.transaction.size() + 8 + 1 + self.topoheight.size() + 4
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vyridium_common::{
+        account::CiphertextCache,
+        api::{
+            daemon::{
+                MEMPOOL_FULL_ERROR_CODE,
+                TX_ALREADY_IN_BLOCKCHAIN_ERROR_CODE,
+                TX_FEE_TOO_LOW_FOR_MEMPOOL_ERROR_CODE
+            },
+            wallet::PendingTransactionStatus
+        },
+        config::{COIN_VALUE, VYRIDIUM_ASSET},
+        crypto::{Hash, KeyPair},
+        serializer::Serializer,
+        transaction::{
+            builder::{FeeBuilder, TransactionBuilder, TransactionTypeBuilder, TransferBuilder},
+            Reference,
+            Transaction,
+            TxVersion
+        }
+    };
+    use crate::{storage::Balance, transaction_builder::TransactionBuilderState};
+    use super::PendingTransaction;
+
+    fn create_tx() -> Transaction {
+        let keypair = KeyPair::new();
+        let mut state = TransactionBuilderState::new(false, Reference { topoheight: 0, hash: Hash::zero() }, 0);
+        let ciphertext = CiphertextCache::Decompressed(keypair.get_public_key().encrypt(10 * COIN_VALUE));
+        state.add_balance(VYRIDIUM_ASSET, Balance::new(10 * COIN_VALUE, ciphertext));
+        let data = TransactionTypeBuilder::Transfers(vec![TransferBuilder {
+            asset: VYRIDIUM_ASSET,
+            amount: COIN_VALUE,
+            destination: KeyPair::new().get_public_key().to_address(false),
+            extra_data: None
+        }]);
+        TransactionBuilder::new(TxVersion::V0, keypair.get_public_key().compress(), data, FeeBuilder::Value(1000))
+            .build(&mut state, &keypair)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_serializer() {
+        let mut transaction = PendingTransaction::new(create_tx(), 1000);
+        transaction.mark_rebroadcasted();
+        transaction.set_status(PendingTransactionStatus::Executed, Some(42));
+
+        let bytes = transaction.to_bytes();
+        assert_eq!(bytes.len(), transaction.size());
+        let read = PendingTransaction::from_bytes(&bytes).unwrap();
+        assert_eq!(read.get_hash(), transaction.get_hash());
+        assert_eq!(read.get_status(), PendingTransactionStatus::Executed);
+        assert_eq!(read.get_topoheight(), Some(42));
+
+        let entry = read.serializable();
+        assert_eq!(entry.submitted_at, 1000);
+        assert_eq!(entry.rebroadcasts, 1);
+    }
+
+    #[test]
+    fn test_rebroadcast_error_status() {
+        // the mempool can't take it for now, it stays pending
+        let mut transaction = PendingTransaction::new(create_tx(), 1000);
+        assert!(!transaction.apply_rebroadcast_error(TX_FEE_TOO_LOW_FOR_MEMPOOL_ERROR_CODE));
+        assert!(!transaction.apply_rebroadcast_error(MEMPOOL_FULL_ERROR_CODE));
+        assert_eq!(transaction.get_status(), PendingTransactionStatus::Pending);
+
+        assert!(transaction.apply_rebroadcast_error(TX_ALREADY_IN_BLOCKCHAIN_ERROR_CODE));
+        assert_eq!(transaction.get_status(), PendingTransactionStatus::Executed);
+
+        // any other error of the daemon means it can't be executed anymore
+        let mut transaction = PendingTransaction::new(create_tx(), 1000);
+        assert!(transaction.apply_rebroadcast_error(250));
+        assert_eq!(transaction.get_status(), PendingTransactionStatus::Invalid);
+    }
+}
@@ -0,0 +1,281 @@
+use vyridium_common::{
+    api::{
+        wallet::{PaymentSchedule, ScheduledPaymentEntry as RPCScheduledPaymentEntry},
+        DataElement
+    },
+    crypto::{Hash, PublicKey},
+    serializer::{Reader, ReaderError, Serializer, Writer},
+    time::TimestampSeconds,
+    transaction::{builder::TransferBuilder, EXTRA_DATA_LIMIT_SIZE}
+};
+use crate::cron::{CronError, CronExpression};
+
+// Maximum size in bytes of the error saved for a skipped run
+const MAX_ERROR_SIZE: usize = 255;
+
+// Payment submitted automatically by the wallet based on its schedule
+#[derive(Debug, Clone)]
+pub struct ScheduledPayment {
+    id: u64,
+    destination: PublicKey,
+    asset: Hash,
+    amount: u64,
+    extra_data: Option<DataElement>,
+    schedule: PaymentSchedule,
+    paused: bool,
+    // Topoheight or timestamp in seconds of the next run, depending on the schedule
+    next_run: u64,
+    runs: u64,
+    skipped: u64,
+    last_tx: Option<Hash>,
+    last_error: Option<String>
+}
+
+// Compute the next run of a schedule after the topoheight and time given
+fn compute_next_run(schedule: &PaymentSchedule, topoheight: u64, now: TimestampSeconds) -> Result<u64, CronError> {
+    match schedule {
+        PaymentSchedule::Topoheight { interval } => Ok(topoheight.saturating_add(*interval)),
+        PaymentSchedule::Cron { expression } => {
+            let cron: CronExpression = expression.parse()?;
+            // An expression that never matches will never run
+            Ok(cron.next_after(now).unwrap_or(u64::MAX))
+        }
+    }
+}
+
+impl ScheduledPayment {
+    // Create a new scheduled payment, its first run is computed from the current topoheight and time
+    pub fn new(id: u64, destination: PublicKey, asset: Hash, amount: u64, extra_data: Option<DataElement>, schedule: PaymentSchedule, topoheight: u64, now: TimestampSeconds) -> Result<Self, CronError> {
+        let next_run = compute_next_run(&schedule, topoheight, now)?;
+        Ok(Self {
+            id,
+            destination,
+            asset,
+            amount,
+            extra_data,
+            schedule,
+            paused: false,
+            next_run,
+            runs: 0,
+            skipped: 0,
+            last_tx: None,
+            last_error: None
+        })
+    }
+
+    // Verify the amount, interval and extra data size
+    pub fn is_valid(&self) -> bool {
+        let valid_schedule = match &self.schedule {
+            PaymentSchedule::Topoheight { interval } => *interval > 0,
+            PaymentSchedule::Cron { expression } => expression.len() <= u8::MAX as usize
+        };
+
+        valid_schedule
+            && self.amount > 0
+            && self.extra_data.as_ref().map_or(true, |data| data.size() <= EXTRA_DATA_LIMIT_SIZE)
+    }
+
+    pub fn get_id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // Pausing a payment skips all its runs until it is resumed
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    // Check if the payment must be run at this topoheight and time
+    pub fn is_due(&self, topoheight: u64, now: TimestampSeconds) -> bool {
+        if self.paused {
+            return false
+        }
+
+        match self.schedule {
+            PaymentSchedule::Topoheight { .. } => topoheight >= self.next_run,
+            PaymentSchedule::Cron { .. } => now >= self.next_run
+        }
+    }
+
+    // Build the transfer to submit for a run
+    pub fn to_transfer(&self, mainnet: bool) -> TransferBuilder {
+        TransferBuilder {
+            destination: self.destination.clone().to_address(mainnet),
+            amount: self.amount,
+            asset: self.asset.clone(),
+            extra_data: self.extra_data.clone()
+        }
+    }
+
+    // Save the result of a run and compute the next one
+    // Runs missed while the wallet was offline are not replayed
+    pub fn record_run(&mut self, result: Result<Hash, String>, topoheight: u64, now: TimestampSeconds) {
+        match result {
+            Ok(hash) => {
+                self.runs += 1;
+                self.last_tx = Some(hash);
+                self.last_error = None;
+            },
+            Err(error) => {
+                self.skipped += 1;
+                self.last_tx = None;
+                self.set_last_error(error);
+            }
+        }
+
+        // the expression was already verified on creation
+        self.next_run = compute_next_run(&self.schedule, topoheight, now).unwrap_or(u64::MAX);
+    }
+
+    // Report an error of the last run, it is truncated to not grow the storage
+    pub fn set_last_error(&mut self, mut error: String) {
+        if error.len() > MAX_ERROR_SIZE {
+            let mut end = MAX_ERROR_SIZE;
+            while !error.is_char_boundary(end) {
+                end -= 1;
+            }
+            error.truncate(end);
+        }
+        self.last_error = Some(error);
+    }
+
+    // Convert to RPC Scheduled Payment Entry
+    pub fn serializable(&self, mainnet: bool) -> RPCScheduledPaymentEntry {
+        RPCScheduledPaymentEntry {
+            id: self.id,
+            destination: self.destination.clone().to_address(mainnet),
+            asset: self.asset.clone(),
+            amount: self.amount,
+            extra_data: self.extra_data.clone(),
+            schedule: self.schedule.clone(),
+            paused: self.paused,
+            next_run: self.next_run,
+            runs: self.runs,
+            skipped: self.skipped,
+            last_tx: self.last_tx.clone(),
+            last_error: self.last_error.clone()
+        }
+    }
+}
+
+impl Serializer for ScheduledPayment {
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let id = reader.read_u64()?;
+        let destination = PublicKey::read(reader)?;
+        let asset = Hash::read(reader)?;
+        let amount = reader.read_u64()?;
+        let extra_data = Option::read(reader)?;
+        let schedule = match reader.read_u8()? {
+            0 => PaymentSchedule::Topoheight { interval: reader.read_u64()? },
+            1 => PaymentSchedule::Cron { expression: reader.read_string()? },
+            _ => return Err(ReaderError::InvalidValue)
+        };
+        let paused = reader.read_bool()?;
+        let next_run = reader.read_u64()?;
+        let runs = reader.read_u64()?;
+        let skipped = reader.read_u64()?;
+        let last_tx = Option::read(reader)?;
+        let last_error = reader.read_optional_string()?;
+
+        Ok(Self {
+            id,
+            destination,
+            asset,
+            amount,
+            extra_data,
+            schedule,
+            paused,
+            next_run,
+            runs,
+            skipped,
+            last_tx,
+            last_error
+        })
+    }
+
+    fn write(&self, writer: &mut Writer) {
+        writer.write_u64(&self.id);
+        self.destination.write(writer);
+        self.asset.write(writer);
+        writer.write_u64(&self.amount);
+        self.extra_data.write(writer);
+        match &self.schedule {
+            PaymentSchedule::Topoheight { interval } => {
+                writer.write_u8(0);
+                writer.write_u64(interval);
+            },
+            PaymentSchedule::Cron { expression } => {
+                writer.write_u8(1);
+                writer.write_string(expression);
+            }
+        }
+        writer.write_bool(self.paused);
+        writer.write_u64(&self.next_run);
+        writer.write_u64(&self.runs);
+        writer.write_u64(&self.skipped);
+        self.last_tx.write(writer);
+        writer.write_optional_string(&self.last_error);
+    }
+
+    fn size(&self) -> usize {
+        let schedule_size = 1 + match &self.schedule {
+            PaymentSchedule::Topoheight { .. } => 8,
+            PaymentSchedule::Cron { expression } => expression.size()
+        };
+        // last error is written with only one byte for its length
+        let last_error_size = 1 + self.last_error.as_ref().map_or(0, |error| error.len());
+
+        8 + self.destination.size() + self.asset.size() + 8 + self.extra_data.size()
+            + schedule_size + 1 + 8 + 8 + 8 + self.last_tx.size() + last_error_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vyridium_common::{
+        api::wallet::PaymentSchedule,
+        config::VYRIDIUM_ASSET,
+        crypto::{Hash, KeyPair},
+        serializer::Serializer
+    };
+    use super::ScheduledPayment;
+
+    #[test]
+    fn test_topoheight_schedule() {
+        let key = KeyPair::new().get_public_key().compress();
+        let mut payment = ScheduledPayment::new(1, key, VYRIDIUM_ASSET, 100, None, PaymentSchedule::Topoheight { interval: 10 }, 50, 0).unwrap();
+        assert!(payment.is_valid());
+        assert!(!payment.is_due(59, 0));
+        assert!(payment.is_due(60, 0));
+
+        payment.record_run(Err("Not enough funds".to_owned()), 60, 0);
+        assert!(!payment.is_due(69, 0));
+        assert!(payment.is_due(70, 0));
+
+        payment.set_paused(true);
+        assert!(!payment.is_due(70, 0));
+
+        payment.record_run(Ok(Hash::zero()), 70, 0);
+        payment.set_last_error("Transaction submitted but wallet state not updated".to_owned());
+        let bytes = payment.to_bytes();
+        assert_eq!(bytes.len(), payment.size());
+
+        let entry = ScheduledPayment::from_bytes(&bytes).unwrap().serializable(false);
+        assert_eq!(entry.runs, 1);
+        assert_eq!(entry.skipped, 1);
+        assert_eq!(entry.last_tx, Some(Hash::zero()));
+        assert!(entry.last_error.is_some());
+        assert_eq!(entry.next_run, 80);
+        assert!(entry.paused);
+    }
+
+    #[test]
+    fn test_invalid_cron_schedule() {
+        let key = KeyPair::new().get_public_key().compress();
+        let schedule = PaymentSchedule::Cron { expression: "every day".to_owned() };
+        assert!(ScheduledPayment::new(1, key, VYRIDIUM_ASSET, 100, None, schedule, 0, 0).is_err());
+    }
+}
@@ -21,7 +21,8 @@ use vyridium_common::{
         elgamal::CompressedCiphertext,
         Hash,
         PrivateKey,
-        PublicKey
+        PublicKey,
+        HASH_SIZE
     },
     network::Network,
     serializer::{
@@ -38,14 +39,20 @@ use anyhow::{
     anyhow
 };
 use crate::{
+    application::{ApplicationPermissions, ApplicationPolicy},
+    backup::BackupTree,
     cipher::Cipher,
     config::SALT_SIZE,
+    contact::Contact,
     entry::{
         EntryData,
         TransactionEntry,
         Transfer
     },
-    error::WalletError
+    error::WalletError,
+    invoice::{self, Invoice},
+    pending_transaction::PendingTransaction,
+    scheduled_payment::ScheduledPayment
 };
 use self::backend::{Db, Tree};
 use log::{trace, debug, error};
@@ -67,6 +74,14 @@ const TOP_BLOCK_HASH_KEY: &[u8] = b"TOPBH";
 const NETWORK: &[u8] = b"NET";
 // Last coinbase reward topoheight
 const LCRT: &[u8] = b"LCRT";
+// Count of accounts derived from the seed
+const ACCOUNTS_COUNT_KEY: &[u8] = b"ACCS";
+// Account currently used by the wallet
+const ACTIVE_ACCOUNT_KEY: &[u8] = b"ACTACC";
+// Prefix of the account names
+const ACCOUNT_NAME_PREFIX: &[u8] = b"ACCNAME";
+// Next ID to use for a scheduled payment
+const SCHEDULED_PAYMENT_ID_KEY: &[u8] = b"SCHPID";
 
 // Default cache size
 const DEFAULT_CACHE_SIZE: usize = 100;
@@ -119,19 +134,37 @@ pub struct TxCache {
 }
 
 // Implement an encrypted storage system 
+// Each account derived from the seed has its own namespace
+// for its transactions, balances, assets, nonce and sync state
 pub struct EncryptedStorage {
     // cipher used to encrypt/decrypt/hash data
     cipher: Cipher,
+    // Account currently opened
+    account: u32,
     // All transactions where this wallet is part of
     transactions: Tree,
     // balances for each asset
     balances: Tree,
-    // extra data (network, topoheight, etc)
+    // extra data shared by all accounts (network, private key, accounts)
     extra: Tree,
+    // extra data of the account (nonce, topoheight, etc)
+    account_extra: Tree,
     // all assets tracked by the wallet
     assets: Tree,
+    // address book shared by all accounts
+    contacts: Tree,
+    // permissions granted to XSWD applications, shared by all accounts
+    applications: Tree,
+    // policies set on XSWD applications, shared by all accounts and kept when the permissions expire
+    application_policies: Tree,
     // This tree is used to store all topoheight where a change in the wallet occured
     changes_topoheight: Tree,
+    // invoices created by the account
+    invoices: Tree,
+    // transactions submitted and not yet executed in a stable block
+    pending_transactions: Tree,
+    // payments submitted automatically based on their schedule
+    scheduled_payments: Tree,
     // The inner storage
     inner: Storage,
     // Caches
@@ -156,11 +189,19 @@ impl EncryptedStorage {
     pub fn new(inner: Storage, key: &[u8], salt: [u8; SALT_SIZE], network: Network) -> Result<Self> {
         let cipher = Cipher::new(key, Some(salt))?;
         let mut storage = Self {
+            account: 0,
             transactions: inner.db.open_tree(&cipher.hash_key("transactions"))?,
             balances: inner.db.open_tree(&cipher.hash_key("balances"))?,
             extra: inner.db.open_tree(&cipher.hash_key("extra"))?,
+            account_extra: inner.db.open_tree(&cipher.hash_key("extra"))?,
             assets: inner.db.open_tree(&cipher.hash_key("assets"))?,
+            contacts: inner.db.open_tree(&cipher.hash_key("contacts"))?,
+            applications: inner.db.open_tree(&cipher.hash_key("applications"))?,
+            application_policies: inner.db.open_tree(&cipher.hash_key("application_policies"))?,
             changes_topoheight: inner.db.open_tree(&cipher.hash_key("changes_topoheight"))?,
+            invoices: inner.db.open_tree(&cipher.hash_key("invoices"))?,
+            pending_transactions: inner.db.open_tree(&cipher.hash_key("pending_transactions"))?,
+            scheduled_payments: inner.db.open_tree(&cipher.hash_key("scheduled_payments"))?,
             cipher,
             inner,
             balances_cache: Mutex::new(LruCache::new(NonZeroUsize::new(DEFAULT_CACHE_SIZE).unwrap())),
@@ -180,14 +221,137 @@ impl EncryptedStorage {
             storage.set_network(&network)?;
         }
 
+        // Reopen the account used the last time
+        if storage.contains_data(&storage.extra, ACTIVE_ACCOUNT_KEY)? {
+            let account: u32 = storage.load_from_disk(&storage.extra, ACTIVE_ACCOUNT_KEY)?;
+            if account != 0 {
+                storage.open_account(account)?;
+            }
+        }
+
         // Load one-time the last coinbase reward topoheight
-        if storage.contains_data(&storage.extra, LCRT)? {
-            storage.last_coinbase_reward_topoheight = Some(storage.load_from_disk(&storage.extra, LCRT)?);
+        if storage.contains_data(&storage.account_extra, LCRT)? {
+            storage.last_coinbase_reward_topoheight = Some(storage.load_from_disk(&storage.account_extra, LCRT)?);
         }
 
         Ok(storage)
     }
 
+    // Name of a tree for the requested account
+    // First account is using the original names to stay compatible with existing wallets
+    fn get_account_tree_name(name: &str, account: u32) -> String {
+        if account == 0 {
+            name.to_owned()
+        } else {
+            format!("{}_{}", name, account)
+        }
+    }
+
+    // Open all the trees of the account
+    fn open_account(&mut self, account: u32) -> Result<()> {
+        trace!("open account {}", account);
+        let open = |name: &str| self.inner.db.open_tree(&self.cipher.hash_key(Self::get_account_tree_name(name, account)));
+        let transactions = open("transactions")?;
+        let balances = open("balances")?;
+        let account_extra = open("extra")?;
+        let assets = open("assets")?;
+        let changes_topoheight = open("changes_topoheight")?;
+        let invoices = open("invoices")?;
+        let pending_transactions = open("pending_transactions")?;
+        let scheduled_payments = open("scheduled_payments")?;
+
+        self.transactions = transactions;
+        self.balances = balances;
+        self.account_extra = account_extra;
+        self.assets = assets;
+        self.changes_topoheight = changes_topoheight;
+        self.invoices = invoices;
+        self.pending_transactions = pending_transactions;
+        self.scheduled_payments = scheduled_payments;
+        self.account = account;
+
+        Ok(())
+    }
+
+    // Get the account currently used
+    pub fn get_active_account(&self) -> u32 {
+        self.account
+    }
+
+    // Verify that the account expected by a request is the active one
+    pub fn verify_active_account(&self, account: Option<u32>) -> Result<()> {
+        if let Some(account) = account {
+            if account != self.account {
+                return Err(WalletError::AccountNotActive(account, self.account).into())
+            }
+        }
+
+        Ok(())
+    }
+
+    // Switch to another account
+    // All caches are cleared as they belong to the previous account
+    pub async fn set_active_account(&mut self, account: u32) -> Result<()> {
+        trace!("set active account {}", account);
+        if account >= self.get_accounts_count()? {
+            return Err(WalletError::AccountNotFound(account).into())
+        }
+
+        self.open_account(account)?;
+        self.balances_cache.lock().await.clear();
+        self.unconfirmed_balances_cache.lock().await.clear();
+        self.assets_cache.lock().await.clear();
+        self.tx_cache = None;
+        self.synced_topoheight = None;
+        self.last_coinbase_reward_topoheight = if self.contains_data(&self.account_extra, LCRT)? {
+            Some(self.load_from_disk(&self.account_extra, LCRT)?)
+        } else {
+            None
+        };
+
+        self.save_to_disk(&self.extra, ACTIVE_ACCOUNT_KEY, &account.to_be_bytes())
+    }
+
+    // Get the count of accounts derived from the seed
+    // There is always at least one account
+    pub fn get_accounts_count(&self) -> Result<u32> {
+        trace!("get accounts count");
+        if !self.contains_data(&self.extra, ACCOUNTS_COUNT_KEY)? {
+            return Ok(1)
+        }
+
+        self.load_from_disk(&self.extra, ACCOUNTS_COUNT_KEY)
+    }
+
+    // Register a new account and returns its index
+    pub fn add_account(&mut self, name: Option<String>) -> Result<u32> {
+        trace!("add account");
+        let account = self.get_accounts_count()?;
+        if let Some(name) = name {
+            self.save_to_disk(&self.extra, &Self::get_account_name_key(account), &name.to_bytes())?;
+        }
+        self.save_to_disk(&self.extra, ACCOUNTS_COUNT_KEY, &(account + 1).to_be_bytes())?;
+
+        Ok(account)
+    }
+
+    fn get_account_name_key(account: u32) -> Vec<u8> {
+        let mut key = ACCOUNT_NAME_PREFIX.to_vec();
+        key.extend_from_slice(&account.to_be_bytes());
+        key
+    }
+
+    // Get the name set for an account
+    pub fn get_account_name(&self, account: u32) -> Result<Option<String>> {
+        trace!("get account name {}", account);
+        let key = Self::get_account_name_key(account);
+        if !self.contains_data(&self.extra, &key)? {
+            return Ok(None)
+        }
+
+        self.load_from_disk(&self.extra, &key).map(Some)
+    }
+
     // Flush on disk to make sure it is saved
     pub fn flush(&mut self) -> Result<()> {
         trace!("Flushing storage");
@@ -203,6 +367,32 @@ impl EncryptedStorage {
         }
     }
 
+    // Copy all the trees of the wallet, keys and values are kept encrypted
+    pub fn export_trees(&self) -> Result<Vec<BackupTree>> {
+        trace!("export trees");
+        let mut trees = Vec::new();
+        for name in self.inner.db.tree_names() {
+            // All trees of the wallet are named using a hashed key
+            if name.len() != HASH_SIZE {
+                continue;
+            }
+
+            let tree = self.inner.db.open_tree(&name)?;
+            let mut entries = Vec::with_capacity(tree.len());
+            for res in tree.iter() {
+                let (key, value) = res?;
+                entries.push((key.to_vec(), value.to_vec()));
+            }
+
+            trees.push(BackupTree {
+                name: name.to_vec(),
+                entries
+            });
+        }
+
+        Ok(trees)
+    }
+
     // Key must be hashed or encrypted before calling this function
     fn internal_load<V: Serializer>(&self, tree: &Tree, key: &[u8]) -> Result<V> {
         trace!("internal load");
@@ -685,33 +875,39 @@ impl EncryptedStorage {
 
     // delete all transactions above the specified topoheight
     // This will go through each transaction, deserialize it, check topoheight, and delete it if required
-    pub fn delete_transactions_above_topoheight(&mut self, topoheight: u64) -> Result<()> {
+    // Returns the invoices whose payments were reverted
+    pub fn delete_transactions_above_topoheight(&mut self, topoheight: u64) -> Result<Vec<Invoice>> {
         trace!("delete transactions above topoheight {}", topoheight);
+        let mut reverted = Vec::new();
         for el in self.transactions.iter().values() {
             let value = el?;
             let entry = TransactionEntry::from_bytes(&self.cipher.decrypt_value(&value)?)?;
             if entry.get_topoheight() > topoheight {
-                self.delete_transaction(entry.get_hash())?;
+                let invoices = self.delete_transaction(entry.get_hash())?;
+                Self::merge_reverted_invoices(&mut reverted, invoices);
             }
         }
 
-        Ok(())
+        Ok(reverted)
     }
 
     // delete all transactions at the specified topoheight
     // This will go through each transaction, deserialize it, check topoheight, and delete it if required
     // Maybe we can optimize it by keeping a lookuptable of topoheight -> txs ?
-    pub fn delete_transactions_at_topoheight(&mut self, topoheight: u64) -> Result<()> {
+    // Returns the invoices whose payments were reverted
+    pub fn delete_transactions_at_topoheight(&mut self, topoheight: u64) -> Result<Vec<Invoice>> {
         trace!("delete transactions at topoheight {}", topoheight);
+        let mut reverted = Vec::new();
         for el in self.transactions.iter().values() {
             let value = el?;
             let entry = TransactionEntry::from_bytes(&self.cipher.decrypt_value(&value)?)?;
             if entry.get_topoheight() == topoheight {
-                self.delete_transaction(entry.get_hash())?;
+                let invoices = self.delete_transaction(entry.get_hash())?;
+                Self::merge_reverted_invoices(&mut reverted, invoices);
             }
         }
 
-        Ok(())
+        Ok(reverted)
     }
 
     // Filter when the data is deserialized to not load all transactions in memory
@@ -786,17 +982,355 @@ impl EncryptedStorage {
     }
 
     // Delete a transaction saved in wallet using its hash
-    pub fn delete_transaction(&mut self, hash: &Hash) -> Result<()> {
+    // Invoices paid by this transaction are reverted and returned
+    pub fn delete_transaction(&mut self, hash: &Hash) -> Result<Vec<Invoice>> {
         trace!("delete transaction {}", hash);
+        let reverted = if self.has_transaction(hash)? {
+            let entry = self.get_transaction(hash)?;
+            self.revert_invoice_payments(&entry)?
+        } else {
+            Vec::new()
+        };
+
         self.transactions.remove(self.cipher.hash_key(hash.as_bytes()))?;
-        Ok(())
+        Ok(reverted)
     }
 
     // Delete all transactions from this wallet
-    pub fn delete_transactions(&mut self) -> Result<()> {
+    // All invoice payments are reverted and the invoices updated are returned
+    pub fn delete_transactions(&mut self) -> Result<Vec<Invoice>> {
         trace!("delete transactions");
+        let mut reverted = Vec::new();
+        for mut invoice in self.get_invoices()? {
+            let hashes: Vec<Hash> = invoice.get_payments().iter().map(|(hash, _, _)| hash.clone()).collect();
+            if !hashes.is_empty() {
+                for hash in hashes {
+                    invoice.revert_payment(&hash);
+                }
+                self.save_invoice(&invoice)?;
+                reverted.push(invoice);
+            }
+        }
+
         self.transactions.clear()?;
-        Ok(())
+        Ok(reverted)
+    }
+
+    // Add the invoices reverted by a transaction, keeping only the last version of each
+    fn merge_reverted_invoices(reverted: &mut Vec<Invoice>, invoices: Vec<Invoice>) {
+        for invoice in invoices {
+            reverted.retain(|other| other.get_id() != invoice.get_id());
+            reverted.push(invoice);
+        }
+    }
+
+    // Remove the payments of a transaction from the invoices it paid
+    // Returns the invoices updated
+    fn revert_invoice_payments(&mut self, entry: &TransactionEntry) -> Result<Vec<Invoice>> {
+        let transfers = match entry.get_entry() {
+            EntryData::Incoming { transfers, .. } => transfers,
+            _ => return Ok(Vec::new())
+        };
+
+        let mut reverted: Vec<Invoice> = Vec::new();
+        for transfer in transfers {
+            let id = match transfer.get_extra_data().as_ref().and_then(invoice::get_invoice_id) {
+                Some(id) => id,
+                None => continue
+            };
+
+            // several transfers of the same transaction can pay the same invoice
+            if reverted.iter().any(|invoice| invoice.get_id() == id) {
+                continue;
+            }
+
+            if self.has_invoice(id)? {
+                let mut invoice = self.get_invoice(id)?;
+                if invoice.revert_payment(entry.get_hash()) {
+                    debug!("Payment of transaction {} reverted for invoice {}", entry.get_hash(), id);
+                    self.save_invoice(&invoice)?;
+                    reverted.push(invoice);
+                }
+            }
+        }
+
+        Ok(reverted)
+    }
+
+    // Check if a contact exists in the address book using its label
+    pub fn has_contact(&self, label: &str) -> Result<bool> {
+        trace!("has contact {}", label);
+        self.contains_data(&self.contacts, label.as_bytes())
+    }
+
+    // Retrieve a contact from the address book using its label
+    pub fn get_contact(&self, label: &str) -> Result<Contact> {
+        trace!("get contact {}", label);
+        if !self.has_contact(label)? {
+            return Err(WalletError::ContactNotFound(label.to_owned()).into())
+        }
+
+        self.load_from_disk(&self.contacts, label.as_bytes())
+    }
+
+    // Add a contact to the address book
+    // Its label must be unique
+    pub fn add_contact(&mut self, contact: &Contact) -> Result<()> {
+        trace!("add contact {}", contact.get_label());
+        if !contact.is_valid() {
+            return Err(WalletError::InvalidContact.into())
+        }
+
+        if self.has_contact(contact.get_label())? {
+            return Err(WalletError::ContactAlreadyExists(contact.get_label().to_owned()).into())
+        }
+
+        self.save_to_disk(&self.contacts, contact.get_label().as_bytes(), &contact.to_bytes())
+    }
+
+    // Delete a contact from the address book using its label
+    pub fn delete_contact(&mut self, label: &str) -> Result<()> {
+        trace!("delete contact {}", label);
+        if !self.has_contact(label)? {
+            return Err(WalletError::ContactNotFound(label.to_owned()).into())
+        }
+
+        self.delete_from_disk(&self.contacts, label.as_bytes())
+    }
+
+    // Retrieve all contacts from the address book sorted by label
+    pub fn get_contacts(&self) -> Result<Vec<Contact>> {
+        trace!("get contacts");
+        let mut contacts = Vec::new();
+        for res in self.contacts.iter() {
+            let (_, value) = res?;
+            let raw_value = &self.cipher.decrypt_value(&value)?;
+            let mut reader = Reader::new(raw_value);
+            contacts.push(Contact::read(&mut reader)?);
+        }
+        contacts.sort_by(|a, b| a.get_label().cmp(b.get_label()));
+
+        Ok(contacts)
+    }
+
+    // Retrieve the labels of all contacts by their public key
+    // It is used to resolve the contacts in the transactions history
+    pub fn get_contacts_labels(&self) -> Result<HashMap<PublicKey, String>> {
+        trace!("get contacts labels");
+        let labels = self.get_contacts()?
+            .into_iter()
+            .map(|contact| (contact.get_key().clone(), contact.get_label().to_owned()))
+            .collect();
+
+        Ok(labels)
+    }
+
+    // Check if permissions are saved for an XSWD application
+    pub fn has_application_permissions(&self, id: &str) -> Result<bool> {
+        trace!("has application permissions {}", id);
+        self.contains_data(&self.applications, id.as_bytes())
+    }
+
+    // Retrieve the permissions saved for an XSWD application
+    pub fn get_application_permissions(&self, id: &str) -> Result<ApplicationPermissions> {
+        trace!("get application permissions {}", id);
+        if !self.has_application_permissions(id)? {
+            return Err(WalletError::XSWDApplicationNotFound(id.to_owned()).into())
+        }
+
+        self.load_from_disk(&self.applications, id.as_bytes())
+    }
+
+    // Save the permissions granted to an XSWD application
+    pub fn save_application_permissions(&mut self, application: &ApplicationPermissions) -> Result<()> {
+        trace!("save application permissions {}", application.get_id());
+        self.save_to_disk(&self.applications, application.get_id().as_bytes(), &application.to_bytes())
+    }
+
+    // Delete the permissions saved for an XSWD application
+    pub fn delete_application_permissions(&mut self, id: &str) -> Result<()> {
+        trace!("delete application permissions {}", id);
+        if !self.has_application_permissions(id)? {
+            return Err(WalletError::XSWDApplicationNotFound(id.to_owned()).into())
+        }
+
+        self.delete_from_disk(&self.applications, id.as_bytes())
+    }
+
+    // Retrieve the permissions saved for all XSWD applications sorted by name
+    pub fn get_applications_permissions(&self) -> Result<Vec<ApplicationPermissions>> {
+        trace!("get applications permissions");
+        let mut applications = Vec::new();
+        for res in self.applications.iter() {
+            let (_, value) = res?;
+            let raw_value = &self.cipher.decrypt_value(&value)?;
+            let mut reader = Reader::new(raw_value);
+            applications.push(ApplicationPermissions::read(&mut reader)?);
+        }
+        applications.sort_by(|a, b| a.get_name().cmp(b.get_name()));
+
+        Ok(applications)
+    }
+
+    // Check if a policy is saved for an XSWD application
+    pub fn has_application_policy(&self, id: &str) -> Result<bool> {
+        trace!("has application policy {}", id);
+        self.contains_data(&self.application_policies, id.as_bytes())
+    }
+
+    // Retrieve the policy saved for an XSWD application
+    pub fn get_application_policy(&self, id: &str) -> Result<ApplicationPolicy> {
+        trace!("get application policy {}", id);
+        if !self.has_application_policy(id)? {
+            return Err(WalletError::XSWDApplicationNotFound(id.to_owned()).into())
+        }
+
+        self.load_from_disk(&self.application_policies, id.as_bytes())
+    }
+
+    // Save the policy of an XSWD application
+    pub fn save_application_policy(&mut self, id: &str, policy: &ApplicationPolicy) -> Result<()> {
+        trace!("save application policy {}", id);
+        self.save_to_disk(&self.application_policies, id.as_bytes(), &policy.to_bytes())
+    }
+
+    // Delete the policy saved for an XSWD application
+    pub fn delete_application_policy(&mut self, id: &str) -> Result<()> {
+        trace!("delete application policy {}", id);
+        if !self.has_application_policy(id)? {
+            return Err(WalletError::XSWDApplicationNotFound(id.to_owned()).into())
+        }
+
+        self.delete_from_disk(&self.application_policies, id.as_bytes())
+    }
+
+    // Check if an invoice exists using its ID
+    pub fn has_invoice(&self, id: &str) -> Result<bool> {
+        trace!("has invoice {}", id);
+        self.contains_data(&self.invoices, id.as_bytes())
+    }
+
+    // Retrieve an invoice using its ID
+    pub fn get_invoice(&self, id: &str) -> Result<Invoice> {
+        trace!("get invoice {}", id);
+        if !self.has_invoice(id)? {
+            return Err(WalletError::InvoiceNotFound(id.to_owned()).into())
+        }
+
+        self.load_from_disk(&self.invoices, id.as_bytes())
+    }
+
+    // Save or update an invoice
+    pub fn save_invoice(&mut self, invoice: &Invoice) -> Result<()> {
+        trace!("save invoice {}", invoice.get_id());
+        self.save_to_disk(&self.invoices, invoice.get_id().as_bytes(), &invoice.to_bytes())
+    }
+
+    // Retrieve all invoices of the account
+    pub fn get_invoices(&self) -> Result<Vec<Invoice>> {
+        trace!("get invoices");
+        let mut invoices = Vec::new();
+        for res in self.invoices.iter() {
+            let (_, value) = res?;
+            let raw_value = &self.cipher.decrypt_value(&value)?;
+            let mut reader = Reader::new(raw_value);
+            invoices.push(Invoice::read(&mut reader)?);
+        }
+
+        Ok(invoices)
+    }
+
+    // Track a transaction submitted to the daemon
+    pub fn save_pending_transaction(&mut self, transaction: &PendingTransaction) -> Result<()> {
+        trace!("save pending transaction {}", transaction.get_hash());
+        self.save_to_disk(&self.pending_transactions, transaction.get_hash().as_bytes(), &transaction.to_bytes())
+    }
+
+    // Check if a submitted transaction is still tracked
+    pub fn has_pending_transaction(&self, hash: &Hash) -> Result<bool> {
+        trace!("has pending transaction {}", hash);
+        self.contains_data(&self.pending_transactions, hash.as_bytes())
+    }
+
+    // Stop tracking a submitted transaction
+    pub fn delete_pending_transaction(&mut self, hash: &Hash) -> Result<()> {
+        trace!("delete pending transaction {}", hash);
+        self.delete_from_disk(&self.pending_transactions, hash.as_bytes())
+    }
+
+    // Retrieve all tracked transactions ordered by nonce
+    pub fn get_pending_transactions(&self) -> Result<Vec<PendingTransaction>> {
+        trace!("get pending transactions");
+        let mut transactions = Vec::new();
+        for res in self.pending_transactions.iter() {
+            let (_, value) = res?;
+            let raw_value = &self.cipher.decrypt_value(&value)?;
+            let mut reader = Reader::new(raw_value);
+            transactions.push(PendingTransaction::read(&mut reader)?);
+        }
+        transactions.sort_by_key(|tx| tx.get_transaction().get_nonce());
+
+        Ok(transactions)
+    }
+
+    // Get a new unique ID for a scheduled payment of the account
+    pub fn get_next_scheduled_payment_id(&mut self) -> Result<u64> {
+        trace!("get next scheduled payment id");
+        let id = if self.contains_data(&self.account_extra, SCHEDULED_PAYMENT_ID_KEY)? {
+            self.load_from_disk(&self.account_extra, SCHEDULED_PAYMENT_ID_KEY)?
+        } else {
+            0
+        };
+        self.save_to_disk(&self.account_extra, SCHEDULED_PAYMENT_ID_KEY, &(id + 1).to_be_bytes())?;
+
+        Ok(id)
+    }
+
+    // Check if a scheduled payment exists using its ID
+    pub fn has_scheduled_payment(&self, id: u64) -> Result<bool> {
+        trace!("has scheduled payment {}", id);
+        self.contains_data(&self.scheduled_payments, &id.to_be_bytes())
+    }
+
+    // Retrieve a scheduled payment using its ID
+    pub fn get_scheduled_payment(&self, id: u64) -> Result<ScheduledPayment> {
+        trace!("get scheduled payment {}", id);
+        if !self.has_scheduled_payment(id)? {
+            return Err(WalletError::ScheduledPaymentNotFound(id).into())
+        }
+
+        self.load_from_disk(&self.scheduled_payments, &id.to_be_bytes())
+    }
+
+    // Save or update a scheduled payment
+    pub fn save_scheduled_payment(&mut self, payment: &ScheduledPayment) -> Result<()> {
+        trace!("save scheduled payment {}", payment.get_id());
+        self.save_to_disk(&self.scheduled_payments, &payment.get_id().to_be_bytes(), &payment.to_bytes())
+    }
+
+    // Cancel a scheduled payment
+    pub fn delete_scheduled_payment(&mut self, id: u64) -> Result<()> {
+        trace!("delete scheduled payment {}", id);
+        if !self.has_scheduled_payment(id)? {
+            return Err(WalletError::ScheduledPaymentNotFound(id).into())
+        }
+
+        self.delete_from_disk(&self.scheduled_payments, &id.to_be_bytes())
+    }
+
+    // Retrieve all scheduled payments of the account ordered by ID
+    pub fn get_scheduled_payments(&self) -> Result<Vec<ScheduledPayment>> {
+        trace!("get scheduled payments");
+        let mut payments = Vec::new();
+        for res in self.scheduled_payments.iter() {
+            let (_, value) = res?;
+            let raw_value = &self.cipher.decrypt_value(&value)?;
+            let mut reader = Reader::new(raw_value);
+            payments.push(ScheduledPayment::read(&mut reader)?);
+        }
+        payments.sort_by_key(|payment| payment.get_id());
+
+        Ok(payments)
     }
 
     // Delete all balances from this wallet
@@ -852,7 +1386,7 @@ impl EncryptedStorage {
     // Retrieve the nonce used to create new transactions
     pub fn get_nonce(&self) -> Result<u64> {
         trace!("get nonce");
-        self.load_from_disk(&self.extra, NONCE_KEY)
+        self.load_from_disk(&self.account_extra, NONCE_KEY)
     }
 
     // Get the unconfirmed nonce to use to build ordered TXs
@@ -878,7 +1412,7 @@ impl EncryptedStorage {
     // If the unconfirmed nonce is lower than the new nonce, we reset it
     pub fn set_nonce(&mut self, nonce: u64) -> Result<()> {
         trace!("set nonce to {}", nonce);
-        self.save_to_disk(&self.extra, NONCE_KEY, &nonce.to_be_bytes())
+        self.save_to_disk(&self.account_extra, NONCE_KEY, &nonce.to_be_bytes())
     }
 
     // Store the last coinbase reward topoheight
@@ -891,9 +1425,9 @@ impl EncryptedStorage {
                 return Ok(());
             }
 
-            self.save_to_disk(&self.extra, LCRT, &topoheight.to_be_bytes())?;
+            self.save_to_disk(&self.account_extra, LCRT, &topoheight.to_be_bytes())?;
         } else {
-            self.delete_from_disk(&self.extra, LCRT)?;
+            self.delete_from_disk(&self.account_extra, LCRT)?;
         }
 
         self.last_coinbase_reward_topoheight = topoheight;
@@ -922,7 +1456,7 @@ impl EncryptedStorage {
     pub fn set_synced_topoheight(&mut self, topoheight: u64) -> Result<()> {
         trace!("set synced topoheight to {}", topoheight);
         self.synced_topoheight = Some(topoheight);
-        self.save_to_disk(&self.extra, TOPOHEIGHT_KEY, &topoheight.to_be_bytes())
+        self.save_to_disk(&self.account_extra, TOPOHEIGHT_KEY, &topoheight.to_be_bytes())
     }
 
     // Get the topoheight until which the wallet is synchronized
@@ -934,32 +1468,32 @@ impl EncryptedStorage {
             return Ok(topoheight);
         }
 
-        let synced_topoheight = self.load_from_disk(&self.extra, TOPOHEIGHT_KEY)?;
+        let synced_topoheight = self.load_from_disk(&self.account_extra, TOPOHEIGHT_KEY)?;
         Ok(synced_topoheight)
     }
 
     // Delete the top block hash
     pub fn delete_top_block_hash(&mut self) -> Result<()> {
         trace!("delete top block hash");
-        self.delete_from_disk(&self.extra, TOP_BLOCK_HASH_KEY)
+        self.delete_from_disk(&self.account_extra, TOP_BLOCK_HASH_KEY)
     }
 
     // Set the top block hash until which the wallet is synchronized
     pub fn set_top_block_hash(&mut self, hash: &Hash) -> Result<()> {
         trace!("set top block hash to {}", hash);
-        self.save_to_disk(&self.extra, TOP_BLOCK_HASH_KEY, hash.as_bytes())
+        self.save_to_disk(&self.account_extra, TOP_BLOCK_HASH_KEY, hash.as_bytes())
     }
 
     // Check if a top block hash is set 
     pub fn has_top_block_hash(&self) -> Result<bool> {
         trace!("has top block hash");
-        self.contains_data(&self.extra, TOP_BLOCK_HASH_KEY)
+        self.contains_data(&self.account_extra, TOP_BLOCK_HASH_KEY)
     }
 
     // Top block hash until which the wallet is synchronized 
     pub fn get_top_block_hash(&self) -> Result<Hash> {
         trace!("get top block hash");
-        self.load_from_disk(&self.extra, TOP_BLOCK_HASH_KEY)
+        self.load_from_disk(&self.account_extra, TOP_BLOCK_HASH_KEY)
     }
 
     pub fn get_public_storage(&self) -> &Storage {
@@ -1137,4 +1671,77 @@ impl Storage {
         self.db.insert(SALT_KEY, salt)?;
         Ok(())
     }
-}
\ No newline at end of file
+
+    // Import the trees copied from another wallet storage
+    pub fn import_trees(&mut self, trees: &[BackupTree]) -> Result<()> {
+        trace!("import trees");
+        for backup in trees {
+            let tree = self.db.open_tree(&backup.name)?;
+            for (key, value) in &backup.entries {
+                tree.insert(key.as_slice(), value.as_slice())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use vyridium_common::{
+        account::CiphertextCache,
+        config::VYRIDIUM_ASSET,
+        crypto::{elgamal::Ciphertext, Hash},
+        network::Network,
+        tokio::runtime::Runtime
+    };
+    use crate::{
+        entry::{EntryData, TransactionEntry},
+        precomputed_tables::PrecomputedTables,
+        wallet::Wallet
+    };
+    use super::Balance;
+
+    #[test]
+    fn test_account_isolation() {
+        let dir = format!("{}/vyridium-accounts-{:x}", std::env::temp_dir().display(), rand::random::<u64>());
+        // tables are not used as no balance is decrypted
+        let precomputed_tables = Arc::new(PrecomputedTables::with_bytes(&[], 0));
+        let hash = Hash::new([1u8; 32]);
+
+        Runtime::new().unwrap().block_on(async {
+            let wallet = Wallet::create(format!("{}/wallet", dir), "password".to_owned(), None, Network::Testnet, precomputed_tables).unwrap();
+            let mut storage = wallet.get_storage().write().await;
+            storage.set_balance_for(&VYRIDIUM_ASSET, Balance::new(100, CiphertextCache::Decompressed(Ciphertext::zero()))).await.unwrap();
+            storage.save_transaction(&hash, &TransactionEntry::new(hash.clone(), 10, EntryData::Coinbase { reward: 50 })).unwrap();
+            storage.set_nonce(5).unwrap();
+
+            let account = storage.add_account(Some("savings".to_owned())).unwrap();
+            assert_eq!(account, 1);
+
+            // requests for another account are rejected until we switch to it
+            assert!(storage.verify_active_account(Some(account)).is_err());
+            assert!(storage.verify_active_account(Some(0)).is_ok());
+
+            storage.set_active_account(account).await.unwrap();
+            assert!(storage.verify_active_account(Some(0)).is_err());
+            assert!(storage.verify_active_account(Some(account)).is_ok());
+
+            // nothing from the first account is visible
+            assert!(storage.get_balance_for(&VYRIDIUM_ASSET).await.is_err());
+            assert!(!storage.has_transaction(&hash).unwrap());
+            assert!(storage.get_transactions().unwrap().is_empty());
+            assert!(storage.get_nonce().is_err());
+
+            storage.set_nonce(1).unwrap();
+
+            // first account is unchanged
+            storage.set_active_account(0).await.unwrap();
+            assert_eq!(storage.get_balance_for(&VYRIDIUM_ASSET).await.unwrap().amount, 100);
+            assert!(storage.has_transaction(&hash).unwrap());
+            assert_eq!(storage.get_nonce().unwrap(), 5);
+        });
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
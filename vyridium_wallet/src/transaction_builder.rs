@@ -11,16 +11,23 @@ use crate::{error::WalletError, storage::{Balance, EncryptedStorage, TxCache}};
 // We need to give this information during the estimation of fees
 pub struct EstimateFeesState {
     // this is containing the registered keys that we are aware of
-    registered_keys: HashSet<PublicKey>
+    registered_keys: HashSet<PublicKey>,
+    // fee per KB estimated by the daemon for the targeted blocks count
+    fee_per_kb: Option<u64>
 }
 
 impl EstimateFeesState {
     pub fn new() -> Self {
         Self {
-            registered_keys: HashSet::new()
+            registered_keys: HashSet::new(),
+            fee_per_kb: None
         }
     }
 
+    pub fn set_fee_per_kb(&mut self, fee_per_kb: u64) {
+        self.fee_per_kb = Some(fee_per_kb);
+    }
+
     pub fn set_registered_keys(&mut self, registered_keys: HashSet<PublicKey>) {
         self.registered_keys = registered_keys;
     }
@@ -33,6 +40,10 @@ impl EstimateFeesState {
 impl FeeHelper for EstimateFeesState {
     type Error = WalletError;
 
+    fn get_fee_per_kb_for_target(&self, _: u64) -> Option<u64> {
+        self.fee_per_kb
+    }
+
     fn account_exists(&self, key: &PublicKey) -> Result<bool, Self::Error> {
         Ok(self.registered_keys.contains(key))
     }
@@ -53,9 +64,7 @@ pub struct TransactionBuilderState {
 impl TransactionBuilderState {
     pub fn new(mainnet: bool, reference: Reference, nonce: u64) -> Self {
         Self {
-            inner: EstimateFeesState {
-                registered_keys: HashSet::new(),
-            },
+            inner: EstimateFeesState::new(),
             mainnet,
             balances: HashMap::new(),
             reference,
@@ -118,6 +127,10 @@ impl TransactionBuilderState {
 impl FeeHelper for TransactionBuilderState {
     type Error = WalletError;
 
+    fn get_fee_per_kb_for_target(&self, blocks: u64) -> Option<u64> {
+        self.inner.get_fee_per_kb_for_target(blocks)
+    }
+
     fn account_exists(&self, key: &PublicKey) -> Result<bool, Self::Error> {
         self.inner.account_exists(key)
     }
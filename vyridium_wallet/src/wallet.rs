@@ -1,4 +1,7 @@
-use std::sync::{atomic::{AtomicBool, Ordering}, Arc};
+use std::{
+    collections::HashMap,
+    sync::{atomic::{AtomicBool, Ordering}, Arc, RwLock as StdRwLock}
+};
 use anyhow::{Error, Context};
 use serde::Serialize;
 use vyridium_common::{
@@ -13,8 +16,18 @@ use vyridium_common::{
     },
     api::{
         wallet::{
+            AccountEntry,
             BalanceChanged,
+            BatchPayoutRecipient,
+            BatchPayoutResult,
+            CreateInvoiceParams,
+            InvoiceEntry,
+            InvoiceStatus,
+            ListTransactionsParams,
             NotifyEvent,
+            PendingTransactionEntry,
+            ScheduledPaymentEntry,
+            SchedulePaymentParams,
             TransactionEntry
         },
         DataElement
@@ -24,17 +37,22 @@ use vyridium_common::{
         ecdlp::{self, ECDLPTablesFileView},
         elgamal::{Ciphertext, DecryptHandle},
         Address,
+        Hash,
         Hashable,
         KeyPair,
+        PrivateKey,
         PublicKey,
         Signature
     },
+    config::VYRIDIUM_ASSET,
     network::Network,
+    time::get_current_time_in_seconds,
     transaction::{
         builder::{
             FeeBuilder,
             TransactionBuilder,
-            TransactionTypeBuilder
+            TransactionTypeBuilder,
+            TransferBuilder
         },
         TxVersion,
         extra_data::UnknownExtraDataFormat,
@@ -44,14 +62,22 @@ use vyridium_common::{
     }
 };
 use crate::{
+    application::{ApplicationPermissions, ApplicationPolicy},
+    backup::WalletBackup,
+    batch,
     cipher::Cipher,
     config::{
         PASSWORD_ALGORITHM,
         PASSWORD_HASH_SIZE,
         SALT_SIZE
     },
+    entry::{EntryData, TransactionEntry as InnerTransactionEntry},
     error::WalletError,
+    export::{self, HistoryRecord},
+    invoice::{self, Invoice},
     mnemonics,
+    pending_transaction::PendingTransaction,
+    scheduled_payment::ScheduledPayment,
     precomputed_tables::{self, PrecomputedTablesShared},
     storage::{
         EncryptedStorage,
@@ -65,21 +91,23 @@ use crate::{
 #[cfg(feature = "network_handler")]
 use {
     std::collections::HashSet,
-    log::warn,
     crate::{
         network_handler::{
             NetworkHandler,
             SharedNetworkHandler
         },
         daemon_api::DaemonAPI,
+        config::MAX_BLOCKS_RANGE,
         storage::Balance,
     },
-    vyridium_common::config::VYRIDIUM_ASSET,
+    vyridium_common::time::TimestampMillis,
 };
 use rand::{rngs::OsRng, RngCore};
 use log::{
     trace,
     debug,
+    info,
+    warn,
     error,
 };
 
@@ -87,6 +115,7 @@ use log::{
 use {
     serde_json::{json, Value},
     async_trait::async_trait,
+    crate::application::Permission,
     crate::api::{
         XSWDNodeMethodHandler,
         register_rpc_methods,
@@ -100,6 +129,7 @@ use {
         XSWDPermissionHandler
     },
     vyridium_common::{
+        time::TimestampSeconds,
         rpc_server::{
             RPCHandler,
             RpcRequest,
@@ -152,7 +182,13 @@ pub enum Event {
     // Wallet is now in online mode
     Online,
     // Wallet is now in offline mode
-    Offline
+    Offline,
+    // When an invoice received a payment or expired
+    InvoiceUpdated(InvoiceEntry),
+    // When a submitted transaction changed of status
+    PendingTransactionUpdated(PendingTransactionEntry),
+    // When a scheduled payment was executed or skipped
+    ScheduledPaymentRun(ScheduledPaymentEntry)
 }
 
 impl Event {
@@ -164,7 +200,10 @@ impl Event {
             Event::NewAsset(_) => NotifyEvent::NewAsset,
             Event::Rescan { .. } => NotifyEvent::Rescan,
             Event::Online => NotifyEvent::Online,
-            Event::Offline => NotifyEvent::Offline
+            Event::Offline => NotifyEvent::Offline,
+            Event::InvoiceUpdated(_) => NotifyEvent::InvoiceUpdated,
+            Event::PendingTransactionUpdated(_) => NotifyEvent::PendingTransactionUpdated,
+            Event::ScheduledPaymentRun(_) => NotifyEvent::ScheduledPaymentRun
         }
     }
 
@@ -175,7 +214,10 @@ pub struct Wallet {
     storage: RwLock<EncryptedStorage>,
     // Inner account with keys and precomputed tables
     // so it can be shared to another thread for decrypting ciphertexts
-    inner: Arc<InnerAccount>,
+    // It is replaced when switching to another account
+    inner: StdRwLock<Arc<InnerAccount>>,
+    // Private key from the seed, used to derive the accounts keys
+    master_key: PrivateKey,
     // network handler for online mode to keep wallet synced
     #[cfg(feature = "network_handler")]
     network_handler: Mutex<Option<SharedNetworkHandler>>,
@@ -237,8 +279,20 @@ impl Wallet {
         precomputed_tables::read_or_generate_precomputed_tables(path, progress_report, PRECOMPUTED_TABLES_L1)
     }
 
-    // Create a new wallet with the specificed storage, keypair and its network
-    fn new(storage: EncryptedStorage, keypair: KeyPair, network: Network, precomputed_tables: PrecomputedTablesShared) -> Arc<Self> {
+    // Derive the private key of an account from the master key
+    // First account is using directly the master key to stay compatible with existing wallets
+    fn derive_account_key(master_key: &PrivateKey, account: u32) -> PrivateKey {
+        if account == 0 {
+            master_key.clone()
+        } else {
+            master_key.derive_child(account)
+        }
+    }
+
+    // Create a new wallet with the specificed storage, master key and its network
+    // The keypair used is the one from the active account in storage
+    fn new(storage: EncryptedStorage, master_key: PrivateKey, network: Network, precomputed_tables: PrecomputedTablesShared) -> Arc<Self> {
+        let keypair = KeyPair::from_private_key(Self::derive_account_key(&master_key, storage.get_active_account()));
         let zelf = Self {
             storage: RwLock::new(storage),
             #[cfg(feature = "network_handler")]
@@ -251,7 +305,8 @@ impl Wallet {
             event_broadcaster: Mutex::new(None),
             history_scan: AtomicBool::new(true),
             force_stable_balance: AtomicBool::new(false),
-            inner: InnerAccount::new(precomputed_tables, keypair)
+            inner: StdRwLock::new(InnerAccount::new(precomputed_tables, keypair)),
+            master_key
         };
 
         Arc::new(zelf)
@@ -314,7 +369,7 @@ impl Wallet {
         // Flush the storage to be sure its written on disk
         storage.flush()?;
 
-        Ok(Self::new(storage, keypair, network, precomputed_tables))
+        Ok(Self::new(storage, keypair.get_private_key().clone(), network, precomputed_tables))
     }
 
     // Open an existing wallet on disk
@@ -355,9 +410,53 @@ impl Wallet {
         let storage = EncryptedStorage::new(storage, &master_key, salt, network)?;
         debug!("Retrieving private key from encrypted storage");
         let private_key =  storage.get_private_key()?;
-        let keypair = KeyPair::from_private_key(private_key);
 
-        Ok(Self::new(storage, keypair, network, precomputed_tables))
+        Ok(Self::new(storage, private_key, network, precomputed_tables))
+    }
+
+    // Restore a wallet on disk from an encrypted backup
+    // All accounts are restored with their history and sync state, so no rescan is needed
+    pub fn restore(name: String, password: String, backup: &[u8], backup_password: String, network: Network, precomputed_tables: PrecomputedTablesShared) -> Result<Arc<Self>, Error> {
+        if name.is_empty() {
+            return Err(WalletError::EmptyName.into())
+        }
+
+        debug!("Decrypting backup");
+        let backup = WalletBackup::decrypt(backup, backup_password)?;
+        if backup.get_network() != network {
+            return Err(WalletError::BackupNetworkMismatch(backup.get_network(), network).into())
+        }
+
+        // generate random salt for hashed password
+        let mut salt: [u8; SALT_SIZE] = [0; SALT_SIZE];
+        OsRng.fill_bytes(&mut salt);
+
+        debug!("hashing provided password");
+        let hashed_password = hash_password(password, &salt)?;
+        let cipher = Cipher::new(&hashed_password, None)?;
+
+        debug!("Creating storage for {}", name);
+        let mut inner = Storage::new(name)?;
+        inner.set_password_salt(&salt)?;
+
+        // the storage is encrypted with the same key and salt as the backed up wallet
+        let encrypted_master_key = cipher.encrypt_value(backup.get_storage_key())?;
+        inner.set_encrypted_master_key(&encrypted_master_key)?;
+        let encrypted_storage_salt = cipher.encrypt_value(backup.get_storage_salt())?;
+        inner.set_encrypted_storage_salt(&encrypted_storage_salt)?;
+
+        debug!("Importing {} trees from backup", backup.get_trees().len());
+        inner.import_trees(backup.get_trees())?;
+
+        debug!("Creating encrypted storage");
+        let mut storage = EncryptedStorage::new(inner, backup.get_storage_key(), *backup.get_storage_salt(), network)?;
+        debug!("Retrieving private key from encrypted storage");
+        let private_key = storage.get_private_key()?;
+
+        // Flush the storage to be sure its written on disk
+        storage.flush()?;
+
+        Ok(Self::new(storage, private_key, network, precomputed_tables))
     }
 
     // Close the wallet
@@ -575,6 +674,37 @@ impl Wallet {
         Ok(())
     }
 
+    // Export a backup of the whole wallet encrypted with the backup password
+    // The wallet password is required to retrieve the key used to encrypt the storage
+    pub async fn export_backup(&self, password: String, backup_password: String) -> Result<Vec<u8>, Error> {
+        let storage = self.storage.read().await;
+        let (storage_key, storage_salt) = {
+            let inner = storage.get_public_storage();
+            let salt = inner.get_password_salt()?;
+            let hashed_password = hash_password(password, &salt)?;
+
+            let cipher = Cipher::new(&hashed_password, None)?;
+            let storage_key = cipher.decrypt_value(&inner.get_encrypted_master_key()?).context("Invalid password provided")?;
+            let storage_salt = cipher.decrypt_value(&inner.get_encrypted_storage_salt()?)?;
+            (storage_key, storage_salt)
+        };
+
+        let storage_salt: [u8; SALT_SIZE] = storage_salt.try_into()
+            .map_err(|_| WalletError::InvalidSaltSize)?;
+
+        debug!("Exporting storage trees");
+        let backup = WalletBackup::new(
+            self.network,
+            get_current_time_in_seconds(),
+            storage.get_synced_topoheight().ok(),
+            storage_key,
+            storage_salt,
+            storage.export_trees()?
+        );
+
+        Ok(backup.encrypt(backup_password)?)
+    }
+
     // Wallet has to be under a Arc to be shared to the spawn_blocking function
     pub async fn decrypt_ciphertext(&self, ciphertext: Ciphertext) -> Result<u64, WalletError> {
         // TODO: is it still useful to spawn a task for that ?
@@ -585,7 +715,7 @@ impl Wallet {
         )))]
         {
             trace!("decrypt ciphertext with a spawn blocking task");
-            let account = Arc::clone(&self.inner);
+            let account = self.get_account();
             spawn_blocking(move || account.decrypt_ciphertext(&ciphertext)).await.context("Error while decrypting ciphertext")?
         }
         #[cfg(all(
@@ -595,14 +725,14 @@ impl Wallet {
         ))]
         {
             trace!("decrypt ciphertext without spawn blocking task");
-            self.inner.decrypt_ciphertext(&ciphertext)
+            self.get_account().decrypt_ciphertext(&ciphertext)
         }
     }
 
     // Decrypt the extra data from a transfer
     pub fn decrypt_extra_data(&self, cipher: UnknownExtraDataFormat, handle: &DecryptHandle, role: Role) -> Result<DataElement, WalletError> {
         trace!("decrypt extra data");
-        cipher.decrypt(&self.inner.keypair.get_private_key(), handle, role).map_err(|_| WalletError::CiphertextDecode)
+        cipher.decrypt(self.get_account().keypair.get_private_key(), handle, role).map_err(|_| WalletError::CiphertextDecode)
     }
 
     // Create a transaction with the given transaction type and fee
@@ -670,7 +800,7 @@ impl Wallet {
                             // So it will be fetch later by state
                             let mut ciphertext = stable_point.version.take_balance();
                             debug!("decrypting stable balance for asset {}", asset);
-                            let amount = self.inner.decrypt_ciphertext(ciphertext.decompressed().map_err(|_| WalletError::CiphertextDecode)?)?;
+                            let amount = self.get_account().decrypt_ciphertext(ciphertext.decompressed().map_err(|_| WalletError::CiphertextDecode)?)?;
                             let balance = Balance {
                                 amount,
                                 ciphertext
@@ -727,10 +857,10 @@ impl Wallet {
         self.add_registered_keys_for_fees_estimation(state.as_mut(), &fee, &transaction_type).await?;
 
         // Create the transaction builder
-        let builder = TransactionBuilder::new(TxVersion::V0, self.get_public_key().clone(), transaction_type, fee);
+        let builder = TransactionBuilder::new(TxVersion::V0, self.get_public_key(), transaction_type, fee);
 
         // Build the final transaction
-        let transaction = builder.build(&mut state, &self.inner.keypair)
+        let transaction = builder.build(&mut state, &self.get_account().keypair)
             .map_err(|e| WalletError::Any(e.into()))?;
 
         let tx_hash = transaction.hash();
@@ -744,23 +874,58 @@ impl Wallet {
     // It will increase the local nonce by 1 if the TX is accepted by the daemon
     // returns error if the wallet is in offline mode or if the TX is rejected
     pub async fn submit_transaction(&self, transaction: &Transaction) -> Result<(), WalletError> {
+        let mut storage = self.storage.write().await;
+        self.submit_transaction_with_storage(&mut storage, transaction).await
+    }
+
+    // submit a transaction using the storage already locked
+    // Once accepted by the daemon, the transaction is tracked until it is executed in a stable block
+    pub async fn submit_transaction_with_storage(&self, storage: &mut EncryptedStorage, transaction: &Transaction) -> Result<(), WalletError> {
         trace!("submit transaction {}", transaction.hash());
         #[cfg(feature = "network_handler")]
         {
             let network_handler = self.network_handler.lock().await;
             if let Some(network_handler) = network_handler.as_ref() {
                 network_handler.get_api().submit_transaction(transaction).await?;
+
+                let pending = PendingTransaction::new(transaction.clone(), get_current_time_in_seconds());
+                storage.save_pending_transaction(&pending)?;
+                self.propagate_event(Event::PendingTransactionUpdated(pending.serializable())).await;
                 return Ok(())
             }
         }
         Err(WalletError::NotOnlineMode)
     }
 
+    // Retrieve the transactions submitted by the active account
+    // that are not yet executed in a stable block
+    pub async fn get_pending_transactions(&self) -> Result<Vec<PendingTransactionEntry>, WalletError> {
+        trace!("get pending transactions");
+        let storage = self.storage.read().await;
+        let transactions = storage.get_pending_transactions()?
+            .iter()
+            .map(PendingTransaction::serializable)
+            .collect();
+
+        Ok(transactions)
+    }
+
     // Search if possible all registered keys for the transaction type
     #[cfg(feature = "network_handler")]
     pub async fn add_registered_keys_for_fees_estimation(&self, state: &mut EstimateFeesState, fee: &FeeBuilder, transaction_type: &TransactionTypeBuilder) -> Result<(), WalletError> {
         trace!("add registered keys for fees estimation");
-        if let FeeBuilder::Multiplier(_) = fee {
+        if let FeeBuilder::Target(blocks) = fee {
+            // Retrieve the fee rate from the daemon, the minimum is used if we are offline
+            if let Some(network_handler) = self.network_handler.lock().await.as_ref() {
+                if network_handler.is_running().await {
+                    let estimation = network_handler.get_api().estimate_fee_rate(*blocks).await?;
+                    debug!("Estimated fee per KB for {} blocks: {}", blocks, estimation.target);
+                    state.set_fee_per_kb(estimation.target);
+                }
+            }
+        }
+
+        if let FeeBuilder::Multiplier(_) | FeeBuilder::Target(_) = fee {
             // To pay exact fees needed, we must verify that we don't have to pay more than needed
             let used_keys = transaction_type.used_keys();
             let mut processed_keys = HashSet::new();
@@ -801,7 +966,7 @@ impl Wallet {
         #[cfg(feature = "network_handler")]
         self.add_registered_keys_for_fees_estimation(&mut state, &FeeBuilder::default(), &tx_type).await?;
 
-        let builder = TransactionBuilder::new(TxVersion::V0, self.get_public_key().clone(), tx_type, FeeBuilder::default());
+        let builder = TransactionBuilder::new(TxVersion::V0, self.get_public_key(), tx_type, FeeBuilder::default());
         let estimated_fees = builder.estimate_fees(&mut state)
             .map_err(|e| WalletError::Any(e.into()))?;
 
@@ -892,13 +1057,14 @@ impl Wallet {
                 debug!("reconnect API");
                 network_handler.get_api().reconnect().await?;
 
-                if topoheight == 0 {
+                let reverted = if topoheight == 0 {
                     debug!("Deleting all transactions for full rescan");
-                    storage.delete_transactions()?;
+                    storage.delete_transactions()?
                 } else {
                     debug!("Deleting transactions above {} for partial rescan", topoheight);
-                    storage.delete_transactions_above_topoheight(topoheight)?;
-                }
+                    storage.delete_transactions_above_topoheight(topoheight)?
+                };
+                self.propagate_invoices_updated(reverted).await;
             }
             debug!("Starting again network handler");
             network_handler.start(auto_reconnect).await.context("Error while restarting network handler")?;
@@ -928,30 +1094,620 @@ impl Wallet {
 
     // Create a signature of the given data
     pub fn sign_data(&self, data: &[u8]) -> Signature {
-        self.inner.keypair.sign(data)
+        self.get_account().keypair.sign(data)
+    }
+
+    // Get the account currently used
+    // It can be shared to another thread without locking the wallet
+    fn get_account(&self) -> Arc<InnerAccount> {
+        Arc::clone(&self.inner.read().expect("inner account lock poisoned"))
     }
 
-    // Get the public key of the wallet
-    pub fn get_public_key(&self) -> &PublicKey {
-        &self.inner.public_key
+    // Get the public key of the active account
+    pub fn get_public_key(&self) -> PublicKey {
+        self.get_account().public_key.clone()
     }
 
     // Get the address of the wallet using its network used
     pub fn get_address(&self) -> Address {
-        self.get_public_key().clone().to_address(self.get_network().is_mainnet())
+        self.get_public_key().to_address(self.get_network().is_mainnet())
     }
 
     // Get the address with integrated data and using its network used
     pub fn get_address_with(&self, data: DataElement) -> Address {
-        self.get_public_key().clone().to_address_with(self.get_network().is_mainnet(), data)
+        self.get_public_key().to_address_with(self.get_network().is_mainnet(), data)
     }
 
     // Returns the seed using the language index provided
     pub fn get_seed(&self, language_index: usize) -> Result<String, Error> {
-        let words = mnemonics::key_to_words(self.inner.keypair.get_private_key(), language_index)?;
+        let words = mnemonics::key_to_words(&self.master_key, language_index)?;
         Ok(words.join(" "))
     }
 
+    // Get the account currently used by the wallet
+    pub async fn get_active_account(&self) -> u32 {
+        let storage = self.storage.read().await;
+        storage.get_active_account()
+    }
+
+    // Get the address of an account derived from the seed
+    fn get_account_address(&self, account: u32) -> Address {
+        let keypair = KeyPair::from_private_key(Self::derive_account_key(&self.master_key, account));
+        keypair.get_public_key().to_address(self.network.is_mainnet())
+    }
+
+    // Create a new account derived from the seed
+    // It is not used until the wallet switch to it
+    pub async fn create_account(&self, name: Option<String>) -> Result<AccountEntry, WalletError> {
+        trace!("create account");
+        let mut storage = self.storage.write().await;
+        let index = storage.add_account(name.clone())?;
+        debug!("Account {} created", index);
+
+        Ok(AccountEntry {
+            index,
+            name,
+            address: self.get_account_address(index)
+        })
+    }
+
+    // List all the accounts derived from the seed
+    pub async fn list_accounts(&self) -> Result<Vec<AccountEntry>, WalletError> {
+        trace!("list accounts");
+        let storage = self.storage.read().await;
+        let mut accounts = Vec::new();
+        for index in 0..storage.get_accounts_count()? {
+            accounts.push(AccountEntry {
+                index,
+                name: storage.get_account_name(index)?,
+                address: self.get_account_address(index)
+            });
+        }
+
+        Ok(accounts)
+    }
+
+    // Switch the wallet to another account derived from the seed
+    // If the wallet is online, the network handler is restarted to sync the new account
+    pub async fn switch_account(&self, account: u32) -> Result<(), WalletError> {
+        trace!("switch to account {}", account);
+        let mut storage = self.storage.write().await;
+        if storage.get_active_account() == account {
+            return Ok(())
+        }
+
+        #[cfg(feature = "network_handler")]
+        let handler = self.network_handler.lock().await;
+        #[cfg(feature = "network_handler")]
+        let running_handler = match handler.as_ref() {
+            Some(network_handler) if network_handler.is_running().await => {
+                debug!("Stopping network handler!");
+                network_handler.stop().await?;
+                Some(network_handler)
+            },
+            _ => None
+        };
+
+        // caches of the previous account are cleared
+        storage.set_active_account(account).await?;
+        {
+            let mut inner = self.inner.write().expect("inner account lock poisoned");
+            let keypair = KeyPair::from_private_key(Self::derive_account_key(&self.master_key, account));
+            *inner = InnerAccount::new(Arc::clone(&inner.precomputed_tables), keypair);
+        }
+        info!("Switched to account {}", account);
+
+        #[cfg(feature = "network_handler")]
+        if let Some(network_handler) = running_handler {
+            debug!("reconnect API");
+            network_handler.get_api().reconnect().await?;
+            debug!("Starting again network handler");
+            network_handler.start(network_handler.is_auto_reconnect()).await.context("Error while restarting network handler")?;
+        }
+
+        Ok(())
+    }
+
+    // Build the history records of all transactions matching the filters
+    // Block timestamps are fetched from the daemon when the wallet is online
+    pub async fn get_history_records(&self, filters: &ListTransactionsParams) -> Result<Vec<HistoryRecord>, WalletError> {
+        trace!("get history records");
+        let key = filters.address.clone().map(|address| address.to_public_key());
+        let transactions = {
+            let storage = self.storage.read().await;
+            storage.verify_active_account(filters.account)?;
+            storage.get_filtered_transactions(key.as_ref(), filters.min_topoheight, filters.max_topoheight, filters.accept_incoming, filters.accept_outgoing, filters.accept_coinbase, filters.accept_burn, filters.query.as_ref())?
+        };
+
+        #[cfg(feature = "network_handler")]
+        let timestamps = self.get_blocks_timestamps(transactions.iter().map(|tx| tx.get_topoheight()).collect()).await;
+        #[cfg(not(feature = "network_handler"))]
+        let timestamps = HashMap::new();
+
+        let storage = self.storage.read().await;
+        let records = export::build_history_records(&storage, transactions, &timestamps, self.network.is_mainnet())?;
+        Ok(records)
+    }
+
+    // Retrieve the timestamp of the blocks at the requested topoheights
+    // Close topoheights are fetched together using a blocks range
+    // Topoheights that couldn't be fetched from the daemon are skipped
+    #[cfg(feature = "network_handler")]
+    async fn get_blocks_timestamps(&self, topoheights: HashSet<u64>) -> HashMap<u64, TimestampMillis> {
+        let mut timestamps = HashMap::new();
+        // The lock is released before fetching so the network handler is not blocked
+        let api = {
+            let handler = self.network_handler.lock().await;
+            match handler.as_ref() {
+                Some(network_handler) if network_handler.is_running().await => network_handler.get_shared_api(),
+                _ => return timestamps
+            }
+        };
+
+        let mut topoheights: Vec<u64> = topoheights.into_iter().collect();
+        topoheights.sort_unstable();
+
+        let mut index = 0;
+        while index < topoheights.len() {
+            let start = topoheights[index];
+            let count = topoheights[index..].iter().take_while(|topoheight| **topoheight - start <= MAX_BLOCKS_RANGE).count();
+            let end = topoheights[index + count - 1];
+            let requested = &topoheights[index..index + count];
+            index += count;
+
+            match api.get_blocks_range_by_topoheight(start, end).await {
+                Ok(blocks) => {
+                    for block in blocks {
+                        if let Some(topoheight) = block.topoheight.filter(|topoheight| requested.binary_search(topoheight).is_ok()) {
+                            timestamps.insert(topoheight, block.timestamp);
+                        }
+                    }
+                },
+                Err(e) => warn!("Error while fetching blocks from topoheight {} to {}: {}", start, end, e)
+            }
+        }
+
+        timestamps
+    }
+
+    // Create a new invoice to be paid on an integrated address of the active account
+    // A random ID is generated if none is provided
+    pub async fn create_invoice(&self, params: CreateInvoiceParams) -> Result<InvoiceEntry, WalletError> {
+        trace!("create invoice");
+        let id = match params.id {
+            Some(id) => id,
+            None => {
+                let mut bytes = [0u8; 16];
+                OsRng.fill_bytes(&mut bytes);
+                hex::encode(bytes)
+            }
+        };
+
+        let now = get_current_time_in_seconds();
+        let expire_at = params.expire_in.map(|delay| now.saturating_add(delay));
+        let invoice = Invoice::new(id, params.asset.unwrap_or(VYRIDIUM_ASSET), params.amount, params.memo, now, expire_at);
+        if !invoice.is_valid() {
+            return Err(WalletError::InvalidInvoice)
+        }
+
+        let mut storage = self.storage.write().await;
+        if storage.has_invoice(invoice.get_id())? {
+            return Err(WalletError::InvoiceAlreadyExists(invoice.get_id().clone()))
+        }
+        storage.save_invoice(&invoice)?;
+        debug!("Invoice {} created", invoice.get_id());
+
+        Ok(invoice.serializable(&self.get_public_key(), self.network.is_mainnet()))
+    }
+
+    // Retrieve an invoice of the active account
+    pub async fn get_invoice(&self, id: &str) -> Result<InvoiceEntry, WalletError> {
+        trace!("get invoice {}", id);
+        let storage = self.storage.read().await;
+        let invoice = storage.get_invoice(id)?;
+        Ok(invoice.serializable(&self.get_public_key(), self.network.is_mainnet()))
+    }
+
+    // List the invoices of the active account sorted by creation time
+    pub async fn list_invoices(&self, status: Option<InvoiceStatus>) -> Result<Vec<InvoiceEntry>, WalletError> {
+        trace!("list invoices");
+        let storage = self.storage.read().await;
+        let mut invoices = storage.get_invoices()?;
+        invoices.sort_by(|a, b| a.get_created_at().cmp(&b.get_created_at()));
+
+        let key = self.get_public_key();
+        let entries = invoices.into_iter()
+            .filter(|invoice| status.map_or(true, |status| invoice.get_status() == status))
+            .map(|invoice| invoice.serializable(&key, self.network.is_mainnet()))
+            .collect();
+
+        Ok(entries)
+    }
+
+    // Apply the transfers of an incoming transaction to the invoices they are paying
+    // Transfers are matched using the invoice ID in their extra data
+    pub async fn process_invoice_payments(&self, entry: &InnerTransactionEntry) -> Result<(), WalletError> {
+        let transfers = match entry.get_entry() {
+            EntryData::Incoming { transfers, .. } => transfers,
+            _ => return Ok(())
+        };
+
+        // sum the transfers of the transaction paying the same invoice
+        let mut payments: Vec<(&String, &Hash, u64)> = Vec::new();
+        for transfer in transfers {
+            let id = match transfer.get_extra_data().as_ref().and_then(invoice::get_invoice_id) {
+                Some(id) => id,
+                None => continue
+            };
+
+            match payments.iter_mut().find(|(payment_id, asset, _)| *payment_id == id && *asset == transfer.get_asset()) {
+                Some((_, _, amount)) => *amount = amount.saturating_add(transfer.get_amount()),
+                None => payments.push((id, transfer.get_asset(), transfer.get_amount()))
+            };
+        }
+
+        let now = get_current_time_in_seconds();
+        let mut updated = Vec::new();
+        {
+            let mut storage = self.storage.write().await;
+            for (id, asset, amount) in payments {
+                if !storage.has_invoice(id)? {
+                    debug!("No invoice {} found for transaction {}", id, entry.get_hash());
+                    continue;
+                }
+
+                let mut invoice = storage.get_invoice(id)?;
+                if invoice.apply_payment(entry.get_hash(), asset, amount, entry.get_topoheight(), now) {
+                    info!("Invoice {} received {} from transaction {}", id, amount, entry.get_hash());
+                    storage.save_invoice(&invoice)?;
+                    updated.push(invoice);
+                } else {
+                    warn!("Transaction {} can't be applied to invoice {}", entry.get_hash(), id);
+                }
+            }
+        }
+
+        self.propagate_invoices_updated(updated).await;
+        Ok(())
+    }
+
+    // Mark as expired all open invoices that reached their expiration time
+    pub async fn expire_invoices(&self) -> Result<(), WalletError> {
+        let now = get_current_time_in_seconds();
+        let mut expired = Vec::new();
+        {
+            let mut storage = self.storage.write().await;
+            for mut invoice in storage.get_invoices()? {
+                if invoice.is_open() && invoice.update_status(now) {
+                    debug!("Invoice {} is now {:?}", invoice.get_id(), invoice.get_status());
+                    storage.save_invoice(&invoice)?;
+                    expired.push(invoice);
+                }
+            }
+        }
+
+        self.propagate_invoices_updated(expired).await;
+        Ok(())
+    }
+
+    // Notify the new state of invoices that were updated
+    pub async fn propagate_invoices_updated(&self, invoices: Vec<Invoice>) {
+        let key = self.get_public_key();
+        for invoice in invoices {
+            self.propagate_event(Event::InvoiceUpdated(invoice.serializable(&key, self.network.is_mainnet()))).await;
+        }
+    }
+
+    // Schedule a payment to be submitted automatically while the wallet is online
+    // Integrated data of the destination is moved to the extra data of the transfer
+    pub async fn schedule_payment(&self, params: SchedulePaymentParams) -> Result<ScheduledPaymentEntry, WalletError> {
+        trace!("schedule payment");
+        if params.destination.is_mainnet() != self.network.is_mainnet() {
+            return Err(WalletError::InvalidAddressParams)
+        }
+
+        let (integrated_data, destination) = params.destination.extract_data();
+        let extra_data = match (integrated_data, params.extra_data) {
+            (Some(_), Some(_)) => return Err(WalletError::InvalidAddressParams),
+            (integrated_data, extra_data) => integrated_data.or(extra_data)
+        };
+
+        let mut storage = self.storage.write().await;
+        storage.verify_active_account(params.account)?;
+        let topoheight = storage.get_synced_topoheight().unwrap_or(0);
+        let id = storage.get_next_scheduled_payment_id()?;
+        let payment = ScheduledPayment::new(
+            id,
+            destination.to_public_key(),
+            params.asset.unwrap_or(VYRIDIUM_ASSET),
+            params.amount,
+            extra_data,
+            params.schedule,
+            topoheight,
+            get_current_time_in_seconds()
+        )?;
+
+        if !payment.is_valid() {
+            return Err(WalletError::InvalidScheduledPayment)
+        }
+
+        storage.save_scheduled_payment(&payment)?;
+        info!("Payment {} scheduled", id);
+
+        Ok(payment.serializable(self.network.is_mainnet()))
+    }
+
+    // List all scheduled payments of the active account
+    pub async fn list_scheduled_payments(&self) -> Result<Vec<ScheduledPaymentEntry>, WalletError> {
+        trace!("list scheduled payments");
+        let storage = self.storage.read().await;
+        let payments = storage.get_scheduled_payments()?
+            .iter()
+            .map(|payment| payment.serializable(self.network.is_mainnet()))
+            .collect();
+
+        Ok(payments)
+    }
+
+    // Pause or resume a scheduled payment
+    pub async fn set_scheduled_payment_paused(&self, id: u64, paused: bool) -> Result<ScheduledPaymentEntry, WalletError> {
+        trace!("set scheduled payment {} paused: {}", id, paused);
+        let mut storage = self.storage.write().await;
+        let mut payment = storage.get_scheduled_payment(id)?;
+        payment.set_paused(paused);
+        storage.save_scheduled_payment(&payment)?;
+
+        Ok(payment.serializable(self.network.is_mainnet()))
+    }
+
+    // Cancel a scheduled payment, it will never run again
+    pub async fn cancel_scheduled_payment(&self, id: u64) -> Result<(), WalletError> {
+        trace!("cancel scheduled payment {}", id);
+        let mut storage = self.storage.write().await;
+        storage.delete_scheduled_payment(id)?;
+        info!("Scheduled payment {} cancelled", id);
+
+        Ok(())
+    }
+
+    // Build and submit the scheduled payments of the active account that are due
+    // Payments of the other accounts only run once their account is active again
+    // A run is skipped and reported if the transaction can't be built (not enough funds) or submitted
+    pub async fn run_scheduled_payments(&self, topoheight: u64) -> Result<(), WalletError> {
+        trace!("run scheduled payments at topoheight {}", topoheight);
+        let now = get_current_time_in_seconds();
+        let payments = {
+            let storage = self.storage.read().await;
+            storage.get_scheduled_payments()?
+        };
+
+        for mut payment in payments.into_iter().filter(|payment| payment.is_due(topoheight, now)) {
+            let transfer = payment.to_transfer(self.network.is_mainnet());
+            // The lock is kept until the TX is applied to the storage
+            let mut storage = self.storage.write().await;
+            let result = match self.create_transaction_with_storage(&storage, TransactionTypeBuilder::Transfers(vec![transfer]), FeeBuilder::default()).await {
+                Ok((mut state, transaction)) => match self.submit_transaction_with_storage(&mut storage, &transaction).await {
+                    Ok(()) => {
+                        // The transaction is already submitted, the run is recorded to not pay it again
+                        let hash = transaction.hash();
+                        let error = match state.apply_changes(&mut storage).await {
+                            Ok(()) => None,
+                            Err(e) => {
+                                warn!("Error while applying changes of scheduled payment transaction {}: {}", hash, e);
+                                storage.clear_tx_cache();
+                                storage.delete_unconfirmed_balances().await;
+                                Some(format!("Transaction submitted but wallet state not updated: {}", e))
+                            }
+                        };
+                        Ok((hash, error))
+                    },
+                    Err(e) => {
+                        storage.clear_tx_cache();
+                        storage.delete_unconfirmed_balances().await;
+                        Err(e)
+                    }
+                },
+                Err(e) => Err(e)
+            };
+
+            match result {
+                Ok((hash, error)) => {
+                    info!("Scheduled payment {} submitted in transaction {}", payment.get_id(), hash);
+                    payment.record_run(Ok(hash), topoheight, now);
+                    if let Some(error) = error {
+                        payment.set_last_error(error);
+                    }
+                },
+                Err(e) => {
+                    warn!("Scheduled payment {} skipped: {}", payment.get_id(), e);
+                    payment.record_run(Err(e.to_string()), topoheight, now);
+                }
+            }
+
+            storage.save_scheduled_payment(&payment)?;
+            drop(storage);
+
+            self.propagate_event(Event::ScheduledPaymentRun(payment.serializable(self.network.is_mainnet()))).await;
+        }
+
+        Ok(())
+    }
+
+    // Send all the transfers in the minimum of transactions
+    // Transactions are built and submitted one after the other with following nonces
+    // Returns the result of each transfer in the same order as requested
+    // account is verified to be the active one under the storage lock
+    pub async fn batch_payout(&self, transfers: Vec<TransferBuilder>, fee: FeeBuilder, account: Option<u32>) -> Result<BatchPayoutResult, WalletError> {
+        trace!("batch payout of {} transfers", transfers.len());
+        if !self.is_online().await {
+            return Err(WalletError::NotOnlineMode)
+        }
+
+        // The lock is kept during the whole batch so the nonces are following each other
+        let mut storage = self.storage.write().await;
+        storage.verify_active_account(account)?;
+
+        let mut recipients = vec![BatchPayoutRecipient { tx_hash: None, error: None }; transfers.len()];
+        let source = self.get_public_key();
+        let mainnet = self.network.is_mainnet();
+
+        // Invalid transfers are rejected alone instead of failing their whole transaction
+        let mut valid = Vec::with_capacity(transfers.len());
+        for (index, transfer) in transfers.into_iter().enumerate() {
+            match batch::verify_transfer(&transfer, &source, mainnet) {
+                Ok(()) => valid.push((index, transfer)),
+                Err(e) => recipients[index].error = Some(e.to_string())
+            }
+        }
+
+        let mut transactions = Vec::new();
+        let mut stopped = false;
+        for chunk in batch::split_transfers(valid) {
+            let (indexes, transfers): (Vec<usize>, Vec<TransferBuilder>) = chunk.into_iter().unzip();
+            // Once a submit failed, the next transactions are not sent to not create a gap in the nonces
+            if stopped {
+                for index in indexes {
+                    recipients[index].error = Some("Previous transaction of the batch failed".to_owned());
+                }
+                continue;
+            }
+
+            let (mut state, transaction) = match self.create_transaction_with_storage(&storage, TransactionTypeBuilder::Transfers(transfers), fee.clone()).await {
+                Ok(v) => v,
+                Err(e) => {
+                    // Nothing was submitted, next transactions can still be sent
+                    warn!("Error while building batch transaction: {}", e);
+                    for index in indexes {
+                        recipients[index].error = Some(e.to_string());
+                    }
+                    continue;
+                }
+            };
+
+            if let Err(e) = self.submit_transaction_with_storage(&mut storage, &transaction).await {
+                warn!("Error while submitting batch transaction: {}", e);
+                storage.clear_tx_cache();
+                storage.delete_unconfirmed_balances().await;
+
+                for index in indexes {
+                    recipients[index].error = Some(e.to_string());
+                }
+                stopped = true;
+                continue;
+            }
+
+            let hash = transaction.hash();
+            info!("Batch transaction {} submitted with {} transfers", hash, indexes.len());
+            // The transaction is already submitted, only its recipients report the error
+            // Next transactions would reuse its nonce, so they are not sent
+            let error = match state.apply_changes(&mut storage).await {
+                Ok(()) => None,
+                Err(e) => {
+                    warn!("Error while applying changes of batch transaction {}: {}", hash, e);
+                    storage.clear_tx_cache();
+                    storage.delete_unconfirmed_balances().await;
+                    stopped = true;
+                    Some(format!("Transaction submitted but wallet state not updated: {}", e))
+                }
+            };
+
+            for index in indexes {
+                recipients[index].tx_hash = Some(hash.clone());
+                recipients[index].error = error.clone();
+            }
+            transactions.push(hash);
+        }
+
+        Ok(BatchPayoutResult {
+            transactions,
+            recipients
+        })
+    }
+
+    // Retrieve the permissions saved for the XSWD applications
+    pub async fn list_xswd_applications(&self) -> Result<Vec<ApplicationPermissions>, WalletError> {
+        trace!("list xswd applications");
+        let storage = self.storage.read().await;
+        let applications = storage.get_applications_permissions()?;
+        Ok(applications)
+    }
+
+    // Retrieve the permissions saved for an XSWD application
+    pub async fn get_xswd_application(&self, id: &str) -> Result<ApplicationPermissions, WalletError> {
+        trace!("get xswd application {}", id);
+        let storage = self.storage.read().await;
+        if !storage.has_application_permissions(id)? {
+            return Err(WalletError::XSWDApplicationNotFound(id.to_owned()))
+        }
+
+        let application = storage.get_application_permissions(id)?;
+        Ok(application)
+    }
+
+    // Retrieve the limits set on an XSWD application
+    // An application with saved permissions and no policy has no limits
+    pub async fn get_xswd_application_policy(&self, id: &str) -> Result<ApplicationPolicy, WalletError> {
+        trace!("get xswd application policy {}", id);
+        let storage = self.storage.read().await;
+        if storage.has_application_policy(id)? {
+            let policy = storage.get_application_policy(id)?;
+            return Ok(policy)
+        }
+
+        if !storage.has_application_permissions(id)? {
+            return Err(WalletError::XSWDApplicationNotFound(id.to_owned()))
+        }
+
+        Ok(ApplicationPolicy::default())
+    }
+
+    // Set the limits verified on each request of an XSWD application
+    // The application must have saved permissions or a policy
+    pub async fn set_xswd_application_policy(&self, id: &str, policy: ApplicationPolicy) -> Result<(), WalletError> {
+        trace!("set xswd application policy {}", id);
+        let mut storage = self.storage.write().await;
+        if !storage.has_application_permissions(id)? && !storage.has_application_policy(id)? {
+            return Err(WalletError::XSWDApplicationNotFound(id.to_owned()))
+        }
+
+        storage.save_application_policy(id, &policy)?;
+        Ok(())
+    }
+
+    // Revoke the permissions and the policy saved for an XSWD application
+    // Its sessions are closed if it is connected
+    pub async fn revoke_xswd_application(&self, id: &str) -> Result<(), WalletError> {
+        trace!("revoke xswd application {}", id);
+        let saved = {
+            let mut storage = self.storage.write().await;
+            let has_permissions = storage.has_application_permissions(id)?;
+            if has_permissions {
+                storage.delete_application_permissions(id)?;
+            }
+
+            let has_policy = storage.has_application_policy(id)?;
+            if has_policy {
+                storage.delete_application_policy(id)?;
+            }
+            has_permissions || has_policy
+        };
+
+        #[cfg(feature = "api_server")]
+        let closed = match self.api_server.lock().await.as_ref() {
+            Some(APIServer::XSWD(xswd)) => xswd.get_handler().close_application_sessions(id).await,
+            _ => 0
+        };
+        #[cfg(not(feature = "api_server"))]
+        let closed = 0;
+
+        if !saved && closed == 0 {
+            return Err(WalletError::XSWDApplicationNotFound(id.to_owned()))
+        }
+
+        info!("XSWD application {} revoked, {} sessions closed", id, closed);
+        Ok(())
+    }
+
     // Current account nonce for transactions
     // Nonce is used against replay attacks on-chain
     pub async fn get_nonce(&self) -> u64 {
@@ -973,6 +1729,8 @@ impl Wallet {
 #[cfg(feature = "api_server")]
 pub enum XSWDEvent {
     RequestPermission(AppStateShared, RpcRequest, oneshot::Sender<Result<PermissionResult, Error>>),
+    // String is the limit exceeded by the request
+    RequestPolicyExceeded(AppStateShared, RpcRequest, String, oneshot::Sender<Result<PermissionResult, Error>>),
     // bool represents if it was signed or not
     RequestApplication(AppStateShared, bool, oneshot::Sender<Result<PermissionResult, Error>>),
     CancelRequest(AppStateShared, oneshot::Sender<Result<(), Error>>)
@@ -989,7 +1747,8 @@ impl XSWDPermissionHandler for Arc<Wallet> {
             let (callback, receiver) = oneshot::channel();
             let event = match request {
                 PermissionRequest::Application(signed) => XSWDEvent::RequestApplication(app_state, signed, callback),
-                PermissionRequest::Request(request) => XSWDEvent::RequestPermission(app_state, request.clone(), callback)
+                PermissionRequest::Request(request) => XSWDEvent::RequestPermission(app_state, request.clone(), callback),
+                PermissionRequest::PolicyExceeded(request, exceeded) => XSWDEvent::RequestPolicyExceeded(app_state, request.clone(), exceeded.to_string(), callback)
             };
 
             // Send the XSWD Message
@@ -1017,9 +1776,79 @@ impl XSWDPermissionHandler for Arc<Wallet> {
         Err(WalletError::NoHandlerAvailable.into())
     }
 
-    async fn get_public_key(&self) -> Result<&DecompressedPublicKey, Error> {
-        Ok(self.inner.keypair.get_public_key())
+    async fn get_public_key(&self) -> Result<DecompressedPublicKey, Error> {
+        Ok(self.get_account().keypair.get_public_key().clone())
+    }
+
+    async fn get_saved_permissions(&self, app: &AppStateShared) -> Result<Option<ApplicationPermissions>, Error> {
+        let mut storage = self.storage.write().await;
+        get_saved_application(&mut storage, app)
+    }
+
+    async fn save_permissions(&self, app: &AppStateShared, permissions: &HashMap<String, Permission>) -> Result<TimestampSeconds, Error> {
+        let now = get_current_time_in_seconds();
+        let mut storage = self.storage.write().await;
+        let application = match get_saved_application(&mut storage, app)? {
+            Some(mut application) => {
+                application.set_permissions(permissions.clone(), now);
+                application
+            },
+            None => ApplicationPermissions::new(
+                app.get_id().clone(),
+                app.get_name().clone(),
+                app.get_url().clone(),
+                permissions.clone(),
+                now
+            )
+        };
+
+        storage.save_application_permissions(&application)?;
+        Ok(application.get_expire_at())
+    }
+
+    // The policy is applied by application ID, even to another application reusing it
+    async fn get_policy(&self, app: &AppStateShared) -> Result<Option<ApplicationPolicy>, Error> {
+        let storage = self.storage.read().await;
+        if !storage.has_application_policy(app.get_id())? {
+            return Ok(None)
+        }
+
+        let policy = storage.get_application_policy(app.get_id())?;
+        Ok(Some(policy).filter(|policy| !policy.is_empty()))
+    }
+
+    async fn record_spending(&self, app: &AppStateShared, amounts: &HashMap<Hash, u64>) -> Result<(), Error> {
+        let mut storage = self.storage.write().await;
+        if storage.has_application_policy(app.get_id())? {
+            let mut policy = storage.get_application_policy(app.get_id())?;
+            policy.record_spending(amounts, get_current_time_in_seconds());
+            storage.save_application_policy(app.get_id(), &policy)?;
+        }
+        Ok(())
+    }
+}
+
+// Retrieve the permissions saved for an application if they are still valid
+// Expired permissions are deleted and the ones granted to another application are ignored
+#[cfg(feature = "api_server")]
+fn get_saved_application(storage: &mut EncryptedStorage, app: &AppStateShared) -> Result<Option<ApplicationPermissions>, Error> {
+    if !storage.has_application_permissions(app.get_id())? {
+        return Ok(None)
     }
+
+    let saved = storage.get_application_permissions(app.get_id())?;
+    if saved.is_expired(get_current_time_in_seconds()) {
+        debug!("Saved permissions of application {} have expired", app.get_id());
+        storage.delete_application_permissions(app.get_id())?;
+        return Ok(None)
+    }
+
+    if !saved.is_same_application(app.get_name(), app.get_url()) {
+        warn!("Application {} is not the one that was granted the saved permissions, ignoring them", app.get_id());
+        return Ok(None)
+    }
+
+    Ok(Some(saved))
 }
 
 #[cfg(feature = "api_server")]